@@ -0,0 +1,199 @@
+import argparse
+import json
+import os
+import queue
+import random
+import serial
+import socket
+import struct
+import sys
+import threading
+import time
+from datetime import datetime, timezone
+
+
+PROTOCOL_VERSION = 2
+
+PUSH_DATA = 0x00
+PUSH_ACK = 0x01
+PULL_DATA = 0x02
+PULL_RESP = 0x03
+PULL_ACK = 0x04
+TX_ACK = 0x05
+
+KEEPALIVE_INTERVAL = 10
+STAT_INTERVAL = 30
+# the firmware could not parse a txpk, no txpk_ack follows
+TXPK_REJECTED = '# txpk rejected'
+
+
+class Stats(object):
+    def __init__(self):
+        self.reset()
+
+    def reset(self):
+        self.rxnb = 0
+        self.rxok = 0
+        self.rxfw = 0
+        self.ackr_sent = 0
+        self.ackr_received = 0
+        self.dwnb = 0
+        self.txnb = 0
+
+    def json(self):
+        ackr = 100.0 * self.ackr_received / self.ackr_sent if self.ackr_sent else 0.0
+        return {
+            'time': datetime.now(timezone.utc).strftime('%Y-%m-%d %H:%M:%S GMT'),
+            'rxnb': self.rxnb,
+            'rxok': self.rxok,
+            'rxfw': self.rxfw,
+            'ackr': round(ackr, 1),
+            'dwnb': self.dwnb,
+            'txnb': self.txnb,
+        }
+
+
+class Bridge(object):
+    def __init__(self, ser, server, eui):
+        self.ser = ser
+        self.server = server
+        self.eui = eui
+        self.sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+        self.sock.settimeout(1)
+        self.stats = Stats()
+        self.stats_lock = threading.Lock()
+        # tokens of the PULL_RESPs waiting for their txpk_ack, in order
+        self.tx_tokens = queue.Queue()
+
+    def send(self, kind, body=None, token=None):
+        if token is None:
+            token = os.urandom(2)
+        packet = struct.pack('B2sB', PROTOCOL_VERSION, token, kind)
+        if kind != PULL_RESP:
+            packet += self.eui
+        if body is not None:
+            packet += json.dumps(body, separators=(',', ':')).encode()
+        self.sock.sendto(packet, self.server)
+
+    def push(self, body):
+        with self.stats_lock:
+            self.stats.ackr_sent += 1
+        self.send(PUSH_DATA, body)
+
+    def pop_token(self):
+        try:
+            return self.tx_tokens.get_nowait()
+        except queue.Empty:
+            return None
+
+    def on_line(self, line):
+        if not line.startswith('{'):
+            if line.startswith(TXPK_REJECTED):
+                self.pop_token()
+            print(line)
+            return
+        try:
+            obj = json.loads(line)
+        except ValueError:
+            print('bad line from the device: {}'.format(line))
+            return
+
+        if 'rxpk' in obj:
+            with self.stats_lock:
+                for rxpk in obj['rxpk']:
+                    self.stats.rxnb += 1
+                    self.stats.rxok += rxpk.get('stat') == 1
+                    self.stats.rxfw += 1
+            print('uplink   {}'.format(obj['rxpk']))
+            self.push(obj)
+        elif 'txpk_ack' in obj:
+            token = self.pop_token()
+            if token is None:
+                return
+            error = obj['txpk_ack'].get('error', 'NONE')
+            if error == 'NONE':
+                with self.stats_lock:
+                    self.stats.txnb += 1
+            print('tx ack   {}'.format(error))
+            self.send(TX_ACK, obj, token)
+
+    def serial_loop(self):
+        buf = b''
+        while True:
+            chunk = self.ser.read(self.ser.in_waiting or 1)
+            if not chunk:
+                continue
+            buf += chunk
+            while b'\n' in buf:
+                line, buf = buf.split(b'\n', 1)
+                self.on_line(line.decode(errors='replace').strip())
+
+    def udp_loop(self):
+        while True:
+            try:
+                packet, _ = self.sock.recvfrom(65535)
+            except socket.timeout:
+                continue
+            if len(packet) < 4 or packet[0] != PROTOCOL_VERSION:
+                continue
+            token, kind = packet[1:3], packet[3]
+
+            if kind == PUSH_ACK:
+                with self.stats_lock:
+                    self.stats.ackr_received += 1
+            elif kind == PULL_RESP:
+                body = packet[4:].decode(errors='replace')
+                with self.stats_lock:
+                    self.stats.dwnb += 1
+                print('downlink {}'.format(body))
+                self.tx_tokens.put(token)
+                self.ser.write(body.encode() + b'\n')
+
+    def run(self):
+        threading.Thread(target=self.serial_loop, daemon=True).start()
+        threading.Thread(target=self.udp_loop, daemon=True).start()
+
+        next_keepalive = next_stat = time.monotonic()
+        while True:
+            now = time.monotonic()
+            if now >= next_keepalive:
+                self.send(PULL_DATA)
+                next_keepalive = now + KEEPALIVE_INTERVAL
+            if now >= next_stat:
+                with self.stats_lock:
+                    stat = self.stats.json()
+                    self.stats.reset()
+                self.push({'stat': stat})
+                next_stat = now + STAT_INTERVAL
+            time.sleep(0.1 + random.random() * 0.1)
+
+
+def main():
+    parser = argparse.ArgumentParser(description='Bridge the RA-08 single-channel gateway UART to the Semtech UDP protocol')
+    parser.add_argument('-p', '--port', default='/dev/ttyUSB0', help='serial port')
+    parser.add_argument('-b', '--baud', type=int, default=115200, help='serial baudrate')
+    parser.add_argument('-s', '--server', default='127.0.0.1', help='network server (or gateway bridge) host')
+    parser.add_argument('-P', '--server-port', type=int, default=1700, help='network server UDP port')
+    parser.add_argument('-e', '--eui', default='AA555A0000000000', help='gateway EUI, 16 hex digits')
+    args = parser.parse_args()
+
+    eui = bytes.fromhex(args.eui)
+    if len(eui) != 8:
+        parser.error('the gateway EUI is 8 bytes')
+
+    ser = serial.Serial(args.port, args.baud, timeout=1)
+    bridge = Bridge(ser, (args.server, args.server_port), eui)
+    print('gateway {} -> {}:{}'.format(args.eui.upper(), args.server, args.server_port))
+
+    try:
+        bridge.run()
+    except KeyboardInterrupt:
+        pass
+    finally:
+        ser.close()
+
+    return 0
+
+
+if __name__ == '__main__':
+    sys.exit(main())
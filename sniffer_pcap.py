@@ -0,0 +1,160 @@
+import argparse
+import serial
+import struct
+import sys
+import time
+
+
+LINKTYPE_LORATAP = 270
+
+FRAME_MAGIC = b'\xA5\x5A'
+FRAME_VERSION = 1
+HEADER_FORMAT = '<BBQIBBBhbiB'
+HEADER_SIZE = struct.calcsize(HEADER_FORMAT)
+
+FLAG_CRC_OK = 1 << 0
+FLAG_CRC_BAD = 1 << 1
+FLAG_IQ_INVERTED = 1 << 2
+
+# bandwidth index used by the firmware -> LoRaTap bandwidth (125 kHz steps)
+LORATAP_BANDWIDTH = {0: 1, 1: 2, 2: 4}
+
+LORAWAN_PUBLIC_SYNCWORD = 0x34
+
+
+def crc16_ccitt(data):
+    crc = 0xFFFF
+    for byte in data:
+        crc ^= byte << 8
+        for _ in range(8):
+            if crc & 0x8000:
+                crc = ((crc << 1) ^ 0x1021) & 0xFFFF
+            else:
+                crc = (crc << 1) & 0xFFFF
+    return crc
+
+
+class SnifferFrame(object):
+    def __init__(self, header, payload):
+        (self.version, self.length, self.timestamp, self.frequency, self.bandwidth,
+         self.spreading_factor, self.coding_rate, self.rssi, self.snr, self.freq_error,
+         self.flags) = header
+        self.payload = payload
+
+    @property
+    def crc_ok(self):
+        return bool(self.flags & FLAG_CRC_OK)
+
+    def loratap_header(self, sync_word):
+        rssi = max(0, min(255, self.rssi + 139))
+        snr = struct.unpack('B', struct.pack('b', max(-32, min(31, self.snr)) * 4))[0]
+        return struct.pack('>BBHIBBBBBBB',
+                           0,                                        # lt_version
+                           0,                                        # lt_padding
+                           15,                                       # lt_length
+                           self.frequency,
+                           LORATAP_BANDWIDTH.get(self.bandwidth, 1),
+                           self.spreading_factor,
+                           rssi,                                     # packet rssi
+                           rssi,                                     # max rssi
+                           rssi,                                     # current rssi
+                           snr,
+                           sync_word)
+
+    def __str__(self):
+        return '[{:>10} ms] {:.1f} MHz SF{} BW{} RSSI {} dBm SNR {} dB FERR {} Hz {} {}'.format(
+            self.timestamp, self.frequency / 1e6, self.spreading_factor,
+            (125, 250, 500)[self.bandwidth] if self.bandwidth < 3 else '?',
+            self.rssi, self.snr, self.freq_error,
+            'CRC_OK' if self.crc_ok else 'CRC_BAD', self.payload.hex())
+
+
+class FrameReader(object):
+    def __init__(self, ser):
+        self.ser = ser
+        self.buf = b''
+
+    def read(self):
+        while True:
+            chunk = self.ser.read(self.ser.in_waiting or 1)
+            if chunk:
+                self.buf += chunk
+
+            start = self.buf.find(FRAME_MAGIC)
+            if start < 0:
+                self.buf = self.buf[-1:]
+                continue
+            self.buf = self.buf[start:]
+
+            if len(self.buf) < 2 + HEADER_SIZE:
+                continue
+
+            header = struct.unpack(HEADER_FORMAT, self.buf[2:2 + HEADER_SIZE])
+            version, length = header[0], header[1]
+            end = 2 + HEADER_SIZE + length
+            if version != FRAME_VERSION:
+                self.buf = self.buf[2:]
+                continue
+            if len(self.buf) < end + 2:
+                continue
+
+            (crc,) = struct.unpack('<H', self.buf[end:end + 2])
+            if crc != crc16_ccitt(self.buf[2:end]):
+                # false magic inside log output or a corrupted frame, resync
+                self.buf = self.buf[2:]
+                continue
+
+            payload = self.buf[2 + HEADER_SIZE:end]
+            self.buf = self.buf[end + 2:]
+            return SnifferFrame(header, payload)
+
+
+class PcapWriter(object):
+    def __init__(self, path):
+        self.f = open(path, 'wb')
+        self.f.write(struct.pack('<IHHiIII', 0xA1B2C3D4, 2, 4, 0, 0, 65535, LINKTYPE_LORATAP))
+        self.f.flush()
+
+    def write(self, frame, sync_word):
+        data = frame.loratap_header(sync_word) + frame.payload
+        now = time.time()
+        self.f.write(struct.pack('<IIII', int(now), int((now % 1) * 1e6), len(data), len(data)))
+        self.f.write(data)
+        self.f.flush()
+
+    def close(self):
+        self.f.close()
+
+
+def main():
+    parser = argparse.ArgumentParser(description='Decode the RA-08 sniffer stream into a LoRaTap pcap file')
+    parser.add_argument('-p', '--port', default='/dev/ttyUSB0', help='serial port')
+    parser.add_argument('-b', '--baud', type=int, default=115200, help='serial baudrate')
+    parser.add_argument('-o', '--output', default='capture.pcap', help='pcap output file')
+    parser.add_argument('-s', '--sync-word', type=lambda x: int(x, 0), default=LORAWAN_PUBLIC_SYNCWORD,
+                        help='sync word to record in the LoRaTap header')
+    parser.add_argument('--drop-bad-crc', action='store_true', help='do not record frames that failed their CRC')
+    args = parser.parse_args()
+
+    ser = serial.Serial(args.port, args.baud, timeout=1)
+    reader = FrameReader(ser)
+    writer = PcapWriter(args.output)
+
+    try:
+        while True:
+            frame = reader.read()
+            print(frame)
+            if args.drop_bad_crc and not frame.crc_ok:
+                continue
+            writer.write(frame, args.sync_word)
+    except KeyboardInterrupt:
+        pass
+    finally:
+        writer.close()
+        ser.close()
+
+    return 0
+
+
+if __name__ == '__main__':
+    sys.exit(main())
@@ -0,0 +1,587 @@
+use heapless::Vec;
+
+use crate::{
+    lora::{
+        mac::DeviceClass,
+        region::{As923Group, Region},
+    },
+    util::{crc16_ccitt, parse_hex},
+};
+
+/// AT command server on the LoRaWAN MAC
+pub mod server;
+
+/// Longest command line, `AT+DTRX` with a 242 byte payload in hex fits
+pub const AT_LINE_MAX_SIZE: usize = 512;
+/// Largest `AT+DTRX` payload
+pub const AT_PAYLOAD_MAX_SIZE: usize = 242;
+
+/// Encoded [`Settings`] size, a multiple of the 8 byte flash program unit
+pub const SETTINGS_SIZE: usize = 64;
+const SETTINGS_MAGIC: [u8; 2] = *b"AT";
+const SETTINGS_VERSION: u8 = 1;
+
+/// Error of a command, printed as `+CME ERROR:<code>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtError {
+    /// unknown command
+    Unknown = 1,
+    /// missing or malformed parameter
+    Parameter = 2,
+    /// the MAC is busy with another request
+    Busy = 3,
+    /// the device hasn't joined yet
+    NotJoined = 4,
+    /// payload doesn't fit at the current data rate
+    Length = 5,
+    /// data rate not supported by the region or the channels
+    Datarate = 6,
+    /// settings could not be written
+    Flash = 7,
+}
+
+impl AtError {
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Read, test or write form of a setting command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Access<T> {
+    /// `AT+CMD?`
+    Query,
+    /// `AT+CMD=?`
+    Test,
+    /// `AT+CMD=<value>`
+    Set(T),
+}
+
+/// `AT+CJOIN` parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JoinArgs {
+    /// start (`true`) or stop joining
+    pub start: bool,
+    /// join again at boot
+    pub auto: bool,
+    /// time between attempts (s)
+    pub interval: u16,
+    /// attempts before `+CJOIN:FAIL`
+    pub attempts: u8,
+}
+
+/// Parsed command line
+// one command at a time lives on the stack, no need to box the payload
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// `AT`
+    Attention,
+    /// `ATZ`, restart the module
+    Reset,
+    /// `AT+CGMR?`, firmware version
+    Version,
+    /// `AT+CDEVEUI`
+    DevEui(Access<[u8; 8]>),
+    /// `AT+CAPPEUI`
+    AppEui(Access<[u8; 8]>),
+    /// `AT+CAPPKEY`
+    AppKey(Access<[u8; 16]>),
+    /// `AT+CDEVADDR?`, assigned by the network
+    DevAddr,
+    /// `AT+CJOIN=<start>,<auto>,<interval>,<attempts>` or `AT+CJOIN?` for the join state
+    Join(Access<JoinArgs>),
+    /// `AT+DTRX=<confirmed>,<trials>,<hex length>,<hex payload>`
+    Dtrx {
+        confirmed: bool,
+        nb_trials: u8,
+        data: Vec<u8, AT_PAYLOAD_MAX_SIZE>,
+    },
+    /// `AT+CCLASS`, 0 = A, 2 = C
+    Class(Access<DeviceClass>),
+    /// `AT+CDATARATE`
+    Datarate(Access<u8>),
+    /// `AT+CADR`
+    Adr(Access<bool>),
+    /// `AT+CREGION`
+    Region(Access<Region>),
+    /// `AT+CTXP`, TX power index
+    TxPower(Access<u8>),
+    /// `AT+CAPPPORT`
+    AppPort(Access<u8>),
+    /// `AT+CLPM`, sleep whenever idle
+    LowPower(Access<bool>),
+    /// `AT+CSAVE`, write the settings to flash
+    Save,
+    /// `AT+CRESTORE`, back to the factory settings
+    Restore,
+}
+
+/// Region code of `AT+CREGION`, the `LoRaMacRegion_t` numbering (AS923-2..4 take 10..12)
+pub fn region_code(region: Region) -> u8 {
+    match region {
+        Region::As923(As923Group::Group1) => 0,
+        Region::Au915 => 1,
+        Region::Cn470 => 2,
+        Region::Eu868 => 5,
+        Region::Kr920 => 6,
+        Region::In865 => 7,
+        Region::Us915 => 8,
+        Region::As923(As923Group::Group2) => 10,
+        Region::As923(As923Group::Group3) => 11,
+        Region::As923(As923Group::Group4) => 12,
+    }
+}
+
+/// Region of an `AT+CREGION` code, `None` for the regions this MAC doesn't implement
+pub fn region_from_code(code: u8) -> Option<Region> {
+    Some(match code {
+        0 => Region::As923(As923Group::Group1),
+        1 => Region::Au915,
+        2 => Region::Cn470,
+        5 => Region::Eu868,
+        6 => Region::Kr920,
+        7 => Region::In865,
+        8 => Region::Us915,
+        10 => Region::As923(As923Group::Group2),
+        11 => Region::As923(As923Group::Group3),
+        12 => Region::As923(As923Group::Group4),
+        _ => return None,
+    })
+}
+
+fn parse_fixed<const N: usize>(hex: &str) -> Result<[u8; N], AtError> {
+    let mut out = [0u8; N];
+    match parse_hex(hex, &mut out) {
+        Some(len) if len == N => Ok(out),
+        _ => Err(AtError::Parameter),
+    }
+}
+
+fn parse_number<T: core::str::FromStr>(value: &str) -> Result<T, AtError> {
+    value.trim().parse().map_err(|_| AtError::Parameter)
+}
+
+fn parse_flag(value: &str) -> Result<bool, AtError> {
+    match value.trim() {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(AtError::Parameter),
+    }
+}
+
+/// Split `AT+CMD=..`, `AT+CMD?` and `AT+CMD=?` and parse the value of the set form
+fn access<T>(
+    rest: &str,
+    parse: impl FnOnce(&str) -> Result<T, AtError>,
+) -> Result<Access<T>, AtError> {
+    match rest {
+        "?" => Ok(Access::Query),
+        "=?" => Ok(Access::Test),
+        _ => Ok(Access::Set(parse(
+            rest.strip_prefix('=').ok_or(AtError::Parameter)?,
+        )?)),
+    }
+}
+
+/// Parse one command line, without its line ending.
+///
+/// Command names are case insensitive, parameters are not.
+pub fn parse(line: &str) -> Result<Command, AtError> {
+    let line = line.trim();
+    let split = line.find(['=', '?']).unwrap_or(line.len());
+    let (name, rest) = line.split_at(split);
+
+    let mut upper = [0u8; 16];
+    let name = upper
+        .get_mut(..name.len())
+        .map(|upper| {
+            upper.copy_from_slice(name.as_bytes());
+            upper.make_ascii_uppercase();
+            &*upper
+        })
+        .ok_or(AtError::Unknown)?;
+
+    let command = match name {
+        b"AT" if rest.is_empty() => Command::Attention,
+        b"ATZ" if rest.is_empty() => Command::Reset,
+        b"AT+CGMR" if rest == "?" || rest.is_empty() => Command::Version,
+        b"AT+CDEVEUI" => Command::DevEui(access(rest, parse_fixed)?),
+        b"AT+CAPPEUI" => Command::AppEui(access(rest, parse_fixed)?),
+        b"AT+CAPPKEY" => Command::AppKey(access(rest, parse_fixed)?),
+        b"AT+CDEVADDR" if rest == "?" => Command::DevAddr,
+        b"AT+CJOIN" => Command::Join(access(rest, parse_join)?),
+        b"AT+DTRX" => parse_dtrx(rest.strip_prefix('=').ok_or(AtError::Parameter)?)?,
+        b"AT+CCLASS" => Command::Class(access(rest, |v| match v.trim() {
+            "0" => Ok(DeviceClass::A),
+            "2" => Ok(DeviceClass::C),
+            _ => Err(AtError::Parameter),
+        })?),
+        b"AT+CDATARATE" => Command::Datarate(access(rest, parse_number)?),
+        b"AT+CADR" => Command::Adr(access(rest, parse_flag)?),
+        b"AT+CREGION" => Command::Region(access(rest, |v| {
+            region_from_code(parse_number(v)?).ok_or(AtError::Parameter)
+        })?),
+        b"AT+CTXP" => Command::TxPower(access(rest, parse_number)?),
+        b"AT+CAPPPORT" => Command::AppPort(access(rest, |v| {
+            parse_number(v).and_then(|port: u8| {
+                (1..=223)
+                    .contains(&port)
+                    .then_some(port)
+                    .ok_or(AtError::Parameter)
+            })
+        })?),
+        b"AT+CLPM" => Command::LowPower(access(rest, parse_flag)?),
+        b"AT+CSAVE" if rest.is_empty() => Command::Save,
+        b"AT+CRESTORE" if rest.is_empty() => Command::Restore,
+        _ => return Err(AtError::Unknown),
+    };
+    Ok(command)
+}
+
+/// `<start>[,<auto>[,<interval>[,<attempts>]]]`
+fn parse_join(value: &str) -> Result<JoinArgs, AtError> {
+    let mut fields = value.split(',');
+    let start = parse_flag(fields.next().ok_or(AtError::Parameter)?)?;
+    let auto = fields.next().map_or(Ok(false), parse_flag)?;
+    let interval = fields.next().map_or(Ok(8), parse_number)?;
+    let attempts = fields.next().map_or(Ok(8), parse_number)?;
+    if fields.next().is_some() || !(1..=3600).contains(&interval) || attempts == 0 {
+        return Err(AtError::Parameter);
+    }
+    Ok(JoinArgs {
+        start,
+        auto,
+        interval,
+        attempts,
+    })
+}
+
+/// `<confirmed>,<trials>,<length>,<hex payload>`, the length counts hex characters like the
+/// Ai-Thinker firmware: `AT+DTRX=1,2,10,0123456789` sends 5 bytes
+fn parse_dtrx(value: &str) -> Result<Command, AtError> {
+    let mut fields = value.split(',');
+    let mut field = || fields.next().ok_or(AtError::Parameter);
+    let confirmed = parse_flag(field()?)?;
+    let nb_trials: u8 = parse_number(field()?)?;
+    let hex_len: usize = parse_number(field()?)?;
+    let hex = field()?.trim();
+    if fields.next().is_some() || !(1..=15).contains(&nb_trials) || hex.len() != hex_len {
+        return Err(AtError::Parameter);
+    }
+
+    let mut data = [0u8; AT_PAYLOAD_MAX_SIZE];
+    let len = parse_hex(hex, &mut data).ok_or(AtError::Parameter)?;
+    Ok(Command::Dtrx {
+        confirmed,
+        nb_trials,
+        data: Vec::from_slice(&data[..len]).map_err(|_| AtError::Parameter)?,
+    })
+}
+
+/// Settings kept in flash by `AT+CSAVE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub dev_eui: [u8; 8],
+    pub app_eui: [u8; 8],
+    pub app_key: [u8; 16],
+    pub region: Region,
+    pub class: DeviceClass,
+    pub datarate: u8,
+    pub adr: bool,
+    pub tx_power: u8,
+    pub app_port: u8,
+    pub low_power: bool,
+    /// join at boot with these parameters
+    pub auto_join: Option<JoinArgs>,
+}
+
+impl Settings {
+    /// Factory settings: no credentials, EU868 class A with ADR
+    pub const fn new() -> Self {
+        Self {
+            dev_eui: [0; 8],
+            app_eui: [0; 8],
+            app_key: [0; 16],
+            region: Region::Eu868,
+            class: DeviceClass::A,
+            datarate: 0,
+            adr: true,
+            tx_power: 0,
+            app_port: 2,
+            low_power: false,
+            auto_join: None,
+        }
+    }
+
+    /// Layout: magic (2), version, DevEUI (8), AppEUI (8), AppKey (16), region code, class,
+    /// datarate, flags (ADR, low power, auto join), TX power, port, join interval (2),
+    /// join attempts, zero padding, CRC-16/CCITT-FALSE of everything before it (2)
+    pub fn encode(&self) -> [u8; SETTINGS_SIZE] {
+        let mut buf = [0u8; SETTINGS_SIZE];
+        buf[0..2].copy_from_slice(&SETTINGS_MAGIC);
+        buf[2] = SETTINGS_VERSION;
+        buf[3..11].copy_from_slice(&self.dev_eui);
+        buf[11..19].copy_from_slice(&self.app_eui);
+        buf[19..35].copy_from_slice(&self.app_key);
+        buf[35] = region_code(self.region);
+        buf[36] = match self.class {
+            DeviceClass::A => 0,
+            DeviceClass::C => 2,
+        };
+        buf[37] = self.datarate;
+        buf[38] =
+            self.adr as u8 | (self.low_power as u8) << 1 | (self.auto_join.is_some() as u8) << 2;
+        buf[39] = self.tx_power;
+        buf[40] = self.app_port;
+        if let Some(join) = self.auto_join {
+            buf[41..43].copy_from_slice(&join.interval.to_le_bytes());
+            buf[43] = join.attempts;
+        }
+        let crc = crc16_ccitt(&buf[..SETTINGS_SIZE - 2]);
+        buf[SETTINGS_SIZE - 2..].copy_from_slice(&crc.to_le_bytes());
+        buf
+    }
+
+    /// `None` for erased flash, another version or a bad CRC
+    pub fn decode(buf: &[u8; SETTINGS_SIZE]) -> Option<Self> {
+        let crc = u16::from_le_bytes([buf[SETTINGS_SIZE - 2], buf[SETTINGS_SIZE - 1]]);
+        if buf[0..2] != SETTINGS_MAGIC
+            || buf[2] != SETTINGS_VERSION
+            || crc != crc16_ccitt(&buf[..SETTINGS_SIZE - 2])
+        {
+            return None;
+        }
+
+        let flags = buf[38];
+        Some(Self {
+            dev_eui: buf[3..11].try_into().ok()?,
+            app_eui: buf[11..19].try_into().ok()?,
+            app_key: buf[19..35].try_into().ok()?,
+            region: region_from_code(buf[35])?,
+            class: match buf[36] {
+                2 => DeviceClass::C,
+                _ => DeviceClass::A,
+            },
+            datarate: buf[37],
+            adr: flags & 1 != 0,
+            low_power: flags & 2 != 0,
+            tx_power: buf[39],
+            app_port: buf[40],
+            auto_join: (flags & 4 != 0).then(|| JoinArgs {
+                start: true,
+                auto: true,
+                interval: u16::from_le_bytes([buf[41], buf[42]]),
+                attempts: buf[43],
+            }),
+        })
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dtrx(confirmed: bool, nb_trials: u8, data: &[u8]) -> Command {
+        Command::Dtrx {
+            confirmed,
+            nb_trials,
+            data: Vec::from_slice(data).unwrap(),
+        }
+    }
+
+    #[test]
+    fn parse_table() {
+        let join = JoinArgs {
+            start: true,
+            auto: false,
+            interval: 8,
+            attempts: 8,
+        };
+        let table: &[(&str, Result<Command, AtError>)] = &[
+            // exec forms
+            ("AT", Ok(Command::Attention)),
+            ("at", Ok(Command::Attention)),
+            ("  ATZ\r", Ok(Command::Reset)),
+            ("AT+CGMR", Ok(Command::Version)),
+            ("AT+CGMR?", Ok(Command::Version)),
+            ("AT+CSAVE", Ok(Command::Save)),
+            ("AT+CRESTORE", Ok(Command::Restore)),
+            ("AT+CSAVE?", Err(AtError::Unknown)),
+            ("AT?", Err(AtError::Unknown)),
+            // query and test forms
+            ("AT+CDEVEUI?", Ok(Command::DevEui(Access::Query))),
+            ("AT+CDEVEUI=?", Ok(Command::DevEui(Access::Test))),
+            ("AT+CDEVADDR?", Ok(Command::DevAddr)),
+            ("AT+CDEVADDR", Err(AtError::Unknown)),
+            ("AT+CJOIN?", Ok(Command::Join(Access::Query))),
+            ("AT+cclass=?", Ok(Command::Class(Access::Test))),
+            ("AT+CLPM?", Ok(Command::LowPower(Access::Query))),
+            // set forms
+            (
+                "AT+CDEVEUI=0011223344556677",
+                Ok(Command::DevEui(Access::Set([
+                    0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77,
+                ]))),
+            ),
+            (
+                "AT+CAPPEUI=70b3d57ed0000001",
+                Ok(Command::AppEui(Access::Set([
+                    0x70, 0xB3, 0xD5, 0x7E, 0xD0, 0x00, 0x00, 0x01,
+                ]))),
+            ),
+            (
+                "AT+CAPPKEY=2B7E151628AED2A6ABF7158809CF4F3C",
+                Ok(Command::AppKey(Access::Set([
+                    0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09,
+                    0xCF, 0x4F, 0x3C,
+                ]))),
+            ),
+            ("AT+CJOIN=1", Ok(Command::Join(Access::Set(join)))),
+            (
+                "AT+CJOIN=1,1,10,3",
+                Ok(Command::Join(Access::Set(JoinArgs {
+                    auto: true,
+                    interval: 10,
+                    attempts: 3,
+                    ..join
+                }))),
+            ),
+            (
+                "AT+CCLASS=2",
+                Ok(Command::Class(Access::Set(DeviceClass::C))),
+            ),
+            ("AT+CDATARATE=5", Ok(Command::Datarate(Access::Set(5)))),
+            ("AT+CADR=0", Ok(Command::Adr(Access::Set(false)))),
+            (
+                "AT+CREGION=5",
+                Ok(Command::Region(Access::Set(Region::Eu868))),
+            ),
+            (
+                "AT+CREGION=11",
+                Ok(Command::Region(Access::Set(Region::As923(
+                    As923Group::Group3,
+                )))),
+            ),
+            ("AT+CTXP=3", Ok(Command::TxPower(Access::Set(3)))),
+            ("AT+CAPPPORT=223", Ok(Command::AppPort(Access::Set(223)))),
+            ("AT+CLPM=1", Ok(Command::LowPower(Access::Set(true)))),
+            // the length counts hex characters
+            (
+                "AT+DTRX=1,2,10,0123456789",
+                Ok(dtrx(true, 2, &[0x01, 0x23, 0x45, 0x67, 0x89])),
+            ),
+            ("AT+DTRX=0,1,4,abCD", Ok(dtrx(false, 1, &[0xAB, 0xCD]))),
+            ("AT+DTRX=0,1,0,", Ok(dtrx(false, 1, &[]))),
+            // bad hex
+            ("AT+CDEVEUI=00112233445566", Err(AtError::Parameter)),
+            ("AT+CDEVEUI=001122334455667788", Err(AtError::Parameter)),
+            ("AT+CDEVEUI=00112233445566GG", Err(AtError::Parameter)),
+            (
+                "AT+CAPPKEY=2B7E151628AED2A6ABF7158809CF4F3",
+                Err(AtError::Parameter),
+            ),
+            ("AT+DTRX=1,2,3,012", Err(AtError::Parameter)),
+            ("AT+DTRX=1,2,4,01X3", Err(AtError::Parameter)),
+            // length not matching the hex
+            ("AT+DTRX=1,2,5,0123456789", Err(AtError::Parameter)),
+            ("AT+DTRX=1,2,12,0123456789", Err(AtError::Parameter)),
+            // bad arity
+            ("AT+DTRX=1,2,10", Err(AtError::Parameter)),
+            ("AT+DTRX=1,2,10,0123456789,1", Err(AtError::Parameter)),
+            ("AT+DTRX?", Err(AtError::Parameter)),
+            ("AT+CJOIN=1,0,8,8,8", Err(AtError::Parameter)),
+            ("AT+CJOIN=", Err(AtError::Parameter)),
+            // out of range values
+            ("AT+DTRX=1,0,2,00", Err(AtError::Parameter)),
+            ("AT+DTRX=1,16,2,00", Err(AtError::Parameter)),
+            ("AT+DTRX=2,1,2,00", Err(AtError::Parameter)),
+            ("AT+CJOIN=1,0,0,8", Err(AtError::Parameter)),
+            ("AT+CJOIN=1,0,8,0", Err(AtError::Parameter)),
+            ("AT+CCLASS=1", Err(AtError::Parameter)),
+            ("AT+CREGION=3", Err(AtError::Parameter)),
+            ("AT+CAPPPORT=0", Err(AtError::Parameter)),
+            ("AT+CAPPPORT=224", Err(AtError::Parameter)),
+            ("AT+CDATARATE=256", Err(AtError::Parameter)),
+            ("AT+CADR=yes", Err(AtError::Parameter)),
+            ("AT+CADR", Err(AtError::Parameter)),
+            // unknown commands
+            ("AT+NOPE", Err(AtError::Unknown)),
+            ("AT+AVERYLONGCOMMANDNAME", Err(AtError::Unknown)),
+            ("", Err(AtError::Unknown)),
+        ];
+        for (line, expected) in table {
+            assert_eq!(&parse(line), expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn dtrx_largest_payload() {
+        let hex = "A5".repeat(AT_PAYLOAD_MAX_SIZE);
+        let line = std::format!("AT+DTRX=0,1,{},{hex}", hex.len());
+        assert!(line.len() <= AT_LINE_MAX_SIZE);
+        assert_eq!(
+            parse(&line),
+            Ok(dtrx(false, 1, &[0xA5; AT_PAYLOAD_MAX_SIZE]))
+        );
+
+        let hex = "A5".repeat(AT_PAYLOAD_MAX_SIZE + 1);
+        let line = std::format!("AT+DTRX=0,1,{},{hex}", hex.len());
+        assert_eq!(parse(&line), Err(AtError::Parameter));
+    }
+
+    #[test]
+    fn regions() {
+        for code in 0..=u8::MAX {
+            if let Some(region) = region_from_code(code) {
+                assert_eq!(region_code(region), code);
+            }
+        }
+    }
+
+    #[test]
+    fn settings() {
+        let settings = Settings {
+            dev_eui: [1; 8],
+            app_eui: [2; 8],
+            app_key: [3; 16],
+            region: Region::As923(As923Group::Group2),
+            class: DeviceClass::C,
+            datarate: 3,
+            adr: false,
+            tx_power: 2,
+            app_port: 10,
+            low_power: true,
+            auto_join: Some(JoinArgs {
+                start: true,
+                auto: true,
+                interval: 300,
+                attempts: 5,
+            }),
+        };
+        let buf = settings.encode();
+        assert_eq!(&buf[..3], b"AT\x01");
+        assert_eq!(Settings::decode(&buf), Some(settings));
+        assert_eq!(
+            Settings::decode(&Settings::new().encode()),
+            Some(Settings::new())
+        );
+
+        // erased flash, corrupted bytes, another version
+        assert_eq!(Settings::decode(&[0xFF; SETTINGS_SIZE]), None);
+        let mut corrupted = buf;
+        corrupted[20] ^= 1;
+        assert_eq!(Settings::decode(&corrupted), None);
+        let mut version = buf;
+        version[2] = 2;
+        let crc = crc16_ccitt(&version[..SETTINGS_SIZE - 2]);
+        version[SETTINGS_SIZE - 2..].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(Settings::decode(&version), None);
+    }
+}
@@ -0,0 +1,419 @@
+use heapless::Vec;
+
+use crate::{
+    at::{
+        AT_LINE_MAX_SIZE, Access, AtError, Command, JoinArgs, SETTINGS_SIZE, Settings, parse,
+        region_code,
+    },
+    cortex::func::{_disable_irq, _enable_irq},
+    lora::{
+        mac::{
+            DeviceClass, EventInfoStatus, JoinParams, MacError, MacPrimitives, McpsConfirm,
+            McpsIndication, McpsType, MlmeConfirm, MlmeType, mac_get_datarate, mac_get_dev_addr,
+            mac_init, mac_is_busy, mac_is_joined, mac_join, mac_send, mac_set_adr,
+            mac_set_datarate, mac_set_device_class, mac_set_fair_use, mac_set_tx_power,
+        },
+        radio::radio_irq_process,
+        timer::{TimerEvent, timer_init, timer_set_value, timer_start, timer_stop},
+    },
+    peripherals::{
+        buffered_uart::uart0,
+        flash::{flash_erase_page, flash_program_bytes},
+        regs::PWR,
+        system::system_reset,
+    },
+    print,
+    print::SerialWriter,
+    util::write_hex,
+};
+
+/// Flash page holding the [`Settings`], the last one, left out of the image by `linker.ld`
+pub const SETTINGS_ADDR: usize = 0x0801_F000;
+
+/// Join attempt in progress
+struct Joining {
+    args: JoinArgs,
+    /// attempts made so far
+    attempt: u8,
+}
+
+struct AtServer {
+    settings: Settings,
+    joining: Option<Joining>,
+    join_timer: TimerEvent,
+    line: Vec<u8, AT_LINE_MAX_SIZE>,
+    /// the current line overflowed, it is dropped at its end
+    overflow: bool,
+}
+
+static mut AT: AtServer = AtServer {
+    settings: Settings::new(),
+    joining: None,
+    join_timer: TimerEvent {
+        id: 0,
+        timestamp: 0,
+        reload_value: 0,
+        is_running: false,
+        callback: None,
+    },
+    line: Vec::new(),
+    overflow: false,
+};
+
+static AT_PRIMITIVES: MacPrimitives = MacPrimitives {
+    mcps_confirm: Some(on_mcps_confirm),
+    mcps_indication: Some(on_mcps_indication),
+    mlme_confirm: Some(on_mlme_confirm),
+    mlme_indication: None,
+    get_battery_level: None,
+};
+
+fn at_state() -> &'static mut AtServer {
+    unsafe { &mut AT }
+}
+
+impl From<MacError> for AtError {
+    fn from(error: MacError) -> Self {
+        match error {
+            MacError::Busy | MacError::NotInitialised => AtError::Busy,
+            MacError::NoNetworkJoined => AtError::NotJoined,
+            MacError::ParameterInvalid => AtError::Parameter,
+            MacError::DatarateInvalid => AtError::Datarate,
+            MacError::LengthError => AtError::Length,
+        }
+    }
+}
+
+/// Settings saved by `AT+CSAVE`, `None` when the page is erased or holds something else
+fn load_settings() -> Option<Settings> {
+    let mut buf = [0u8; SETTINGS_SIZE];
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = unsafe { core::ptr::read_volatile((SETTINGS_ADDR + i) as *const u8) };
+    }
+    Settings::decode(&buf)
+}
+
+fn save_settings(settings: &Settings) -> Result<(), AtError> {
+    let buf = settings.encode();
+    _disable_irq();
+    let result = flash_erase_page(SETTINGS_ADDR)
+        .and_then(|_| flash_program_bytes(SETTINGS_ADDR, &buf, SETTINGS_SIZE));
+    _enable_irq();
+    result.map_err(|_| AtError::Flash)
+}
+
+/// (Re)start the MAC for the region of the settings and apply the rest of them
+fn apply_settings(settings: &Settings) {
+    mac_init(&AT_PRIMITIVES, settings.region);
+    // the module joins public networks, keep to the TTN airtime budget there
+    mac_set_fair_use(true);
+    mac_set_device_class(settings.class);
+    mac_set_adr(settings.adr);
+    // out of range values of another region fall back to the region defaults
+    let _ = mac_set_datarate(settings.datarate);
+    let _ = mac_set_tx_power(settings.tx_power);
+}
+
+fn join_params(settings: &Settings) -> JoinParams {
+    JoinParams {
+        dev_eui: settings.dev_eui,
+        app_eui: settings.app_eui,
+        app_key: settings.app_key,
+        // the attempts are spread by the server, one join-request each
+        nb_trials: 1,
+    }
+}
+
+fn start_join(args: JoinArgs) -> Result<(), AtError> {
+    let at = at_state();
+    timer_stop(&mut at.join_timer);
+    at.joining = None;
+    if !args.start {
+        return Ok(());
+    }
+    mac_join(&join_params(&at.settings))?;
+    at.joining = Some(Joining { args, attempt: 1 });
+    Ok(())
+}
+
+/// Next join attempt after the interval
+fn on_join_timer() {
+    let at = at_state();
+    let Some(joining) = at.joining.as_mut() else {
+        return;
+    };
+    if mac_join(&join_params(&at.settings)).is_ok() {
+        joining.attempt += 1;
+    } else {
+        // busy with an uplink, try again later
+        timer_start(&mut at.join_timer);
+    }
+}
+
+fn on_mlme_confirm(confirm: &MlmeConfirm) {
+    if confirm.request != MlmeType::Join {
+        return;
+    }
+    let at = at_state();
+    let Some(joining) = at.joining.as_ref() else {
+        return;
+    };
+
+    if confirm.status == EventInfoStatus::Ok {
+        at.joining = None;
+        print!("+CJOIN:OK\r\n");
+    } else if joining.attempt < joining.args.attempts {
+        timer_set_value(&mut at.join_timer, joining.args.interval as usize * 1000);
+        timer_start(&mut at.join_timer);
+    } else {
+        at.joining = None;
+        print!("+CJOIN:FAIL\r\n");
+    }
+}
+
+fn on_mcps_confirm(confirm: &McpsConfirm) {
+    let delivered = confirm.status == EventInfoStatus::Ok
+        && (confirm.request != McpsType::Confirmed || confirm.ack_received);
+    let result = if delivered { "OK" } else { "ERR" };
+    print!("{}+SENT:{:02X}\r\n", result, confirm.nb_trials);
+}
+
+/// `OK+RECV:<type>,<port>,<length>[,<hex payload>]`, type bit 0 is set for confirmed
+/// downlinks and bit 1 when the downlink acknowledged the last uplink
+fn on_mcps_indication(indication: &McpsIndication) {
+    if indication.status != EventInfoStatus::Ok || !(indication.rx_data || indication.ack_received)
+    {
+        return;
+    }
+    let kind =
+        (indication.indication == McpsType::Confirmed) as u8 | (indication.ack_received as u8) << 1;
+    let payload = if indication.rx_data {
+        indication.buffer
+    } else {
+        &[]
+    };
+
+    print!(
+        "OK+RECV:{:02X},{:02X},{:02X}",
+        kind,
+        indication.port,
+        payload.len()
+    );
+    if !payload.is_empty() {
+        print!(",");
+        let _ = write_hex(&mut SerialWriter, payload);
+    }
+    print!("\r\n");
+}
+
+/// `+NAME:<syntax>` answer of the `AT+NAME=?` form
+fn test(name: &str, syntax: &str) {
+    print!("+{}:{}\r\n", name, syntax);
+}
+
+fn print_hex(name: &str, data: &[u8]) {
+    print!("+{}:", name);
+    let _ = write_hex(&mut SerialWriter, data);
+    print!("\r\n");
+}
+
+/// Run one command, its answers come before the final `OK`
+fn execute(command: Command) -> Result<(), AtError> {
+    let at = at_state();
+    let settings = &mut at.settings;
+    match command {
+        Command::Attention => {}
+        Command::Reset => system_reset(),
+        Command::Version => {
+            print!("+CGMR:{}\r\n", env!("CARGO_PKG_VERSION"));
+        }
+
+        Command::DevEui(Access::Query) => print_hex("CDEVEUI", &settings.dev_eui),
+        Command::DevEui(Access::Test) => test("CDEVEUI", "<16 hex digits>"),
+        Command::DevEui(Access::Set(eui)) => settings.dev_eui = eui,
+        Command::AppEui(Access::Query) => print_hex("CAPPEUI", &settings.app_eui),
+        Command::AppEui(Access::Test) => test("CAPPEUI", "<16 hex digits>"),
+        Command::AppEui(Access::Set(eui)) => settings.app_eui = eui,
+        Command::AppKey(Access::Query) => print_hex("CAPPKEY", &settings.app_key),
+        Command::AppKey(Access::Test) => test("CAPPKEY", "<32 hex digits>"),
+        Command::AppKey(Access::Set(key)) => settings.app_key = key,
+        Command::DevAddr => {
+            if !mac_is_joined() {
+                return Err(AtError::NotJoined);
+            }
+            print!("+CDEVADDR:{:08X}\r\n", mac_get_dev_addr());
+        }
+
+        Command::Join(Access::Query) => {
+            print!("+CJOIN:{}\r\n", mac_is_joined() as u8);
+        }
+        Command::Join(Access::Test) => test(
+            "CJOIN",
+            "<start 0-1>,<auto 0-1>,<interval 1-3600>,<attempts 1-255>",
+        ),
+        Command::Join(Access::Set(args)) => {
+            settings.auto_join = args.auto.then_some(JoinArgs {
+                start: true,
+                ..args
+            });
+            start_join(args)?;
+        }
+
+        Command::Dtrx {
+            confirmed,
+            nb_trials,
+            data,
+        } => {
+            mac_send(settings.app_port, &data, confirmed, nb_trials)?;
+            print!("OK+SEND:{:02X}\r\n", data.len());
+        }
+
+        Command::Class(Access::Query) => {
+            let class = match settings.class {
+                DeviceClass::A => 0,
+                DeviceClass::C => 2,
+            };
+            print!("+CCLASS:{}\r\n", class);
+        }
+        Command::Class(Access::Test) => test("CCLASS", "<0 = A, 2 = C>"),
+        Command::Class(Access::Set(class)) => {
+            mac_set_device_class(class);
+            settings.class = class;
+        }
+        Command::Datarate(Access::Query) => {
+            print!("+CDATARATE:{}\r\n", mac_get_datarate());
+        }
+        Command::Datarate(Access::Test) => test("CDATARATE", "<data rate of the region>"),
+        Command::Datarate(Access::Set(dr)) => {
+            mac_set_datarate(dr)?;
+            settings.datarate = dr;
+        }
+        Command::Adr(Access::Query) => {
+            print!("+CADR:{}\r\n", settings.adr as u8);
+        }
+        Command::Adr(Access::Test) => test("CADR", "<0-1>"),
+        Command::Adr(Access::Set(adr)) => {
+            mac_set_adr(adr);
+            settings.adr = adr;
+        }
+        Command::Region(Access::Query) => {
+            print!("+CREGION:{}\r\n", region_code(settings.region));
+        }
+        Command::Region(Access::Test) => test("CREGION", "<0,1,2,5,6,7,8,10,11,12>"),
+        Command::Region(Access::Set(region)) => {
+            if mac_is_busy() || at.joining.is_some() {
+                return Err(AtError::Busy);
+            }
+            // the channel plan changes, the data rate and power start over from the defaults
+            settings.region = region;
+            apply_settings(settings);
+            settings.datarate = mac_get_datarate();
+            settings.tx_power = 0;
+        }
+        Command::TxPower(Access::Query) => {
+            print!("+CTXP:{}\r\n", settings.tx_power);
+        }
+        Command::TxPower(Access::Test) => test("CTXP", "<power index of the region>"),
+        Command::TxPower(Access::Set(index)) => {
+            mac_set_tx_power(index)?;
+            settings.tx_power = index;
+        }
+        Command::AppPort(Access::Query) => {
+            print!("+CAPPPORT:{}\r\n", settings.app_port);
+        }
+        Command::AppPort(Access::Test) => test("CAPPPORT", "<1-223>"),
+        Command::AppPort(Access::Set(port)) => settings.app_port = port,
+        Command::LowPower(Access::Query) => {
+            print!("+CLPM:{}\r\n", settings.low_power as u8);
+        }
+        Command::LowPower(Access::Test) => test("CLPM", "<0-1>"),
+        Command::LowPower(Access::Set(enable)) => settings.low_power = enable,
+
+        Command::Save => save_settings(settings)?,
+        Command::Restore => {
+            if mac_is_busy() || at.joining.is_some() {
+                return Err(AtError::Busy);
+            }
+            *settings = Settings::new();
+            apply_settings(settings);
+            save_settings(settings)?;
+        }
+    }
+    Ok(())
+}
+
+fn on_line(line: &[u8]) {
+    let result = core::str::from_utf8(line)
+        .map_err(|_| AtError::Parameter)
+        .and_then(parse)
+        .and_then(execute);
+    match result {
+        Ok(()) => {
+            print!("OK\r\n");
+        }
+        Err(error) => {
+            print!("+CME ERROR:{}\r\n", error.code());
+        }
+    }
+}
+
+/// Collect command lines from UART0 without blocking, lines end with `\r` or `\n`
+fn poll_uart() {
+    let at = at_state();
+    while let Some(byte) = uart0().try_read_byte() {
+        if byte != b'\r' && byte != b'\n' {
+            if at.line.push(byte).is_err() {
+                at.overflow = true;
+            }
+            continue;
+        }
+
+        if at.overflow {
+            print!("+CME ERROR:{}\r\n", AtError::Parameter.code());
+        } else if !at.line.is_empty() {
+            let line = at.line.clone();
+            on_line(&line);
+        }
+        at.line.clear();
+        at.overflow = false;
+    }
+}
+
+/// Sleep until an interrupt: the radio, a timer or bytes on UART0
+fn sleep() {
+    _disable_irq();
+    if uart0().rx_available() == 0 {
+        // a pending interrupt ends the sleep even with the interrupts disabled
+        PWR.sleep_wfi(false);
+    }
+    _enable_irq();
+}
+
+/// AT command server start, never returns
+///
+/// Answers the Ai-Thinker RA-08 LoRaWAN AT command set on UART0 (115200 8N1, lines ending in
+/// `\r\n`). Every command ends with `OK` or `+CME ERROR:<code>`, uplink results and downlinks
+/// come as `OK+SENT`/`ERR+SENT` and `OK+RECV` lines.
+pub fn app_start() -> ! {
+    let at = at_state();
+    if let Some(settings) = load_settings() {
+        at.settings = settings;
+    }
+    timer_init(&mut at.join_timer, on_join_timer);
+    apply_settings(&at.settings);
+
+    print!("+BOOT:{}\r\n", env!("CARGO_PKG_VERSION"));
+    if let Some(args) = at.settings.auto_join
+        && start_join(args).is_err()
+    {
+        print!("+CJOIN:FAIL\r\n");
+    }
+
+    loop {
+        radio_irq_process();
+        poll_uart();
+        if at.settings.low_power && at.line.is_empty() {
+            sleep();
+        }
+    }
+}
@@ -5,11 +5,13 @@ use core::ptr;
 use crate::{
     ffi,
     lora::{
+        link_quality::link_stats,
         radio::radio_irq_process,
         timer::{TimerEvent, timer_init, timer_set_value, timer_start, timer_stop},
     },
     peripherals::{delay::delay_ms, gpio::GpioPin, regs::GPIOA},
     print, println,
+    rng::rng_range,
 };
 
 /// 30 seconds between transmissions (ms)
@@ -25,8 +27,12 @@ pub const LORAWAN_CONFIRMED_MSG_ON: bool = true;
 pub const LORAWAN_ADR_ON: bool = true;
 /// think of this as a channel
 pub const LORAWAN_APP_PORT: u8 = 2;
-/// bigger buffer size just in case, currently we only send 4 bytes
-pub const LORAWAN_APP_DATA_MAX_SIZE: usize = 16;
+/// fits a link quality report at DR0, the app data itself is 4 bytes
+pub const LORAWAN_APP_DATA_MAX_SIZE: usize = 51;
+/// port of the link quality reports
+pub const LINK_REPORT_PORT: u8 = 3;
+/// every n-th uplink carries a link quality report instead of the app data, 0 disables them
+pub const LINK_REPORT_EVERY: u32 = 0;
 
 /// EU 868 MHz region
 pub const ACTIVE_REGION: ffi::eLoRaMacRegion_t = ffi::LORAMAC_REGION_EU868;
@@ -69,6 +75,8 @@ struct App {
     next_tx: bool,
     /// current device state
     device_state: DeviceState,
+    /// uplinks prepared so far, schedules the link quality reports
+    uplinks: u32,
 
     /// timer for scheduling next packet transmission
     tx_next_packet_timer: TimerEvent,
@@ -92,6 +100,7 @@ impl App {
             tx_duty_cycle_time: APP_TX_DUTYCYCLE,
             next_tx: true,
             device_state: DeviceState::Init,
+            uplinks: 0,
 
             tx_next_packet_timer: TimerEvent {
                 id: 0,
@@ -104,7 +113,15 @@ impl App {
     }
 
     /// transmit data
-    fn prepare_tx_frame(&mut self, _port: u8) {
+    fn prepare_tx_frame(&mut self, port: u8) {
+        self.uplinks = self.uplinks.wrapping_add(1);
+        if LINK_REPORT_EVERY != 0 && self.uplinks.is_multiple_of(LINK_REPORT_EVERY) {
+            self.app_port = LINK_REPORT_PORT;
+            self.app_data_size = link_stats().encode_report(&mut self.app_data) as u16;
+            return;
+        }
+
+        self.app_port = port;
         self.app_data_size = 4;
         self.app_data[0] = 0x00;
         self.app_data[1] = 0x01;
@@ -400,12 +417,12 @@ pub fn app_start() -> ! {
 
             DeviceState::Send => unsafe {
                 if APP.next_tx {
-                    APP.prepare_tx_frame(APP.app_port);
+                    APP.prepare_tx_frame(LORAWAN_APP_PORT);
                     APP.next_tx = APP.send_frame();
                 }
 
                 APP.tx_duty_cycle_time =
-                    APP_TX_DUTYCYCLE + ffi::randr(0, APP_TX_DUTYCYCLE_RND as i32) as usize;
+                    APP_TX_DUTYCYCLE + rng_range(0, APP_TX_DUTYCYCLE_RND as i32) as usize;
                 APP.device_state = DeviceState::Cycle;
             },
 
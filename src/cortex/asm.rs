@@ -3,7 +3,10 @@
 /// No Operation does nothing. This instruction can be used for code alignment purposes.
 #[inline]
 pub fn _nop() {
-    unsafe { core::arch::asm!("nop") };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("nop");
+    }
 }
 
 /// Wait For Interrupt
@@ -12,7 +15,10 @@ pub fn _nop() {
 /// until one of a number of events occurs.
 #[inline]
 pub fn _wfi() {
-    unsafe { core::arch::asm!("wfi") };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("wfi");
+    }
 }
 
 /// Wait For Event
@@ -21,7 +27,10 @@ pub fn _wfi() {
 /// a low-power state until one of a number of events occurs.
 #[inline]
 pub fn _wfe() {
-    unsafe { core::arch::asm!("wfe") };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("wfe");
+    }
 }
 
 /// Send Event
@@ -29,7 +38,10 @@ pub fn _wfe() {
 /// Send Event is a hint instruction. It causes an event to be signaled to the CPU.
 #[inline]
 pub fn _sev() {
-    unsafe { core::arch::asm!("sev") };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("sev");
+    }
 }
 
 /// Instruction Synchronization Barrier
@@ -39,7 +51,10 @@ pub fn _sev() {
 /// memory, after the instruction has been completed.
 #[inline]
 pub fn _isb() {
-    unsafe { core::arch::asm!("isb") };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("isb");
+    }
 }
 
 /// Data Synchronization Barrier
@@ -48,7 +63,10 @@ pub fn _isb() {
 /// It completes when all explicit memory accesses before this instruction complete.
 #[inline]
 pub fn _dsb() {
-    unsafe { core::arch::asm!("dsb") };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("dsb");
+    }
 }
 
 /// Data Memory Barrier
@@ -57,7 +75,10 @@ pub fn _dsb() {
 /// and after the instruction, without ensuring their completion.
 #[inline]
 pub fn _dmb() {
-    unsafe { core::arch::asm!("dmb") };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("dmb");
+    }
 }
 
 /// Reverse byte order (32 bit)
@@ -4,7 +4,10 @@
 /// Can only be executed in Privileged modes.
 #[inline(always)]
 pub fn _enable_irq() {
-    unsafe { core::arch::asm!("cpsie i", options(nomem, nostack)) };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("cpsie i", options(nomem, nostack));
+    }
 }
 
 /// Disable IRQ Interrupts
@@ -13,7 +16,10 @@ pub fn _enable_irq() {
 /// Can only be executed in Privileged modes.
 #[inline(always)]
 pub fn _disable_irq() {
-    unsafe { core::arch::asm!("cpsid i", options(nomem, nostack)) };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("cpsid i", options(nomem, nostack));
+    }
 }
 
 /// Get Control Register
@@ -23,9 +29,16 @@ pub fn _disable_irq() {
 /// Returns the Control Register value.
 #[inline(always)]
 pub fn _get_control() -> usize {
-    let result: usize;
-    unsafe { core::arch::asm!("MRS {}, control", out(reg) result, options(nomem, nostack)) };
-    result
+    #[cfg(target_arch = "arm")]
+    {
+        let result: usize;
+        unsafe {
+            core::arch::asm!("MRS {}, control", out(reg) result, options(nomem, nostack));
+        }
+        result
+    }
+    #[cfg(not(target_arch = "arm"))]
+    0
 }
 
 /// Set Control Register
@@ -36,7 +49,12 @@ pub fn _get_control() -> usize {
 /// - `control`: Control Register value to set
 #[inline(always)]
 pub fn _set_control(control: usize) {
-    unsafe { core::arch::asm!("MSR control, {}", in(reg) control, options(nostack)) };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("MSR control, {}", in(reg) control, options(nostack));
+    }
+    #[cfg(not(target_arch = "arm"))]
+    let _ = control;
 }
 
 /// Get IPSR Register
@@ -46,9 +64,16 @@ pub fn _set_control(control: usize) {
 /// Returns the IPSR Register value.
 #[inline(always)]
 pub fn _get_ipsr() -> usize {
-    let result: usize;
-    unsafe { core::arch::asm!("MRS {}, ipsr", out(reg) result, options(nomem, nostack)) };
-    result
+    #[cfg(target_arch = "arm")]
+    {
+        let result: usize;
+        unsafe {
+            core::arch::asm!("MRS {}, ipsr", out(reg) result, options(nomem, nostack));
+        }
+        result
+    }
+    #[cfg(not(target_arch = "arm"))]
+    0
 }
 
 /// Get APSR Register
@@ -58,9 +83,16 @@ pub fn _get_ipsr() -> usize {
 /// Returns the APSR Register value.
 #[inline(always)]
 pub fn _get_apsr() -> usize {
-    let result: usize;
-    unsafe { core::arch::asm!("MRS {}, apsr", out(reg) result, options(nomem, nostack)) };
-    result
+    #[cfg(target_arch = "arm")]
+    {
+        let result: usize;
+        unsafe {
+            core::arch::asm!("MRS {}, apsr", out(reg) result, options(nomem, nostack));
+        }
+        result
+    }
+    #[cfg(not(target_arch = "arm"))]
+    0
 }
 
 /// Get xPSR Register
@@ -70,9 +102,16 @@ pub fn _get_apsr() -> usize {
 /// Returns the xPSR Register value.
 #[inline(always)]
 pub fn _get_xpsr() -> usize {
-    let result: usize;
-    unsafe { core::arch::asm!("MRS {}, xpsr", out(reg) result, options(nomem, nostack)) };
-    result
+    #[cfg(target_arch = "arm")]
+    {
+        let result: usize;
+        unsafe {
+            core::arch::asm!("MRS {}, xpsr", out(reg) result, options(nomem, nostack));
+        }
+        result
+    }
+    #[cfg(not(target_arch = "arm"))]
+    0
 }
 
 /// Get Process Stack Pointer
@@ -82,9 +121,16 @@ pub fn _get_xpsr() -> usize {
 /// Returns the PSP Register value.
 #[inline(always)]
 pub fn _get_psp() -> usize {
-    let result: usize;
-    unsafe { core::arch::asm!("MRS {}, psp", out(reg) result, options(nomem, nostack)) };
-    result
+    #[cfg(target_arch = "arm")]
+    {
+        let result: usize;
+        unsafe {
+            core::arch::asm!("MRS {}, psp", out(reg) result, options(nomem, nostack));
+        }
+        result
+    }
+    #[cfg(not(target_arch = "arm"))]
+    0
 }
 
 /// Set Process Stack Pointer
@@ -95,7 +141,12 @@ pub fn _get_psp() -> usize {
 /// - `top_of_proc_stack`: Process Stack Pointer value to set
 #[inline(always)]
 pub fn _set_psp(top_of_proc_stack: usize) {
-    unsafe { core::arch::asm!("MSR psp, {}", in(reg) top_of_proc_stack, options(nomem, nostack)) };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("MSR psp, {}", in(reg) top_of_proc_stack, options(nomem, nostack));
+    }
+    #[cfg(not(target_arch = "arm"))]
+    let _ = top_of_proc_stack;
 }
 
 /// Get Main Stack Pointer
@@ -105,9 +156,16 @@ pub fn _set_psp(top_of_proc_stack: usize) {
 /// Returns the MSP Register value.
 #[inline(always)]
 pub fn _get_msp() -> usize {
-    let result: usize;
-    unsafe { core::arch::asm!("MRS {}, msp", out(reg) result, options(nomem, nostack)) };
-    result
+    #[cfg(target_arch = "arm")]
+    {
+        let result: usize;
+        unsafe {
+            core::arch::asm!("MRS {}, msp", out(reg) result, options(nomem, nostack));
+        }
+        result
+    }
+    #[cfg(not(target_arch = "arm"))]
+    0
 }
 
 /// Set Main Stack Pointer
@@ -118,7 +176,12 @@ pub fn _get_msp() -> usize {
 /// - `top_of_main_stack`: Main Stack Pointer value to set
 #[inline(always)]
 pub fn _set_msp(top_of_main_stack: usize) {
-    unsafe { core::arch::asm!("MSR msp, {}", in(reg) top_of_main_stack, options(nomem, nostack)) };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("MSR msp, {}", in(reg) top_of_main_stack, options(nomem, nostack));
+    }
+    #[cfg(not(target_arch = "arm"))]
+    let _ = top_of_main_stack;
 }
 
 /// Get Priority Mask
@@ -128,9 +191,16 @@ pub fn _set_msp(top_of_main_stack: usize) {
 /// Returns the Priority Mask value.
 #[inline(always)]
 pub fn _get_primask() -> usize {
-    let result: usize;
-    unsafe { core::arch::asm!("MRS {}, primask", out(reg) result, options(nomem, nostack)) };
-    result
+    #[cfg(target_arch = "arm")]
+    {
+        let result: usize;
+        unsafe {
+            core::arch::asm!("MRS {}, primask", out(reg) result, options(nomem, nostack));
+        }
+        result
+    }
+    #[cfg(not(target_arch = "arm"))]
+    0
 }
 
 /// Set Priority Mask
@@ -141,5 +211,10 @@ pub fn _get_primask() -> usize {
 /// - `pri_mask`: Priority Mask
 #[inline(always)]
 pub fn _set_primask(pri_mask: usize) {
-    unsafe { core::arch::asm!("MSR primask, {}", in(reg) pri_mask, options(nostack)) };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("MSR primask, {}", in(reg) pri_mask, options(nostack));
+    }
+    #[cfg(not(target_arch = "arm"))]
+    let _ = pri_mask;
 }
@@ -0,0 +1,390 @@
+use crate::crypto::{BLOCK_SIZE, Block, BlockCipher, KeyInit};
+
+/// AES-128 rounds
+const ROUNDS: usize = 10;
+
+/// Key schedule round constants
+const RCON: [u8; ROUNDS] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1B, 0x36];
+
+/// Software AES-128.
+///
+/// Constant time: the S-box is evaluated as a boolean circuit (Boyar-Peralta) over bit planes
+/// of the whole state, so there are no secret dependent table lookups or branches.
+#[derive(Clone)]
+pub struct Aes128 {
+    round_keys: [Block; ROUNDS + 1],
+}
+
+impl Aes128 {
+    /// Expand a 128-bit key
+    pub fn new(key: &[u8; 16]) -> Self {
+        let mut round_keys = [[0u8; BLOCK_SIZE]; ROUNDS + 1];
+        round_keys[0] = *key;
+
+        for round in 1..=ROUNDS {
+            let prev = round_keys[round - 1];
+
+            // RotWord + SubWord of the last word, through the 16 byte S-box
+            let mut temp = [0u8; BLOCK_SIZE];
+            temp[..4].copy_from_slice(&[prev[13], prev[14], prev[15], prev[12]]);
+            sub_bytes(&mut temp);
+            temp[0] ^= RCON[round - 1];
+
+            let next = &mut round_keys[round];
+            for i in 0..4 {
+                next[i] = prev[i] ^ temp[i];
+            }
+            for i in 4..BLOCK_SIZE {
+                next[i] = prev[i] ^ next[i - 4];
+            }
+        }
+
+        Self { round_keys }
+    }
+}
+
+impl BlockCipher for Aes128 {
+    fn encrypt_block(&mut self, block: &mut Block) {
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..ROUNDS {
+            sub_bytes(block);
+            shift_rows(block);
+            mix_columns(block);
+            add_round_key(block, &self.round_keys[round]);
+        }
+        sub_bytes(block);
+        shift_rows(block);
+        add_round_key(block, &self.round_keys[ROUNDS]);
+    }
+
+    fn decrypt_block(&mut self, block: &mut Block) {
+        add_round_key(block, &self.round_keys[ROUNDS]);
+        for round in (1..ROUNDS).rev() {
+            inv_shift_rows(block);
+            inv_sub_bytes(block);
+            add_round_key(block, &self.round_keys[round]);
+            inv_mix_columns(block);
+        }
+        inv_shift_rows(block);
+        inv_sub_bytes(block);
+        add_round_key(block, &self.round_keys[0]);
+    }
+}
+
+impl KeyInit for Aes128 {
+    fn with_key(key: &[u8; 16]) -> Self {
+        Self::new(key)
+    }
+}
+
+impl Drop for Aes128 {
+    fn drop(&mut self) {
+        // don't leave the key schedule on the stack
+        for key in self.round_keys.iter_mut() {
+            for byte in key.iter_mut() {
+                unsafe { core::ptr::write_volatile(byte, 0) };
+            }
+        }
+    }
+}
+
+fn add_round_key(block: &mut Block, key: &Block) {
+    for (b, k) in block.iter_mut().zip(key.iter()) {
+        *b ^= k;
+    }
+}
+
+/// Multiply by x in GF(2^8), without branching on the value
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ (0x1B & 0u8.wrapping_sub(b >> 7))
+}
+
+fn shift_rows(block: &mut Block) {
+    let s = *block;
+    for col in 0..4 {
+        for row in 0..4 {
+            block[col * 4 + row] = s[((col + row) % 4) * 4 + row];
+        }
+    }
+}
+
+fn inv_shift_rows(block: &mut Block) {
+    let s = *block;
+    for col in 0..4 {
+        for row in 0..4 {
+            block[((col + row) % 4) * 4 + row] = s[col * 4 + row];
+        }
+    }
+}
+
+fn mix_columns(block: &mut Block) {
+    for col in block.chunks_exact_mut(4) {
+        let (a0, a1, a2, a3) = (col[0], col[1], col[2], col[3]);
+        let all = a0 ^ a1 ^ a2 ^ a3;
+        col[0] ^= all ^ xtime(a0 ^ a1);
+        col[1] ^= all ^ xtime(a1 ^ a2);
+        col[2] ^= all ^ xtime(a2 ^ a3);
+        col[3] ^= all ^ xtime(a3 ^ a0);
+    }
+}
+
+fn inv_mix_columns(block: &mut Block) {
+    // InvMixColumns = MixColumns after multiplying each column by {04}x^2 + {05}
+    for col in block.chunks_exact_mut(4) {
+        let u = xtime(xtime(col[0] ^ col[2]));
+        let v = xtime(xtime(col[1] ^ col[3]));
+        col[0] ^= u;
+        col[1] ^= v;
+        col[2] ^= u;
+        col[3] ^= v;
+    }
+    mix_columns(block);
+}
+
+/// Inverse of the S-box affine transform, constant included
+fn inv_affine(b: u8) -> u8 {
+    b.rotate_left(1) ^ b.rotate_left(3) ^ b.rotate_left(6) ^ 0x05
+}
+
+fn inv_sub_bytes(block: &mut Block) {
+    // S^-1(x) = A^-1(S(A^-1(x))): the field inversion is the S-box without its affine part
+    for b in block.iter_mut() {
+        *b = inv_affine(*b);
+    }
+    sub_bytes(block);
+    for b in block.iter_mut() {
+        *b = inv_affine(*b);
+    }
+}
+
+/// S-box over the 16 bytes at once, bit `i` of every byte packed in `u[7 - i]`
+fn sub_bytes(block: &mut Block) {
+    let mut u = [0u16; 8];
+    for (j, &b) in block.iter().enumerate() {
+        for (i, plane) in u.iter_mut().enumerate() {
+            *plane |= (((b >> (7 - i)) & 1) as u16) << j;
+        }
+    }
+
+    let s = sbox_circuit(&u);
+
+    for (j, b) in block.iter_mut().enumerate() {
+        let mut out = 0u8;
+        for (i, plane) in s.iter().enumerate() {
+            out |= (((plane >> j) & 1) as u8) << (7 - i);
+        }
+        *b = out;
+    }
+}
+
+/// Boyar-Peralta depth 16 S-box circuit, `u[0]` / `s[0]` are the most significant bits
+fn sbox_circuit(u: &[u16; 8]) -> [u16; 8] {
+    let [u0, u1, u2, u3, u4, u5, u6, u7] = *u;
+
+    // top linear layer
+    let t1 = u0 ^ u3;
+    let t2 = u0 ^ u5;
+    let t3 = u0 ^ u6;
+    let t4 = u3 ^ u5;
+    let t5 = u4 ^ u6;
+    let t6 = t1 ^ t5;
+    let t7 = u1 ^ u2;
+    let t8 = u7 ^ t6;
+    let t9 = u7 ^ t7;
+    let t10 = t6 ^ t7;
+    let t11 = u1 ^ u5;
+    let t12 = u2 ^ u5;
+    let t13 = t3 ^ t4;
+    let t14 = t6 ^ t11;
+    let t15 = t5 ^ t11;
+    let t16 = t5 ^ t12;
+    let t17 = t9 ^ t16;
+    let t18 = u3 ^ u7;
+    let t19 = t7 ^ t18;
+    let t20 = t1 ^ t19;
+    let t21 = u6 ^ u7;
+    let t22 = t7 ^ t21;
+    let t23 = t2 ^ t22;
+    let t24 = t2 ^ t10;
+    let t25 = t20 ^ t17;
+    let t26 = t3 ^ t16;
+    let t27 = t1 ^ t12;
+
+    // inversion in GF(2^4)^2
+    let m1 = t13 & t6;
+    let m2 = t23 & t8;
+    let m3 = t14 ^ m1;
+    let m4 = t19 & u7;
+    let m5 = m4 ^ m1;
+    let m6 = t3 & t16;
+    let m7 = t22 & t9;
+    let m8 = t26 ^ m6;
+    let m9 = t20 & t17;
+    let m10 = m9 ^ m6;
+    let m11 = t1 & t15;
+    let m12 = t4 & t27;
+    let m13 = m12 ^ m11;
+    let m14 = t2 & t10;
+    let m15 = m14 ^ m11;
+    let m16 = m3 ^ m2;
+    let m17 = m5 ^ t24;
+    let m18 = m8 ^ m7;
+    let m19 = m10 ^ m15;
+    let m20 = m16 ^ m13;
+    let m21 = m17 ^ m15;
+    let m22 = m18 ^ m13;
+    let m23 = m19 ^ t25;
+    let m24 = m22 ^ m23;
+    let m25 = m22 & m20;
+    let m26 = m21 ^ m25;
+    let m27 = m20 ^ m21;
+    let m28 = m23 ^ m25;
+    let m29 = m28 & m27;
+    let m30 = m26 & m24;
+    let m31 = m20 & m23;
+    let m32 = m27 & m31;
+    let m33 = m27 ^ m25;
+    let m34 = m21 & m22;
+    let m35 = m24 & m34;
+    let m36 = m24 ^ m25;
+    let m37 = m21 ^ m29;
+    let m38 = m32 ^ m33;
+    let m39 = m23 ^ m30;
+    let m40 = m35 ^ m36;
+    let m41 = m38 ^ m40;
+    let m42 = m37 ^ m39;
+    let m43 = m37 ^ m38;
+    let m44 = m39 ^ m40;
+    let m45 = m42 ^ m41;
+    let m46 = m44 & t6;
+    let m47 = m40 & t8;
+    let m48 = m39 & u7;
+    let m49 = m43 & t16;
+    let m50 = m38 & t9;
+    let m51 = m37 & t17;
+    let m52 = m42 & t15;
+    let m53 = m45 & t27;
+    let m54 = m41 & t10;
+    let m55 = m44 & t13;
+    let m56 = m40 & t23;
+    let m57 = m39 & t19;
+    let m58 = m43 & t3;
+    let m59 = m38 & t22;
+    let m60 = m37 & t20;
+    let m61 = m42 & t1;
+    let m62 = m45 & t4;
+    let m63 = m41 & t2;
+
+    // bottom linear layer
+    let l0 = m61 ^ m62;
+    let l1 = m50 ^ m56;
+    let l2 = m46 ^ m48;
+    let l3 = m47 ^ m55;
+    let l4 = m54 ^ m58;
+    let l5 = m49 ^ m61;
+    let l6 = m62 ^ l5;
+    let l7 = m46 ^ l3;
+    let l8 = m51 ^ m59;
+    let l9 = m52 ^ m53;
+    let l10 = m53 ^ l4;
+    let l11 = m60 ^ l2;
+    let l12 = m48 ^ m51;
+    let l13 = m50 ^ l0;
+    let l14 = m52 ^ m61;
+    let l15 = m55 ^ l1;
+    let l16 = m56 ^ l0;
+    let l17 = m57 ^ l1;
+    let l18 = m58 ^ l8;
+    let l19 = m63 ^ l4;
+    let l20 = l0 ^ l1;
+    let l21 = l1 ^ l7;
+    let l22 = l3 ^ l12;
+    let l23 = l18 ^ l2;
+    let l24 = l15 ^ l9;
+    let l25 = l6 ^ l10;
+    let l26 = l7 ^ l9;
+    let l27 = l8 ^ l10;
+    let l28 = l11 ^ l14;
+    let l29 = l11 ^ l17;
+
+    [
+        l6 ^ l24,
+        !(l16 ^ l26),
+        !(l19 ^ l28),
+        l6 ^ l21,
+        l20 ^ l22,
+        l25 ^ l29,
+        !(l13 ^ l27),
+        !(l6 ^ l23),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FIPS-197 appendix A.1 key
+    const KEY: [u8; 16] = [
+        0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F,
+        0x3C,
+    ];
+
+    #[test]
+    fn key_schedule() {
+        let aes = Aes128::new(&KEY);
+        assert_eq!(aes.round_keys[0], KEY);
+        assert_eq!(
+            aes.round_keys[1],
+            [
+                0xA0, 0xFA, 0xFE, 0x17, 0x88, 0x54, 0x2C, 0xB1, 0x23, 0xA3, 0x39, 0x39, 0x2A, 0x6C,
+                0x76, 0x05
+            ]
+        );
+        assert_eq!(
+            aes.round_keys[ROUNDS],
+            [
+                0xD0, 0x14, 0xF9, 0xA8, 0xC9, 0xEE, 0x25, 0x89, 0xE1, 0x3F, 0x0C, 0xC8, 0xB6, 0x63,
+                0x0C, 0xA6
+            ]
+        );
+    }
+
+    #[test]
+    fn sbox() {
+        let mut block = [
+            0x00, 0x01, 0x53, 0xFF, 0x10, 0x9A, 0xC9, 0x7C, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        sub_bytes(&mut block);
+        assert_eq!(block[..8], [0x63, 0x7C, 0xED, 0x16, 0xCA, 0xB8, 0xDD, 0x10]);
+
+        // the inverse circuit undoes every value
+        for start in (0..=255u8).step_by(BLOCK_SIZE) {
+            let values: Block = core::array::from_fn(|i| start + i as u8);
+            let mut block = values;
+            sub_bytes(&mut block);
+            inv_sub_bytes(&mut block);
+            assert_eq!(block, values);
+        }
+    }
+
+    #[test]
+    fn encrypt_decrypt() {
+        // SP 800-38A F.1.1, ECB-AES128 block 1
+        let mut aes = Aes128::new(&KEY);
+        let plain = [
+            0x6B, 0xC1, 0xBE, 0xE2, 0x2E, 0x40, 0x9F, 0x96, 0xE9, 0x3D, 0x7E, 0x11, 0x73, 0x93,
+            0x17, 0x2A,
+        ];
+        let mut block = plain;
+        aes.encrypt_block(&mut block);
+        assert_eq!(
+            block,
+            [
+                0x3A, 0xD7, 0x7B, 0xB4, 0x0D, 0x7A, 0x36, 0x60, 0xA8, 0x9E, 0xCA, 0xF3, 0x24, 0x66,
+                0xEF, 0x97
+            ]
+        );
+        aes.decrypt_block(&mut block);
+        assert_eq!(block, plain);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::crypto::{BLOCK_SIZE, Block, BlockCipher};
+
+/// Double a value in GF(2^128), used for the CMAC subkeys
+fn gf_double(block: &Block) -> Block {
+    let mut out = [0u8; BLOCK_SIZE];
+    let mut carry = 0u8;
+    for i in (0..BLOCK_SIZE).rev() {
+        out[i] = (block[i] << 1) | carry;
+        carry = block[i] >> 7;
+    }
+    // constant time reduction
+    out[BLOCK_SIZE - 1] ^= 0x87 & 0u8.wrapping_sub(carry);
+    out
+}
+
+fn xor(dst: &mut Block, src: &Block) {
+    for (d, s) in dst.iter_mut().zip(src.iter()) {
+        *d ^= s;
+    }
+}
+
+/// Streaming AES-CMAC state.
+///
+/// Doesn't own the cipher, so the C `AES_CMAC_CTX` can be mapped onto it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CmacState {
+    /// chaining value
+    pub x: Block,
+    /// last, possibly partial, block
+    pub last: Block,
+    /// bytes in `last`
+    pub filled: usize,
+}
+
+impl CmacState {
+    /// Empty state
+    pub const fn new() -> Self {
+        Self {
+            x: [0; BLOCK_SIZE],
+            last: [0; BLOCK_SIZE],
+            filled: 0,
+        }
+    }
+
+    /// Absorb `data`. The last block is kept back until [`CmacState::finalize`]
+    pub fn update<C: BlockCipher>(&mut self, cipher: &mut C, data: &[u8]) {
+        for &byte in data {
+            if self.filled == BLOCK_SIZE {
+                // not the last block, chain it
+                xor(&mut self.x, &self.last);
+                cipher.encrypt_block(&mut self.x);
+                self.filled = 0;
+            }
+            self.last[self.filled] = byte;
+            self.filled += 1;
+        }
+    }
+
+    /// Pad the last block and compute the tag
+    pub fn finalize<C: BlockCipher>(mut self, cipher: &mut C) -> Block {
+        let mut l = [0u8; BLOCK_SIZE];
+        cipher.encrypt_block(&mut l);
+        let k1 = gf_double(&l);
+
+        if self.filled == BLOCK_SIZE {
+            xor(&mut self.last, &k1);
+        } else {
+            self.last[self.filled] = 0x80;
+            self.last[self.filled + 1..].fill(0);
+            xor(&mut self.last, &gf_double(&k1));
+        }
+
+        xor(&mut self.x, &self.last);
+        cipher.encrypt_block(&mut self.x);
+        self.x
+    }
+}
+
+/// AES-CMAC over the concatenation of `chunks`
+pub fn cmac<C: BlockCipher>(cipher: &mut C, chunks: &[&[u8]]) -> Block {
+    let mut state = CmacState::new();
+    for chunk in chunks {
+        state.update(cipher, chunk);
+    }
+    state.finalize(cipher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::aes::Aes128;
+
+    const KEY: [u8; 16] = [
+        0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F,
+        0x3C,
+    ];
+
+    #[test]
+    fn subkeys() {
+        // RFC 4493 section 4, subkey generation
+        let mut l = [0u8; BLOCK_SIZE];
+        Aes128::new(&KEY).encrypt_block(&mut l);
+        let k1 = gf_double(&l);
+        assert_eq!(
+            k1,
+            [
+                0xFB, 0xEE, 0xD6, 0x18, 0x35, 0x71, 0x33, 0x66, 0x7C, 0x85, 0xE0, 0x8F, 0x72, 0x36,
+                0xA8, 0xDE
+            ]
+        );
+        assert_eq!(
+            gf_double(&k1),
+            [
+                0xF7, 0xDD, 0xAC, 0x30, 0x6A, 0xE2, 0x66, 0xCC, 0xF9, 0x0B, 0xC1, 0x1E, 0xE4, 0x6D,
+                0x51, 0x3B
+            ]
+        );
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let mut aes = Aes128::new(&KEY);
+        let message: [u8; 64] = core::array::from_fn(|i| (i * 13) as u8);
+        for len in [0, 1, 15, 16, 17, 32, 33, 64] {
+            let expected = cmac(&mut aes, &[&message[..len]]);
+            for split in 0..=len {
+                let mut state = CmacState::new();
+                state.update(&mut aes, &message[..split]);
+                state.update(&mut aes, &message[split..len]);
+                assert_eq!(state.finalize(&mut aes), expected, "{len} split at {split}");
+            }
+        }
+    }
+
+    #[test]
+    fn rfc4493_64_bytes() {
+        let mut aes = Aes128::new(&KEY);
+        let message: [u8; 64] = [
+            0x6B, 0xC1, 0xBE, 0xE2, 0x2E, 0x40, 0x9F, 0x96, 0xE9, 0x3D, 0x7E, 0x11, 0x73, 0x93,
+            0x17, 0x2A, 0xAE, 0x2D, 0x8A, 0x57, 0x1E, 0x03, 0xAC, 0x9C, 0x9E, 0xB7, 0x6F, 0xAC,
+            0x45, 0xAF, 0x8E, 0x51, 0x30, 0xC8, 0x1C, 0x46, 0xA3, 0x5C, 0xE4, 0x11, 0xE5, 0xFB,
+            0xC1, 0x19, 0x1A, 0x0A, 0x52, 0xEF, 0xF6, 0x9F, 0x24, 0x45, 0xDF, 0x4F, 0x9B, 0x17,
+            0xAD, 0x2B, 0x41, 0x7B, 0xE6, 0x6C, 0x37, 0x10,
+        ];
+        assert_eq!(
+            cmac(&mut aes, &[&message]),
+            [
+                0x51, 0xF0, 0xBE, 0xBF, 0x7E, 0x3B, 0x9D, 0x92, 0xFC, 0x49, 0x74, 0x17, 0x79, 0x36,
+                0x3C, 0xFE
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use rand_core::{CryptoRng, RngCore, SeedableRng};
+
+use crate::crypto::{BLOCK_SIZE, Block, KeyInit, aes::Aes128};
+
+/// Seed length: key plus counter block
+pub const SEED_SIZE: usize = 32;
+
+/// Generate calls allowed between reseeds.
+///
+/// SP 800-90A allows 2^48, this is low enough for the radio noise to be mixed in regularly.
+pub const RESEED_INTERVAL: u32 = 1 << 16;
+
+/// Largest request served by a single generate call
+const MAX_REQUEST: usize = 1 << 12;
+
+/// CTR_DRBG over AES-128 (NIST SP 800-90A, no derivation function).
+///
+/// The caller condenses its entropy into a full-entropy [`SEED_SIZE`] seed, the service in
+/// [`crate::rng`] hashes the TRNG and radio noise with SHA-256 for that.
+pub struct CtrDrbg<C: KeyInit = Aes128> {
+    cipher: C,
+    v: Block,
+    reseed_counter: u32,
+}
+
+impl<C: KeyInit> CtrDrbg<C> {
+    /// Instantiate from a full-entropy seed
+    pub fn new(seed: &[u8; SEED_SIZE]) -> Self {
+        let mut drbg = Self {
+            cipher: C::with_key(&[0; 16]),
+            v: [0; BLOCK_SIZE],
+            reseed_counter: 1,
+        };
+        drbg.update(seed);
+        drbg
+    }
+
+    /// Mix a fresh seed into the state
+    pub fn reseed(&mut self, seed: &[u8; SEED_SIZE]) {
+        self.update(seed);
+        self.reseed_counter = 1;
+    }
+
+    /// The reseed interval is over, [`Self::generate`] keeps working but should get a reseed
+    pub fn needs_reseed(&self) -> bool {
+        self.reseed_counter > RESEED_INTERVAL
+    }
+
+    /// Fill `out` with random bytes, with backtracking resistance after each call
+    pub fn generate(&mut self, out: &mut [u8]) {
+        for request in out.chunks_mut(MAX_REQUEST) {
+            for chunk in request.chunks_mut(BLOCK_SIZE) {
+                let block = self.next_block();
+                chunk.copy_from_slice(&block[..chunk.len()]);
+            }
+            self.update(&[0; SEED_SIZE]);
+            self.reseed_counter = self.reseed_counter.saturating_add(1);
+        }
+    }
+
+    fn next_block(&mut self) -> Block {
+        increment(&mut self.v);
+        let mut block = self.v;
+        self.cipher.encrypt_block(&mut block);
+        block
+    }
+
+    /// `CTR_DRBG_Update`: derive a new key and V from the state and `provided`
+    fn update(&mut self, provided: &[u8; SEED_SIZE]) {
+        let mut temp = [0u8; SEED_SIZE];
+        for chunk in temp.chunks_exact_mut(BLOCK_SIZE) {
+            chunk.copy_from_slice(&self.next_block());
+        }
+        for (t, p) in temp.iter_mut().zip(provided.iter()) {
+            *t ^= p;
+        }
+
+        let mut key = [0u8; 16];
+        key.copy_from_slice(&temp[..BLOCK_SIZE]);
+        self.cipher = C::with_key(&key);
+        self.v.copy_from_slice(&temp[BLOCK_SIZE..]);
+
+        for byte in key.iter_mut().chain(temp.iter_mut()) {
+            unsafe { core::ptr::write_volatile(byte, 0) };
+        }
+    }
+}
+
+impl<C: KeyInit> Drop for CtrDrbg<C> {
+    fn drop(&mut self) {
+        // the cipher wipes the key itself
+        for byte in self.v.iter_mut() {
+            unsafe { core::ptr::write_volatile(byte, 0) };
+        }
+    }
+}
+
+/// 128-bit big endian increment of V
+fn increment(v: &mut Block) {
+    for byte in v.iter_mut().rev() {
+        *byte = byte.wrapping_add(1);
+        if *byte != 0 {
+            break;
+        }
+    }
+}
+
+impl<C: KeyInit> RngCore for CtrDrbg<C> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.generate(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.generate(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        self.generate(dst);
+    }
+}
+
+impl<C: KeyInit> CryptoRng for CtrDrbg<C> {}
+
+impl<C: KeyInit> SeedableRng for CtrDrbg<C> {
+    type Seed = [u8; SEED_SIZE];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self::new(&seed)
+    }
+}
@@ -0,0 +1,295 @@
+use crate::crypto::{
+    aes::Aes128,
+    cmac::cmac,
+    sha256::{Sha256, Sha256Compress, SoftCompress},
+};
+
+/// Constant time software AES-128
+pub mod aes;
+/// AES-CMAC (RFC 4493)
+pub mod cmac;
+/// AES-128 CTR_DRBG (NIST SP 800-90A)
+pub mod drbg;
+/// `aes.h` / `cmac.h` entry points for the C LoRaMac, replacing `libcrypto.a` and `cmac.c`
+pub mod sdk;
+/// SHA-256 (FIPS 180-4)
+pub mod sha256;
+
+/// Cipher block size
+pub const BLOCK_SIZE: usize = 16;
+
+/// Single cipher block
+pub type Block = [u8; BLOCK_SIZE];
+
+/// Input is not a whole number of blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockLengthError;
+
+/// Keyed 128-bit block cipher.
+///
+/// Implemented in software by [`Aes128`] and on the security engine by
+/// [`HwAes128`](crate::peripherals::sae::HwAes128), so the modes here run the same on both.
+pub trait BlockCipher {
+    /// Encrypt a single block in place
+    fn encrypt_block(&mut self, block: &mut Block);
+    /// Decrypt a single block in place
+    fn decrypt_block(&mut self, block: &mut Block);
+}
+
+/// Block cipher set up from a 128-bit key
+pub trait KeyInit: BlockCipher + Sized {
+    /// Expand or load `key`
+    fn with_key(key: &[u8; 16]) -> Self;
+}
+
+/// Encrypt blocks in place in ECB mode
+pub fn ecb_encrypt<C: BlockCipher>(
+    cipher: &mut C,
+    data: &mut [u8],
+) -> Result<(), BlockLengthError> {
+    if !data.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(BlockLengthError);
+    }
+    for chunk in data.chunks_exact_mut(BLOCK_SIZE) {
+        cipher.encrypt_block(chunk.try_into().unwrap());
+    }
+    Ok(())
+}
+
+/// Decrypt blocks in place in ECB mode
+pub fn ecb_decrypt<C: BlockCipher>(
+    cipher: &mut C,
+    data: &mut [u8],
+) -> Result<(), BlockLengthError> {
+    if !data.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(BlockLengthError);
+    }
+    for chunk in data.chunks_exact_mut(BLOCK_SIZE) {
+        cipher.decrypt_block(chunk.try_into().unwrap());
+    }
+    Ok(())
+}
+
+/// Encrypt or decrypt in place in CTR mode.
+///
+/// `counter` is the initial counter block, incremented as a big endian 128-bit number per block
+/// and left pointing at the next unused block so a stream can be continued.
+pub fn ctr_apply<C: BlockCipher>(cipher: &mut C, counter: &mut Block, data: &mut [u8]) {
+    for chunk in data.chunks_mut(BLOCK_SIZE) {
+        let mut keystream = *counter;
+        cipher.encrypt_block(&mut keystream);
+        for (byte, k) in chunk.iter_mut().zip(keystream.iter()) {
+            *byte ^= k;
+        }
+        increment_counter(counter);
+    }
+}
+
+fn increment_counter(counter: &mut Block) {
+    let mut carry = 1u16;
+    for byte in counter.iter_mut().rev() {
+        let sum = *byte as u16 + carry;
+        *byte = sum as u8;
+        carry = sum >> 8;
+    }
+}
+
+/// FIPS-197 appendix C.1
+const AES_KEY: [u8; 16] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+];
+const AES_PLAINTEXT: Block = [
+    0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF,
+];
+const AES_CIPHERTEXT: Block = [
+    0x69, 0xC4, 0xE0, 0xD8, 0x6A, 0x7B, 0x04, 0x30, 0xD8, 0xCD, 0xB7, 0x80, 0x70, 0xB4, 0xC5, 0x5A,
+];
+
+/// NIST SP 800-38A F.5.1 (CTR-AES128), first two blocks
+const CTR_KEY: [u8; 16] = [
+    0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F, 0x3C,
+];
+const CTR_COUNTER: Block = [
+    0xF0, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8, 0xF9, 0xFA, 0xFB, 0xFC, 0xFD, 0xFE, 0xFF,
+];
+const CTR_PLAINTEXT: [u8; 32] = [
+    0x6B, 0xC1, 0xBE, 0xE2, 0x2E, 0x40, 0x9F, 0x96, 0xE9, 0x3D, 0x7E, 0x11, 0x73, 0x93, 0x17, 0x2A,
+    0xAE, 0x2D, 0x8A, 0x57, 0x1E, 0x03, 0xAC, 0x9C, 0x9E, 0xB7, 0x6F, 0xAC, 0x45, 0xAF, 0x8E, 0x51,
+];
+const CTR_CIPHERTEXT: [u8; 32] = [
+    0x87, 0x4D, 0x61, 0x91, 0xB6, 0x20, 0xE3, 0x26, 0x1B, 0xEF, 0x68, 0x64, 0x99, 0x0D, 0xB6, 0xCE,
+    0x98, 0x06, 0xF6, 0x6B, 0x79, 0x70, 0xFD, 0xFF, 0x86, 0x17, 0x18, 0x7B, 0xB9, 0xFF, 0xFD, 0xFF,
+];
+
+/// RFC 4493 section 4, examples 1 to 3 (the key is the SP 800-38A one)
+const CMAC_MESSAGE: [u8; 40] = [
+    0x6B, 0xC1, 0xBE, 0xE2, 0x2E, 0x40, 0x9F, 0x96, 0xE9, 0x3D, 0x7E, 0x11, 0x73, 0x93, 0x17, 0x2A,
+    0xAE, 0x2D, 0x8A, 0x57, 0x1E, 0x03, 0xAC, 0x9C, 0x9E, 0xB7, 0x6F, 0xAC, 0x45, 0xAF, 0x8E, 0x51,
+    0x30, 0xC8, 0x1C, 0x46, 0xA3, 0x5C, 0xE4, 0x11,
+];
+const CMAC_VECTORS: [(usize, Block); 3] = [
+    (
+        0,
+        [
+            0xBB, 0x1D, 0x69, 0x29, 0xE9, 0x59, 0x37, 0x28, 0x7F, 0xA3, 0x7D, 0x12, 0x9B, 0x75,
+            0x67, 0x46,
+        ],
+    ),
+    (
+        16,
+        [
+            0x07, 0x0A, 0x16, 0xB4, 0x6B, 0x4D, 0x41, 0x44, 0xF7, 0x9B, 0xDD, 0x9D, 0xD0, 0x4A,
+            0x28, 0x7C,
+        ],
+    ),
+    (
+        40,
+        [
+            0xDF, 0xA6, 0x67, 0x47, 0xDE, 0x9A, 0xE6, 0x30, 0x30, 0xCA, 0x32, 0x61, 0x14, 0x97,
+            0xC8, 0x27,
+        ],
+    ),
+];
+
+/// Check a cipher against the FIPS-197, SP 800-38A and RFC 4493 known answers.
+///
+/// `new` builds the cipher for a key, so the same vectors cover [`Aes128`] and any other
+/// [`BlockCipher`] implementation. Returns `false` on the first mismatch.
+pub fn known_answer_test<C: BlockCipher>(mut new: impl FnMut(&[u8; 16]) -> C) -> bool {
+    let mut cipher = new(&AES_KEY);
+    let mut block = AES_PLAINTEXT;
+    cipher.encrypt_block(&mut block);
+    if block != AES_CIPHERTEXT {
+        return false;
+    }
+    cipher.decrypt_block(&mut block);
+    if block != AES_PLAINTEXT {
+        return false;
+    }
+
+    let mut cipher = new(&CTR_KEY);
+    let mut counter = CTR_COUNTER;
+    let mut data = CTR_PLAINTEXT;
+    ctr_apply(&mut cipher, &mut counter, &mut data);
+    if data != CTR_CIPHERTEXT {
+        return false;
+    }
+
+    CMAC_VECTORS
+        .iter()
+        .all(|(len, mac)| cmac(&mut cipher, &[&CMAC_MESSAGE[..*len]]) == *mac)
+}
+
+/// FIPS 180-4 examples: "abc", the empty message and the two block message
+const SHA256_VECTORS: [(&[u8], [u8; 32]); 3] = [
+    (
+        b"abc",
+        [
+            0xBA, 0x78, 0x16, 0xBF, 0x8F, 0x01, 0xCF, 0xEA, 0x41, 0x41, 0x40, 0xDE, 0x5D, 0xAE,
+            0x22, 0x23, 0xB0, 0x03, 0x61, 0xA3, 0x96, 0x17, 0x7A, 0x9C, 0xB4, 0x10, 0xFF, 0x61,
+            0xF2, 0x00, 0x15, 0xAD,
+        ],
+    ),
+    (
+        b"",
+        [
+            0xE3, 0xB0, 0xC4, 0x42, 0x98, 0xFC, 0x1C, 0x14, 0x9A, 0xFB, 0xF4, 0xC8, 0x99, 0x6F,
+            0xB9, 0x24, 0x27, 0xAE, 0x41, 0xE4, 0x64, 0x9B, 0x93, 0x4C, 0xA4, 0x95, 0x99, 0x1B,
+            0x78, 0x52, 0xB8, 0x55,
+        ],
+    ),
+    (
+        b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+        [
+            0x24, 0x8D, 0x6A, 0x61, 0xD2, 0x06, 0x38, 0xB8, 0xE5, 0xC0, 0x26, 0x93, 0x0C, 0x3E,
+            0x60, 0x39, 0xA3, 0x3C, 0xE4, 0x59, 0x64, 0xFF, 0x21, 0x67, 0xF6, 0xEC, 0xED, 0xD4,
+            0x19, 0xDB, 0x06, 0xC1,
+        ],
+    ),
+];
+
+/// Check a SHA-256 compression function against the FIPS 180-4 examples
+pub fn sha256_known_answer_test<C: Sha256Compress>(mut new: impl FnMut() -> C) -> bool {
+    SHA256_VECTORS.iter().all(|(message, digest)| {
+        let mut hasher = Sha256::with_core(new());
+        // split the input so the buffering is covered too
+        let (head, tail) = message.split_at(message.len() / 3);
+        hasher.update(head);
+        hasher.update(tail);
+        hasher.finalize() == *digest
+    })
+}
+
+/// Run the known answer tests on the software AES and SHA-256
+pub fn self_test() -> bool {
+    known_answer_test(Aes128::new) && sha256_known_answer_test(|| SoftCompress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn software_known_answers() {
+        assert!(self_test());
+    }
+
+    #[test]
+    fn known_answer_test_catches_a_broken_cipher() {
+        struct Identity;
+        impl BlockCipher for Identity {
+            fn encrypt_block(&mut self, _block: &mut Block) {}
+            fn decrypt_block(&mut self, _block: &mut Block) {}
+        }
+        assert!(!known_answer_test(|_| Identity));
+    }
+
+    #[test]
+    fn ecb() {
+        let mut cipher = Aes128::new(&AES_KEY);
+        let mut data = [AES_PLAINTEXT, AES_PLAINTEXT].concat();
+        ecb_encrypt(&mut cipher, &mut data).unwrap();
+        assert_eq!(data[..16], AES_CIPHERTEXT);
+        assert_eq!(data[16..], AES_CIPHERTEXT);
+        ecb_decrypt(&mut cipher, &mut data).unwrap();
+        assert_eq!(data[..16], AES_PLAINTEXT);
+
+        assert_eq!(
+            ecb_encrypt(&mut cipher, &mut [0u8; 17]),
+            Err(BlockLengthError)
+        );
+        assert_eq!(
+            ecb_decrypt(&mut cipher, &mut [0u8; 15]),
+            Err(BlockLengthError)
+        );
+    }
+
+    #[test]
+    fn ctr_stream_continues() {
+        let mut cipher = Aes128::new(&CTR_KEY);
+        let mut counter = CTR_COUNTER;
+        let mut data = CTR_PLAINTEXT;
+        // odd split, the counter only moves on whole or final blocks
+        ctr_apply(&mut cipher, &mut counter, &mut data[..16]);
+        ctr_apply(&mut cipher, &mut counter, &mut data[16..]);
+        assert_eq!(data, CTR_CIPHERTEXT);
+
+        // decryption is the same operation
+        let mut counter = CTR_COUNTER;
+        ctr_apply(&mut cipher, &mut counter, &mut data);
+        assert_eq!(data, CTR_PLAINTEXT);
+    }
+
+    #[test]
+    fn counter_carries() {
+        let mut counter = [0xFF; BLOCK_SIZE];
+        counter[0] = 0x00;
+        increment_counter(&mut counter);
+        assert_eq!(counter[0], 0x01);
+        assert!(counter[1..].iter().all(|&b| b == 0));
+
+        let mut counter = [0xFF; BLOCK_SIZE];
+        increment_counter(&mut counter);
+        assert_eq!(counter, [0; BLOCK_SIZE]);
+    }
+}
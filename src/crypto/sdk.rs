@@ -0,0 +1,245 @@
+use crate::crypto::{BLOCK_SIZE, BlockCipher, aes::Aes128, cmac::CmacState};
+
+// `enum AES_ENUM` results
+const AES_SUCCESS: u8 = 0;
+const AES_NULL: u8 = 1;
+const AES_LENGTH_ERROR: u8 = 2;
+const AES_LENGTH_ZERO: u8 = 3;
+const AES_STATE_ERROR: u8 = 4;
+const AES_KEY_LEN_ERROR: u8 = 6;
+
+/// `AES_ECB_MODE`, the only mode `LoRaMacCrypto.c` uses
+const AES_ECB_MODE: u8 = 0;
+/// `AES_DEC_MODE`
+const AES_DEC_MODE: u8 = 1;
+
+/// Key loaded by `aes_init`, the SDK engine keeps it between calls too
+static mut SDK_CIPHER: Option<Aes128> = None;
+
+/// `AES_CMAC_CTX` from `cmac.h`
+#[repr(C)]
+pub struct AesCmacCtx {
+    m_n: u32,
+    x: [u8; BLOCK_SIZE],
+    m_last: [u8; BLOCK_SIZE],
+}
+
+impl AesCmacCtx {
+    fn state(&self) -> CmacState {
+        CmacState {
+            x: self.x,
+            last: self.m_last,
+            filled: self.m_n as usize,
+        }
+    }
+
+    fn store(&mut self, state: &CmacState) {
+        self.x = state.x;
+        self.m_last = state.last;
+        self.m_n = state.filled as u32;
+    }
+}
+
+// ── extern "C" wrappers matching aes.h ─────────────────────────────────────
+
+/// Load an AES-128 key. Only ECB is supported, like the LoRaMac needs.
+///
+/// # Safety
+/// `key` must point to `keymod` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aes_init(key: *mut u8, keymod: u8, mode: u8, _iv_or_nonce: *mut u8) -> u8 {
+    if key.is_null() {
+        return AES_NULL;
+    }
+    if keymod != 16 {
+        return AES_KEY_LEN_ERROR;
+    }
+    if mode != AES_ECB_MODE {
+        return AES_STATE_ERROR;
+    }
+
+    let key = unsafe { &*(key as *const [u8; 16]) };
+    unsafe { SDK_CIPHER = Some(Aes128::new(key)) };
+    AES_SUCCESS
+}
+
+/// Encrypt (`en_de` 0) or decrypt (1) `in_len` bytes with the key from `aes_init`.
+///
+/// # Safety
+/// `input` and `out` must point to `in_len` bytes, they may be the same buffer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aes_crypto(input: *mut u8, in_len: u16, en_de: u8, out: *mut u8) -> u8 {
+    if input.is_null() || out.is_null() {
+        return AES_NULL;
+    }
+    if in_len == 0 {
+        return AES_LENGTH_ZERO;
+    }
+    if !(in_len as usize).is_multiple_of(BLOCK_SIZE) {
+        return AES_LENGTH_ERROR;
+    }
+    let Some(cipher) = (unsafe { SDK_CIPHER.as_mut() }) else {
+        return AES_STATE_ERROR;
+    };
+
+    for offset in (0..in_len as usize).step_by(BLOCK_SIZE) {
+        // copy through a local block, C passes the same buffer as input and output
+        let mut block = [0u8; BLOCK_SIZE];
+        unsafe {
+            core::ptr::copy(input.add(offset), block.as_mut_ptr(), BLOCK_SIZE);
+        }
+        if en_de == AES_DEC_MODE {
+            cipher.decrypt_block(&mut block);
+        } else {
+            cipher.encrypt_block(&mut block);
+        }
+        unsafe {
+            core::ptr::copy(block.as_ptr(), out.add(offset), BLOCK_SIZE);
+        }
+    }
+    AES_SUCCESS
+}
+
+/// Drop the loaded key
+#[unsafe(no_mangle)]
+pub extern "C" fn aes_close() -> u8 {
+    unsafe { SDK_CIPHER = None };
+    AES_SUCCESS
+}
+
+// ── extern "C" wrappers matching cmac.h ────────────────────────────────────
+
+/// # Safety
+/// `ctx` must be a valid `AES_CMAC_CTX`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn AES_CMAC_Init(ctx: *mut AesCmacCtx) {
+    let ctx = unsafe { &mut *ctx };
+    ctx.store(&CmacState::new());
+}
+
+/// The key is shared with `aes_crypto`, like the SDK engine.
+///
+/// # Safety
+/// `key` must point to 16 readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn AES_CMAC_SetKey(_ctx: *mut AesCmacCtx, key: *const u8) {
+    unsafe { aes_init(key as *mut u8, 16, AES_ECB_MODE, core::ptr::null_mut()) };
+}
+
+/// # Safety
+/// `ctx` must be a valid `AES_CMAC_CTX` and `data` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn AES_CMAC_Update(ctx: *mut AesCmacCtx, data: *const u8, len: u32) {
+    let ctx = unsafe { &mut *ctx };
+    let Some(cipher) = (unsafe { SDK_CIPHER.as_mut() }) else {
+        return;
+    };
+    if len == 0 {
+        return;
+    }
+    let data = unsafe { core::slice::from_raw_parts(data, len as usize) };
+
+    let mut state = ctx.state();
+    state.update(cipher, data);
+    ctx.store(&state);
+}
+
+/// # Safety
+/// `digest` must point to 16 writable bytes and `ctx` must be a valid `AES_CMAC_CTX`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn AES_CMAC_Final(digest: *mut u8, ctx: *mut AesCmacCtx) {
+    let ctx = unsafe { &mut *ctx };
+    let Some(cipher) = (unsafe { SDK_CIPHER.as_mut() }) else {
+        return;
+    };
+
+    let mac = ctx.state().finalize(cipher);
+    unsafe { core::ptr::copy_nonoverlapping(mac.as_ptr(), digest, BLOCK_SIZE) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 16] = [
+        0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F,
+        0x3C,
+    ];
+
+    /// One test, the C API keeps its key in a global
+    #[test]
+    fn c_api() {
+        let mut key = KEY;
+        let null = core::ptr::null_mut();
+        unsafe {
+            assert_eq!(aes_init(null, 16, AES_ECB_MODE, null), AES_NULL);
+            assert_eq!(
+                aes_init(key.as_mut_ptr(), 24, AES_ECB_MODE, null),
+                AES_KEY_LEN_ERROR
+            );
+            assert_eq!(aes_init(key.as_mut_ptr(), 16, 2, null), AES_STATE_ERROR);
+
+            let mut data = [0u8; 32];
+            assert_eq!(aes_close(), AES_SUCCESS);
+            assert_eq!(
+                aes_crypto(data.as_mut_ptr(), 16, 0, data.as_mut_ptr()),
+                AES_STATE_ERROR
+            );
+
+            assert_eq!(
+                aes_init(key.as_mut_ptr(), 16, AES_ECB_MODE, null),
+                AES_SUCCESS
+            );
+            assert_eq!(
+                aes_crypto(data.as_mut_ptr(), 0, 0, data.as_mut_ptr()),
+                AES_LENGTH_ZERO
+            );
+            assert_eq!(
+                aes_crypto(data.as_mut_ptr(), 17, 0, data.as_mut_ptr()),
+                AES_LENGTH_ERROR
+            );
+
+            // in place, like LoRaMacCrypto.c
+            let plain = data;
+            assert_eq!(
+                aes_crypto(data.as_mut_ptr(), 32, 0, data.as_mut_ptr()),
+                AES_SUCCESS
+            );
+            let mut expected = [0u8; 16];
+            Aes128::new(&KEY).encrypt_block(&mut expected);
+            assert_eq!(data[..16], expected);
+            assert_eq!(data[16..], expected);
+            assert_eq!(
+                aes_crypto(data.as_mut_ptr(), 32, AES_DEC_MODE, data.as_mut_ptr()),
+                AES_SUCCESS
+            );
+            assert_eq!(data, plain);
+
+            // RFC 4493 example 3, fed in uneven pieces
+            let message: [u8; 40] = [
+                0x6B, 0xC1, 0xBE, 0xE2, 0x2E, 0x40, 0x9F, 0x96, 0xE9, 0x3D, 0x7E, 0x11, 0x73, 0x93,
+                0x17, 0x2A, 0xAE, 0x2D, 0x8A, 0x57, 0x1E, 0x03, 0xAC, 0x9C, 0x9E, 0xB7, 0x6F, 0xAC,
+                0x45, 0xAF, 0x8E, 0x51, 0x30, 0xC8, 0x1C, 0x46, 0xA3, 0x5C, 0xE4, 0x11,
+            ];
+            let mut ctx = AesCmacCtx {
+                m_n: 0,
+                x: [0; BLOCK_SIZE],
+                m_last: [0; BLOCK_SIZE],
+            };
+            AES_CMAC_Init(&mut ctx);
+            AES_CMAC_SetKey(&mut ctx, KEY.as_ptr());
+            AES_CMAC_Update(&mut ctx, message.as_ptr(), 7);
+            AES_CMAC_Update(&mut ctx, message.as_ptr(), 0);
+            AES_CMAC_Update(&mut ctx, message[7..].as_ptr(), 33);
+            let mut digest = [0u8; 16];
+            AES_CMAC_Final(digest.as_mut_ptr(), &mut ctx);
+            assert_eq!(
+                digest,
+                [
+                    0xDF, 0xA6, 0x67, 0x47, 0xDE, 0x9A, 0xE6, 0x30, 0x30, 0xCA, 0x32, 0x61, 0x14,
+                    0x97, 0xC8, 0x27
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,255 @@
+/// SHA-256 digest size
+pub const DIGEST_SIZE: usize = 32;
+/// SHA-256 block size
+pub const BLOCK_SIZE: usize = 64;
+
+/// Chaining value, H0..H7 as big endian bytes (the layout the security engine uses)
+pub type State = [u8; DIGEST_SIZE];
+
+/// SHA-256 compression function.
+///
+/// Padding and buffering live in [`Sha256`], so a hardware engine only has to run blocks.
+pub trait Sha256Compress {
+    /// Process one 64 byte block into `state`
+    fn compress(&mut self, state: &mut State, block: &[u8; BLOCK_SIZE]);
+}
+
+/// Initial hash value
+const H0: State = [
+    0x6A, 0x09, 0xE6, 0x67, 0xBB, 0x67, 0xAE, 0x85, 0x3C, 0x6E, 0xF3, 0x72, 0xA5, 0x4F, 0xF5, 0x3A,
+    0x51, 0x0E, 0x52, 0x7F, 0x9B, 0x05, 0x68, 0x8C, 0x1F, 0x83, 0xD9, 0xAB, 0x5B, 0xE0, 0xCD, 0x19,
+];
+
+/// Round constants
+pub const K: [u32; 64] = [
+    0x428A2F98, 0x71374491, 0xB5C0FBCF, 0xE9B5DBA5, 0x3956C25B, 0x59F111F1, 0x923F82A4, 0xAB1C5ED5,
+    0xD807AA98, 0x12835B01, 0x243185BE, 0x550C7DC3, 0x72BE5D74, 0x80DEB1FE, 0x9BDC06A7, 0xC19BF174,
+    0xE49B69C1, 0xEFBE4786, 0x0FC19DC6, 0x240CA1CC, 0x2DE92C6F, 0x4A7484AA, 0x5CB0A9DC, 0x76F988DA,
+    0x983E5152, 0xA831C66D, 0xB00327C8, 0xBF597FC7, 0xC6E00BF3, 0xD5A79147, 0x06CA6351, 0x14292967,
+    0x27B70A85, 0x2E1B2138, 0x4D2C6DFC, 0x53380D13, 0x650A7354, 0x766A0ABB, 0x81C2C92E, 0x92722C85,
+    0xA2BFE8A1, 0xA81A664B, 0xC24B8B70, 0xC76C51A3, 0xD192E819, 0xD6990624, 0xF40E3585, 0x106AA070,
+    0x19A4C116, 0x1E376C08, 0x2748774C, 0x34B0BCB5, 0x391C0CB3, 0x4ED8AA4A, 0x5B9CCA4F, 0x682E6FF3,
+    0x748F82EE, 0x78A5636F, 0x84C87814, 0x8CC70208, 0x90BEFFFA, 0xA4506CEB, 0xBEF9A3F7, 0xC67178F2,
+];
+
+/// Software compression function
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SoftCompress;
+
+impl Sha256Compress for SoftCompress {
+    fn compress(&mut self, state: &mut State, block: &[u8; BLOCK_SIZE]) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let mut h = [0u32; 8];
+        for (i, word) in state.chunks_exact(4).enumerate() {
+            h[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (i, v) in [a, b, c, d, e, f, g, hh].iter().enumerate() {
+            let sum = h[i].wrapping_add(*v);
+            state[i * 4..i * 4 + 4].copy_from_slice(&sum.to_be_bytes());
+        }
+    }
+}
+
+/// Streaming SHA-256 over a compression function
+pub struct Sha256<C: Sha256Compress = SoftCompress> {
+    core: C,
+    state: State,
+    block: [u8; BLOCK_SIZE],
+    filled: usize,
+    length: u64,
+}
+
+impl Sha256 {
+    /// Software SHA-256
+    pub const fn new() -> Self {
+        Self::with_core(SoftCompress)
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Sha256Compress> Sha256<C> {
+    /// SHA-256 on the given compression function
+    pub const fn with_core(core: C) -> Self {
+        Self {
+            core,
+            state: H0,
+            block: [0; BLOCK_SIZE],
+            filled: 0,
+            length: 0,
+        }
+    }
+
+    /// Absorb `data`
+    pub fn update(&mut self, data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+        for &byte in data {
+            self.block[self.filled] = byte;
+            self.filled += 1;
+            if self.filled == BLOCK_SIZE {
+                self.core.compress(&mut self.state, &self.block);
+                self.filled = 0;
+            }
+        }
+    }
+
+    /// Pad and return the digest
+    pub fn finalize(mut self) -> [u8; DIGEST_SIZE] {
+        let bit_length = self.length.wrapping_mul(8);
+
+        self.block[self.filled] = 0x80;
+        self.block[self.filled + 1..].fill(0);
+        if self.filled >= BLOCK_SIZE - 8 {
+            // no room for the length
+            self.core.compress(&mut self.state, &self.block);
+            self.block.fill(0);
+        }
+        self.block[BLOCK_SIZE - 8..].copy_from_slice(&bit_length.to_be_bytes());
+        self.core.compress(&mut self.state, &self.block);
+
+        self.state
+    }
+}
+
+/// SHA-256 of `data` in software
+pub fn sha256(data: &[u8]) -> [u8; DIGEST_SIZE] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::sha256_known_answer_test;
+    use core::cell::Cell;
+
+    /// Counts the blocks it is given
+    struct Counting<'a>(&'a Cell<usize>);
+
+    impl Sha256Compress for Counting<'_> {
+        fn compress(&mut self, state: &mut State, block: &[u8; BLOCK_SIZE]) {
+            self.0.set(self.0.get() + 1);
+            SoftCompress.compress(state, block);
+        }
+    }
+
+    #[test]
+    fn known_answers() {
+        assert!(sha256_known_answer_test(|| SoftCompress));
+
+        struct Broken;
+        impl Sha256Compress for Broken {
+            fn compress(&mut self, _state: &mut State, _block: &[u8; BLOCK_SIZE]) {}
+        }
+        assert!(!sha256_known_answer_test(|| Broken));
+    }
+
+    #[test]
+    fn padding_boundaries() {
+        // 55 bytes is the longest message that fits one block with its length
+        let vectors: [(usize, [u8; DIGEST_SIZE]); 3] = [
+            (
+                55,
+                [
+                    0x9F, 0x43, 0x90, 0xF8, 0xD3, 0x0C, 0x2D, 0xD9, 0x2E, 0xC9, 0xF0, 0x95, 0xB6,
+                    0x5E, 0x2B, 0x9A, 0xE9, 0xB0, 0xA9, 0x25, 0xA5, 0x25, 0x8E, 0x24, 0x1C, 0x9F,
+                    0x1E, 0x91, 0x0F, 0x73, 0x43, 0x18,
+                ],
+            ),
+            (
+                56,
+                [
+                    0xB3, 0x54, 0x39, 0xA4, 0xAC, 0x6F, 0x09, 0x48, 0xB6, 0xD6, 0xF9, 0xE3, 0xC6,
+                    0xAF, 0x0F, 0x5F, 0x59, 0x0C, 0xE2, 0x0F, 0x1B, 0xDE, 0x70, 0x90, 0xEF, 0x79,
+                    0x70, 0x68, 0x6E, 0xC6, 0x73, 0x8A,
+                ],
+            ),
+            (
+                64,
+                [
+                    0xFF, 0xE0, 0x54, 0xFE, 0x7A, 0xE0, 0xCB, 0x6D, 0xC6, 0x5C, 0x3A, 0xF9, 0xB6,
+                    0x1D, 0x52, 0x09, 0xF4, 0x39, 0x85, 0x1D, 0xB4, 0x3D, 0x0B, 0xA5, 0x99, 0x73,
+                    0x37, 0xDF, 0x15, 0x46, 0x68, 0xEB,
+                ],
+            ),
+        ];
+        let message = [b'a'; 64];
+        for (len, digest) in vectors {
+            assert_eq!(sha256(&message[..len]), digest, "{len} bytes");
+        }
+
+        // the length needs a second block from 56 bytes on
+        for (len, blocks) in [
+            (0, 1),
+            (55, 1),
+            (56, 2),
+            (63, 2),
+            (64, 2),
+            (119, 2),
+            (120, 3),
+        ] {
+            let count = Cell::new(0);
+            let mut hasher = Sha256::with_core(Counting(&count));
+            hasher.update(&[b'a'; 120][..len]);
+            let _ = hasher.finalize();
+            assert_eq!(count.get(), blocks, "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn million_a() {
+        let mut hasher = Sha256::new();
+        for _ in 0..1000 {
+            hasher.update(&[b'a'; 1000]);
+        }
+        assert_eq!(
+            hasher.finalize(),
+            [
+                0xCD, 0xC7, 0x6E, 0x5C, 0x99, 0x14, 0xFB, 0x92, 0x81, 0xA1, 0xC7, 0xE2, 0x84, 0xD7,
+                0x3E, 0x67, 0xF1, 0x80, 0x9A, 0x48, 0xA4, 0x97, 0x20, 0x0E, 0x04, 0x6D, 0x39, 0xCC,
+                0xC7, 0x11, 0x2C, 0xD0
+            ]
+        );
+    }
+}
@@ -0,0 +1,742 @@
+use core::fmt::{self, Write};
+
+use heapless::Vec;
+
+use crate::{
+    lora::radio::{
+        RadioEvents, RadioModem, radio_check_rf_frequency, radio_get_irq_micros, radio_init,
+        radio_irq_process, radio_rx, radio_send, radio_set_channel, radio_set_public_network,
+        radio_set_rx_config, radio_set_tx_config, radio_standby,
+    },
+    peripherals::{
+        buffered_uart::uart0,
+        gpio::{GpioMode, GpioPin},
+        micros::{micros, micros_init},
+        regs::GPIOA,
+    },
+    print::SerialWriter,
+    println,
+};
+
+/// Frequency to listen on (Hz)
+pub const GATEWAY_FREQUENCY: u32 = 868_100_000;
+/// LoRa bandwidth index (0 = 125 kHz, 1 = 250 kHz, 2 = 500 kHz)
+pub const GATEWAY_BANDWIDTH: u8 = 0;
+/// LoRa spreading factor (5..12)
+pub const GATEWAY_SPREADING_FACTOR: u8 = 7;
+/// LoRa coding rate (1 = 4/5 .. 4 = 4/8)
+pub const GATEWAY_CODING_RATE: u8 = 1;
+/// LoRa preamble length in symbols
+pub const GATEWAY_PREAMBLE_LENGTH: u16 = 8;
+/// forward frames that failed their CRC check (`"stat":-1`)
+pub const GATEWAY_FORWARD_CRC_ERROR: bool = false;
+/// highest output power of the radio (dBm), `txpk` powers above are clamped
+pub const GATEWAY_MAX_TX_POWER: i8 = 22;
+
+/// Downlinks due sooner than this (µs) are refused with `TOO_LATE`
+pub const TX_MIN_LEAD: u32 = 30_000;
+/// Downlinks due later than this (µs) are refused with `TOO_EARLY`
+pub const TX_MAX_LEAD: u32 = 10_000_000;
+/// Time (µs) before a downlink at which the radio gets configured
+const TX_SETUP_TIME: u32 = 10_000;
+/// Transmission timeout (ms)
+const TX_TIMEOUT: usize = 3_000;
+/// Longest line accepted from the host
+pub const LINE_MAX_SIZE: usize = 1024;
+
+/// LoRa bandwidths (kHz) by bandwidth index
+const BANDWIDTHS_KHZ: [u16; 3] = [125, 250, 500];
+
+/// Base64 (RFC 4648, padded) of `data` into `out`, returns the length written or `None` when
+/// `out` is too small
+pub fn base64_encode(data: &[u8], out: &mut [u8]) -> Option<usize> {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let len = data.len().div_ceil(3) * 4;
+    let out = out.get_mut(..len)?;
+    for (chunk, quad) in data.chunks(3).zip(out.chunks_mut(4)) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for (i, c) in quad.iter_mut().enumerate() {
+            *c = if i <= chunk.len() {
+                ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3F]
+            } else {
+                b'='
+            };
+        }
+    }
+    Some(len)
+}
+
+/// Decode base64 `text` into `out`, returns the length written.
+///
+/// `None` on a character outside the alphabet or when `out` is too small.
+pub fn base64_decode(text: &[u8], out: &mut [u8]) -> Option<usize> {
+    fn value(c: u8) -> Option<u32> {
+        Some(match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        } as u32)
+    }
+
+    let text = match text.iter().position(|&c| c == b'=') {
+        Some(pad) => &text[..pad],
+        None => text,
+    };
+    let mut len = 0;
+    for chunk in text.chunks(4) {
+        let mut n = 0;
+        for (i, &c) in chunk.iter().enumerate() {
+            n |= value(c)? << (18 - 6 * i);
+        }
+        // a lone character carries less than a byte
+        if chunk.len() == 1 {
+            return None;
+        }
+        for i in 0..chunk.len() - 1 {
+            *out.get_mut(len)? = (n >> (16 - 8 * i)) as u8;
+            len += 1;
+        }
+    }
+    Some(len)
+}
+
+/// Raw value of `key` in a flat JSON object: the text between the quotes for strings, the
+/// trimmed token otherwise. Enough for the packet forwarder objects, which have no escapes.
+pub fn json_value<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let mut rest = json;
+    loop {
+        let start = rest.find('"')?;
+        let after = &rest[start + 1..];
+        let end = after.find('"')?;
+        let name = &after[..end];
+        rest = after[end + 1..].trim_start();
+
+        let Some(value) = rest.strip_prefix(':') else {
+            // a string value, not a key
+            continue;
+        };
+        let value = value.trim_start();
+        if name != key {
+            rest = value;
+            if let Some(string) = value.strip_prefix('"') {
+                rest = &string[string.find('"')? + 1..];
+            }
+            continue;
+        }
+
+        return if let Some(string) = value.strip_prefix('"') {
+            Some(&string[..string.find('"')?])
+        } else {
+            let end = value.find([',', '}', ']']).unwrap_or(value.len());
+            Some(value[..end].trim_end())
+        };
+    }
+}
+
+/// `"SF7BW125"` to the spreading factor and bandwidth index
+pub fn parse_datr(datr: &str) -> Option<(u8, u8)> {
+    let (sf, bw) = datr.strip_prefix("SF")?.split_once("BW")?;
+    let sf = sf.parse().ok().filter(|sf| (5..=12).contains(sf))?;
+    let bw: u16 = bw.parse().ok()?;
+    let bw = BANDWIDTHS_KHZ.iter().position(|&b| b == bw)?;
+    Some((sf, bw as u8))
+}
+
+/// `"4/5"` .. `"4/8"` to the coding rate (1 .. 4)
+pub fn parse_codr(codr: &str) -> Option<u8> {
+    match codr {
+        "4/5" => Some(1),
+        "4/6" => Some(2),
+        "4/7" => Some(3),
+        "4/8" => Some(4),
+        _ => None,
+    }
+}
+
+/// `"868.1"` (MHz) to Hz, without going through floats
+pub fn parse_freq(freq: &str) -> Option<u32> {
+    let (mhz, fraction) = freq.split_once('.').unwrap_or((freq, ""));
+    if fraction.len() > 6 || !fraction.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let mut hz: u32 = mhz.parse::<u32>().ok()?.checked_mul(1_000_000)?;
+    for (i, c) in fraction.bytes().enumerate() {
+        hz += (c - b'0') as u32 * 10u32.pow(5 - i as u32);
+    }
+    Some(hz)
+}
+
+/// Received uplink, written as a packet forwarder `rxpk`
+pub struct Rxpk<'a> {
+    /// receive time (µs), wraps
+    pub tmst: u32,
+    /// frequency (Hz)
+    pub freq: u32,
+    pub crc_ok: bool,
+    /// spreading factor
+    pub sf: u8,
+    /// bandwidth index (0 = 125 kHz, 1 = 250 kHz, 2 = 500 kHz)
+    pub bw: u8,
+    /// coding rate (1 = 4/5 .. 4 = 4/8)
+    pub cr: u8,
+    /// packet RSSI (dBm)
+    pub rssi: i16,
+    /// packet SNR (dB)
+    pub snr: i8,
+    pub payload: &'a [u8],
+}
+
+impl Rxpk<'_> {
+    /// Write `{"rxpk":[{..}]}`, the body of a `PUSH_DATA`
+    pub fn write_json(&self, w: &mut impl Write) -> fmt::Result {
+        let mut data = [0u8; 340];
+        let len = base64_encode(self.payload, &mut data).ok_or(fmt::Error)?;
+        let data = core::str::from_utf8(&data[..len]).map_err(|_| fmt::Error)?;
+
+        write!(
+            w,
+            "{{\"rxpk\":[{{\"tmst\":{},\"chan\":0,\"rfch\":0,\"freq\":{}.{:06},\"stat\":{},\
+             \"modu\":\"LORA\",\"datr\":\"SF{}BW{}\",\"codr\":\"4/{}\",\"rssi\":{},\
+             \"lsnr\":{},\"size\":{},\"data\":\"{}\"}}]}}",
+            self.tmst,
+            self.freq / 1_000_000,
+            self.freq % 1_000_000,
+            if self.crc_ok { 1 } else { -1 },
+            self.sf,
+            BANDWIDTHS_KHZ.get(self.bw as usize).unwrap_or(&125),
+            self.cr + 4,
+            self.rssi,
+            self.snr,
+            self.payload.len(),
+            data,
+        )
+    }
+}
+
+/// `txpk` rejected before being scheduled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxpkError {
+    /// a required field is missing or malformed
+    Field(&'static str),
+    /// FSK downlinks are not supported
+    Modulation,
+}
+
+/// Downlink requested by the network server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Txpk {
+    /// send right away, `tmst` is ignored
+    pub imme: bool,
+    /// send time (µs) on the `rxpk` clock
+    pub tmst: u32,
+    /// frequency (Hz)
+    pub freq: u32,
+    /// output power (dBm)
+    pub power: i8,
+    /// spreading factor
+    pub sf: u8,
+    /// bandwidth index (0 = 125 kHz, 1 = 250 kHz, 2 = 500 kHz)
+    pub bw: u8,
+    /// coding rate (1 = 4/5 .. 4 = 4/8)
+    pub cr: u8,
+    /// inverted IQ, set for downlinks to end devices
+    pub ipol: bool,
+    /// preamble length (symbols)
+    pub preamble: u16,
+    pub payload: Vec<u8, 255>,
+}
+
+impl Txpk {
+    /// Parse `{"txpk":{..}}`, the body of a `PULL_RESP`
+    pub fn parse(json: &str) -> Result<Self, TxpkError> {
+        let field = |key: &'static str| json_value(json, key).ok_or(TxpkError::Field(key));
+        let flag = |key: &'static str| json_value(json, key) == Some("true");
+
+        if json_value(json, "modu").is_some_and(|modu| modu != "LORA") {
+            return Err(TxpkError::Modulation);
+        }
+        let imme = flag("imme");
+        let tmst = if imme {
+            0
+        } else {
+            field("tmst")?
+                .parse()
+                .map_err(|_| TxpkError::Field("tmst"))?
+        };
+        let freq = parse_freq(field("freq")?).ok_or(TxpkError::Field("freq"))?;
+        let power = match json_value(json, "powe") {
+            Some(powe) => powe.parse().map_err(|_| TxpkError::Field("powe"))?,
+            None => 14,
+        };
+        let (sf, bw) = parse_datr(field("datr")?).ok_or(TxpkError::Field("datr"))?;
+        let cr = parse_codr(field("codr")?).ok_or(TxpkError::Field("codr"))?;
+        let preamble = match json_value(json, "prea") {
+            Some(prea) => prea.parse().map_err(|_| TxpkError::Field("prea"))?,
+            None => GATEWAY_PREAMBLE_LENGTH,
+        };
+
+        let mut payload = [0u8; 255];
+        let len = base64_decode(field("data")?.as_bytes(), &mut payload)
+            .ok_or(TxpkError::Field("data"))?;
+        if let Some(size) = json_value(json, "size")
+            && size.parse() != Ok(len)
+        {
+            return Err(TxpkError::Field("size"));
+        }
+
+        Ok(Self {
+            imme,
+            tmst,
+            freq,
+            power,
+            sf,
+            bw,
+            cr,
+            ipol: flag("ipol"),
+            preamble,
+            payload: Vec::from_slice(&payload[..len]).unwrap_or_default(),
+        })
+    }
+}
+
+/// `TX_ACK` error codes of the packet forwarder protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxAck {
+    None,
+    TooLate,
+    TooEarly,
+    CollisionPacket,
+    TxFreq,
+}
+
+impl TxAck {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TxAck::None => "NONE",
+            TxAck::TooLate => "TOO_LATE",
+            TxAck::TooEarly => "TOO_EARLY",
+            TxAck::CollisionPacket => "COLLISION_PACKET",
+            TxAck::TxFreq => "TX_FREQ",
+        }
+    }
+}
+
+/// Check the send time of a downlink against `now` (µs), both on the wrapping `rxpk` clock
+pub fn check_schedule(tmst: u32, now: u32) -> Result<u32, TxAck> {
+    // negative once the time has passed
+    let lead = tmst.wrapping_sub(now) as i32;
+    if lead < TX_MIN_LEAD as i32 {
+        Err(TxAck::TooLate)
+    } else if lead > TX_MAX_LEAD as i32 {
+        Err(TxAck::TooEarly)
+    } else {
+        Ok(lead as u32)
+    }
+}
+
+struct Gateway {
+    /// downlink waiting for its send time
+    pending: Option<Txpk>,
+    /// the radio is set up for the pending downlink
+    tx_ready: bool,
+    transmitting: bool,
+    line: Vec<u8, LINE_MAX_SIZE>,
+}
+
+static mut GATEWAY: Gateway = Gateway {
+    pending: None,
+    tx_ready: false,
+    transmitting: false,
+    line: Vec::new(),
+};
+
+/// The `rxpk` clock: time since boot (µs), wrapping like the concentrator counter
+fn tmst_now() -> u32 {
+    micros()
+}
+
+fn listen() {
+    radio_set_channel(GATEWAY_FREQUENCY as usize);
+    radio_set_rx_config(
+        RadioModem::LoRa,
+        GATEWAY_BANDWIDTH as usize,
+        GATEWAY_SPREADING_FACTOR as usize,
+        GATEWAY_CODING_RATE,
+        0,
+        GATEWAY_PREAMBLE_LENGTH,
+        0,
+        false,
+        0,
+        true,
+        false,
+        0,
+        false,
+        true,
+    );
+    radio_rx(0);
+}
+
+/// Forward a packet received at `tmst`, latched by the radio interrupt: the packet forwarder
+/// schedules the RX1/RX2 downlink from it
+fn forward(payload: &[u8], tmst: u32, rssi: i16, snr: i8, crc_ok: bool) {
+    let rxpk = Rxpk {
+        tmst,
+        freq: GATEWAY_FREQUENCY,
+        crc_ok,
+        sf: GATEWAY_SPREADING_FACTOR,
+        bw: GATEWAY_BANDWIDTH,
+        cr: GATEWAY_CODING_RATE,
+        rssi,
+        snr,
+        payload,
+    };
+    if rxpk.write_json(&mut SerialWriter).is_ok() {
+        println!();
+    }
+    GPIOA.toggle(GpioPin::GREEN_LED);
+}
+
+fn on_rx_done(payload: &[u8], rssi: i16, snr: i8) {
+    forward(payload, radio_get_irq_micros(), rssi, snr, true);
+}
+
+fn on_rx_crc_error(payload: &[u8], rssi: i16, snr: i8) {
+    if GATEWAY_FORWARD_CRC_ERROR {
+        forward(payload, radio_get_irq_micros(), rssi, snr, false);
+    }
+}
+
+fn on_tx_done() {
+    unsafe { GATEWAY.transmitting = false };
+    listen();
+}
+
+/// Configure the radio shortly before the pending downlink and send it at its time.
+///
+/// Polled from the main loop, the radio stays in reception until the last
+/// [`TX_SETUP_TIME`].
+fn poll_tx() {
+    let gateway = unsafe { &mut GATEWAY };
+    let Some(txpk) = &gateway.pending else {
+        return;
+    };
+    let lead = txpk.tmst.wrapping_sub(tmst_now()) as i32;
+
+    if !gateway.tx_ready && (txpk.imme || lead <= TX_SETUP_TIME as i32) {
+        radio_standby();
+        radio_set_channel(txpk.freq as usize);
+        radio_set_tx_config(
+            RadioModem::LoRa,
+            txpk.power.min(GATEWAY_MAX_TX_POWER),
+            0,
+            txpk.bw as usize,
+            txpk.sf as usize,
+            txpk.cr,
+            txpk.preamble,
+            false,
+            true,
+            false,
+            0,
+            txpk.ipol,
+            TX_TIMEOUT,
+        );
+        gateway.tx_ready = true;
+    }
+    if gateway.tx_ready && (txpk.imme || lead <= 0) {
+        gateway.tx_ready = false;
+        gateway.transmitting = true;
+        radio_send(&txpk.payload);
+        gateway.pending = None;
+    }
+}
+
+/// Handle a `{"txpk":..}` line from the host, answers with `{"txpk_ack":..}`
+fn on_txpk(json: &str) {
+    let gateway = unsafe { &mut GATEWAY };
+    let txpk = match Txpk::parse(json) {
+        Ok(txpk) => txpk,
+        Err(error) => {
+            println!("# txpk rejected: {:?}", error);
+            return;
+        }
+    };
+
+    let ack = if gateway.pending.is_some() || gateway.transmitting {
+        Err(TxAck::CollisionPacket)
+    } else if !radio_check_rf_frequency(txpk.freq as usize) {
+        Err(TxAck::TxFreq)
+    } else if txpk.imme {
+        Ok(0)
+    } else {
+        check_schedule(txpk.tmst, tmst_now())
+    };
+
+    let ack = match ack {
+        Ok(_) => {
+            gateway.pending = Some(txpk);
+            TxAck::None
+        }
+        Err(error) => error,
+    };
+    println!("{{\"txpk_ack\":{{\"error\":\"{}\"}}}}", ack.as_str());
+}
+
+/// Collect host lines from UART0 without blocking
+fn poll_uart() {
+    let gateway = unsafe { &mut GATEWAY };
+    while let Some(byte) = uart0().try_read_byte() {
+        if byte != b'\n' {
+            if gateway.line.push(byte).is_err() {
+                // overlong line, drop it
+                gateway.line.clear();
+            }
+            continue;
+        }
+
+        if let Ok(line) = core::str::from_utf8(&gateway.line)
+            && line.contains("\"txpk\"")
+        {
+            on_txpk(line.trim_end());
+        }
+        gateway.line.clear();
+    }
+}
+
+/// radio events used by the gateway
+static GATEWAY_EVENTS: RadioEvents = RadioEvents {
+    tx_done: Some(on_tx_done),
+    tx_timeout: Some(on_tx_done),
+    rx_done: Some(on_rx_done),
+    rx_timeout: None,
+    rx_error: None,
+    rx_crc_error: Some(on_rx_crc_error),
+    fhss_change_channel: None,
+    cad_done: None,
+};
+
+/// single-channel gateway start, never returns
+///
+/// Listens for uplinks with the public sync word and writes one packet forwarder
+/// `{"rxpk":..}` object per line on UART0. `{"txpk":..}` lines from the host are scheduled on
+/// the `tmst` clock and answered with `{"txpk_ack":..}`. Other output starts with `#`.
+/// `gateway_bridge.py` carries the lines to a network server over the Semtech UDP protocol.
+pub fn app_start() -> ! {
+    GPIOA.init(GpioPin::GREEN_LED, GpioMode::OutputPPLow);
+
+    radio_init(&GATEWAY_EVENTS);
+    radio_set_public_network(true);
+    micros_init();
+    listen();
+    println!(
+        "# single-channel gateway on {} Hz SF{}",
+        GATEWAY_FREQUENCY, GATEWAY_SPREADING_FACTOR
+    );
+
+    loop {
+        radio_irq_process();
+        poll_uart();
+        poll_tx();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Buf(heapless::String<512>);
+
+    impl Write for Buf {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0.push_str(s).map_err(|_| fmt::Error)
+        }
+    }
+
+    #[test]
+    fn base64() {
+        // RFC 4648 test vectors
+        let vectors: [(&[u8], &[u8]); 7] = [
+            (b"", b""),
+            (b"f", b"Zg=="),
+            (b"fo", b"Zm8="),
+            (b"foo", b"Zm9v"),
+            (b"foob", b"Zm9vYg=="),
+            (b"fooba", b"Zm9vYmE="),
+            (b"foobar", b"Zm9vYmFy"),
+        ];
+        for (data, text) in vectors {
+            let mut out = [0u8; 16];
+            let len = base64_encode(data, &mut out).unwrap();
+            assert_eq!(&out[..len], text);
+            let len = base64_decode(text, &mut out).unwrap();
+            assert_eq!(&out[..len], data);
+        }
+
+        let all: [u8; 256] = core::array::from_fn(|i| i as u8);
+        let mut text = [0u8; 344];
+        let len = base64_encode(&all, &mut text).unwrap();
+        assert_eq!(len, 344);
+        assert!(text.starts_with(b"AAECAwQF") && text.ends_with(b"/P3+/w=="));
+        let mut out = [0u8; 256];
+        assert_eq!(base64_decode(&text, &mut out), Some(256));
+        assert_eq!(out, all);
+
+        // unpadded input decodes too
+        assert_eq!(base64_decode(b"Zm8", &mut out), Some(2));
+        assert_eq!(base64_encode(b"foo", &mut [0u8; 3]), None);
+        assert_eq!(base64_decode(b"Zm9v", &mut [0u8; 2]), None);
+        assert_eq!(base64_decode(b"Zm9*", &mut out), None);
+        assert_eq!(base64_decode(b"Zm9vY", &mut out), None);
+    }
+
+    #[test]
+    fn json_values() {
+        let json = r#"{"txpk": {"imme":false, "tmst" : 12345,"freq":869.525,
+            "modu":"LORA","datr":"SF9BW125","ipol":true,"data":"QA==","list":[1,2]}}"#;
+        assert_eq!(json_value(json, "imme"), Some("false"));
+        assert_eq!(json_value(json, "tmst"), Some("12345"));
+        assert_eq!(json_value(json, "freq"), Some("869.525"));
+        assert_eq!(json_value(json, "modu"), Some("LORA"));
+        assert_eq!(json_value(json, "datr"), Some("SF9BW125"));
+        assert_eq!(json_value(json, "ipol"), Some("true"));
+        assert_eq!(json_value(json, "data"), Some("QA=="));
+        assert_eq!(json_value(json, "list"), Some("[1"));
+        assert_eq!(json_value(json, "powe"), None);
+        // keys inside string values don't match
+        assert_eq!(json_value(r#"{"a":"tmst","b":1}"#, "tmst"), None);
+        assert_eq!(json_value(r#"{"a":"x:y","tmst":2}"#, "tmst"), Some("2"));
+        assert_eq!(json_value(r#"{"tmst":"#, "tmst"), Some(""));
+        assert_eq!(json_value(r#"{"data":"unterminated"#, "data"), None);
+    }
+
+    #[test]
+    fn datr_codr_freq() {
+        assert_eq!(parse_datr("SF7BW125"), Some((7, 0)));
+        assert_eq!(parse_datr("SF12BW500"), Some((12, 2)));
+        assert_eq!(parse_datr("SF5BW250"), Some((5, 1)));
+        assert_eq!(parse_datr("SF13BW125"), None);
+        assert_eq!(parse_datr("SF7BW62"), None);
+        assert_eq!(parse_datr("SF7"), None);
+        assert_eq!(parse_datr("50000"), None);
+
+        assert_eq!(parse_codr("4/5"), Some(1));
+        assert_eq!(parse_codr("4/8"), Some(4));
+        assert_eq!(parse_codr("4/9"), None);
+        assert_eq!(parse_codr("OFF"), None);
+
+        assert_eq!(parse_freq("868.1"), Some(868_100_000));
+        assert_eq!(parse_freq("869.525"), Some(869_525_000));
+        assert_eq!(parse_freq("923.200001"), Some(923_200_001));
+        assert_eq!(parse_freq("915"), Some(915_000_000));
+        assert_eq!(parse_freq("868.1000001"), None);
+        assert_eq!(parse_freq("868.-1"), None);
+        assert_eq!(parse_freq("-868.1"), None);
+        assert_eq!(parse_freq("5000.0"), None);
+    }
+
+    #[test]
+    fn txpk() {
+        let json = r#"{"txpk":{"imme":false,"tmst":4000000,"freq":869.525,"rfch":0,"powe":27,
+            "modu":"LORA","datr":"SF9BW125","codr":"4/5","ipol":true,"size":3,"data":"YAEC"}}"#;
+        let txpk = Txpk::parse(json).unwrap();
+        assert_eq!(
+            txpk,
+            Txpk {
+                imme: false,
+                tmst: 4_000_000,
+                freq: 869_525_000,
+                power: 27,
+                sf: 9,
+                bw: 0,
+                cr: 1,
+                ipol: true,
+                preamble: GATEWAY_PREAMBLE_LENGTH,
+                payload: Vec::from_slice(&[0x60, 0x01, 0x02]).unwrap(),
+            }
+        );
+
+        // immediate, defaults for the optional fields
+        let txpk = Txpk::parse(
+            r#"{"txpk":{"imme":true,"freq":868.1,"datr":"SF7BW125","codr":"4/6","prea":10,"data":""}}"#,
+        )
+        .unwrap();
+        assert!(txpk.imme && !txpk.ipol);
+        assert_eq!(
+            (txpk.tmst, txpk.power, txpk.cr, txpk.preamble),
+            (0, 14, 2, 10)
+        );
+        assert!(txpk.payload.is_empty());
+
+        let base = r#""freq":868.1,"datr":"SF7BW125","codr":"4/5","data":"YAEC""#;
+        let parse = |fields: &str| Txpk::parse(&std::format!("{{\"txpk\":{{{fields}}}}}"));
+        assert!(parse(&std::format!("\"tmst\":1,{base}")).is_ok());
+        assert_eq!(parse(base), Err(TxpkError::Field("tmst")));
+        assert_eq!(
+            parse(&std::format!("\"tmst\":-1,{base}")),
+            Err(TxpkError::Field("tmst"))
+        );
+        assert_eq!(
+            parse(&std::format!("\"tmst\":1,\"modu\":\"FSK\",{base}")),
+            Err(TxpkError::Modulation)
+        );
+        assert_eq!(
+            parse(&std::format!("\"tmst\":1,\"size\":4,{base}")),
+            Err(TxpkError::Field("size"))
+        );
+        assert_eq!(
+            parse(&std::format!("\"tmst\":1,\"powe\":\"x\",{base}")),
+            Err(TxpkError::Field("powe"))
+        );
+        assert_eq!(
+            parse(r#""tmst":1,"freq":868.1,"datr":"SF7BW125","codr":"4/5","data":"Y*EC""#),
+            Err(TxpkError::Field("data"))
+        );
+        assert_eq!(
+            parse(r#""tmst":1,"datr":"SF7BW125","codr":"4/5","data":"""#),
+            Err(TxpkError::Field("freq"))
+        );
+    }
+
+    #[test]
+    fn rxpk() {
+        let rxpk = Rxpk {
+            tmst: 3_512_348_611,
+            freq: 868_100_000,
+            crc_ok: true,
+            sf: 7,
+            bw: 0,
+            cr: 1,
+            rssi: -35,
+            snr: 5,
+            payload: &[0x40, 0x01, 0x02],
+        };
+        let mut out = Buf(heapless::String::new());
+        rxpk.write_json(&mut out).unwrap();
+        assert_eq!(
+            out.0.as_str(),
+            r#"{"rxpk":[{"tmst":3512348611,"chan":0,"rfch":0,"freq":868.100000,"stat":1,"modu":"LORA","datr":"SF7BW125","codr":"4/5","rssi":-35,"lsnr":5,"size":3,"data":"QAEC"}]}"#
+        );
+    }
+
+    #[test]
+    fn schedule() {
+        assert_eq!(check_schedule(1_000_000, 0), Ok(1_000_000));
+        assert_eq!(check_schedule(TX_MIN_LEAD, 0), Ok(TX_MIN_LEAD));
+        assert_eq!(check_schedule(TX_MIN_LEAD - 1, 0), Err(TxAck::TooLate));
+        assert_eq!(check_schedule(0, 5), Err(TxAck::TooLate));
+        assert_eq!(check_schedule(TX_MAX_LEAD, 0), Ok(TX_MAX_LEAD));
+        assert_eq!(check_schedule(TX_MAX_LEAD + 1, 0), Err(TxAck::TooEarly));
+        // across the wrap of the clock
+        assert_eq!(check_schedule(500_000, u32::MAX - 499_999), Ok(1_000_000));
+        assert_eq!(check_schedule(u32::MAX, 1_000), Err(TxAck::TooLate));
+        assert_eq!(TxAck::TooLate.as_str(), "TOO_LATE");
+    }
+}
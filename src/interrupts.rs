@@ -1,6 +1,17 @@
 #![allow(clippy::empty_loop)]
 
-use crate::{lora::radio::radio_on_dio_irq, peripherals::regs::RTC};
+use crate::{
+    lora::radio::radio_on_dio_irq,
+    peripherals::{
+        buffered_lpuart::lpuart_irq_handler,
+        buffered_uart::uart_irq_handler,
+        gpio_irq::gpio_irq_handler,
+        i2c_target::i2c_target_irq_handler,
+        micros::micros_irq_handler,
+        regs::{I2C0, I2C1, I2C2, RTC, SSP0, SSP1, SSP2, UART0, UART1, UART2, UART3},
+        spi_target::spi_target_irq_handler,
+    },
+};
 
 /// This function handles the NMI exception
 #[unsafe(no_mangle)]
@@ -67,44 +78,52 @@ pub extern "C" fn EFC_IRQHandler() {
     loop {}
 }
 
+/// This function handles UART3 Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn UART3_IRQHandler() {
-    loop {}
+    uart_irq_handler(&UART3);
 }
 
+/// This function handles I2C2 Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn I2C2_IRQHandler() {
-    loop {}
+    i2c_target_irq_handler(&I2C2);
 }
 
+/// This function handles UART0 Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn UART0_IRQHandler() {
-    loop {}
+    uart_irq_handler(&UART0);
 }
 
+/// This function handles UART1 Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn UART1_IRQHandler() {
-    loop {}
+    uart_irq_handler(&UART1);
 }
 
+/// This function handles UART2 Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn UART2_IRQHandler() {
-    loop {}
+    uart_irq_handler(&UART2);
 }
 
+/// This function handles LPUART Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn LPUART_IRQHandler() {
-    loop {}
+    lpuart_irq_handler();
 }
 
+/// This function handles SSP0 Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn SSP0_IRQHandler() {
-    loop {}
+    spi_target_irq_handler(&SSP0);
 }
 
+/// This function handles SSP1 Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn SSP1_IRQHandler() {
-    loop {}
+    spi_target_irq_handler(&SSP1);
 }
 
 #[unsafe(no_mangle)]
@@ -112,14 +131,16 @@ pub extern "C" fn QSPI_IRQHandler() {
     loop {}
 }
 
+/// This function handles I2C0 Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn I2C0_IRQHandler() {
-    loop {}
+    i2c_target_irq_handler(&I2C0);
 }
 
+/// This function handles I2C1 Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn I2C1_IRQHandler() {
-    loop {}
+    i2c_target_irq_handler(&I2C1);
 }
 
 #[unsafe(no_mangle)]
@@ -137,9 +158,10 @@ pub extern "C" fn AFEC_IRQHandler() {
     loop {}
 }
 
+/// This function handles SSP2 Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn SSP2_IRQHandler() {
-    loop {}
+    spi_target_irq_handler(&SSP2);
 }
 
 #[unsafe(no_mangle)]
@@ -158,14 +180,16 @@ pub extern "C" fn LORA_IRQHandler() {
     radio_on_dio_irq();
 }
 
+/// This function handles GPIO Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn GPIO_IRQHandler() {
-    loop {}
+    gpio_irq_handler();
 }
 
+/// This function handles TIMER0 Interrupts.
 #[unsafe(no_mangle)]
 pub extern "C" fn TIMER0_IRQHandler() {
-    loop {}
+    micros_irq_handler();
 }
 
 #[unsafe(no_mangle)]
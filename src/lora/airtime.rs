@@ -0,0 +1,289 @@
+/// Largest amount of duty cycle bands of a region
+pub const MAX_BANDS: usize = 8;
+
+/// Length of the rolling window (ms)
+pub const WINDOW_MS: u64 = 24 * 60 * 60 * 1000;
+/// Slots the rolling window is split in
+pub const WINDOW_SLOTS: usize = 24;
+/// Length of a slot (ms)
+const SLOT_MS: u64 = WINDOW_MS / WINDOW_SLOTS as u64;
+
+/// The Things Network fair use policy: 30 s of uplink airtime per 24 h
+pub const TTN_FAIR_USE_MS: u32 = 30_000;
+
+/// Column of the transmissions outside any band (raw radio on arbitrary frequencies)
+const NO_BAND: usize = MAX_BANDS;
+
+/// Airtime bookkeeping: LoRaWAN band duty cycles, the aggregated duty cycle
+/// (`DutyCycleReq`) and a rolling 24 h budget.
+///
+/// Pure, the caller passes the current time (ms) and the time on air of every transmission.
+/// A transmission counts in the window until its whole slot (1 h) has left it, so the budget
+/// errs on the safe side.
+pub struct AirtimeLedger {
+    /// duty cycle of each band, as `1 / x`
+    bands: &'static [u16],
+    /// time until which each band is blocked
+    band_time_off: [u64; MAX_BANDS],
+    aggregated_duty_cycle: u16,
+    aggregated_time_off: u64,
+    /// airtime allowed in the rolling window, `None` for no limit
+    window_budget: Option<u32>,
+    /// airtime per slot and band, slot `n` of the time line is row `n % WINDOW_SLOTS`
+    slots: [[u32; MAX_BANDS + 1]; WINDOW_SLOTS],
+    /// newest slot written to
+    last_slot: u64,
+}
+
+impl AirtimeLedger {
+    pub const fn new() -> Self {
+        Self {
+            bands: &[],
+            band_time_off: [0; MAX_BANDS],
+            aggregated_duty_cycle: 1,
+            aggregated_time_off: 0,
+            window_budget: None,
+            slots: [[0; MAX_BANDS + 1]; WINDOW_SLOTS],
+            last_slot: 0,
+        }
+    }
+
+    /// Use the band duty cycles of a region (`1 / x`), clears the band time-offs
+    pub fn set_bands(&mut self, bands: &'static [u16]) {
+        self.bands = &bands[..bands.len().min(MAX_BANDS)];
+        self.band_time_off = [0; MAX_BANDS];
+    }
+
+    /// Duty cycle over all bands, as `1 / x` (`DutyCycleReq`)
+    pub fn set_aggregated_duty_cycle(&mut self, duty_cycle: u16) {
+        self.aggregated_duty_cycle = duty_cycle.max(1);
+    }
+
+    /// Airtime allowed per rolling 24 h, `None` for no limit
+    pub fn set_window_budget(&mut self, budget_ms: Option<u32>) {
+        self.window_budget = budget_ms;
+    }
+
+    /// Clear the duty cycle time-offs, the rolling window is kept
+    pub fn reset_duty_cycle(&mut self) {
+        self.band_time_off = [0; MAX_BANDS];
+        self.aggregated_duty_cycle = 1;
+        self.aggregated_time_off = 0;
+    }
+
+    /// Account for a transmission of `time_on_air` ms in `band`, ended at `now`
+    pub fn record(&mut self, now: u64, band: Option<u8>, time_on_air: u32) {
+        let toa = time_on_air as u64;
+        let band = band.map(usize::from).filter(|&b| b < self.bands.len());
+
+        if let Some(b) = band {
+            self.band_time_off[b] = now + toa * (self.bands[b].max(1) as u64 - 1);
+        }
+        self.aggregated_time_off = now + toa * (self.aggregated_duty_cycle as u64 - 1);
+
+        let slot = now / SLOT_MS;
+        self.clear_slots(slot);
+        let row = &mut self.slots[slot as usize % WINDOW_SLOTS];
+        let column = &mut row[band.unwrap_or(NO_BAND)];
+        *column = column.saturating_add(time_on_air);
+    }
+
+    /// Zero the rows reused since the last record
+    fn clear_slots(&mut self, slot: u64) {
+        if slot <= self.last_slot {
+            return;
+        }
+        let first = (self.last_slot + 1).max(slot.saturating_sub(WINDOW_SLOTS as u64 - 1));
+        for n in first..=slot {
+            self.slots[n as usize % WINDOW_SLOTS] = [0; MAX_BANDS + 1];
+        }
+        self.last_slot = slot;
+    }
+
+    /// Slots still in the window at `now` with their airtime rows, oldest first
+    fn window(&self, now: u64) -> impl Iterator<Item = (u64, &[u32; MAX_BANDS + 1])> {
+        let current = now / SLOT_MS;
+        (0..WINDOW_SLOTS as u64).rev().filter_map(move |age| {
+            let slot = self.last_slot.checked_sub(age)?;
+            (slot + WINDOW_SLOTS as u64 > current)
+                .then(|| (slot, &self.slots[slot as usize % WINDOW_SLOTS]))
+        })
+    }
+
+    /// Airtime (ms) spent in the last 24 h
+    pub fn window_airtime(&self, now: u64) -> u64 {
+        self.window(now)
+            .map(|(_, row)| row.iter().map(|&t| t as u64).sum::<u64>())
+            .sum()
+    }
+
+    /// Airtime (ms) spent in `band` in the last 24 h, `None` for transmissions outside bands
+    pub fn band_window_airtime(&self, now: u64, band: Option<u8>) -> u64 {
+        let column = band.map_or(NO_BAND, |b| (b as usize).min(NO_BAND));
+        self.window(now).map(|(_, row)| row[column] as u64).sum()
+    }
+
+    /// Time until which `band` is blocked by its duty cycle
+    pub fn band_time_off(&self, band: u8) -> u64 {
+        self.band_time_off.get(band as usize).copied().unwrap_or(0)
+    }
+
+    /// Time (ms from `now`) until `time_on_air` ms may be sent in `band`.
+    ///
+    /// `None` when it never can: longer than the whole 24 h budget.
+    pub fn time_until_tx(&self, now: u64, band: Option<u8>, time_on_air: u32) -> Option<u64> {
+        let mut free_at = self.aggregated_time_off;
+        if let Some(b) = band {
+            free_at = free_at.max(self.band_time_off(b));
+        }
+
+        if let Some(budget) = self.window_budget {
+            let (budget, toa) = (budget as u64, time_on_air as u64);
+            if toa > budget {
+                return None;
+            }
+            let mut used = self.window_airtime(now);
+            if used + toa > budget {
+                // wait for the oldest slots to leave the window
+                for (slot, row) in self.window(now) {
+                    used -= row.iter().map(|&t| t as u64).sum::<u64>();
+                    free_at = free_at.max((slot + WINDOW_SLOTS as u64) * SLOT_MS);
+                    if used + toa <= budget {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Some(free_at.saturating_sub(now))
+    }
+}
+
+impl Default for AirtimeLedger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static mut AIRTIME: AirtimeLedger = AirtimeLedger::new();
+
+/// Ledger shared by the LoRaWAN MAC and raw radio users
+pub fn airtime() -> &'static mut AirtimeLedger {
+    unsafe { &mut AIRTIME }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = SLOT_MS;
+
+    /// 1 % and 0.1 % bands, like EU868
+    fn ledger() -> AirtimeLedger {
+        let mut ledger = AirtimeLedger::new();
+        ledger.set_bands(&[100, 1000]);
+        ledger
+    }
+
+    #[test]
+    fn band_duty_cycle() {
+        let mut ledger = ledger();
+        assert_eq!(ledger.time_until_tx(0, Some(0), 100), Some(0));
+
+        ledger.record(1_000, Some(0), 100);
+        assert_eq!(ledger.band_time_off(0), 1_000 + 100 * 99);
+        assert_eq!(ledger.time_until_tx(1_000, Some(0), 100), Some(9_900));
+        assert_eq!(ledger.time_until_tx(5_000, Some(0), 100), Some(5_900));
+        assert_eq!(ledger.time_until_tx(10_900, Some(0), 100), Some(0));
+        // the other band is free
+        assert_eq!(ledger.time_until_tx(1_000, Some(1), 100), Some(0));
+
+        ledger.record(2_000, Some(1), 50);
+        assert_eq!(ledger.time_until_tx(2_000, Some(1), 50), Some(50 * 999));
+
+        ledger.reset_duty_cycle();
+        assert_eq!(ledger.time_until_tx(2_000, Some(0), 100), Some(0));
+        assert_eq!(ledger.time_until_tx(2_000, Some(1), 100), Some(0));
+    }
+
+    #[test]
+    fn aggregated_duty_cycle() {
+        let mut ledger = ledger();
+        ledger.set_aggregated_duty_cycle(1 << 4);
+        ledger.record(0, Some(1), 200);
+        // blocks every band and transmissions outside them
+        assert_eq!(ledger.time_until_tx(0, Some(0), 10), Some(200 * 15));
+        assert_eq!(ledger.time_until_tx(0, None, 10), Some(200 * 15));
+        // the stricter band duty cycle wins
+        assert_eq!(ledger.time_until_tx(0, Some(1), 10), Some(200 * 999));
+
+        ledger.set_aggregated_duty_cycle(0);
+        ledger.record(10_000, None, 200);
+        assert_eq!(ledger.time_until_tx(10_000, None, 10), Some(0));
+    }
+
+    #[test]
+    fn unknown_band() {
+        let mut ledger = ledger();
+        ledger.record(0, Some(5), 100);
+        assert_eq!(ledger.band_time_off(5), 0);
+        assert_eq!(ledger.time_until_tx(0, Some(5), 100), Some(0));
+        // counted outside the bands
+        assert_eq!(ledger.band_window_airtime(0, None), 100);
+    }
+
+    #[test]
+    fn rolling_window() {
+        let mut ledger = ledger();
+        ledger.record(0, Some(0), 100);
+        ledger.record(HOUR + 1, Some(1), 200);
+        ledger.record(2 * HOUR, None, 300);
+        assert_eq!(ledger.window_airtime(2 * HOUR), 600);
+        assert_eq!(ledger.band_window_airtime(2 * HOUR, Some(0)), 100);
+        assert_eq!(ledger.band_window_airtime(2 * HOUR, Some(1)), 200);
+        assert_eq!(ledger.band_window_airtime(2 * HOUR, None), 300);
+
+        // the first slot leaves the window once all of it is older than 24 h
+        assert_eq!(ledger.window_airtime(WINDOW_MS - 1), 600);
+        assert_eq!(ledger.window_airtime(WINDOW_MS), 500);
+        assert_eq!(ledger.window_airtime(WINDOW_MS + HOUR), 300);
+        assert_eq!(ledger.window_airtime(2 * WINDOW_MS), 0);
+
+        // reused rows start from zero
+        ledger.record(WINDOW_MS, Some(0), 10);
+        assert_eq!(ledger.band_window_airtime(WINDOW_MS, Some(0)), 10);
+        assert_eq!(ledger.window_airtime(WINDOW_MS), 510);
+
+        // a gap longer than the window clears everything
+        ledger.record(5 * WINDOW_MS, None, 20);
+        assert_eq!(ledger.window_airtime(5 * WINDOW_MS), 20);
+    }
+
+    #[test]
+    fn window_budget() {
+        let mut ledger = ledger();
+        ledger.set_window_budget(Some(TTN_FAIR_USE_MS));
+        assert_eq!(ledger.time_until_tx(0, None, TTN_FAIR_USE_MS + 1), None);
+
+        ledger.record(0, None, 10_000);
+        ledger.record(3 * HOUR, None, 15_000);
+        let now = 5 * HOUR;
+        assert_eq!(ledger.time_until_tx(now, None, 5_000), Some(0));
+        // waits for the first slot to leave the window
+        assert_eq!(
+            ledger.time_until_tx(now, None, 5_001),
+            Some(WINDOW_MS - now)
+        );
+        // and for both of them
+        assert_eq!(
+            ledger.time_until_tx(now, None, 20_000),
+            Some(WINDOW_MS + 3 * HOUR - now)
+        );
+        // duty cycle time-offs still apply on top
+        ledger.record(now, Some(0), 1_000);
+        assert_eq!(ledger.time_until_tx(now, Some(0), 10), Some(99_000));
+
+        ledger.set_window_budget(None);
+        assert_eq!(ledger.time_until_tx(now, None, 20_000), Some(0));
+    }
+}
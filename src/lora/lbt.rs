@@ -0,0 +1,108 @@
+use crate::{
+    lora::{
+        radio::{
+            RadioModem, radio_cad_detect, radio_get_channel, radio_get_modem,
+            radio_is_channel_free, radio_send, radio_set_channel, radio_standby,
+        },
+        region::{Region, as923, kr920},
+    },
+    peripherals::delay::delay_ms,
+    rng::rng_range,
+};
+
+/// Listen-before-talk settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LbtConfig {
+    /// the channel is busy above this RSSI (dBm)
+    pub rssi_threshold: i16,
+    /// time the RSSI must stay below the threshold (ms)
+    pub sense_time: usize,
+    /// symbols of the CAD run before sensing, 0 to skip it (LoRa only)
+    pub cad_symbols: u8,
+    /// sensing attempts before giving up
+    pub max_attempts: u8,
+    /// shortest random backoff between attempts (ms)
+    pub backoff_min: usize,
+    /// longest random backoff between attempts (ms)
+    pub backoff_max: usize,
+}
+
+impl LbtConfig {
+    /// KR920: -65 dBm for 6 ms
+    pub const KR920: Self = Self {
+        rssi_threshold: kr920::RSSI_FREE_TH,
+        sense_time: kr920::CARRIER_SENSE_TIME,
+        cad_symbols: 0,
+        max_attempts: 8,
+        backoff_min: 10,
+        backoff_max: 100,
+    };
+
+    /// AS923 in Japan (ARIB STD-T108): -80 dBm for 5 ms
+    pub const AS923_JAPAN: Self = Self {
+        rssi_threshold: as923::RSSI_FREE_TH_JAPAN,
+        sense_time: as923::CARRIER_SENSE_TIME_JAPAN,
+        cad_symbols: 0,
+        max_attempts: 8,
+        backoff_min: 10,
+        backoff_max: 100,
+    };
+
+    /// Settings a region always requires. AS923 needs LBT only in Japan, which the
+    /// channel plan can't tell, use [`Self::AS923_JAPAN`] there.
+    pub fn for_region(region: Region) -> Option<Self> {
+        match region {
+            Region::Kr920 => Some(Self::KR920),
+            _ => None,
+        }
+    }
+
+    /// Same settings with a CAD over `symbols` before every sensing
+    pub const fn with_cad(mut self, symbols: u8) -> Self {
+        self.cad_symbols = symbols;
+        self
+    }
+}
+
+/// Listen-before-talk failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LbtError {
+    /// the channel was busy on every attempt
+    ChannelBusy,
+}
+
+/// Wait until `frequency` is clear: an optional CAD, then the RSSI below the threshold for
+/// the whole sensing time. Busy attempts are retried after a random backoff.
+///
+/// Leaves the radio asleep on `frequency`.
+pub fn lbt_wait_clear(
+    modem: RadioModem,
+    frequency: usize,
+    config: &LbtConfig,
+) -> Result<(), LbtError> {
+    for attempt in 0..config.max_attempts.max(1) {
+        if attempt > 0 {
+            delay_ms(rng_range(config.backoff_min as i32, config.backoff_max as i32) as usize);
+        }
+
+        if config.cad_symbols > 0 && modem == RadioModem::LoRa {
+            radio_set_channel(frequency);
+            if radio_cad_detect(config.cad_symbols) {
+                continue;
+            }
+        }
+
+        if radio_is_channel_free(modem, frequency, config.rssi_threshold, config.sense_time) {
+            return Ok(());
+        }
+    }
+    Err(LbtError::ChannelBusy)
+}
+
+/// [`radio_send`] on the current channel once it is clear
+pub fn radio_send_lbt(buffer: &[u8], config: &LbtConfig) -> Result<(), LbtError> {
+    lbt_wait_clear(radio_get_modem(), radio_get_channel(), config)?;
+    radio_standby();
+    radio_send(buffer);
+    Ok(())
+}
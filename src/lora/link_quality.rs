@@ -0,0 +1,349 @@
+use heapless::LinearMap;
+
+use crate::lora::radio::sx126x::{ModulationParams, RadioLoRaBandwidths};
+
+/// Samples kept for the rolling RSSI / SNR figures
+pub const LINK_WINDOW: usize = 16;
+/// Channels tracked separately, later ones only count in the totals
+pub const LINK_CHANNELS: usize = 16;
+/// Datarates tracked separately
+pub const LINK_RATES: usize = 8;
+
+/// Diagnostic report format version
+pub const LINK_REPORT_VERSION: u8 = 1;
+/// Report header: version, counters and the overall RSSI / SNR summaries
+pub const LINK_REPORT_HEADER_SIZE: usize = 1 + 6 * 2 + 6;
+/// Per channel report entry: frequency, received frames, mean RSSI and SNR
+pub const LINK_REPORT_CHANNEL_SIZE: usize = 3 + 2 + 2;
+
+/// Datarate as the radio sees it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkRate {
+    /// LoRa spreading factor and bandwidth
+    LoRa {
+        sf: u8,
+        bandwidth: RadioLoRaBandwidths,
+    },
+    /// (G)FSK bit rate in bit/s
+    Fsk { bit_rate: u32 },
+}
+
+impl LinkRate {
+    /// Datarate of the radio modulation parameters
+    pub fn from_modulation(params: &ModulationParams) -> Self {
+        match params {
+            ModulationParams::LoRa(lora) => Self::LoRa {
+                sf: lora.spreading_factor as u8,
+                bandwidth: lora.bandwidth,
+            },
+            ModulationParams::Gfsk(gfsk) => Self::Fsk {
+                bit_rate: gfsk.bit_rate as u32,
+            },
+        }
+    }
+}
+
+/// Something the radio reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkEvent {
+    /// Frame received with a good CRC
+    RxOk { rssi: i16, snr: i8 },
+    /// Frame received with a bad CRC
+    CrcError { rssi: i16, snr: i8 },
+    /// LoRa header with a bad CRC
+    HeaderError,
+    /// Nothing received in the RX window
+    RxTimeout,
+    /// Frame sent
+    TxDone,
+    /// Transmission did not finish in time
+    TxTimeout,
+}
+
+/// Event counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkCounters {
+    pub rx_ok: u32,
+    pub crc_errors: u32,
+    pub header_errors: u32,
+    pub rx_timeouts: u32,
+    pub tx_done: u32,
+    pub tx_timeouts: u32,
+}
+
+impl LinkCounters {
+    /// Frames received, bad CRCs included
+    pub fn rx_frames(&self) -> u32 {
+        self.rx_ok.saturating_add(self.crc_errors)
+    }
+
+    /// Share of the received frames with a good CRC, in percent
+    pub fn rx_success_percent(&self) -> Option<u8> {
+        let total = self.rx_frames() as u64 + self.header_errors as u64;
+        (total != 0).then(|| (self.rx_ok as u64 * 100 / total) as u8)
+    }
+}
+
+/// Min, max and mean of the samples in a [`Window`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub min: i16,
+    pub max: i16,
+    pub mean: i16,
+    /// Number of samples behind the figures
+    pub samples: u8,
+}
+
+/// Last [`LINK_WINDOW`] samples of a value
+#[derive(Debug, Clone, Copy)]
+pub struct Window {
+    samples: [i16; LINK_WINDOW],
+    len: usize,
+    next: usize,
+}
+
+impl Window {
+    pub const fn new() -> Self {
+        Self {
+            samples: [0; LINK_WINDOW],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    /// Add a sample, dropping the oldest one when full
+    pub fn push(&mut self, value: i16) {
+        self.samples[self.next] = value;
+        self.next = (self.next + 1) % LINK_WINDOW;
+        self.len = (self.len + 1).min(LINK_WINDOW);
+    }
+
+    /// `None` until the first sample
+    pub fn summary(&self) -> Option<Summary> {
+        let samples = &self.samples[..self.len];
+        let min = *samples.iter().min()?;
+        let max = *samples.iter().max()?;
+        let sum: i32 = samples.iter().map(|&s| s as i32).sum();
+        Some(Summary {
+            min,
+            max,
+            mean: (sum / self.len as i32) as i16,
+            samples: self.len as u8,
+        })
+    }
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counters and signal figures of one channel, datarate or the whole link
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinkBucket {
+    pub counters: LinkCounters,
+    pub rssi: Window,
+    pub snr: Window,
+}
+
+impl LinkBucket {
+    pub const fn new() -> Self {
+        Self {
+            counters: LinkCounters {
+                rx_ok: 0,
+                crc_errors: 0,
+                header_errors: 0,
+                rx_timeouts: 0,
+                tx_done: 0,
+                tx_timeouts: 0,
+            },
+            rssi: Window::new(),
+            snr: Window::new(),
+        }
+    }
+
+    fn record(&mut self, event: LinkEvent) {
+        let c = &mut self.counters;
+        let counter = match event {
+            LinkEvent::RxOk { rssi, snr } => {
+                self.rssi.push(rssi);
+                self.snr.push(snr as i16);
+                &mut c.rx_ok
+            }
+            // bad frames say nothing about the wanted signal, only count them
+            LinkEvent::CrcError { .. } => &mut c.crc_errors,
+            LinkEvent::HeaderError => &mut c.header_errors,
+            LinkEvent::RxTimeout => &mut c.rx_timeouts,
+            LinkEvent::TxDone => &mut c.tx_done,
+            LinkEvent::TxTimeout => &mut c.tx_timeouts,
+        };
+        *counter = counter.saturating_add(1);
+    }
+}
+
+/// Radio statistics per channel, per datarate and overall
+pub struct LinkStats {
+    total: LinkBucket,
+    channels: LinearMap<u32, LinkBucket, LINK_CHANNELS>,
+    rates: LinearMap<LinkRate, LinkBucket, LINK_RATES>,
+}
+
+impl LinkStats {
+    pub const fn new() -> Self {
+        Self {
+            total: LinkBucket::new(),
+            channels: LinearMap::new(),
+            rates: LinearMap::new(),
+        }
+    }
+
+    /// Count `event`, seen on `channel` (Hz) at `rate`
+    pub fn record(&mut self, channel: u32, rate: LinkRate, event: LinkEvent) {
+        self.total.record(event);
+        if let Some(bucket) = bucket(&mut self.channels, channel) {
+            bucket.record(event);
+        }
+        if let Some(bucket) = bucket(&mut self.rates, rate) {
+            bucket.record(event);
+        }
+    }
+
+    /// Everything seen since the last reset
+    pub fn total(&self) -> &LinkBucket {
+        &self.total
+    }
+
+    /// Figures of one channel
+    pub fn channel(&self, channel: u32) -> Option<&LinkBucket> {
+        self.channels.get(&channel)
+    }
+
+    /// Figures of one datarate
+    pub fn rate(&self, rate: LinkRate) -> Option<&LinkBucket> {
+        self.rates.get(&rate)
+    }
+
+    /// Tracked channels in first seen order
+    pub fn channels(&self) -> impl Iterator<Item = (u32, &LinkBucket)> {
+        self.channels
+            .iter()
+            .map(|(&channel, bucket)| (channel, bucket))
+    }
+
+    /// Tracked datarates in first seen order
+    pub fn rates(&self) -> impl Iterator<Item = (LinkRate, &LinkBucket)> {
+        self.rates.iter().map(|(&rate, bucket)| (rate, bucket))
+    }
+
+    /// Forget everything
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Encode a diagnostic uplink payload into `buf`, returns its length.
+    ///
+    /// Layout, multi-byte values big endian:
+    /// - version (1)
+    /// - rx ok, crc errors, header errors, rx timeouts, tx done, tx timeouts (2 each, saturated)
+    /// - RSSI min, max, mean and SNR min, max, mean (1 each, dBm / dB, 0x7F without samples)
+    /// - per channel while it fits: frequency / 100 (3), rx ok (2), mean RSSI and SNR (1 each)
+    ///
+    /// Returns 0 when `buf` can't hold the header.
+    pub fn encode_report(&self, buf: &mut [u8]) -> usize {
+        if buf.len() < LINK_REPORT_HEADER_SIZE {
+            return 0;
+        }
+
+        let c = &self.total.counters;
+        buf[0] = LINK_REPORT_VERSION;
+        let counters = [
+            c.rx_ok,
+            c.crc_errors,
+            c.header_errors,
+            c.rx_timeouts,
+            c.tx_done,
+            c.tx_timeouts,
+        ];
+        for (i, counter) in counters.iter().enumerate() {
+            buf[1 + i * 2..3 + i * 2].copy_from_slice(&saturate_u16(*counter).to_be_bytes());
+        }
+
+        let mut len = 1 + counters.len() * 2;
+        for window in [&self.total.rssi, &self.total.snr] {
+            let figures = match window.summary() {
+                Some(s) => [s.min, s.max, s.mean].map(saturate_i8),
+                None => [NO_SAMPLES; 3],
+            };
+            for figure in figures {
+                buf[len] = figure as u8;
+                len += 1;
+            }
+        }
+
+        for (channel, bucket) in self.channels() {
+            let Some(entry) = buf.get_mut(len..len + LINK_REPORT_CHANNEL_SIZE) else {
+                break;
+            };
+            entry[..3].copy_from_slice(&(channel / 100).to_be_bytes()[1..]);
+            entry[3..5].copy_from_slice(&saturate_u16(bucket.counters.rx_ok).to_be_bytes());
+            entry[5] = bucket
+                .rssi
+                .summary()
+                .map_or(NO_SAMPLES, |s| saturate_i8(s.mean)) as u8;
+            entry[6] = bucket
+                .snr
+                .summary()
+                .map_or(NO_SAMPLES, |s| saturate_i8(s.mean)) as u8;
+            len += LINK_REPORT_CHANNEL_SIZE;
+        }
+
+        len
+    }
+}
+
+impl Default for LinkStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Report value of a window without samples
+const NO_SAMPLES: i8 = i8::MAX;
+
+fn saturate_u16(value: u32) -> u16 {
+    value.min(u16::MAX as u32) as u16
+}
+
+fn saturate_i8(value: i16) -> i8 {
+    // i8::MAX is reserved for "no samples"
+    value.clamp(i8::MIN as i16, i8::MAX as i16 - 1) as i8
+}
+
+/// Bucket of `key`, added if there is room
+fn bucket<K: Eq + Copy, const N: usize>(
+    map: &mut LinearMap<K, LinkBucket, N>,
+    key: K,
+) -> Option<&mut LinkBucket> {
+    if !map.contains_key(&key) {
+        map.insert(key, LinkBucket::new()).ok()?;
+    }
+    map.get_mut(&key)
+}
+
+static mut LINK_STATS: LinkStats = LinkStats::new();
+
+/// Statistics collected by the radio driver
+pub fn link_stats() -> &'static LinkStats {
+    unsafe { &LINK_STATS }
+}
+
+/// Count a radio event, called by the radio driver
+pub fn link_record(channel: u32, rate: LinkRate, event: LinkEvent) {
+    unsafe { LINK_STATS.record(channel, rate, event) };
+}
+
+/// Clear the collected statistics
+pub fn link_stats_reset() {
+    unsafe { LINK_STATS.reset() };
+}
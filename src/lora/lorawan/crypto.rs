@@ -0,0 +1,291 @@
+use crate::crypto::{Block, BlockCipher, KeyInit, aes::Aes128, cmac::cmac};
+
+/// AES-128 key
+pub type AesKey = [u8; 16];
+
+/// Single AES-128 block operations used by LoRaWAN.
+///
+/// Lets the same frame code run on the hardware security engine or in software on the host.
+pub trait AesBackend {
+    /// Encrypt a single block in place
+    fn encrypt_block(&mut self, key: &AesKey, block: &mut [u8; 16]);
+    /// Decrypt a single block in place
+    fn decrypt_block(&mut self, key: &AesKey, block: &mut [u8; 16]);
+}
+
+/// Frame direction, part of the MIC and encryption blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Direction {
+    /// end-device to network
+    Uplink = 0,
+    /// network to end-device
+    Downlink = 1,
+}
+
+/// [`AesBackend`] on a [`KeyInit`] cipher, set up again only when the key changes
+pub struct CachedAes<C: KeyInit> {
+    key: Option<(AesKey, C)>,
+}
+
+/// [`AesBackend`] on the software AES
+pub type SoftAes = CachedAes<Aes128>;
+
+impl<C: KeyInit> CachedAes<C> {
+    /// Backend without a key loaded
+    pub const fn new() -> Self {
+        Self { key: None }
+    }
+
+    fn cipher(&mut self, key: &AesKey) -> &mut C {
+        if !matches!(&self.key, Some((loaded, _)) if loaded == key) {
+            self.key = Some((*key, C::with_key(key)));
+        }
+        &mut self.key.as_mut().unwrap().1
+    }
+}
+
+impl<C: KeyInit> Default for CachedAes<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: KeyInit> AesBackend for CachedAes<C> {
+    fn encrypt_block(&mut self, key: &AesKey, block: &mut [u8; 16]) {
+        self.cipher(key).encrypt_block(block);
+    }
+
+    fn decrypt_block(&mut self, key: &AesKey, block: &mut [u8; 16]) {
+        self.cipher(key).decrypt_block(block);
+    }
+}
+
+/// An [`AesBackend`] bound to one key, to run the generic block cipher modes on it
+struct Keyed<'a, B: AesBackend> {
+    backend: &'a mut B,
+    key: &'a AesKey,
+}
+
+impl<B: AesBackend> BlockCipher for Keyed<'_, B> {
+    fn encrypt_block(&mut self, block: &mut Block) {
+        self.backend.encrypt_block(self.key, block);
+    }
+
+    fn decrypt_block(&mut self, block: &mut Block) {
+        self.backend.decrypt_block(self.key, block);
+    }
+}
+
+/// AES-CMAC (RFC 4493) over the concatenation of `chunks`
+pub fn aes_cmac<B: AesBackend>(backend: &mut B, key: &AesKey, chunks: &[&[u8]]) -> [u8; 16] {
+    cmac(&mut Keyed { backend, key }, chunks)
+}
+
+/// Build the B0 / A block shared by the MIC and payload encryption
+fn frame_block(first: u8, dir: Direction, dev_addr: u32, fcnt: u32, last: u8) -> [u8; 16] {
+    let mut block = [0u8; 16];
+    block[0] = first;
+    block[5] = dir as u8;
+    block[6..10].copy_from_slice(&dev_addr.to_le_bytes());
+    block[10..14].copy_from_slice(&fcnt.to_le_bytes());
+    block[15] = last;
+    block
+}
+
+/// Compute the MIC of a data frame (`MHDR | FHDR | FPort | FRMPayload`)
+pub fn compute_mic<B: AesBackend>(
+    backend: &mut B,
+    key: &AesKey,
+    msg: &[u8],
+    dev_addr: u32,
+    dir: Direction,
+    fcnt: u32,
+) -> u32 {
+    let b0 = frame_block(0x49, dir, dev_addr, fcnt, msg.len() as u8);
+    let cmac = aes_cmac(backend, key, &[&b0, msg]);
+    u32::from_le_bytes([cmac[0], cmac[1], cmac[2], cmac[3]])
+}
+
+/// Compute the MIC of a join-request or (decrypted) join-accept
+pub fn compute_join_mic<B: AesBackend>(backend: &mut B, key: &AesKey, msg: &[u8]) -> u32 {
+    let cmac = aes_cmac(backend, key, &[msg]);
+    u32::from_le_bytes([cmac[0], cmac[1], cmac[2], cmac[3]])
+}
+
+/// Encrypt or decrypt a FRMPayload in place (the operation is symmetric)
+pub fn payload_encrypt<B: AesBackend>(
+    backend: &mut B,
+    key: &AesKey,
+    data: &mut [u8],
+    dev_addr: u32,
+    dir: Direction,
+    fcnt: u32,
+) {
+    for (i, chunk) in data.chunks_mut(16).enumerate() {
+        let mut s = frame_block(0x01, dir, dev_addr, fcnt, (i + 1) as u8);
+        backend.encrypt_block(key, &mut s);
+        for (byte, k) in chunk.iter_mut().zip(s.iter()) {
+            *byte ^= k;
+        }
+    }
+}
+
+/// Decrypt a join-accept (everything after the MHDR, MIC included) in place.
+///
+/// The network encrypts join-accepts with AES *decrypt*, so the device uses encrypt.
+pub fn join_accept_decrypt<B: AesBackend>(backend: &mut B, key: &AesKey, data: &mut [u8]) {
+    for chunk in data.chunks_exact_mut(16) {
+        let block: &mut [u8; 16] = chunk.try_into().unwrap();
+        backend.encrypt_block(key, block);
+    }
+}
+
+/// Encrypt a join-accept (everything after the MHDR, MIC included) in place, network side
+pub fn join_accept_encrypt<B: AesBackend>(backend: &mut B, key: &AesKey, data: &mut [u8]) {
+    for chunk in data.chunks_exact_mut(16) {
+        let block: &mut [u8; 16] = chunk.try_into().unwrap();
+        backend.decrypt_block(key, block);
+    }
+}
+
+/// Session keys derived after a successful join
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionKeys {
+    /// Network session key
+    pub nwk_skey: AesKey,
+    /// Application session key
+    pub app_skey: AesKey,
+}
+
+/// Derive the session keys from the AppKey, the join-accept nonces and the DevNonce
+pub fn derive_session_keys<B: AesBackend>(
+    backend: &mut B,
+    app_key: &AesKey,
+    join_nonce: &[u8; 3],
+    net_id: &[u8; 3],
+    dev_nonce: u16,
+) -> SessionKeys {
+    let mut nonce = [0u8; 16];
+    nonce[1..4].copy_from_slice(join_nonce);
+    nonce[4..7].copy_from_slice(net_id);
+    nonce[7..9].copy_from_slice(&dev_nonce.to_le_bytes());
+
+    let mut nwk_skey = nonce;
+    nwk_skey[0] = 0x01;
+    backend.encrypt_block(app_key, &mut nwk_skey);
+
+    let mut app_skey = nonce;
+    app_skey[0] = 0x02;
+    backend.encrypt_block(app_key, &mut app_skey);
+
+    SessionKeys { nwk_skey, app_skey }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APP_KEY: AesKey = [
+        0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F,
+        0x3C,
+    ];
+
+    #[test]
+    fn cmac_rfc4493() {
+        let mut aes = SoftAes::new();
+        let msg = [
+            0x6B, 0xC1, 0xBE, 0xE2, 0x2E, 0x40, 0x9F, 0x96, 0xE9, 0x3D, 0x7E, 0x11, 0x73, 0x93,
+            0x17, 0x2A,
+        ];
+        assert_eq!(
+            aes_cmac(&mut aes, &APP_KEY, &[]),
+            [
+                0xBB, 0x1D, 0x69, 0x29, 0xE9, 0x59, 0x37, 0x28, 0x7F, 0xA3, 0x7D, 0x12, 0x9B, 0x75,
+                0x67, 0x46
+            ]
+        );
+        let expected = [
+            0x07, 0x0A, 0x16, 0xB4, 0x6B, 0x4D, 0x41, 0x44, 0xF7, 0x9B, 0xDD, 0x9D, 0xD0, 0x4A,
+            0x28, 0x7C,
+        ];
+        assert_eq!(aes_cmac(&mut aes, &APP_KEY, &[&msg]), expected);
+        // chunks are concatenated
+        assert_eq!(
+            aes_cmac(&mut aes, &APP_KEY, &[&msg[..5], &msg[5..]]),
+            expected
+        );
+    }
+
+    #[test]
+    fn payload_encrypt_is_symmetric() {
+        let mut aes = SoftAes::new();
+        let plain: [u8; 40] = core::array::from_fn(|i| i as u8);
+        let mut data = plain;
+        payload_encrypt(
+            &mut aes,
+            &APP_KEY,
+            &mut data,
+            0x26011F2A,
+            Direction::Uplink,
+            7,
+        );
+        assert_ne!(data, plain);
+        let mut down = plain;
+        payload_encrypt(
+            &mut aes,
+            &APP_KEY,
+            &mut down,
+            0x26011F2A,
+            Direction::Downlink,
+            7,
+        );
+        assert_ne!(down, data);
+        payload_encrypt(
+            &mut aes,
+            &APP_KEY,
+            &mut data,
+            0x26011F2A,
+            Direction::Uplink,
+            7,
+        );
+        assert_eq!(data, plain);
+    }
+
+    #[test]
+    fn join_accept_encrypt_round_trip() {
+        let mut aes = SoftAes::new();
+        let plain: [u8; 32] = core::array::from_fn(|i| (i * 7) as u8);
+        let mut data = plain;
+        join_accept_encrypt(&mut aes, &APP_KEY, &mut data);
+        assert_ne!(data, plain);
+        join_accept_decrypt(&mut aes, &APP_KEY, &mut data);
+        assert_eq!(data, plain);
+    }
+
+    #[test]
+    fn session_keys() {
+        let mut aes = SoftAes::new();
+        let keys = derive_session_keys(
+            &mut aes,
+            &APP_KEY,
+            &[0x01, 0x02, 0x03],
+            &[0x00, 0x00, 0x13],
+            0x1234,
+        );
+        assert_eq!(
+            keys.nwk_skey,
+            [
+                0x5C, 0x46, 0x66, 0x2E, 0xAC, 0x2B, 0x47, 0xA0, 0x3F, 0xA9, 0x08, 0xC4, 0x68, 0x0C,
+                0xA8, 0xE8
+            ]
+        );
+        assert_eq!(
+            keys.app_skey,
+            [
+                0xF5, 0xE6, 0x38, 0xC6, 0xB8, 0x30, 0xC7, 0x2E, 0x42, 0xA4, 0x4C, 0x92, 0xAC, 0x0B,
+                0x6A, 0x0D
+            ]
+        );
+    }
+}
@@ -0,0 +1,848 @@
+use crate::lora::lorawan::crypto::{
+    AesBackend, AesKey, Direction, compute_join_mic, compute_mic, join_accept_decrypt,
+    join_accept_encrypt, payload_encrypt,
+};
+
+/// LoRaWAN MIC, encryption and key derivation
+pub mod crypto;
+
+/// Maximum PHYPayload size
+pub const PHY_PAYLOAD_MAX_SIZE: usize = 255;
+/// MHDR size
+pub const MHDR_SIZE: usize = 1;
+/// MIC size
+pub const MIC_SIZE: usize = 4;
+/// FHDR size without FOpts
+pub const FHDR_MIN_SIZE: usize = 7;
+/// Maximum FOpts size
+pub const FOPTS_MAX_SIZE: usize = 15;
+/// Join-request size, MHDR and MIC included
+pub const JOIN_REQUEST_SIZE: usize = 23;
+/// Join-accept size without CFList, MHDR and MIC included
+pub const JOIN_ACCEPT_SIZE: usize = 17;
+/// CFList size
+pub const CF_LIST_SIZE: usize = 16;
+
+/// LoRaWAN frame parsing/building error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoRaWanError {
+    /// frame is shorter than its headers require
+    TooShort,
+    /// output buffer too small or payload longer than allowed
+    TooLong,
+    /// MType does not match the requested operation
+    InvalidMType,
+    /// only LoRaWAN R1 (major 0) is supported
+    UnsupportedMajor,
+    /// FOpts present together with FPort 0
+    InvalidFOpts,
+    /// MIC check failed
+    InvalidMic,
+}
+
+/// Message type (LoRaWAN Specification V1.0.2, chapter 4.2.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MType {
+    JoinRequest = 0,
+    JoinAccept = 1,
+    UnconfirmedDataUp = 2,
+    UnconfirmedDataDown = 3,
+    ConfirmedDataUp = 4,
+    ConfirmedDataDown = 5,
+    RejoinRequest = 6,
+    Proprietary = 7,
+}
+
+impl MType {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0x07 {
+            0 => MType::JoinRequest,
+            1 => MType::JoinAccept,
+            2 => MType::UnconfirmedDataUp,
+            3 => MType::UnconfirmedDataDown,
+            4 => MType::ConfirmedDataUp,
+            5 => MType::ConfirmedDataDown,
+            6 => MType::RejoinRequest,
+            _ => MType::Proprietary,
+        }
+    }
+
+    /// Whether this message type is a data frame
+    pub fn is_data(&self) -> bool {
+        matches!(
+            self,
+            MType::UnconfirmedDataUp
+                | MType::UnconfirmedDataDown
+                | MType::ConfirmedDataUp
+                | MType::ConfirmedDataDown
+        )
+    }
+
+    /// Direction of a data frame
+    pub fn direction(&self) -> Direction {
+        match self {
+            MType::UnconfirmedDataDown | MType::ConfirmedDataDown | MType::JoinAccept => {
+                Direction::Downlink
+            }
+            _ => Direction::Uplink,
+        }
+    }
+}
+
+/// MAC header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mhdr(pub u8);
+
+impl Mhdr {
+    /// Build a LoRaWAN R1 header for the given message type
+    pub fn new(mtype: MType) -> Self {
+        Self((mtype as u8) << 5)
+    }
+
+    /// Message type
+    pub fn mtype(&self) -> MType {
+        MType::from_bits(self.0 >> 5)
+    }
+
+    /// Major version (0 = LoRaWAN R1)
+    pub fn major(&self) -> u8 {
+        self.0 & 0x03
+    }
+}
+
+/// Frame control octet
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FCtrl(pub u8);
+
+impl FCtrl {
+    /// Adaptive data rate
+    pub fn adr(&self) -> bool {
+        self.0 & (1 << 7) != 0
+    }
+
+    /// ADR acknowledgement request (uplink only)
+    pub fn adr_ack_req(&self) -> bool {
+        self.0 & (1 << 6) != 0
+    }
+
+    /// Acknowledgement of the last confirmed frame
+    pub fn ack(&self) -> bool {
+        self.0 & (1 << 5) != 0
+    }
+
+    /// Frame pending (downlink) or Class B enabled (uplink)
+    pub fn f_pending(&self) -> bool {
+        self.0 & (1 << 4) != 0
+    }
+
+    /// FOpts length
+    pub fn fopts_len(&self) -> usize {
+        (self.0 & 0x0F) as usize
+    }
+
+    /// Set the ADR bit
+    pub fn set_adr(mut self, enable: bool) -> Self {
+        self.set_bit(7, enable);
+        self
+    }
+
+    /// Set the ADRACKReq bit
+    pub fn set_adr_ack_req(mut self, enable: bool) -> Self {
+        self.set_bit(6, enable);
+        self
+    }
+
+    /// Set the ACK bit
+    pub fn set_ack(mut self, enable: bool) -> Self {
+        self.set_bit(5, enable);
+        self
+    }
+
+    /// Set the FPending / ClassB bit
+    pub fn set_f_pending(mut self, enable: bool) -> Self {
+        self.set_bit(4, enable);
+        self
+    }
+
+    fn set_bit(&mut self, bit: u8, enable: bool) {
+        if enable {
+            self.0 |= 1 << bit;
+        } else {
+            self.0 &= !(1 << bit);
+        }
+    }
+}
+
+/// Frame header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fhdr<'a> {
+    /// Device address
+    pub dev_addr: u32,
+    /// Frame control
+    pub fctrl: FCtrl,
+    /// 16 LSBs of the frame counter
+    pub fcnt: u16,
+    /// MAC commands piggybacked in the header
+    pub fopts: &'a [u8],
+}
+
+/// Data frame (confirmed or unconfirmed, up or down)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataFrame<'a> {
+    /// MAC header
+    pub mhdr: Mhdr,
+    /// Frame header
+    pub fhdr: Fhdr<'a>,
+    /// Port, `None` when the frame carries no payload
+    pub fport: Option<u8>,
+    /// FRMPayload as it appears on air (encrypted)
+    pub frm_payload: &'a [u8],
+    /// Message integrity code
+    pub mic: u32,
+    /// `MHDR | FHDR | FPort | FRMPayload`, the MIC input
+    raw: &'a [u8],
+}
+
+impl DataFrame<'_> {
+    /// Direction of the frame
+    pub fn direction(&self) -> Direction {
+        self.mhdr.mtype().direction()
+    }
+
+    /// Whether the frame requires an acknowledgement
+    pub fn is_confirmed(&self) -> bool {
+        matches!(
+            self.mhdr.mtype(),
+            MType::ConfirmedDataUp | MType::ConfirmedDataDown
+        )
+    }
+
+    /// Check the MIC with the network session key and the full 32-bit frame counter
+    pub fn verify_mic<B: AesBackend>(&self, backend: &mut B, nwk_skey: &AesKey, fcnt: u32) -> bool {
+        compute_mic(
+            backend,
+            nwk_skey,
+            self.raw,
+            self.fhdr.dev_addr,
+            self.direction(),
+            fcnt,
+        ) == self.mic
+    }
+
+    /// Decrypt FRMPayload into `out`, returns the decrypted slice.
+    ///
+    /// `key` is the NwkSKey when FPort is 0, the AppSKey otherwise.
+    pub fn decrypt_payload<'o, B: AesBackend>(
+        &self,
+        backend: &mut B,
+        key: &AesKey,
+        fcnt: u32,
+        out: &'o mut [u8],
+    ) -> Result<&'o [u8], LoRaWanError> {
+        let len = self.frm_payload.len();
+        let out = out.get_mut(..len).ok_or(LoRaWanError::TooLong)?;
+        out.copy_from_slice(self.frm_payload);
+        payload_encrypt(
+            backend,
+            key,
+            out,
+            self.fhdr.dev_addr,
+            self.direction(),
+            fcnt,
+        );
+        Ok(out)
+    }
+}
+
+/// Join-request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JoinRequest {
+    /// AppEUI / JoinEUI, most significant byte first
+    pub app_eui: [u8; 8],
+    /// DevEUI, most significant byte first
+    pub dev_eui: [u8; 8],
+    /// Device nonce
+    pub dev_nonce: u16,
+}
+
+impl JoinRequest {
+    /// Encode and sign the join-request into `out`, returns the amount of bytes written
+    pub fn build<B: AesBackend>(
+        &self,
+        backend: &mut B,
+        app_key: &AesKey,
+        out: &mut [u8],
+    ) -> Result<usize, LoRaWanError> {
+        let out = out
+            .get_mut(..JOIN_REQUEST_SIZE)
+            .ok_or(LoRaWanError::TooLong)?;
+        out[0] = Mhdr::new(MType::JoinRequest).0;
+        copy_reversed(&mut out[1..9], &self.app_eui);
+        copy_reversed(&mut out[9..17], &self.dev_eui);
+        out[17..19].copy_from_slice(&self.dev_nonce.to_le_bytes());
+        let mic = compute_join_mic(backend, app_key, &out[..19]);
+        out[19..23].copy_from_slice(&mic.to_le_bytes());
+        Ok(JOIN_REQUEST_SIZE)
+    }
+}
+
+/// Join-accept, decrypted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JoinAccept {
+    /// AppNonce / JoinNonce
+    pub join_nonce: [u8; 3],
+    /// Network identifier
+    pub net_id: [u8; 3],
+    /// Device address
+    pub dev_addr: u32,
+    /// RX1DROffset (bits 6..4) and RX2DataRate (bits 3..0)
+    pub dl_settings: u8,
+    /// Delay before RX1 in seconds (0 means 1)
+    pub rx_delay: u8,
+    /// Optional list of channel frequencies or channel masks
+    pub cf_list: Option<[u8; CF_LIST_SIZE]>,
+}
+
+impl JoinAccept {
+    /// RX1 data rate offset
+    pub fn rx1_dr_offset(&self) -> u8 {
+        (self.dl_settings >> 4) & 0x07
+    }
+
+    /// RX2 data rate
+    pub fn rx2_datarate(&self) -> u8 {
+        self.dl_settings & 0x0F
+    }
+
+    /// Decrypt and verify a join-accept PHYPayload
+    pub fn decrypt<B: AesBackend>(
+        backend: &mut B,
+        app_key: &AesKey,
+        phy: &[u8],
+    ) -> Result<Self, LoRaWanError> {
+        if phy.len() != JOIN_ACCEPT_SIZE && phy.len() != JOIN_ACCEPT_SIZE + CF_LIST_SIZE {
+            return Err(LoRaWanError::TooShort);
+        }
+        let mhdr = Mhdr(phy[0]);
+        if mhdr.mtype() != MType::JoinAccept {
+            return Err(LoRaWanError::InvalidMType);
+        }
+
+        let mut buf = [0u8; JOIN_ACCEPT_SIZE + CF_LIST_SIZE];
+        let buf = &mut buf[..phy.len()];
+        buf.copy_from_slice(phy);
+        join_accept_decrypt(backend, app_key, &mut buf[MHDR_SIZE..]);
+
+        let mic_pos = buf.len() - MIC_SIZE;
+        let mic = u32::from_le_bytes(buf[mic_pos..].try_into().unwrap());
+        if compute_join_mic(backend, app_key, &buf[..mic_pos]) != mic {
+            return Err(LoRaWanError::InvalidMic);
+        }
+
+        Ok(Self {
+            join_nonce: buf[1..4].try_into().unwrap(),
+            net_id: buf[4..7].try_into().unwrap(),
+            dev_addr: u32::from_le_bytes(buf[7..11].try_into().unwrap()),
+            dl_settings: buf[11],
+            rx_delay: buf[12],
+            cf_list: if mic_pos > 13 {
+                Some(buf[13..29].try_into().unwrap())
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Encode, sign and encrypt the join-accept into `out`, network side
+    pub fn build<B: AesBackend>(
+        &self,
+        backend: &mut B,
+        app_key: &AesKey,
+        out: &mut [u8],
+    ) -> Result<usize, LoRaWanError> {
+        let len = JOIN_ACCEPT_SIZE
+            + if self.cf_list.is_some() {
+                CF_LIST_SIZE
+            } else {
+                0
+            };
+        let out = out.get_mut(..len).ok_or(LoRaWanError::TooLong)?;
+        out[0] = Mhdr::new(MType::JoinAccept).0;
+        out[1..4].copy_from_slice(&self.join_nonce);
+        out[4..7].copy_from_slice(&self.net_id);
+        out[7..11].copy_from_slice(&self.dev_addr.to_le_bytes());
+        out[11] = self.dl_settings;
+        out[12] = self.rx_delay;
+        if let Some(cf_list) = &self.cf_list {
+            out[13..29].copy_from_slice(cf_list);
+        }
+        let mic_pos = len - MIC_SIZE;
+        let mic = compute_join_mic(backend, app_key, &out[..mic_pos]);
+        out[mic_pos..].copy_from_slice(&mic.to_le_bytes());
+        join_accept_encrypt(backend, app_key, &mut out[MHDR_SIZE..]);
+        Ok(len)
+    }
+}
+
+/// A parsed PHYPayload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhyPayload<'a> {
+    /// join-request with its MIC
+    JoinRequest(JoinRequest, u32),
+    /// encrypted join-accept, decrypt it with [`JoinAccept::decrypt`]
+    JoinAccept(&'a [u8]),
+    /// data frame
+    Data(DataFrame<'a>),
+    /// proprietary or unsupported frame, MHDR and raw bytes
+    Proprietary(Mhdr, &'a [u8]),
+}
+
+impl<'a> PhyPayload<'a> {
+    /// Parse a raw PHYPayload
+    pub fn parse(phy: &'a [u8]) -> Result<Self, LoRaWanError> {
+        if phy.len() < MHDR_SIZE + MIC_SIZE {
+            return Err(LoRaWanError::TooShort);
+        }
+        if phy.len() > PHY_PAYLOAD_MAX_SIZE {
+            return Err(LoRaWanError::TooLong);
+        }
+
+        let mhdr = Mhdr(phy[0]);
+        let mtype = mhdr.mtype();
+        if mtype == MType::Proprietary {
+            return Ok(PhyPayload::Proprietary(mhdr, phy));
+        }
+        if mhdr.major() != 0 {
+            return Err(LoRaWanError::UnsupportedMajor);
+        }
+
+        match mtype {
+            MType::JoinRequest => {
+                if phy.len() != JOIN_REQUEST_SIZE {
+                    return Err(LoRaWanError::TooShort);
+                }
+                let mut app_eui = [0u8; 8];
+                let mut dev_eui = [0u8; 8];
+                copy_reversed(&mut app_eui, &phy[1..9]);
+                copy_reversed(&mut dev_eui, &phy[9..17]);
+                let req = JoinRequest {
+                    app_eui,
+                    dev_eui,
+                    dev_nonce: u16::from_le_bytes([phy[17], phy[18]]),
+                };
+                let mic = u32::from_le_bytes(phy[19..23].try_into().unwrap());
+                Ok(PhyPayload::JoinRequest(req, mic))
+            }
+            MType::JoinAccept => {
+                if phy.len() != JOIN_ACCEPT_SIZE && phy.len() != JOIN_ACCEPT_SIZE + CF_LIST_SIZE {
+                    return Err(LoRaWanError::TooShort);
+                }
+                Ok(PhyPayload::JoinAccept(phy))
+            }
+            MType::RejoinRequest => Ok(PhyPayload::Proprietary(mhdr, phy)),
+            _ => Self::parse_data(mhdr, phy).map(PhyPayload::Data),
+        }
+    }
+
+    fn parse_data(mhdr: Mhdr, phy: &'a [u8]) -> Result<DataFrame<'a>, LoRaWanError> {
+        let mic_pos = phy.len() - MIC_SIZE;
+        if mic_pos < MHDR_SIZE + FHDR_MIN_SIZE {
+            return Err(LoRaWanError::TooShort);
+        }
+
+        let dev_addr = u32::from_le_bytes(phy[1..5].try_into().unwrap());
+        let fctrl = FCtrl(phy[5]);
+        let fcnt = u16::from_le_bytes([phy[6], phy[7]]);
+        let fopts_end = MHDR_SIZE + FHDR_MIN_SIZE + fctrl.fopts_len();
+        if fopts_end > mic_pos {
+            return Err(LoRaWanError::TooShort);
+        }
+        let fopts = &phy[MHDR_SIZE + FHDR_MIN_SIZE..fopts_end];
+
+        let (fport, frm_payload) = if fopts_end < mic_pos {
+            (Some(phy[fopts_end]), &phy[fopts_end + 1..mic_pos])
+        } else {
+            (None, &phy[mic_pos..mic_pos])
+        };
+        if fport == Some(0) && !fopts.is_empty() {
+            return Err(LoRaWanError::InvalidFOpts);
+        }
+
+        Ok(DataFrame {
+            mhdr,
+            fhdr: Fhdr {
+                dev_addr,
+                fctrl,
+                fcnt,
+                fopts,
+            },
+            fport,
+            frm_payload,
+            mic: u32::from_le_bytes(phy[mic_pos..].try_into().unwrap()),
+            raw: &phy[..mic_pos],
+        })
+    }
+}
+
+/// Builder for data frames
+pub struct DataFrameBuilder<'a> {
+    mtype: MType,
+    dev_addr: u32,
+    fctrl: FCtrl,
+    fcnt: u32,
+    fopts: &'a [u8],
+    fport: Option<u8>,
+    payload: &'a [u8],
+}
+
+impl<'a> DataFrameBuilder<'a> {
+    /// Start a data frame of the given type for `dev_addr` with the full 32-bit frame counter
+    pub fn new(mtype: MType, dev_addr: u32, fcnt: u32) -> Self {
+        Self {
+            mtype,
+            dev_addr,
+            fctrl: FCtrl::default(),
+            fcnt,
+            fopts: &[],
+            fport: None,
+            payload: &[],
+        }
+    }
+
+    /// Set the FCtrl flags, the FOpts length is filled in by [`Self::build`]
+    pub fn fctrl(mut self, fctrl: FCtrl) -> Self {
+        self.fctrl = fctrl;
+        self
+    }
+
+    /// Piggyback MAC commands in FOpts (sent in clear text)
+    pub fn fopts(mut self, fopts: &'a [u8]) -> Self {
+        self.fopts = fopts;
+        self
+    }
+
+    /// Set the port and the plain text FRMPayload
+    pub fn payload(mut self, fport: u8, payload: &'a [u8]) -> Self {
+        self.fport = Some(fport);
+        self.payload = payload;
+        self
+    }
+
+    /// Encode, encrypt and sign the frame into `out`, returns the amount of bytes written.
+    ///
+    /// The FRMPayload is encrypted with the NwkSKey when FPort is 0, the AppSKey otherwise.
+    pub fn build<B: AesBackend>(
+        &self,
+        backend: &mut B,
+        nwk_skey: &AesKey,
+        app_skey: &AesKey,
+        out: &mut [u8],
+    ) -> Result<usize, LoRaWanError> {
+        if !self.mtype.is_data() {
+            return Err(LoRaWanError::InvalidMType);
+        }
+        if self.fopts.len() > FOPTS_MAX_SIZE {
+            return Err(LoRaWanError::TooLong);
+        }
+        if self.fport == Some(0) && !self.fopts.is_empty() {
+            return Err(LoRaWanError::InvalidFOpts);
+        }
+
+        let fport_len = if self.fport.is_some() { 1 } else { 0 };
+        let mic_pos = MHDR_SIZE + FHDR_MIN_SIZE + self.fopts.len() + fport_len + self.payload.len();
+        let len = mic_pos + MIC_SIZE;
+        if len > PHY_PAYLOAD_MAX_SIZE {
+            return Err(LoRaWanError::TooLong);
+        }
+        let out = out.get_mut(..len).ok_or(LoRaWanError::TooLong)?;
+
+        out[0] = Mhdr::new(self.mtype).0;
+        out[1..5].copy_from_slice(&self.dev_addr.to_le_bytes());
+        out[5] = (self.fctrl.0 & 0xF0) | self.fopts.len() as u8;
+        out[6..8].copy_from_slice(&(self.fcnt as u16).to_le_bytes());
+        let mut pos = MHDR_SIZE + FHDR_MIN_SIZE;
+        out[pos..pos + self.fopts.len()].copy_from_slice(self.fopts);
+        pos += self.fopts.len();
+
+        let dir = self.mtype.direction();
+        if let Some(fport) = self.fport {
+            out[pos] = fport;
+            pos += 1;
+            let key = if fport == 0 { nwk_skey } else { app_skey };
+            let payload = &mut out[pos..mic_pos];
+            payload.copy_from_slice(self.payload);
+            payload_encrypt(backend, key, payload, self.dev_addr, dir, self.fcnt);
+        }
+
+        let mic = compute_mic(
+            backend,
+            nwk_skey,
+            &out[..mic_pos],
+            self.dev_addr,
+            dir,
+            self.fcnt,
+        );
+        out[mic_pos..].copy_from_slice(&mic.to_le_bytes());
+        Ok(len)
+    }
+}
+
+/// Copy `src` into `dst` in reverse byte order (EUIs are sent LSB first)
+fn copy_reversed(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src.iter().rev()) {
+        *d = *s;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lora::lorawan::crypto::SoftAes;
+
+    const NWK_SKEY: AesKey = [
+        0x44, 0x02, 0x42, 0x41, 0xED, 0x4C, 0xE9, 0xA6, 0x8C, 0x6A, 0x8B, 0xC0, 0x55, 0x23, 0x3F,
+        0xD3,
+    ];
+    const APP_SKEY: AesKey = [
+        0xEC, 0x92, 0x58, 0x02, 0xAE, 0x43, 0x0C, 0xA7, 0x7F, 0xD3, 0xDD, 0x73, 0xCB, 0x2C, 0xC5,
+        0x88,
+    ];
+    const APP_KEY: AesKey = [
+        0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F,
+        0x3C,
+    ];
+
+    /// Unconfirmed uplink carrying "test" on port 1, FCnt 2
+    const UPLINK: [u8; 17] = [
+        0x40, 0xF1, 0x7D, 0xBE, 0x49, 0x00, 0x02, 0x00, 0x01, 0x95, 0x43, 0x78, 0x76, 0x2B, 0x11,
+        0xFF, 0x0D,
+    ];
+
+    const JOIN_REQUEST: [u8; 23] = [
+        0x00, 0x00, 0x00, 0x00, 0xD0, 0x7E, 0xD5, 0xB3, 0x70, 0x30, 0x05, 0x1C, 0x00, 0x0B, 0xA3,
+        0x04, 0x00, 0x34, 0x12, 0xE7, 0xBE, 0xEF, 0x69,
+    ];
+
+    const JOIN_ACCEPT: [u8; 17] = [
+        0x20, 0xC2, 0xB1, 0x1E, 0x85, 0xEB, 0xBA, 0x6C, 0x6E, 0xAE, 0xD8, 0x08, 0x55, 0xC8, 0x7A,
+        0x8C, 0x63,
+    ];
+
+    #[test]
+    fn parse_uplink() {
+        let PhyPayload::Data(frame) = PhyPayload::parse(&UPLINK).unwrap() else {
+            panic!("not a data frame");
+        };
+        assert_eq!(frame.mhdr.mtype(), MType::UnconfirmedDataUp);
+        assert_eq!(frame.fhdr.dev_addr, 0x49BE7DF1);
+        assert_eq!(frame.fhdr.fcnt, 2);
+        assert!(frame.fhdr.fopts.is_empty());
+        assert_eq!(frame.fport, Some(1));
+        assert_eq!(frame.mic, 0x0DFF112B);
+        assert!(!frame.is_confirmed());
+
+        let mut aes = SoftAes::new();
+        assert!(frame.verify_mic(&mut aes, &NWK_SKEY, 2));
+        assert!(!frame.verify_mic(&mut aes, &NWK_SKEY, 0x1_0002));
+        let mut out = [0u8; 16];
+        let plain = frame
+            .decrypt_payload(&mut aes, &APP_SKEY, 2, &mut out)
+            .unwrap();
+        assert_eq!(plain, b"test");
+    }
+
+    #[test]
+    fn build_uplink() {
+        let mut aes = SoftAes::new();
+        let mut out = [0u8; 32];
+        let len = DataFrameBuilder::new(MType::UnconfirmedDataUp, 0x49BE7DF1, 2)
+            .payload(1, b"test")
+            .build(&mut aes, &NWK_SKEY, &APP_SKEY, &mut out)
+            .unwrap();
+        assert_eq!(&out[..len], &UPLINK);
+    }
+
+    #[test]
+    fn fopts_round_trip() {
+        let mut aes = SoftAes::new();
+        let mut out = [0u8; 32];
+        let fopts = [0x02, 0x03];
+        let len = DataFrameBuilder::new(MType::ConfirmedDataDown, 0x01020304, 0x1_0005)
+            .fctrl(FCtrl::default().set_ack(true).set_adr(true))
+            .fopts(&fopts)
+            .build(&mut aes, &NWK_SKEY, &APP_SKEY, &mut out)
+            .unwrap();
+        assert_eq!(len, MHDR_SIZE + FHDR_MIN_SIZE + fopts.len() + MIC_SIZE);
+
+        let PhyPayload::Data(frame) = PhyPayload::parse(&out[..len]).unwrap() else {
+            panic!("not a data frame");
+        };
+        assert!(frame.is_confirmed());
+        assert_eq!(frame.direction(), Direction::Downlink);
+        assert!(frame.fhdr.fctrl.ack());
+        assert!(frame.fhdr.fctrl.adr());
+        assert_eq!(frame.fhdr.fctrl.fopts_len(), 2);
+        assert_eq!(frame.fhdr.fopts, &fopts);
+        assert_eq!(frame.fhdr.fcnt, 5);
+        assert_eq!(frame.fport, None);
+        assert!(frame.verify_mic(&mut aes, &NWK_SKEY, 0x1_0005));
+        assert!(!frame.verify_mic(&mut aes, &NWK_SKEY, 5));
+    }
+
+    #[test]
+    fn port_zero_uses_nwk_skey() {
+        let mut aes = SoftAes::new();
+        let mut out = [0u8; 32];
+        let len = DataFrameBuilder::new(MType::UnconfirmedDataUp, 1, 0)
+            .payload(0, &[0x02])
+            .build(&mut aes, &NWK_SKEY, &APP_SKEY, &mut out)
+            .unwrap();
+        let PhyPayload::Data(frame) = PhyPayload::parse(&out[..len]).unwrap() else {
+            panic!("not a data frame");
+        };
+        let mut plain = [0u8; 1];
+        assert_eq!(
+            frame.decrypt_payload(&mut aes, &NWK_SKEY, 0, &mut plain),
+            Ok(&[0x02][..])
+        );
+    }
+
+    #[test]
+    fn builder_rejects_invalid_frames() {
+        let mut aes = SoftAes::new();
+        let mut out = [0u8; 255];
+        assert_eq!(
+            DataFrameBuilder::new(MType::JoinRequest, 1, 0)
+                .build(&mut aes, &NWK_SKEY, &APP_SKEY, &mut out),
+            Err(LoRaWanError::InvalidMType)
+        );
+        assert_eq!(
+            DataFrameBuilder::new(MType::UnconfirmedDataUp, 1, 0)
+                .fopts(&[0; 16])
+                .build(&mut aes, &NWK_SKEY, &APP_SKEY, &mut out),
+            Err(LoRaWanError::TooLong)
+        );
+        assert_eq!(
+            DataFrameBuilder::new(MType::UnconfirmedDataUp, 1, 0)
+                .fopts(&[0x02])
+                .payload(0, &[])
+                .build(&mut aes, &NWK_SKEY, &APP_SKEY, &mut out),
+            Err(LoRaWanError::InvalidFOpts)
+        );
+        assert_eq!(
+            DataFrameBuilder::new(MType::UnconfirmedDataUp, 1, 0)
+                .payload(1, &[0; 243])
+                .build(&mut aes, &NWK_SKEY, &APP_SKEY, &mut out),
+            Err(LoRaWanError::TooLong)
+        );
+        assert_eq!(
+            DataFrameBuilder::new(MType::UnconfirmedDataUp, 1, 0)
+                .payload(1, b"test")
+                .build(&mut aes, &NWK_SKEY, &APP_SKEY, &mut out[..16]),
+            Err(LoRaWanError::TooLong)
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed_frames() {
+        assert_eq!(PhyPayload::parse(&UPLINK[..4]), Err(LoRaWanError::TooShort));
+        assert_eq!(PhyPayload::parse(&[0u8; 256]), Err(LoRaWanError::TooLong));
+        // FOpts length runs into the MIC
+        let mut frame = UPLINK;
+        frame[5] = 0x0F;
+        assert_eq!(PhyPayload::parse(&frame), Err(LoRaWanError::TooShort));
+        // FOpts with FPort 0
+        let frame = [0x40, 1, 0, 0, 0, 0x01, 0, 0, 0x02, 0x00, 0, 0, 0, 0];
+        assert_eq!(PhyPayload::parse(&frame), Err(LoRaWanError::InvalidFOpts));
+        // LoRaWAN major 1
+        let mut frame = UPLINK;
+        frame[0] |= 0x01;
+        assert_eq!(
+            PhyPayload::parse(&frame),
+            Err(LoRaWanError::UnsupportedMajor)
+        );
+        // proprietary frames are passed through whatever their content
+        let frame = [0xE0, 1, 2, 3, 4];
+        assert!(matches!(
+            PhyPayload::parse(&frame),
+            Ok(PhyPayload::Proprietary(Mhdr(0xE0), _))
+        ));
+    }
+
+    #[test]
+    fn join_request() {
+        let req = JoinRequest {
+            app_eui: [0x70, 0xB3, 0xD5, 0x7E, 0xD0, 0x00, 0x00, 0x00],
+            dev_eui: [0x00, 0x04, 0xA3, 0x0B, 0x00, 0x1C, 0x05, 0x30],
+            dev_nonce: 0x1234,
+        };
+        let mut aes = SoftAes::new();
+        let mut out = [0u8; JOIN_REQUEST_SIZE];
+        assert_eq!(
+            req.build(&mut aes, &APP_KEY, &mut out),
+            Ok(JOIN_REQUEST_SIZE)
+        );
+        assert_eq!(out, JOIN_REQUEST);
+
+        let Ok(PhyPayload::JoinRequest(parsed, mic)) = PhyPayload::parse(&JOIN_REQUEST) else {
+            panic!("not a join-request");
+        };
+        assert_eq!(parsed, req);
+        assert_eq!(mic, 0x69EFBEE7);
+    }
+
+    #[test]
+    fn join_accept() {
+        let mut aes = SoftAes::new();
+        assert!(matches!(
+            PhyPayload::parse(&JOIN_ACCEPT),
+            Ok(PhyPayload::JoinAccept(_))
+        ));
+
+        let accept = JoinAccept::decrypt(&mut aes, &APP_KEY, &JOIN_ACCEPT).unwrap();
+        assert_eq!(accept.join_nonce, [0x01, 0x02, 0x03]);
+        assert_eq!(accept.net_id, [0x00, 0x00, 0x13]);
+        assert_eq!(accept.dev_addr, 0x26011F2A);
+        assert_eq!(accept.rx1_dr_offset(), 0);
+        assert_eq!(accept.rx2_datarate(), 3);
+        assert_eq!(accept.rx_delay, 1);
+        assert_eq!(accept.cf_list, None);
+
+        let mut out = [0u8; JOIN_ACCEPT_SIZE];
+        assert_eq!(
+            accept.build(&mut aes, &APP_KEY, &mut out),
+            Ok(JOIN_ACCEPT_SIZE)
+        );
+        assert_eq!(out, JOIN_ACCEPT);
+
+        let mut tampered = JOIN_ACCEPT;
+        tampered[5] ^= 0x01;
+        assert_eq!(
+            JoinAccept::decrypt(&mut aes, &APP_KEY, &tampered),
+            Err(LoRaWanError::InvalidMic)
+        );
+    }
+
+    #[test]
+    fn join_accept_cf_list_round_trip() {
+        let mut aes = SoftAes::new();
+        let accept = JoinAccept {
+            join_nonce: [0xAA, 0xBB, 0xCC],
+            net_id: [0x01, 0x00, 0x00],
+            dev_addr: 0x12345678,
+            dl_settings: 0x25,
+            rx_delay: 5,
+            cf_list: Some([
+                0x18, 0x4F, 0x84, 0xE8, 0x56, 0x84, 0xB8, 0x5E, 0x84, 0x88, 0x66, 0x84, 0x58, 0x6E,
+                0x84, 0x00,
+            ]),
+        };
+        let mut out = [0u8; JOIN_ACCEPT_SIZE + CF_LIST_SIZE];
+        assert_eq!(accept.build(&mut aes, &APP_KEY, &mut out), Ok(out.len()));
+        let decrypted = JoinAccept::decrypt(&mut aes, &APP_KEY, &out).unwrap();
+        assert_eq!(decrypted, accept);
+        assert_eq!(decrypted.rx1_dr_offset(), 2);
+        assert_eq!(decrypted.rx2_datarate(), 5);
+    }
+}
@@ -0,0 +1,226 @@
+/// LinkCheckReq / LinkCheckAns
+pub const LINK_CHECK: u8 = 0x02;
+/// LinkADRReq / LinkADRAns
+pub const LINK_ADR: u8 = 0x03;
+/// DutyCycleReq / DutyCycleAns
+pub const DUTY_CYCLE: u8 = 0x04;
+/// RXParamSetupReq / RXParamSetupAns
+pub const RX_PARAM_SETUP: u8 = 0x05;
+/// DevStatusReq / DevStatusAns
+pub const DEV_STATUS: u8 = 0x06;
+/// NewChannelReq / NewChannelAns
+pub const NEW_CHANNEL: u8 = 0x07;
+/// RXTimingSetupReq / RXTimingSetupAns
+pub const RX_TIMING_SETUP: u8 = 0x08;
+/// TxParamSetupReq / TxParamSetupAns
+pub const TX_PARAM_SETUP: u8 = 0x09;
+/// DlChannelReq / DlChannelAns
+pub const DL_CHANNEL: u8 = 0x0A;
+/// DeviceTimeReq / DeviceTimeAns
+pub const DEVICE_TIME: u8 = 0x0D;
+
+/// MAC command sent by the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownlinkCommand {
+    /// answer to a LinkCheckReq
+    LinkCheckAns { margin: u8, gw_cnt: u8 },
+    /// data rate, TX power, repetitions and channel mask change
+    LinkAdrReq {
+        dr: u8,
+        tx_power: u8,
+        ch_mask: u16,
+        ch_mask_cntl: u8,
+        nb_trans: u8,
+    },
+    /// aggregated duty cycle limit, `1 / 2^max_duty_cycle`
+    DutyCycleReq { max_duty_cycle: u8 },
+    /// RX1 offset and RX2 parameters change
+    RxParamSetupReq {
+        rx1_dr_offset: u8,
+        rx2_dr: u8,
+        frequency: u32,
+    },
+    /// battery and margin request
+    DevStatusReq,
+    /// channel creation, modification or removal
+    NewChannelReq {
+        index: u8,
+        frequency: u32,
+        min_dr: u8,
+        max_dr: u8,
+    },
+    /// RX1 delay change (seconds, 0 means 1)
+    RxTimingSetupReq { delay: u8 },
+    /// dwell time and max EIRP change
+    TxParamSetupReq { eirp_dwell_time: u8 },
+    /// RX1 frequency change of a channel
+    DlChannelReq { index: u8, frequency: u32 },
+    /// answer to a DeviceTimeReq, GPS time at the end of the uplink
+    DeviceTimeAns { seconds: u32, fraction: u8 },
+}
+
+/// Iterator over the MAC commands of FOpts or of a port 0 FRMPayload.
+///
+/// Stops at the first unknown or truncated command, the rest can't be parsed.
+pub struct CommandIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> CommandIter<'a> {
+    /// Iterate over `data`
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+}
+
+/// 24-bit frequency in 100 Hz steps
+fn frequency(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) * 100
+}
+
+impl Iterator for CommandIter<'_> {
+    type Item = DownlinkCommand;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&cid, rest) = self.data.split_first()?;
+        let len = match cid {
+            LINK_CHECK => 2,
+            LINK_ADR => 4,
+            DUTY_CYCLE => 1,
+            RX_PARAM_SETUP => 4,
+            DEV_STATUS => 0,
+            NEW_CHANNEL => 5,
+            RX_TIMING_SETUP => 1,
+            TX_PARAM_SETUP => 1,
+            DL_CHANNEL => 4,
+            DEVICE_TIME => 5,
+            _ => {
+                self.data = &[];
+                return None;
+            }
+        };
+        if rest.len() < len {
+            self.data = &[];
+            return None;
+        }
+        let (p, rest) = rest.split_at(len);
+        self.data = rest;
+
+        Some(match cid {
+            LINK_CHECK => DownlinkCommand::LinkCheckAns {
+                margin: p[0],
+                gw_cnt: p[1],
+            },
+            LINK_ADR => DownlinkCommand::LinkAdrReq {
+                dr: p[0] >> 4,
+                tx_power: p[0] & 0x0F,
+                ch_mask: u16::from_le_bytes([p[1], p[2]]),
+                ch_mask_cntl: (p[3] >> 4) & 0x07,
+                nb_trans: p[3] & 0x0F,
+            },
+            DUTY_CYCLE => DownlinkCommand::DutyCycleReq {
+                max_duty_cycle: p[0] & 0x0F,
+            },
+            RX_PARAM_SETUP => DownlinkCommand::RxParamSetupReq {
+                rx1_dr_offset: (p[0] >> 4) & 0x07,
+                rx2_dr: p[0] & 0x0F,
+                frequency: frequency(&p[1..4]),
+            },
+            DEV_STATUS => DownlinkCommand::DevStatusReq,
+            NEW_CHANNEL => DownlinkCommand::NewChannelReq {
+                index: p[0],
+                frequency: frequency(&p[1..4]),
+                min_dr: p[4] & 0x0F,
+                max_dr: p[4] >> 4,
+            },
+            RX_TIMING_SETUP => DownlinkCommand::RxTimingSetupReq { delay: p[0] & 0x0F },
+            TX_PARAM_SETUP => DownlinkCommand::TxParamSetupReq {
+                eirp_dwell_time: p[0],
+            },
+            DL_CHANNEL => DownlinkCommand::DlChannelReq {
+                index: p[0],
+                frequency: frequency(&p[1..4]),
+            },
+            _ => DownlinkCommand::DeviceTimeAns {
+                seconds: u32::from_le_bytes([p[0], p[1], p[2], p[3]]),
+                fraction: p[4],
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        let data = [
+            LINK_CHECK,
+            20,
+            2,
+            LINK_ADR,
+            0x52,
+            0xFF,
+            0x00,
+            0x61,
+            DEV_STATUS,
+            DL_CHANNEL,
+            1,
+            0xC8,
+            0x85,
+            0x84,
+            DEVICE_TIME,
+            0x01,
+            0x02,
+            0x03,
+            0x04,
+            0x80,
+        ];
+        let mut commands = CommandIter::new(&data);
+        assert_eq!(
+            commands.next(),
+            Some(DownlinkCommand::LinkCheckAns {
+                margin: 20,
+                gw_cnt: 2
+            })
+        );
+        assert_eq!(
+            commands.next(),
+            Some(DownlinkCommand::LinkAdrReq {
+                dr: 5,
+                tx_power: 2,
+                ch_mask: 0x00FF,
+                ch_mask_cntl: 6,
+                nb_trans: 1
+            })
+        );
+        assert_eq!(commands.next(), Some(DownlinkCommand::DevStatusReq));
+        assert_eq!(
+            commands.next(),
+            Some(DownlinkCommand::DlChannelReq {
+                index: 1,
+                frequency: 868_500_000
+            })
+        );
+        assert_eq!(
+            commands.next(),
+            Some(DownlinkCommand::DeviceTimeAns {
+                seconds: 0x04030201,
+                fraction: 0x80
+            })
+        );
+        assert_eq!(commands.next(), None);
+    }
+
+    #[test]
+    fn stops_on_unknown_or_truncated_commands() {
+        let mut commands = CommandIter::new(&[DEV_STATUS, 0x7F, DEV_STATUS]);
+        assert_eq!(commands.next(), Some(DownlinkCommand::DevStatusReq));
+        assert_eq!(commands.next(), None);
+        assert_eq!(commands.next(), None);
+
+        let mut commands = CommandIter::new(&[LINK_ADR, 0x52, 0xFF]);
+        assert_eq!(commands.next(), None);
+    }
+}
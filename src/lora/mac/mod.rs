@@ -0,0 +1,2079 @@
+#[cfg(any(feature = "soft-crypto", test))]
+use crate::lora::lorawan::crypto::SoftAes;
+#[cfg(not(any(feature = "soft-crypto", test)))]
+use crate::{lora::lorawan::crypto::CachedAes, peripherals::sae::HwAes128};
+use crate::{
+    lora::{
+        airtime::{TTN_FAIR_USE_MS, airtime},
+        lbt::{LbtConfig, lbt_wait_clear},
+        lorawan::{
+            DataFrameBuilder, FCtrl, FOPTS_MAX_SIZE, JoinAccept, JoinRequest, MType,
+            PHY_PAYLOAD_MAX_SIZE, PhyPayload,
+            crypto::{AesKey, Direction, SessionKeys, derive_session_keys},
+        },
+        radio::{
+            RadioEvents, RadioModem, radio_get_wakeup_time, radio_init, radio_random, radio_rx,
+            radio_send, radio_set_channel, radio_set_max_payload_length, radio_set_public_network,
+            radio_set_rx_config, radio_set_tx_config, radio_sleep, radio_standby,
+            radio_time_on_air,
+        },
+        region::{CHANNEL_MASK_SIZE, ChannelPlan, DataRate, MAX_CHANNELS, Modulation, Region},
+        timer::{
+            TimerEvent, timer_get_current_time, timer_init, timer_set_value, timer_start,
+            timer_stop,
+        },
+    },
+    rng::{rng_add_entropy, rng_range, rng_u32},
+};
+
+use commands::{CommandIter, DownlinkCommand};
+
+/// MAC command encoding/decoding
+pub mod commands;
+
+/// Uplinks without downlink before ADRACKReq is set
+pub const ADR_ACK_LIMIT: u32 = 64;
+/// Uplinks after ADRACKReq before the data rate is lowered
+pub const ADR_ACK_DELAY: u32 = 32;
+/// Default delay between the end of the uplink and RX1 (ms)
+pub const RECEIVE_DELAY1: usize = 1000;
+/// Delay between the end of the join-request and RX1 (ms)
+pub const JOIN_ACCEPT_DELAY1: usize = 5000;
+/// Delay between the end of the join-request and RX2 (ms)
+pub const JOIN_ACCEPT_DELAY2: usize = 6000;
+/// Largest accepted gap between two downlink frame counters
+pub const MAX_FCNT_GAP: u32 = 16384;
+/// Delay before retransmitting an unacknowledged confirmed uplink (ms)
+pub const ACK_TIMEOUT: usize = 2000;
+/// Random part of `ACK_TIMEOUT`, +/- (ms)
+pub const ACK_TIMEOUT_RND: usize = 1000;
+/// Longest time a RX window is kept open (ms)
+pub const MAX_RX_WINDOW: usize = 3000;
+/// Largest amount of transmissions of a confirmed uplink
+pub const MAX_ACK_RETRIES: u8 = 8;
+
+/// Preamble symbols the radio needs to detect a frame
+const MIN_RX_SYMBOLS: u32 = 6;
+/// Worst case timing error of the RX windows (ms)
+const SYSTEM_MAX_RX_ERROR: f64 = 10.0;
+/// Room for pending MAC command answers
+const MAC_COMMANDS_SIZE: usize = 64;
+/// Room for answers repeated until a downlink is received
+const STICKY_COMMANDS_SIZE: usize = 16;
+/// PHYPayload minus MACPayload (MHDR + MIC), for the RX max payload length
+const PHY_OVERHEAD: u8 = 5;
+
+/// LoRaWAN device class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceClass {
+    /// RX windows only after uplinks
+    A,
+    /// RX2 open whenever the device doesn't transmit
+    C,
+}
+
+/// Errors of the MAC requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacError {
+    /// a request is already in progress
+    Busy,
+    /// the device isn't joined yet
+    NoNetworkJoined,
+    /// invalid port, parameter or EUI
+    ParameterInvalid,
+    /// data rate not supported by the region or the enabled channels
+    DatarateInvalid,
+    /// payload (plus pending MAC commands) doesn't fit at the current data rate
+    LengthError,
+    /// `mac_init` wasn't called
+    NotInitialised,
+}
+
+/// Outcome of a request, reported through the confirm/indication primitives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventInfoStatus {
+    /// success
+    Ok,
+    /// generic failure
+    Error,
+    /// radio didn't finish the transmission in time
+    TxTimeout,
+    /// nothing received in RX1
+    Rx1Timeout,
+    /// nothing received in RX2
+    Rx2Timeout,
+    /// invalid frame in RX1
+    Rx1Error,
+    /// invalid frame in RX2
+    Rx2Error,
+    /// no join-accept after every trial
+    JoinFail,
+    /// downlink frame counter already received
+    DownlinkRepeated,
+    /// downlink frame counter too far from the previous one
+    DownlinkTooManyFramesLoss,
+    /// downlink for another device
+    AddressFail,
+    /// downlink MIC check failed
+    MicFail,
+}
+
+/// Data service request types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum McpsType {
+    /// no acknowledgement
+    Unconfirmed,
+    /// acknowledged by the network
+    Confirmed,
+    /// proprietary frame
+    Proprietary,
+}
+
+/// Management service request / indication types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MlmeType {
+    /// over-the-air activation
+    Join,
+    /// link margin and gateway count
+    LinkCheck,
+    /// network time
+    DeviceTime,
+    /// the network expects an uplink (answers or ACK pending)
+    ScheduleUplink,
+}
+
+/// Receive slot of a downlink
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RxSlot {
+    /// first window
+    Rx1,
+    /// second window
+    Rx2,
+    /// class C continuous RX2 outside of the uplink windows
+    ClassC,
+}
+
+/// Result of a data uplink
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct McpsConfirm {
+    /// type of the uplink
+    pub request: McpsType,
+    /// outcome
+    pub status: EventInfoStatus,
+    /// data rate of the last transmission
+    pub datarate: u8,
+    /// TX power index of the last transmission
+    pub tx_power: u8,
+    /// the network acknowledged the (confirmed) uplink
+    pub ack_received: bool,
+    /// amount of transmissions
+    pub nb_trials: u8,
+    /// time on air of the last transmission (ms)
+    pub tx_time_on_air: usize,
+    /// frame counter of the uplink
+    pub uplink_counter: u32,
+    /// channel index of the last transmission
+    pub channel: u8,
+}
+
+/// Received downlink
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct McpsIndication<'a> {
+    /// type of the downlink
+    pub indication: McpsType,
+    /// outcome
+    pub status: EventInfoStatus,
+    /// application port, 0 for MAC only frames
+    pub port: u8,
+    /// data rate the frame was received at
+    pub rx_datarate: u8,
+    /// the network has more data pending
+    pub frame_pending: bool,
+    /// decrypted application payload
+    pub buffer: &'a [u8],
+    /// `buffer` holds application data
+    pub rx_data: bool,
+    /// packet RSSI (dBm)
+    pub rssi: i16,
+    /// packet SNR (dB)
+    pub snr: i8,
+    /// window the frame was received in
+    pub rx_slot: RxSlot,
+    /// the frame acknowledges the last confirmed uplink
+    pub ack_received: bool,
+    /// downlink frame counter
+    pub downlink_counter: u32,
+}
+
+/// Result of a management request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MlmeConfirm {
+    /// type of the request
+    pub request: MlmeType,
+    /// outcome
+    pub status: EventInfoStatus,
+    /// time on air of the last transmission (ms)
+    pub tx_time_on_air: usize,
+    /// LinkCheckAns demodulation margin (dB)
+    pub demod_margin: u8,
+    /// LinkCheckAns gateway count
+    pub nb_gateways: u8,
+    /// amount of join-requests sent
+    pub nb_trials: u8,
+    /// DeviceTimeAns GPS time (ms since the GPS epoch)
+    pub gps_time: u64,
+}
+
+/// Unsolicited management event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MlmeIndication {
+    /// type of the event
+    pub indication: MlmeType,
+    /// outcome
+    pub status: EventInfoStatus,
+}
+
+/// MAC layer callback functions, same split as `LoRaMacPrimitives_t`
+pub struct MacPrimitives {
+    pub mcps_confirm: Option<fn(&McpsConfirm)>,
+    pub mcps_indication: Option<fn(&McpsIndication)>,
+    pub mlme_confirm: Option<fn(&MlmeConfirm)>,
+    pub mlme_indication: Option<fn(&MlmeIndication)>,
+    /// battery level for DevStatusAns: 0 external power, 1..254 level, 255 unknown
+    pub get_battery_level: Option<fn() -> u8>,
+}
+
+/// OTAA join parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JoinParams {
+    /// DevEUI, most significant byte first
+    pub dev_eui: [u8; 8],
+    /// AppEUI / JoinEUI, most significant byte first
+    pub app_eui: [u8; 8],
+    /// AppKey
+    pub app_key: AesKey,
+    /// amount of join-requests to send before giving up
+    pub nb_trials: u8,
+}
+
+/// AES backend of the MAC, the security engine unless `soft-crypto` is enabled
+#[cfg(not(any(feature = "soft-crypto", test)))]
+type MacAes = CachedAes<HwAes128>;
+#[cfg(any(feature = "soft-crypto", test))]
+type MacAes = SoftAes;
+
+/// FOpts, FPort and FRMPayload of an uplink
+type UplinkParts<'a> = (&'a [u8], Option<u8>, &'a [u8]);
+
+/// What the MAC is waiting for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MacState {
+    /// no request in progress
+    Idle,
+    /// every channel is blocked by the duty cycle
+    TxDelayed,
+    /// radio transmitting
+    Tx,
+    /// waiting for RX1
+    WaitRx1,
+    /// RX1 open
+    Rx1,
+    /// waiting for RX2
+    WaitRx2,
+    /// RX2 open
+    Rx2,
+    /// waiting before retransmitting a confirmed uplink
+    AckTimeout,
+}
+
+/// Request in progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Request {
+    Join,
+    Data { confirmed: bool },
+}
+
+/// MAC layer state
+struct Mac {
+    primitives: Option<&'static MacPrimitives>,
+    aes: MacAes,
+    region: Region,
+    class: DeviceClass,
+    adr_on: bool,
+
+    // activation
+    joined: bool,
+    dev_eui: [u8; 8],
+    app_eui: [u8; 8],
+    app_key: AesKey,
+    dev_nonce: u16,
+    dev_addr: u32,
+    keys: SessionKeys,
+    fcnt_up: u32,
+    fcnt_down: Option<u32>,
+
+    // radio parameters
+    plan: ChannelPlan,
+    default_mask: [u16; CHANNEL_MASK_SIZE],
+    datarate: u8,
+    tx_power: u8,
+    max_eirp: f32,
+    antenna_gain: f32,
+    nb_trans: u8,
+    rx1_dr_offset: u8,
+    rx2_frequency: u32,
+    rx2_dr: u8,
+    receive_delay1: usize,
+    /// listen before talk, from the region or `mac_set_lbt`
+    lbt: Option<LbtConfig>,
+
+    // MAC commands
+    adr_ack_counter: u32,
+    mac_commands: heapless::Vec<u8, MAC_COMMANDS_SIZE>,
+    sticky_commands: heapless::Vec<u8, STICKY_COMMANDS_SIZE>,
+    srv_ack_requested: bool,
+    link_check_pending: bool,
+    device_time_pending: bool,
+    last_snr: i8,
+
+    // request in progress
+    state: MacState,
+    request: Request,
+    tx_buf: [u8; PHY_PAYLOAD_MAX_SIZE],
+    tx_len: usize,
+    tx_dr: u8,
+    channel: u8,
+    trial: u8,
+    max_trials: u8,
+    ack_received: bool,
+    last_status: EventInfoStatus,
+    tx_time_on_air: usize,
+    tx_done_time: u64,
+    rx2_pending: bool,
+}
+
+impl Mac {
+    const fn new() -> Self {
+        Self {
+            primitives: None,
+            aes: MacAes::new(),
+            region: Region::Eu868,
+            class: DeviceClass::A,
+            adr_on: true,
+
+            joined: false,
+            dev_eui: [0; 8],
+            app_eui: [0; 8],
+            app_key: [0; 16],
+            dev_nonce: 0,
+            dev_addr: 0,
+            keys: SessionKeys {
+                nwk_skey: [0; 16],
+                app_skey: [0; 16],
+            },
+            fcnt_up: 0,
+            fcnt_down: None,
+
+            plan: ChannelPlan::empty(),
+            default_mask: [0; CHANNEL_MASK_SIZE],
+            datarate: 0,
+            tx_power: 0,
+            max_eirp: 0.0,
+            antenna_gain: 0.0,
+            nb_trans: 1,
+            rx1_dr_offset: 0,
+            rx2_frequency: 0,
+            rx2_dr: 0,
+            receive_delay1: RECEIVE_DELAY1,
+            lbt: None,
+
+            adr_ack_counter: 0,
+            mac_commands: heapless::Vec::new(),
+            sticky_commands: heapless::Vec::new(),
+            srv_ack_requested: false,
+            link_check_pending: false,
+            device_time_pending: false,
+            last_snr: 0,
+
+            state: MacState::Idle,
+            request: Request::Join,
+            tx_buf: [0; PHY_PAYLOAD_MAX_SIZE],
+            tx_len: 0,
+            tx_dr: 0,
+            channel: 0,
+            trial: 0,
+            max_trials: 1,
+            ack_received: false,
+            last_status: EventInfoStatus::Ok,
+            tx_time_on_air: 0,
+            tx_done_time: 0,
+            rx2_pending: false,
+        }
+    }
+
+    /// Reset the radio parameters to the region defaults
+    fn reset_region_defaults(&mut self) {
+        let region = self.region;
+        self.plan = region.default_channels();
+        self.default_mask = self.plan.mask;
+        self.datarate = region.default_dr();
+        self.tx_power = 0;
+        self.max_eirp = region.max_eirp();
+        self.antenna_gain = region.antenna_gain();
+        self.nb_trans = 1;
+        self.rx1_dr_offset = 0;
+        (self.rx2_frequency, self.rx2_dr) = region.rx2_default();
+        self.receive_delay1 = RECEIVE_DELAY1;
+        airtime().set_bands(region.bands());
+        airtime().reset_duty_cycle();
+    }
+
+    fn data_rate(&self, dr: u8) -> &'static DataRate {
+        self.region
+            .data_rate(dr)
+            .unwrap_or(&self.region.data_rates()[0])
+    }
+
+    /// Pending answers and requests, sticky ones first
+    fn fopts(&self) -> heapless::Vec<u8, { STICKY_COMMANDS_SIZE + MAC_COMMANDS_SIZE }> {
+        let mut fopts = heapless::Vec::new();
+        let _ = fopts.extend_from_slice(&self.sticky_commands);
+        let _ = fopts.extend_from_slice(&self.mac_commands);
+        fopts
+    }
+
+    /// Largest FRMPayload at the current data rate with the pending MAC commands
+    fn max_payload(&self) -> Result<usize, MacError> {
+        let fopts_len = self.sticky_commands.len() + self.mac_commands.len();
+        if fopts_len > FOPTS_MAX_SIZE {
+            return Err(MacError::LengthError);
+        }
+        Ok(self.region.max_payload(self.datarate, fopts_len))
+    }
+
+    /// FOpts, FPort and FRMPayload of an uplink of `data` on `port`, next to `fopts`, the
+    /// pending MAC commands. An empty `data` flushes them, as a port 0 payload when they don't
+    /// fit FOpts. Either way the frame has to fit the current data rate.
+    fn uplink_parts<'a>(
+        &self,
+        fopts: &'a [u8],
+        port: u8,
+        data: &'a [u8],
+    ) -> Result<UplinkParts<'a>, MacError> {
+        let (fopts, fport, payload) = if !data.is_empty() {
+            (fopts, Some(port), data)
+        } else if fopts.len() > FOPTS_MAX_SIZE {
+            // too many answers for FOpts, send them as a port 0 payload
+            (&[][..], Some(0), fopts)
+        } else {
+            (fopts, None, &[][..])
+        };
+        if fopts.len() > FOPTS_MAX_SIZE
+            || payload.len() > self.region.max_payload(self.datarate, fopts.len())
+        {
+            return Err(MacError::LengthError);
+        }
+        Ok((fopts, fport, payload))
+    }
+
+    // ── uplinks ──────────────────────────────────────────────────────────
+
+    /// Build the next join-request into the TX buffer
+    fn prepare_join(&mut self) {
+        self.dev_nonce = rng_u32() as u16;
+        self.tx_dr = self.region.join_dr(self.trial as u16);
+        let request = JoinRequest {
+            app_eui: self.app_eui,
+            dev_eui: self.dev_eui,
+            dev_nonce: self.dev_nonce,
+        };
+        self.tx_len = request
+            .build(&mut self.aes, &self.app_key, &mut self.tx_buf)
+            .unwrap_or(0);
+    }
+
+    /// ADR backoff, run once per new uplink
+    fn adr_next(&mut self) -> bool {
+        if !self.adr_on {
+            return false;
+        }
+
+        self.adr_ack_counter = self.adr_ack_counter.saturating_add(1);
+        let adr_ack_req = self.adr_ack_counter >= ADR_ACK_LIMIT;
+        if self.adr_ack_counter >= ADR_ACK_LIMIT + ADR_ACK_DELAY
+            && (self.adr_ack_counter - ADR_ACK_LIMIT).is_multiple_of(ADR_ACK_DELAY)
+        {
+            // no answer from the network, regain the link step by step
+            if self.tx_power != 0 {
+                self.tx_power = 0;
+            } else if self.datarate > self.region.min_tx_dr() {
+                self.datarate -= 1;
+            } else {
+                for (mask, default) in self.plan.mask.iter_mut().zip(self.default_mask) {
+                    *mask |= default;
+                }
+            }
+        }
+        adr_ack_req
+    }
+
+    /// Pick a free channel for `tx_dr` and transmit, or wait for the duty cycle
+    fn schedule_tx(&mut self) {
+        let now = timer_get_current_time();
+        let join = self.request == Request::Join;
+        let time_on_air = self.data_rate(self.tx_dr).time_on_air(self.tx_len as u8);
+
+        let mut candidates = [0u8; MAX_CHANNELS];
+        let mut count = 0;
+        let mut next_free = u64::MAX;
+        for i in 0..MAX_CHANNELS {
+            let Some(channel) = self.plan.channels[i] else {
+                continue;
+            };
+            if !self.plan.is_enabled(i)
+                || !channel.supports_dr(self.tx_dr)
+                || (join && !self.region.is_join_channel(i))
+            {
+                continue;
+            }
+            let Some(wait) = airtime().time_until_tx(now, Some(channel.band), time_on_air) else {
+                continue;
+            };
+            if wait > 0 {
+                next_free = next_free.min(now + wait);
+                continue;
+            }
+            candidates[count] = i as u8;
+            count += 1;
+        }
+
+        if count > 0 {
+            self.channel = candidates[rng_range(0, count as i32 - 1) as usize];
+            self.send_on_channel();
+        } else if next_free != u64::MAX {
+            self.state = MacState::TxDelayed;
+            timer_set_value(unsafe { &mut TX_DELAYED_TIMER }, (next_free - now) as usize);
+            timer_start(unsafe { &mut TX_DELAYED_TIMER });
+        } else {
+            // no channel supports the data rate, or the frame exceeds the airtime budget
+            self.last_status = EventInfoStatus::Error;
+            self.finish_request();
+        }
+    }
+
+    /// Configure the radio for the selected channel and data rate and transmit
+    fn send_on_channel(&mut self) {
+        let Some(channel) = self.plan.channels[self.channel as usize] else {
+            return;
+        };
+        let power = self
+            .region
+            .tx_power_dbm(self.tx_power, self.max_eirp, self.antenna_gain)
+            .unwrap_or(0);
+
+        radio_standby();
+        if let Some(lbt) = &self.lbt {
+            let modem = match self.data_rate(self.tx_dr).modulation {
+                Modulation::LoRa { .. } => RadioModem::LoRa,
+                Modulation::Fsk { .. } => RadioModem::Fsk,
+            };
+            if lbt_wait_clear(modem, channel.frequency as usize, lbt).is_err() {
+                // busy on every attempt, pick a channel again after a backoff
+                self.state = MacState::TxDelayed;
+                let backoff = rng_range(lbt.backoff_min as i32, lbt.backoff_max as i32);
+                timer_set_value(unsafe { &mut TX_DELAYED_TIMER }, backoff as usize);
+                timer_start(unsafe { &mut TX_DELAYED_TIMER });
+                return;
+            }
+        }
+        radio_set_channel(channel.frequency as usize);
+        let modem = match self.data_rate(self.tx_dr).modulation {
+            Modulation::LoRa { sf, bw } => {
+                radio_set_tx_config(
+                    RadioModem::LoRa,
+                    power,
+                    0,
+                    bw as usize,
+                    sf as usize,
+                    1,
+                    8,
+                    false,
+                    true,
+                    false,
+                    0,
+                    false,
+                    3000,
+                );
+                RadioModem::LoRa
+            }
+            Modulation::Fsk { bitrate } => {
+                radio_set_tx_config(
+                    RadioModem::Fsk,
+                    power,
+                    25_000,
+                    0,
+                    bitrate as usize,
+                    0,
+                    5,
+                    false,
+                    true,
+                    false,
+                    0,
+                    false,
+                    3000,
+                );
+                RadioModem::Fsk
+            }
+        };
+
+        self.tx_time_on_air = radio_time_on_air(modem, self.tx_len as u8);
+        self.state = MacState::Tx;
+        radio_send(&self.tx_buf[..self.tx_len]);
+    }
+
+    // ── receive windows ──────────────────────────────────────────────────
+
+    /// Symbol timeout and opening offset (ms) of a window at `dr`
+    fn rx_window_params(&self, dr: u8) -> (u16, i32) {
+        let t_symbol = self.data_rate(dr).symbol_time();
+        let timeout = (libm::ceil(
+            ((2 * MIN_RX_SYMBOLS - 8) as f64 * t_symbol + 2.0 * SYSTEM_MAX_RX_ERROR) / t_symbol,
+        ) as u32)
+            .max(MIN_RX_SYMBOLS);
+        let offset = libm::ceil(
+            4.0 * t_symbol - (timeout as f64 * t_symbol) / 2.0 - radio_get_wakeup_time() as f64,
+        ) as i32;
+        (timeout as u16, offset)
+    }
+
+    /// Configure the radio for a receive window
+    fn rx_config(&self, frequency: u32, dr: u8, continuous: bool) {
+        let (symb_timeout, _) = self.rx_window_params(dr);
+        let data_rate = self.data_rate(dr);
+
+        radio_standby();
+        radio_set_channel(frequency as usize);
+        let modem = match data_rate.modulation {
+            Modulation::LoRa { sf, bw } => {
+                radio_set_rx_config(
+                    RadioModem::LoRa,
+                    bw as usize,
+                    sf as usize,
+                    1,
+                    0,
+                    8,
+                    symb_timeout,
+                    false,
+                    0,
+                    false,
+                    false,
+                    0,
+                    true,
+                    continuous,
+                );
+                RadioModem::LoRa
+            }
+            Modulation::Fsk { bitrate } => {
+                radio_set_rx_config(
+                    RadioModem::Fsk,
+                    50_000,
+                    bitrate as usize,
+                    0,
+                    83_333,
+                    5,
+                    symb_timeout,
+                    false,
+                    0,
+                    true,
+                    false,
+                    0,
+                    false,
+                    continuous,
+                );
+                RadioModem::Fsk
+            }
+        };
+        radio_set_max_payload_length(modem, data_rate.max_payload + PHY_OVERHEAD);
+    }
+
+    fn open_rx1(&mut self) {
+        let Some(channel) = self.plan.channels[self.channel as usize] else {
+            return;
+        };
+        let frequency = if channel.rx1_frequency != 0 {
+            channel.rx1_frequency
+        } else {
+            channel.frequency
+        };
+        let dr = self.region.rx1_dr(self.tx_dr, self.rx1_dr_offset);
+
+        self.state = MacState::Rx1;
+        self.rx_config(frequency, dr, false);
+        radio_rx(MAX_RX_WINDOW);
+    }
+
+    fn open_rx2(&mut self) {
+        if self.class == DeviceClass::C {
+            self.state = MacState::Rx2;
+            self.open_rx2_continuous();
+            // RX2 never times out in class C, bound the window ourselves
+            timer_set_value(unsafe { &mut ACK_TIMEOUT_TIMER }, MAX_RX_WINDOW);
+            timer_start(unsafe { &mut ACK_TIMEOUT_TIMER });
+        } else {
+            self.state = MacState::Rx2;
+            self.rx_config(self.rx2_frequency, self.rx2_dr, false);
+            radio_rx(MAX_RX_WINDOW);
+        }
+    }
+
+    /// Class C: listen on RX2 until the next transmission
+    fn open_rx2_continuous(&self) {
+        self.rx_config(self.rx2_frequency, self.rx2_dr, true);
+        radio_rx(0);
+    }
+
+    /// Nothing valid received in the current window
+    fn window_failed(&mut self, rx1_status: EventInfoStatus, rx2_status: EventInfoStatus) {
+        match self.state {
+            MacState::Rx1 => {
+                self.last_status = rx1_status;
+                self.state = MacState::WaitRx2;
+                if self.rx2_pending {
+                    self.open_rx2();
+                } else if self.class == DeviceClass::C {
+                    self.open_rx2_continuous();
+                } else {
+                    radio_sleep();
+                }
+            }
+            MacState::Rx2 if self.class == DeviceClass::A => {
+                self.last_status = rx2_status;
+                radio_sleep();
+                self.attempt_done(false);
+            }
+            _ => {}
+        }
+    }
+
+    // ── completion ───────────────────────────────────────────────────────
+
+    /// The RX windows of a transmission are over
+    fn attempt_done(&mut self, downlink: bool) {
+        timer_stop(unsafe { &mut RX_WINDOW1_TIMER });
+        timer_stop(unsafe { &mut RX_WINDOW2_TIMER });
+        timer_stop(unsafe { &mut ACK_TIMEOUT_TIMER });
+
+        match self.request {
+            Request::Join => {
+                if self.trial < self.max_trials {
+                    self.trial += 1;
+                    self.prepare_join();
+                    self.schedule_tx();
+                } else {
+                    self.last_status = EventInfoStatus::JoinFail;
+                    self.finish_request();
+                }
+            }
+            Request::Data { confirmed: true } => {
+                if self.ack_received {
+                    self.last_status = EventInfoStatus::Ok;
+                    self.finish_request();
+                } else if self.trial < self.max_trials {
+                    self.state = MacState::AckTimeout;
+                    let delay = ACK_TIMEOUT as i32
+                        + rng_range(-(ACK_TIMEOUT_RND as i32), ACK_TIMEOUT_RND as i32);
+                    timer_set_value(unsafe { &mut ACK_TIMEOUT_TIMER }, delay as usize);
+                    timer_start(unsafe { &mut ACK_TIMEOUT_TIMER });
+                    self.resume_rx();
+                } else {
+                    self.finish_request();
+                }
+            }
+            Request::Data { confirmed: false } => {
+                // NbTrans repetitions stop on the first valid downlink
+                if !downlink && self.trial < self.max_trials {
+                    self.trial += 1;
+                    self.schedule_tx();
+                } else {
+                    self.last_status = EventInfoStatus::Ok;
+                    self.finish_request();
+                }
+            }
+        }
+    }
+
+    /// Retransmit an unacknowledged confirmed uplink
+    fn retransmit(&mut self) {
+        self.trial += 1;
+        self.tx_dr = self.retransmit_dr();
+        self.schedule_tx();
+    }
+
+    /// Data rate of transmission `trial`, lowered every second retransmission
+    /// as long as the frame still fits
+    fn retransmit_dr(&self) -> u8 {
+        if self.trial % 2 == 1 && self.tx_dr > self.region.min_tx_dr() {
+            let dr = self.tx_dr - 1;
+            let mac_payload_len = self.tx_len - PHY_OVERHEAD as usize;
+            if self.data_rate(dr).max_payload as usize >= mac_payload_len {
+                return dr;
+            }
+        }
+        self.tx_dr
+    }
+
+    /// Report the request result and go back to idle
+    fn finish_request(&mut self) {
+        self.state = MacState::Idle;
+        let status = self.last_status;
+
+        match self.request {
+            Request::Join => {
+                let confirm = MlmeConfirm {
+                    request: MlmeType::Join,
+                    status,
+                    tx_time_on_air: self.tx_time_on_air,
+                    demod_margin: 0,
+                    nb_gateways: 0,
+                    nb_trials: self.trial,
+                    gps_time: 0,
+                };
+                self.resume_rx();
+                self.mlme_confirm(&confirm);
+            }
+            Request::Data { confirmed } => {
+                let confirm = McpsConfirm {
+                    request: if confirmed {
+                        McpsType::Confirmed
+                    } else {
+                        McpsType::Unconfirmed
+                    },
+                    status,
+                    datarate: self.tx_dr,
+                    tx_power: self.tx_power,
+                    ack_received: self.ack_received,
+                    nb_trials: self.trial,
+                    tx_time_on_air: self.tx_time_on_air,
+                    uplink_counter: self.fcnt_up,
+                    channel: self.channel,
+                };
+                self.fcnt_up = self.fcnt_up.wrapping_add(1);
+
+                // answers that never came
+                let pending = [
+                    (self.link_check_pending, MlmeType::LinkCheck),
+                    (self.device_time_pending, MlmeType::DeviceTime),
+                ];
+                self.link_check_pending = false;
+                self.device_time_pending = false;
+
+                self.resume_rx();
+                self.mcps_confirm(&confirm);
+                for (was_pending, request) in pending {
+                    if was_pending {
+                        self.mlme_confirm(&MlmeConfirm {
+                            request,
+                            status: if status == EventInfoStatus::Ok {
+                                EventInfoStatus::Rx2Timeout
+                            } else {
+                                status
+                            },
+                            tx_time_on_air: self.tx_time_on_air,
+                            demod_margin: 0,
+                            nb_gateways: 0,
+                            nb_trials: self.trial,
+                            gps_time: 0,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Put the radio back in the class idle mode
+    fn resume_rx(&self) {
+        if self.class == DeviceClass::C && self.joined {
+            self.open_rx2_continuous();
+        } else {
+            radio_sleep();
+        }
+    }
+
+    // ── downlinks ────────────────────────────────────────────────────────
+
+    fn rx_slot(&self) -> RxSlot {
+        match self.state {
+            MacState::Rx1 => RxSlot::Rx1,
+            MacState::WaitRx1 | MacState::WaitRx2 | MacState::Rx2 => RxSlot::Rx2,
+            _ => RxSlot::ClassC,
+        }
+    }
+
+    fn on_join_accept(&mut self, phy: &[u8]) -> bool {
+        if self.request != Request::Join || self.state == MacState::Idle {
+            return false;
+        }
+        let Ok(accept) = JoinAccept::decrypt(&mut self.aes, &self.app_key, phy) else {
+            return false;
+        };
+
+        self.keys = derive_session_keys(
+            &mut self.aes,
+            &self.app_key,
+            &accept.join_nonce,
+            &accept.net_id,
+            self.dev_nonce,
+        );
+        self.dev_addr = accept.dev_addr;
+        self.fcnt_up = 0;
+        self.fcnt_down = None;
+        self.adr_ack_counter = 0;
+        self.mac_commands.clear();
+        self.sticky_commands.clear();
+        self.rx1_dr_offset = accept.rx1_dr_offset();
+        self.rx2_dr = accept.rx2_datarate();
+        self.receive_delay1 = accept.rx_delay.clamp(1, 15) as usize * 1000;
+        if let Some(cf_list) = &accept.cf_list {
+            self.region.apply_cf_list(&mut self.plan, cf_list);
+            self.default_mask = self.plan.mask;
+        }
+        self.joined = true;
+
+        timer_stop(unsafe { &mut RX_WINDOW2_TIMER });
+        timer_stop(unsafe { &mut ACK_TIMEOUT_TIMER });
+        self.last_status = EventInfoStatus::Ok;
+        self.finish_request();
+        true
+    }
+
+    /// Handle a received PHYPayload, returns false if it isn't for this device
+    fn on_downlink(&mut self, phy: &[u8], rssi: i16, snr: i8) -> bool {
+        let frame = match PhyPayload::parse(phy) {
+            Ok(PhyPayload::JoinAccept(phy)) => return self.on_join_accept(phy),
+            Ok(PhyPayload::Data(frame)) if frame.direction() == Direction::Downlink => frame,
+            _ => return false,
+        };
+        if !self.joined {
+            return false;
+        }
+        let slot = self.rx_slot();
+
+        let mut indication = McpsIndication {
+            indication: if frame.is_confirmed() {
+                McpsType::Confirmed
+            } else {
+                McpsType::Unconfirmed
+            },
+            status: EventInfoStatus::Ok,
+            port: frame.fport.unwrap_or(0),
+            rx_datarate: match slot {
+                RxSlot::Rx1 => self.region.rx1_dr(self.tx_dr, self.rx1_dr_offset),
+                _ => self.rx2_dr,
+            },
+            frame_pending: frame.fhdr.fctrl.f_pending(),
+            buffer: &[],
+            rx_data: false,
+            rssi,
+            snr,
+            rx_slot: slot,
+            ack_received: false,
+            downlink_counter: 0,
+        };
+
+        if frame.fhdr.dev_addr != self.dev_addr {
+            indication.status = EventInfoStatus::AddressFail;
+            self.mcps_indication(&indication);
+            return false;
+        }
+
+        let Some(fcnt) = extend_fcnt(self.fcnt_down, frame.fhdr.fcnt) else {
+            indication.status = EventInfoStatus::DownlinkTooManyFramesLoss;
+            self.mcps_indication(&indication);
+            return false;
+        };
+        indication.downlink_counter = fcnt;
+        if !frame.verify_mic(&mut self.aes, &self.keys.nwk_skey, fcnt) {
+            indication.status = EventInfoStatus::MicFail;
+            self.mcps_indication(&indication);
+            return false;
+        }
+        if self.fcnt_down == Some(fcnt) {
+            indication.status = EventInfoStatus::DownlinkRepeated;
+            self.mcps_indication(&indication);
+            return true;
+        }
+        self.fcnt_down = Some(fcnt);
+
+        // any valid downlink answers ADRACKReq and acknowledges the sticky answers
+        self.adr_ack_counter = 0;
+        self.sticky_commands.clear();
+        self.last_snr = snr;
+        if frame.is_confirmed() {
+            self.srv_ack_requested = true;
+        }
+        if frame.fhdr.fctrl.ack()
+            && self.request == (Request::Data { confirmed: true })
+            && self.state != MacState::Idle
+        {
+            self.ack_received = true;
+            indication.ack_received = true;
+        }
+
+        let mut payload = [0u8; PHY_PAYLOAD_MAX_SIZE];
+        match frame.fport {
+            Some(0) => {
+                if let Ok(commands) =
+                    frame.decrypt_payload(&mut self.aes, &self.keys.nwk_skey, fcnt, &mut payload)
+                {
+                    self.process_commands(commands);
+                }
+            }
+            Some(_) => {
+                self.process_commands(frame.fhdr.fopts);
+                if let Ok(data) =
+                    frame.decrypt_payload(&mut self.aes, &self.keys.app_skey, fcnt, &mut payload)
+                {
+                    indication.buffer = data;
+                    indication.rx_data = true;
+                }
+            }
+            None => self.process_commands(frame.fhdr.fopts),
+        }
+
+        self.mcps_indication(&indication);
+
+        if self.srv_ack_requested || !self.sticky_commands.is_empty() {
+            self.mlme_indication(&MlmeIndication {
+                indication: MlmeType::ScheduleUplink,
+                status: EventInfoStatus::Ok,
+            });
+        }
+        true
+    }
+
+    /// Apply downlink MAC commands and queue their answers
+    fn process_commands(&mut self, data: &[u8]) {
+        let mut commands = CommandIter::new(data).peekable();
+        while let Some(command) = commands.next() {
+            match command {
+                DownlinkCommand::LinkCheckAns { margin, gw_cnt } => {
+                    if self.link_check_pending {
+                        self.link_check_pending = false;
+                        self.mlme_confirm(&MlmeConfirm {
+                            request: MlmeType::LinkCheck,
+                            status: EventInfoStatus::Ok,
+                            tx_time_on_air: self.tx_time_on_air,
+                            demod_margin: margin,
+                            nb_gateways: gw_cnt,
+                            nb_trials: self.trial,
+                            gps_time: 0,
+                        });
+                    }
+                }
+                DownlinkCommand::LinkAdrReq {
+                    dr,
+                    tx_power,
+                    ch_mask,
+                    ch_mask_cntl,
+                    nb_trans,
+                } => {
+                    // consecutive requests form a single atomic block
+                    let mut plan = self.plan;
+                    let mut status =
+                        self.region
+                            .link_adr_req(&mut plan, dr, tx_power, ch_mask, ch_mask_cntl);
+                    let mut mask_ok = status.channel_mask_ack;
+                    let (mut dr, mut tx_power, mut nb_trans) = (dr, tx_power, nb_trans);
+                    let mut count = 1;
+                    while let Some(&DownlinkCommand::LinkAdrReq {
+                        dr: d,
+                        tx_power: p,
+                        ch_mask,
+                        ch_mask_cntl,
+                        nb_trans: n,
+                    }) = commands.peek()
+                    {
+                        commands.next();
+                        status = self
+                            .region
+                            .link_adr_req(&mut plan, d, p, ch_mask, ch_mask_cntl);
+                        mask_ok &= status.channel_mask_ack;
+                        (dr, tx_power, nb_trans) = (d, p, n);
+                        count += 1;
+                    }
+                    status.channel_mask_ack = mask_ok;
+
+                    if status.is_ok() {
+                        self.plan.mask = plan.mask;
+                        if dr != 0x0F {
+                            self.datarate = dr;
+                        }
+                        if tx_power != 0x0F {
+                            self.tx_power = tx_power;
+                        }
+                        self.nb_trans = nb_trans.max(1);
+                    }
+                    for _ in 0..count {
+                        self.queue_answer(&[commands::LINK_ADR, status.to_byte()]);
+                    }
+                }
+                DownlinkCommand::DutyCycleReq { max_duty_cycle } => {
+                    airtime().set_aggregated_duty_cycle(1 << max_duty_cycle);
+                    self.queue_answer(&[commands::DUTY_CYCLE]);
+                }
+                DownlinkCommand::RxParamSetupReq {
+                    rx1_dr_offset,
+                    rx2_dr,
+                    frequency,
+                } => {
+                    let channel_ack = self.region.band_of(frequency).is_some();
+                    let rx2_dr_ack = self.region.data_rate(rx2_dr).is_some();
+                    let offset_ack = rx1_dr_offset <= self.region.max_rx1_dr_offset();
+                    if channel_ack && rx2_dr_ack && offset_ack {
+                        self.rx1_dr_offset = rx1_dr_offset;
+                        self.rx2_dr = rx2_dr;
+                        self.rx2_frequency = frequency;
+                    }
+                    let status =
+                        (offset_ack as u8) << 2 | (rx2_dr_ack as u8) << 1 | channel_ack as u8;
+                    self.queue_sticky(&[commands::RX_PARAM_SETUP, status]);
+                }
+                DownlinkCommand::DevStatusReq => {
+                    let battery = self
+                        .primitives
+                        .and_then(|p| p.get_battery_level)
+                        .map_or(255, |f| f());
+                    let margin = self.last_snr.clamp(-32, 31) as u8 & 0x3F;
+                    self.queue_answer(&[commands::DEV_STATUS, battery, margin]);
+                }
+                DownlinkCommand::NewChannelReq {
+                    index,
+                    frequency,
+                    min_dr,
+                    max_dr,
+                } => {
+                    let status = self.region.new_channel_req(
+                        &mut self.plan,
+                        index,
+                        frequency,
+                        min_dr,
+                        max_dr,
+                    );
+                    if frequency != 0 && status.to_byte() == 0b11 {
+                        self.default_mask = self.plan.mask;
+                    }
+                    self.queue_answer(&[commands::NEW_CHANNEL, status.to_byte()]);
+                }
+                DownlinkCommand::RxTimingSetupReq { delay } => {
+                    self.receive_delay1 = delay.max(1) as usize * 1000;
+                    self.queue_sticky(&[commands::RX_TIMING_SETUP]);
+                }
+                DownlinkCommand::TxParamSetupReq { eirp_dwell_time } => {
+                    // only answered by regions with dwell time rules (AS923, AU915)
+                    if let Some(eirp) = self.region.tx_param_setup_eirp(eirp_dwell_time) {
+                        self.max_eirp = eirp;
+                        self.queue_answer(&[commands::TX_PARAM_SETUP]);
+                    }
+                }
+                DownlinkCommand::DlChannelReq { index, frequency } => {
+                    let status = self.region.dl_channel_req(&mut self.plan, index, frequency);
+                    self.queue_sticky(&[commands::DL_CHANNEL, status.to_byte()]);
+                }
+                DownlinkCommand::DeviceTimeAns { seconds, fraction } => {
+                    if self.device_time_pending {
+                        self.device_time_pending = false;
+                        // GPS time at the end of the uplink, bring it to now
+                        let gps_time = seconds as u64 * 1000
+                            + (fraction as u64 * 1000) / 256
+                            + (timer_get_current_time() - self.tx_done_time);
+                        self.mlme_confirm(&MlmeConfirm {
+                            request: MlmeType::DeviceTime,
+                            status: EventInfoStatus::Ok,
+                            tx_time_on_air: self.tx_time_on_air,
+                            demod_margin: 0,
+                            nb_gateways: 0,
+                            nb_trials: self.trial,
+                            gps_time,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    fn queue_answer(&mut self, answer: &[u8]) {
+        let _ = self.mac_commands.extend_from_slice(answer);
+    }
+
+    fn queue_sticky(&mut self, answer: &[u8]) {
+        let _ = self.sticky_commands.extend_from_slice(answer);
+    }
+
+    // ── primitives ───────────────────────────────────────────────────────
+
+    fn mcps_confirm(&self, confirm: &McpsConfirm) {
+        if let Some(f) = self.primitives.and_then(|p| p.mcps_confirm) {
+            f(confirm);
+        }
+    }
+
+    fn mcps_indication(&self, indication: &McpsIndication) {
+        if let Some(f) = self.primitives.and_then(|p| p.mcps_indication) {
+            f(indication);
+        }
+    }
+
+    fn mlme_confirm(&self, confirm: &MlmeConfirm) {
+        if let Some(f) = self.primitives.and_then(|p| p.mlme_confirm) {
+            f(confirm);
+        }
+    }
+
+    fn mlme_indication(&self, indication: &MlmeIndication) {
+        if let Some(f) = self.primitives.and_then(|p| p.mlme_indication) {
+            f(indication);
+        }
+    }
+}
+
+/// Extend a received 16-bit downlink frame counter with the last accepted one.
+///
+/// With no downlink accepted yet (ABP, or a resumed session) the received counter is taken as
+/// it is. `None` when the counter is more than [`MAX_FCNT_GAP`] ahead or when the 32-bit
+/// counter would roll over, the session has to be renewed by then.
+fn extend_fcnt(last: Option<u32>, fcnt: u16) -> Option<u32> {
+    let Some(last) = last else {
+        return Some(fcnt as u32);
+    };
+    let mut extended = (last & 0xFFFF_0000) | fcnt as u32;
+    if extended < last {
+        extended = extended.checked_add(0x1_0000)?;
+    }
+    (extended - last <= MAX_FCNT_GAP).then_some(extended)
+}
+
+static mut MAC: Mac = Mac::new();
+
+static mut TX_DELAYED_TIMER: TimerEvent = TimerEvent {
+    id: 0,
+    timestamp: 0,
+    reload_value: 0,
+    is_running: false,
+    callback: None,
+};
+static mut RX_WINDOW1_TIMER: TimerEvent = TimerEvent {
+    id: 0,
+    timestamp: 0,
+    reload_value: 0,
+    is_running: false,
+    callback: None,
+};
+static mut RX_WINDOW2_TIMER: TimerEvent = TimerEvent {
+    id: 0,
+    timestamp: 0,
+    reload_value: 0,
+    is_running: false,
+    callback: None,
+};
+static mut ACK_TIMEOUT_TIMER: TimerEvent = TimerEvent {
+    id: 0,
+    timestamp: 0,
+    reload_value: 0,
+    is_running: false,
+    callback: None,
+};
+
+fn mac_state() -> &'static mut Mac {
+    unsafe { &mut MAC }
+}
+
+// ── radio events ─────────────────────────────────────────────────────────
+
+fn on_radio_tx_done() {
+    let mac = mac_state();
+    let now = timer_get_current_time();
+    mac.tx_done_time = now;
+    mac.rx2_pending = false;
+
+    let band = mac.plan.channels[mac.channel as usize].map(|channel| channel.band);
+    airtime().record(now, band, mac.tx_time_on_air as u32);
+
+    let (delay1, delay2) = match mac.request {
+        Request::Join => (JOIN_ACCEPT_DELAY1, JOIN_ACCEPT_DELAY2),
+        Request::Data { .. } => (mac.receive_delay1, mac.receive_delay1 + 1000),
+    };
+    let (_, offset1) = mac.rx_window_params(mac.region.rx1_dr(mac.tx_dr, mac.rx1_dr_offset));
+    let (_, offset2) = mac.rx_window_params(mac.rx2_dr);
+
+    mac.state = MacState::WaitRx1;
+    if mac.class == DeviceClass::C {
+        // listen on RX2 until RX1 opens
+        mac.open_rx2_continuous();
+    } else {
+        radio_sleep();
+    }
+
+    timer_set_value(
+        unsafe { &mut RX_WINDOW1_TIMER },
+        (delay1 as i32 + offset1).max(0) as usize,
+    );
+    timer_start(unsafe { &mut RX_WINDOW1_TIMER });
+    timer_set_value(
+        unsafe { &mut RX_WINDOW2_TIMER },
+        (delay2 as i32 + offset2).max(0) as usize,
+    );
+    timer_start(unsafe { &mut RX_WINDOW2_TIMER });
+}
+
+fn on_radio_tx_timeout() {
+    let mac = mac_state();
+    mac.last_status = EventInfoStatus::TxTimeout;
+    mac.finish_request();
+}
+
+fn on_radio_rx_done(payload: &[u8], rssi: i16, snr: i8) {
+    let mac = mac_state();
+    let valid = mac.on_downlink(payload, rssi, snr);
+
+    // a join-accept finishes the request by itself
+    match mac.state {
+        MacState::Rx1 | MacState::Rx2 | MacState::WaitRx1 | MacState::WaitRx2 if valid => {
+            if mac.class == DeviceClass::A {
+                radio_sleep();
+            }
+            mac.attempt_done(true);
+        }
+        MacState::Rx1 | MacState::Rx2 => {
+            mac.window_failed(EventInfoStatus::Rx1Error, EventInfoStatus::Rx2Error)
+        }
+        // class C ACK while waiting to retransmit
+        MacState::AckTimeout | MacState::TxDelayed if mac.ack_received => {
+            timer_stop(unsafe { &mut ACK_TIMEOUT_TIMER });
+            timer_stop(unsafe { &mut TX_DELAYED_TIMER });
+            mac.last_status = EventInfoStatus::Ok;
+            mac.finish_request();
+        }
+        _ => {}
+    }
+}
+
+fn on_radio_rx_timeout() {
+    mac_state().window_failed(EventInfoStatus::Rx1Timeout, EventInfoStatus::Rx2Timeout);
+}
+
+fn on_radio_rx_error() {
+    mac_state().window_failed(EventInfoStatus::Rx1Error, EventInfoStatus::Rx2Error);
+}
+
+static MAC_RADIO_EVENTS: RadioEvents = RadioEvents {
+    tx_done: Some(on_radio_tx_done),
+    tx_timeout: Some(on_radio_tx_timeout),
+    rx_done: Some(on_radio_rx_done),
+    rx_timeout: Some(on_radio_rx_timeout),
+    rx_error: Some(on_radio_rx_error),
+    rx_crc_error: None,
+    fhss_change_channel: None,
+    cad_done: None,
+};
+
+// ── timer events ─────────────────────────────────────────────────────────
+
+fn on_tx_delayed_timer() {
+    let mac = mac_state();
+    if mac.state == MacState::TxDelayed {
+        mac.schedule_tx();
+    }
+}
+
+fn on_rx_window1_timer() {
+    let mac = mac_state();
+    if mac.state == MacState::WaitRx1 {
+        mac.open_rx1();
+    }
+}
+
+fn on_rx_window2_timer() {
+    let mac = mac_state();
+    match mac.state {
+        MacState::WaitRx2 => mac.open_rx2(),
+        // RX1 still busy receiving, open RX2 once it's done
+        MacState::Rx1 => mac.rx2_pending = true,
+        _ => {}
+    }
+}
+
+fn on_ack_timeout_timer() {
+    let mac = mac_state();
+    match mac.state {
+        // end of the class C RX2 window
+        MacState::Rx2 => {
+            mac.last_status = EventInfoStatus::Rx2Timeout;
+            mac.attempt_done(false);
+        }
+        MacState::AckTimeout => mac.retransmit(),
+        _ => {}
+    }
+}
+
+// ── public API ───────────────────────────────────────────────────────────
+
+/// Initialise the MAC layer and the radio for `region`
+pub fn mac_init(primitives: &'static MacPrimitives, region: Region) {
+    let mac = mac_state();
+    *mac = Mac::new();
+    mac.primitives = Some(primitives);
+    mac.region = region;
+    mac.lbt = LbtConfig::for_region(region);
+    mac.reset_region_defaults();
+
+    timer_init(unsafe { &mut TX_DELAYED_TIMER }, on_tx_delayed_timer);
+    timer_init(unsafe { &mut RX_WINDOW1_TIMER }, on_rx_window1_timer);
+    timer_init(unsafe { &mut RX_WINDOW2_TIMER }, on_rx_window2_timer);
+    timer_init(unsafe { &mut ACK_TIMEOUT_TIMER }, on_ack_timeout_timer);
+
+    radio_init(&MAC_RADIO_EVENTS);
+    rng_add_entropy(&(radio_random() as u32).to_le_bytes());
+    radio_set_public_network(true);
+    radio_sleep();
+}
+
+/// Start an over-the-air activation, the result comes through `mlme_confirm`
+pub fn mac_join(params: &JoinParams) -> Result<(), MacError> {
+    let mac = mac_state();
+    if mac.primitives.is_none() {
+        return Err(MacError::NotInitialised);
+    }
+    if mac.state != MacState::Idle {
+        return Err(MacError::Busy);
+    }
+
+    mac.dev_eui = params.dev_eui;
+    mac.app_eui = params.app_eui;
+    mac.app_key = params.app_key;
+    mac.joined = false;
+    mac.reset_region_defaults();
+
+    mac.request = Request::Join;
+    mac.trial = 1;
+    mac.max_trials = params.nb_trials.max(1);
+    mac.last_status = EventInfoStatus::Ok;
+    mac.prepare_join();
+    mac.schedule_tx();
+    Ok(())
+}
+
+/// Activation by personalisation
+pub fn mac_activate_abp(
+    dev_addr: u32,
+    nwk_skey: &AesKey,
+    app_skey: &AesKey,
+) -> Result<(), MacError> {
+    let mac = mac_state();
+    if mac.state != MacState::Idle {
+        return Err(MacError::Busy);
+    }
+    mac.dev_addr = dev_addr;
+    mac.keys = SessionKeys {
+        nwk_skey: *nwk_skey,
+        app_skey: *app_skey,
+    };
+    mac.fcnt_up = 0;
+    mac.fcnt_down = None;
+    mac.joined = true;
+    mac.resume_rx();
+    Ok(())
+}
+
+/// Largest payload that can be sent right now, with the pending MAC commands
+pub fn mac_query_tx_possible(size: usize) -> Result<usize, MacError> {
+    let max = mac_state().max_payload()?;
+    if size > max {
+        return Err(MacError::LengthError);
+    }
+    Ok(max)
+}
+
+/// Listen before every uplink, `None` to transmit right away.
+///
+/// KR920 enables it in `mac_init`, AS923 in Japan needs [`LbtConfig::AS923_JAPAN`].
+pub fn mac_set_lbt(config: Option<LbtConfig>) {
+    mac_state().lbt = config;
+}
+
+/// Hold the uplinks to The Things Network fair use policy (30 s of airtime per rolling 24 h).
+///
+/// Kept across `mac_init`, frames that would exceed the budget are delayed until enough
+/// airtime has left the window.
+pub fn mac_set_fair_use(enable: bool) {
+    airtime().set_window_budget(enable.then_some(TTN_FAIR_USE_MS));
+}
+
+/// Time (ms) until a `size` byte payload may be sent at `dr` on one of the enabled channels,
+/// with the pending MAC commands.
+///
+/// Accounts for the band and aggregated duty cycles and the rolling airtime budget of the
+/// shared [`airtime`] ledger.
+pub fn mac_time_until_tx(size: usize, dr: u8) -> Result<u64, MacError> {
+    let mac = mac_state();
+    let data_rate = mac.region.data_rate(dr).ok_or(MacError::DatarateInvalid)?;
+    let fopts_len = mac.sticky_commands.len() + mac.mac_commands.len();
+    if size > mac.region.max_payload(dr, fopts_len) {
+        return Err(MacError::LengthError);
+    }
+    // MHDR + FHDR + FPort + MIC
+    let len = PHY_OVERHEAD as usize + 7 + fopts_len + 1 + size;
+    let time_on_air = data_rate.time_on_air(len as u8);
+
+    let now = timer_get_current_time();
+    let mut wait = None;
+    for i in 0..MAX_CHANNELS {
+        let Some(channel) = mac.plan.channels[i] else {
+            continue;
+        };
+        if !mac.plan.is_enabled(i) || !channel.supports_dr(dr) {
+            continue;
+        }
+        if let Some(channel_wait) = airtime().time_until_tx(now, Some(channel.band), time_on_air) {
+            wait = Some(wait.map_or(channel_wait, |w: u64| w.min(channel_wait)));
+        }
+    }
+    wait.ok_or(MacError::DatarateInvalid)
+}
+
+/// Send `data` on `port`, the result comes through `mcps_confirm`.
+///
+/// `nb_trials` is the amount of transmissions of a confirmed uplink, unconfirmed uplinks
+/// are repeated as requested by the network (LinkADRReq NbTrans).
+/// An empty `data` flushes the pending MAC commands.
+pub fn mac_send(port: u8, data: &[u8], confirmed: bool, nb_trials: u8) -> Result<(), MacError> {
+    let mac = mac_state();
+    if mac.state != MacState::Idle {
+        return Err(MacError::Busy);
+    }
+    if !mac.joined {
+        return Err(MacError::NoNetworkJoined);
+    }
+    if port == 0 || port > 223 {
+        return Err(MacError::ParameterInvalid);
+    }
+
+    let fopts = mac.fopts();
+    let (fopts, fport, payload) = mac.uplink_parts(&fopts, port, data)?;
+
+    let adr_ack_req = mac.adr_next();
+    let fctrl = FCtrl(0)
+        .set_adr(mac.adr_on)
+        .set_adr_ack_req(adr_ack_req)
+        .set_ack(mac.srv_ack_requested);
+    let mtype = if confirmed {
+        MType::ConfirmedDataUp
+    } else {
+        MType::UnconfirmedDataUp
+    };
+
+    let mut builder = DataFrameBuilder::new(mtype, mac.dev_addr, mac.fcnt_up)
+        .fctrl(fctrl)
+        .fopts(fopts);
+    if let Some(fport) = fport {
+        builder = builder.payload(fport, payload);
+    }
+    let Ok(len) = builder.build(
+        &mut mac.aes,
+        &mac.keys.nwk_skey,
+        &mac.keys.app_skey,
+        &mut mac.tx_buf,
+    ) else {
+        return Err(MacError::LengthError);
+    };
+
+    mac.tx_len = len;
+    mac.mac_commands.clear();
+    mac.srv_ack_requested = false;
+    mac.request = Request::Data { confirmed };
+    mac.tx_dr = mac.datarate;
+    mac.trial = 1;
+    mac.max_trials = if confirmed {
+        nb_trials.clamp(1, MAX_ACK_RETRIES)
+    } else {
+        mac.nb_trans
+    };
+    mac.ack_received = false;
+    mac.last_status = EventInfoStatus::Ok;
+    mac.schedule_tx();
+    Ok(())
+}
+
+/// Ask the network for the link margin with the next uplink, answered through `mlme_confirm`
+pub fn mac_link_check_req() {
+    let mac = mac_state();
+    if !mac.link_check_pending {
+        mac.link_check_pending = true;
+        mac.queue_answer(&[commands::LINK_CHECK]);
+    }
+}
+
+/// Ask the network for the time with the next uplink, answered through `mlme_confirm`
+pub fn mac_device_time_req() {
+    let mac = mac_state();
+    if !mac.device_time_pending {
+        mac.device_time_pending = true;
+        mac.queue_answer(&[commands::DEVICE_TIME]);
+    }
+}
+
+/// Whether the device has joined (or been activated)
+pub fn mac_is_joined() -> bool {
+    mac_state().joined
+}
+
+/// Whether a request is in progress
+pub fn mac_is_busy() -> bool {
+    mac_state().state != MacState::Idle
+}
+
+/// Device address assigned by the network
+pub fn mac_get_dev_addr() -> u32 {
+    mac_state().dev_addr
+}
+
+/// Frame counter of the next uplink
+pub fn mac_get_uplink_counter() -> u32 {
+    mac_state().fcnt_up
+}
+
+/// Current device class
+pub fn mac_get_device_class() -> DeviceClass {
+    mac_state().class
+}
+
+/// Switch the device class, class C starts listening right away when joined
+pub fn mac_set_device_class(class: DeviceClass) {
+    let mac = mac_state();
+    mac.class = class;
+    if mac.state == MacState::Idle {
+        mac.resume_rx();
+    }
+}
+
+/// Enable or disable adaptive data rate
+pub fn mac_set_adr(enable: bool) {
+    let mac = mac_state();
+    mac.adr_on = enable;
+    mac.adr_ack_counter = 0;
+}
+
+/// Current uplink data rate
+pub fn mac_get_datarate() -> u8 {
+    mac_state().datarate
+}
+
+/// Set the uplink data rate, overridden by ADR when enabled
+pub fn mac_set_datarate(dr: u8) -> Result<(), MacError> {
+    let mac = mac_state();
+    let region = mac.region;
+    if dr < region.min_tx_dr() || dr > region.max_tx_dr() {
+        return Err(MacError::DatarateInvalid);
+    }
+    let supported = (0..MAX_CHANNELS)
+        .any(|i| mac.plan.is_enabled(i) && mac.plan.channels[i].is_some_and(|c| c.supports_dr(dr)));
+    if !supported {
+        return Err(MacError::DatarateInvalid);
+    }
+    mac.datarate = dr;
+    Ok(())
+}
+
+/// Set the TX power index (0 = max EIRP, each step is -2 dB)
+pub fn mac_set_tx_power(index: u8) -> Result<(), MacError> {
+    let mac = mac_state();
+    if index > mac.region.max_tx_power_index() {
+        return Err(MacError::ParameterInvalid);
+    }
+    mac.tx_power = index;
+    Ok(())
+}
+
+/// Current channel plan
+pub fn mac_get_channel_plan() -> ChannelPlan {
+    mac_state().plan
+}
+
+/// Set the enabled channel mask, also used as the ADR fallback mask
+pub fn mac_set_channel_mask(mask: &[u16; CHANNEL_MASK_SIZE]) -> Result<(), MacError> {
+    let mac = mac_state();
+    let mut plan = mac.plan;
+    plan.mask = *mask;
+    if plan.enabled_count() == 0 {
+        return Err(MacError::ParameterInvalid);
+    }
+    mac.plan.mask = *mask;
+    mac.default_mask = *mask;
+    Ok(())
+}
+
+/// Use the public (0x34) or private (0x12) LoRaWAN sync word
+pub fn mac_set_public_network(enable: bool) {
+    radio_set_public_network(enable);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEV_ADDR: u32 = 0x26011F2A;
+    const NWK_SKEY: AesKey = [0x11; 16];
+    const APP_SKEY: AesKey = [0x22; 16];
+
+    /// EU868 session as after `mac_activate_abp`, without touching the radio
+    fn joined_mac() -> Mac {
+        let mut mac = Mac::new();
+        mac.plan = mac.region.default_channels();
+        mac.default_mask = mac.plan.mask;
+        mac.datarate = mac.region.default_dr();
+        (mac.rx2_frequency, mac.rx2_dr) = mac.region.rx2_default();
+        mac.max_eirp = mac.region.max_eirp();
+        mac.dev_addr = DEV_ADDR;
+        mac.keys = SessionKeys {
+            nwk_skey: NWK_SKEY,
+            app_skey: APP_SKEY,
+        };
+        mac.joined = true;
+        mac
+    }
+
+    fn downlink(builder: DataFrameBuilder) -> heapless::Vec<u8, PHY_PAYLOAD_MAX_SIZE> {
+        let mut out = [0u8; PHY_PAYLOAD_MAX_SIZE];
+        let len = builder
+            .build(&mut SoftAes::new(), &NWK_SKEY, &APP_SKEY, &mut out)
+            .unwrap();
+        heapless::Vec::from_slice(&out[..len]).unwrap()
+    }
+
+    #[test]
+    fn fcnt_extension() {
+        assert_eq!(extend_fcnt(None, 0), Some(0));
+        assert_eq!(extend_fcnt(None, 10), Some(10));
+        // no counter seen yet, the gap check doesn't apply
+        assert_eq!(extend_fcnt(None, 40000), Some(40000));
+        assert_eq!(extend_fcnt(None, u16::MAX), Some(u16::MAX as u32));
+        assert_eq!(extend_fcnt(Some(10), 10), Some(10));
+        assert_eq!(extend_fcnt(Some(0xFFFF), 0x0002), Some(0x1_0002));
+        assert_eq!(extend_fcnt(Some(0x1_FFF0), 0x0005), Some(0x2_0005));
+        assert_eq!(
+            extend_fcnt(Some(0), MAX_FCNT_GAP as u16),
+            Some(MAX_FCNT_GAP)
+        );
+        assert_eq!(extend_fcnt(Some(0), MAX_FCNT_GAP as u16 + 1), None);
+        // older than the last one means the next 16-bit cycle, far too ahead
+        assert_eq!(extend_fcnt(Some(100), 50), None);
+    }
+
+    #[test]
+    fn uplink_parts() {
+        let mut mac = joined_mac();
+        let answers = [commands::LINK_CHECK; 60];
+
+        // in FOpts, or alongside the data
+        assert_eq!(
+            mac.uplink_parts(&answers[..3], 1, &[]),
+            Ok((&answers[..3], None, &[][..]))
+        );
+        assert_eq!(
+            mac.uplink_parts(&answers[..3], 7, &[1, 2]),
+            Ok((&answers[..3], Some(7), &[1, 2][..]))
+        );
+        assert_eq!(
+            mac.uplink_parts(&answers[..16], 7, &[1, 2]),
+            Err(MacError::LengthError)
+        );
+
+        // too many for FOpts, flushed on port 0 as long as the data rate carries them
+        mac.datarate = 5;
+        assert_eq!(
+            mac.uplink_parts(&answers, 1, &[]),
+            Ok((&[][..], Some(0), &answers[..]))
+        );
+        mac.datarate = 0;
+        let max = mac.region.max_payload(0, 0);
+        assert_eq!(
+            mac.uplink_parts(&answers[..max], 1, &[]),
+            Ok((&[][..], Some(0), &answers[..max]))
+        );
+        assert_eq!(
+            mac.uplink_parts(&answers[..max + 1], 1, &[]),
+            Err(MacError::LengthError)
+        );
+    }
+
+    #[test]
+    fn fcnt_rollover_is_rejected() {
+        assert_eq!(extend_fcnt(Some(0xFFFF_FFF0), 0xFFFF), Some(0xFFFF_FFFF));
+        assert_eq!(extend_fcnt(Some(0xFFFF_FFF0), 0x0005), None);
+        assert_eq!(extend_fcnt(Some(u32::MAX), 0x0000), None);
+
+        let mut mac = joined_mac();
+        mac.fcnt_down = Some(0xFFFF_FFF0);
+        // FCnt 5 after 0xFFFF_FFF0 would be 0x1_0000_0005
+        let phy = downlink(DataFrameBuilder::new(
+            MType::UnconfirmedDataDown,
+            DEV_ADDR,
+            5,
+        ));
+        assert!(!mac.on_downlink(&phy, -80, 5));
+        assert_eq!(mac.fcnt_down, Some(0xFFFF_FFF0));
+    }
+
+    #[test]
+    fn downlink_counter_and_replays() {
+        let mut mac = joined_mac();
+        let phy = downlink(
+            DataFrameBuilder::new(MType::UnconfirmedDataDown, DEV_ADDR, 0x1_0003).payload(1, b"hi"),
+        );
+        mac.fcnt_down = Some(0xFFF0);
+        assert!(mac.on_downlink(&phy, -80, 5));
+        assert_eq!(mac.fcnt_down, Some(0x1_0003));
+
+        // the same frame again is reported as repeated, the counter doesn't move
+        assert!(mac.on_downlink(&phy, -80, 5));
+        assert_eq!(mac.fcnt_down, Some(0x1_0003));
+    }
+
+    #[test]
+    fn downlink_rejections() {
+        let mut mac = joined_mac();
+        let other = downlink(DataFrameBuilder::new(
+            MType::UnconfirmedDataDown,
+            DEV_ADDR + 1,
+            1,
+        ));
+        assert!(!mac.on_downlink(&other, -80, 5));
+
+        let mut tampered = downlink(DataFrameBuilder::new(
+            MType::UnconfirmedDataDown,
+            DEV_ADDR,
+            1,
+        ));
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0x01;
+        assert!(!mac.on_downlink(&tampered, -80, 5));
+        assert_eq!(mac.fcnt_down, None);
+
+        // uplinks are ignored
+        let uplink = downlink(DataFrameBuilder::new(MType::UnconfirmedDataUp, DEV_ADDR, 1));
+        assert!(!mac.on_downlink(&uplink, -80, 5));
+
+        mac.joined = false;
+        let valid = downlink(DataFrameBuilder::new(
+            MType::UnconfirmedDataDown,
+            DEV_ADDR,
+            1,
+        ));
+        assert!(!mac.on_downlink(&valid, -80, 5));
+    }
+
+    #[test]
+    fn confirmed_uplink_ack() {
+        let mut mac = joined_mac();
+        mac.request = Request::Data { confirmed: true };
+        mac.state = MacState::Rx1;
+        let no_ack = downlink(DataFrameBuilder::new(
+            MType::UnconfirmedDataDown,
+            DEV_ADDR,
+            1,
+        ));
+        assert!(mac.on_downlink(&no_ack, -80, 5));
+        assert!(!mac.ack_received);
+
+        let ack = downlink(
+            DataFrameBuilder::new(MType::UnconfirmedDataDown, DEV_ADDR, 2)
+                .fctrl(FCtrl::default().set_ack(true)),
+        );
+        assert!(mac.on_downlink(&ack, -80, 5));
+        assert!(mac.ack_received);
+
+        // an ACK outside of a confirmed request is ignored
+        let mut mac = joined_mac();
+        mac.request = Request::Data { confirmed: false };
+        mac.state = MacState::Rx1;
+        assert!(mac.on_downlink(&ack, -80, 5));
+        assert!(!mac.ack_received);
+
+        // a confirmed downlink asks for an ACK in the next uplink
+        let confirmed = downlink(DataFrameBuilder::new(MType::ConfirmedDataDown, DEV_ADDR, 3));
+        assert!(mac.on_downlink(&confirmed, -80, 5));
+        assert!(mac.srv_ack_requested);
+    }
+
+    #[test]
+    fn confirmed_retries_lower_the_data_rate() {
+        let mut mac = joined_mac();
+        mac.tx_dr = 5;
+        mac.tx_len = 20;
+        let drs: heapless::Vec<u8, 8> = (2..=8)
+            .map(|trial| {
+                mac.trial = trial;
+                mac.tx_dr = mac.retransmit_dr();
+                mac.tx_dr
+            })
+            .collect();
+        assert_eq!(drs.as_slice(), &[5, 4, 4, 3, 3, 2, 2]);
+
+        // stays at the minimum
+        mac.tx_dr = mac.region.min_tx_dr();
+        mac.trial = 3;
+        assert_eq!(mac.retransmit_dr(), mac.region.min_tx_dr());
+
+        // or at a data rate the frame still fits in
+        mac.tx_dr = 4;
+        mac.tx_len = 200;
+        mac.trial = 3;
+        assert_eq!(mac.retransmit_dr(), 4);
+    }
+
+    #[test]
+    fn adr_backoff() {
+        let mut mac = joined_mac();
+        mac.datarate = 5;
+        mac.tx_power = 3;
+        mac.plan.mask[0] = 0b001;
+
+        for _ in 1..ADR_ACK_LIMIT {
+            assert!(!mac.adr_next());
+        }
+        assert!(mac.adr_next());
+        for _ in 0..ADR_ACK_DELAY - 1 {
+            assert!(mac.adr_next());
+        }
+        assert_eq!((mac.tx_power, mac.datarate), (3, 5));
+
+        // full power first, then one data rate step every ADR_ACK_DELAY uplinks
+        assert!(mac.adr_next());
+        assert_eq!((mac.tx_power, mac.datarate), (0, 5));
+        for _ in 0..ADR_ACK_DELAY {
+            mac.adr_next();
+        }
+        assert_eq!(mac.datarate, 4);
+        for _ in 0..4 * ADR_ACK_DELAY {
+            mac.adr_next();
+        }
+        assert_eq!(mac.datarate, 0);
+        assert_eq!(mac.plan.mask[0], 0b001);
+        for _ in 0..ADR_ACK_DELAY {
+            mac.adr_next();
+        }
+        assert_eq!(mac.plan.mask[0], mac.default_mask[0]);
+
+        // any valid downlink resets the counter
+        let phy = downlink(DataFrameBuilder::new(
+            MType::UnconfirmedDataDown,
+            DEV_ADDR,
+            1,
+        ));
+        assert!(mac.on_downlink(&phy, -80, 5));
+        assert!(!mac.adr_next());
+
+        mac.adr_on = false;
+        mac.adr_ack_counter = ADR_ACK_LIMIT;
+        assert!(!mac.adr_next());
+    }
+
+    #[test]
+    fn link_adr_req() {
+        let mut mac = joined_mac();
+        // DR3, power index 2, channels 0 and 1, NbTrans 2
+        mac.process_commands(&[commands::LINK_ADR, 0x32, 0x03, 0x00, 0x02]);
+        assert_eq!(mac.datarate, 3);
+        assert_eq!(mac.tx_power, 2);
+        assert_eq!(mac.nb_trans, 2);
+        assert_eq!(mac.plan.mask[0], 0b011);
+        assert_eq!(mac.mac_commands.as_slice(), &[commands::LINK_ADR, 0x07]);
+
+        // an empty mask is refused and nothing changes
+        let mut mac = joined_mac();
+        let dr = mac.datarate;
+        mac.process_commands(&[commands::LINK_ADR, 0x12, 0x00, 0x00, 0x01]);
+        assert_eq!(mac.datarate, dr);
+        assert_eq!(mac.plan.mask, mac.default_mask);
+        assert_eq!(mac.mac_commands.as_slice(), &[commands::LINK_ADR, 0x00]);
+
+        // a block is applied atomically and answered once per request
+        let mut mac = joined_mac();
+        mac.process_commands(&[
+            commands::LINK_ADR,
+            0x50,
+            0x01,
+            0x00,
+            0x01,
+            commands::LINK_ADR,
+            0x41,
+            0x07,
+            0x00,
+            0x01,
+        ]);
+        assert_eq!((mac.datarate, mac.tx_power), (4, 1));
+        assert_eq!(
+            mac.mac_commands.as_slice(),
+            &[commands::LINK_ADR, 0x07, commands::LINK_ADR, 0x07]
+        );
+    }
+
+    #[test]
+    fn mac_commands_answers() {
+        let mut mac = joined_mac();
+        mac.last_snr = -5;
+        mac.process_commands(&[
+            commands::RX_PARAM_SETUP,
+            0x13,
+            0xC8,
+            0x85,
+            0x84,
+            commands::DEV_STATUS,
+            commands::NEW_CHANNEL,
+            3,
+            0x18,
+            0x4F,
+            0x84,
+            0x50,
+            commands::RX_TIMING_SETUP,
+            0x00,
+        ]);
+        assert_eq!(mac.rx1_dr_offset, 1);
+        assert_eq!(mac.rx2_dr, 3);
+        assert_eq!(mac.rx2_frequency, 868_500_000);
+        assert_eq!(
+            mac.plan.channels[3].map(|c| (c.frequency, c.min_dr, c.max_dr)),
+            Some((867_100_000, 0, 5))
+        );
+        assert!(mac.plan.is_enabled(3));
+        assert_eq!(mac.receive_delay1, 1000);
+        assert_eq!(
+            mac.mac_commands.as_slice(),
+            &[commands::DEV_STATUS, 255, 0x3B, commands::NEW_CHANNEL, 0x03]
+        );
+        assert_eq!(
+            mac.sticky_commands.as_slice(),
+            &[commands::RX_PARAM_SETUP, 0x07, commands::RX_TIMING_SETUP]
+        );
+        assert_eq!(mac.fopts().len(), 8);
+
+        // sticky answers are repeated until a downlink is received
+        let phy = downlink(DataFrameBuilder::new(
+            MType::UnconfirmedDataDown,
+            DEV_ADDR,
+            1,
+        ));
+        assert!(mac.on_downlink(&phy, -80, 5));
+        assert!(mac.sticky_commands.is_empty());
+    }
+
+    #[test]
+    fn mac_commands_in_port_zero_payload() {
+        let mut mac = joined_mac();
+        let phy = downlink(
+            DataFrameBuilder::new(MType::UnconfirmedDataDown, DEV_ADDR, 1)
+                .payload(0, &[commands::DEV_STATUS]),
+        );
+        assert!(mac.on_downlink(&phy, -80, 5));
+        assert_eq!(mac.mac_commands[0], commands::DEV_STATUS);
+    }
+}
@@ -0,0 +1,663 @@
+use heapless::Vec;
+
+use crate::lora::mesh::packet::{
+    ADDR_BROADCAST, HEADER_SIZE, Header, MAX_HOPS, MAX_PACKET_SIZE, PacketError, decode, encode,
+    set_relay,
+};
+
+/// Mesh packet format
+pub mod packet;
+/// Mesh node on the LoRa radio driver
+pub mod radio;
+
+/// Packets remembered to drop the copies heard again
+pub const MESH_SEEN: usize = 32;
+/// Neighbours tracked
+pub const MESH_NEIGHBOURS: usize = 16;
+/// Packets waiting for their (re)broadcast
+pub const MESH_QUEUE: usize = 4;
+
+/// SNR (dB) at and below which the smallest contention window is used
+pub const SNR_MIN: i8 = -20;
+/// SNR (dB) at and above which the largest contention window is used
+pub const SNR_MAX: i8 = 10;
+/// Smallest contention window, as a power of two of slots
+pub const CW_MIN: u8 = 2;
+/// Largest contention window, as a power of two of slots
+pub const CW_MAX: u8 = 7;
+
+/// What the node needs from the radio, implemented on the real driver by [`radio`] and by a
+/// simulated network on the host
+pub trait MeshRadio {
+    /// Transmit `packet`, [`MeshNode::on_tx_done`] follows
+    fn transmit(&mut self, packet: &[u8]);
+    /// Time (ms) before `len` bytes may be sent (duty cycle), 0 when free
+    fn time_until_tx(&self, len: usize) -> u64;
+    /// (Re)start the single node timer, [`MeshNode::on_timer`] follows after `ms`
+    fn start_timer(&mut self, ms: u32);
+    /// Stop the node timer
+    fn stop_timer(&mut self);
+    /// Random number for packet ids and rebroadcast delays
+    fn random(&mut self) -> u32;
+}
+
+/// Node settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshConfig {
+    /// own address, [`ADDR_BROADCAST`] is not allowed
+    pub address: u16,
+    /// hop limit of the packets sent, up to [`MAX_HOPS`]
+    pub hop_limit: u8,
+    /// contention slot (ms), about the time to detect a preamble and start transmitting
+    pub slot_time: u32,
+    /// neighbours not heard for this long are forgotten (ms)
+    pub neighbour_timeout: u64,
+    /// rebroadcast packets of other nodes
+    pub relay: bool,
+}
+
+/// Mesh request error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshError {
+    /// the transmit queue is full
+    Busy,
+    /// own address or destination not usable
+    InvalidAddress,
+    /// packet could not be built
+    Packet(PacketError),
+}
+
+impl From<PacketError> for MeshError {
+    fn from(error: PacketError) -> Self {
+        MeshError::Packet(error)
+    }
+}
+
+/// Packet for this node (or broadcast), its payload is in the `on_rx` buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Received {
+    pub origin: u16,
+    pub dst: u16,
+    pub id: u32,
+    /// rebroadcasts on the way
+    pub hops: u8,
+    /// node we heard it from
+    pub relay: u16,
+    pub len: usize,
+    pub rssi: i16,
+    pub snr: i8,
+}
+
+/// Node heard directly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Neighbour {
+    pub address: u16,
+    /// figures of the last packet heard
+    pub rssi: i16,
+    pub snr: i8,
+    /// time of the last packet heard (ms)
+    pub last_heard: u64,
+    /// packets heard
+    pub packets: u32,
+}
+
+/// Packet waiting in the transmit queue
+struct Queued {
+    origin: u16,
+    id: u32,
+    /// time it may go out (ms)
+    due: u64,
+    packet: Vec<u8, MAX_PACKET_SIZE>,
+}
+
+/// Contention window (power of two of slots) for a packet heard at `snr`: far nodes (low SNR)
+/// get small windows and rebroadcast first, as they reach the most new nodes
+pub fn contention_window(snr: i8) -> u8 {
+    let snr = snr.clamp(SNR_MIN, SNR_MAX) as i16;
+    let span = (SNR_MAX - SNR_MIN) as i16;
+    CW_MIN + ((snr - SNR_MIN as i16) * (CW_MAX - CW_MIN) as i16 / span) as u8
+}
+
+/// Rebroadcast delay (ms): a random slot of the [`contention_window`] for `snr`
+pub fn rebroadcast_delay(snr: i8, slot_time: u32, random: u32) -> u32 {
+    let slots = 1u32 << contention_window(snr);
+    (random % slots) * slot_time
+}
+
+/// Managed flooding mesh node: packets are rebroadcast once, after a random delay scaled by
+/// the SNR, until their hop limit runs out. A node drops its pending rebroadcast when it
+/// hears another node relay the same packet first.
+///
+/// Pure state machine, the radio events are passed in with the current time (ms).
+pub struct MeshNode<R: MeshRadio> {
+    radio: R,
+    config: MeshConfig,
+    /// drawn from the radio on the first send, so creating a node doesn't wait for the RNG
+    next_id: Option<u32>,
+    /// (origin, id) of the packets seen, oldest overwritten first
+    seen: [Option<(u16, u32)>; MESH_SEEN],
+    next_seen: usize,
+    neighbours: Vec<Neighbour, MESH_NEIGHBOURS>,
+    queue: Vec<Queued, MESH_QUEUE>,
+    transmitting: bool,
+}
+
+impl<R: MeshRadio> MeshNode<R> {
+    pub fn new(radio: R, config: MeshConfig) -> Result<Self, MeshError> {
+        if config.address == ADDR_BROADCAST {
+            return Err(MeshError::InvalidAddress);
+        }
+        Ok(Self {
+            radio,
+            config: MeshConfig {
+                hop_limit: config.hop_limit.min(MAX_HOPS),
+                ..config
+            },
+            next_id: None,
+            seen: [None; MESH_SEEN],
+            next_seen: 0,
+            neighbours: Vec::new(),
+            queue: Vec::new(),
+            transmitting: false,
+        })
+    }
+
+    pub fn radio(&mut self) -> &mut R {
+        &mut self.radio
+    }
+
+    /// Nodes heard directly within the neighbour timeout
+    pub fn neighbours(&self, now: u64) -> impl Iterator<Item = &Neighbour> {
+        let timeout = self.config.neighbour_timeout;
+        self.neighbours
+            .iter()
+            .filter(move |n| now.saturating_sub(n.last_heard) < timeout)
+    }
+
+    /// Neighbour `address`, if heard within the neighbour timeout
+    pub fn neighbour(&self, address: u16, now: u64) -> Option<&Neighbour> {
+        self.neighbours(now).find(|n| n.address == address)
+    }
+
+    /// Send `payload` to `dst` across the mesh, returns the packet id
+    pub fn send(&mut self, dst: u16, payload: &[u8], now: u64) -> Result<u32, MeshError> {
+        if dst == self.config.address {
+            return Err(MeshError::InvalidAddress);
+        }
+        if self.queue.is_full() {
+            return Err(MeshError::Busy);
+        }
+
+        let id = *self.next_id.get_or_insert_with(|| self.radio.random());
+        let header = Header {
+            flags: 0,
+            dst,
+            origin: self.config.address,
+            id,
+            hop_start: self.config.hop_limit,
+            hop_limit: self.config.hop_limit,
+            relay: self.config.address,
+        };
+        let mut packet = [0u8; MAX_PACKET_SIZE];
+        let len = encode(&header, payload, &mut packet)?;
+
+        self.next_id = Some(id.wrapping_add(1));
+        self.remember(header.origin, header.id);
+        self.enqueue(&header, &packet[..len], now);
+        self.service(now);
+        Ok(header.id)
+    }
+
+    /// The radio finished a transmission
+    pub fn on_tx_done(&mut self, now: u64) {
+        self.transmitting = false;
+        self.service(now);
+    }
+
+    /// The node timer expired
+    pub fn on_timer(&mut self, now: u64) {
+        self.service(now);
+    }
+
+    /// A packet was received, its payload goes to `out` when it is for this node
+    pub fn on_rx(
+        &mut self,
+        packet: &[u8],
+        rssi: i16,
+        snr: i8,
+        now: u64,
+        out: &mut [u8],
+    ) -> Option<Received> {
+        let header = decode(packet).ok()?;
+        if header.relay == self.config.address || header.relay == ADDR_BROADCAST {
+            return None;
+        }
+        self.heard(header.relay, rssi, snr, now);
+
+        if self.has_seen(header.origin, header.id) {
+            // another node relayed it, ours is not needed any more
+            if let Some(i) = self
+                .queue
+                .iter()
+                .position(|q| q.origin == header.origin && q.id == header.id)
+            {
+                self.queue.remove(i);
+                self.service(now);
+            }
+            return None;
+        }
+        self.remember(header.origin, header.id);
+
+        if self.config.relay && header.hop_limit > 0 && header.dst != self.config.address {
+            let mut copy = [0u8; MAX_PACKET_SIZE];
+            copy[..packet.len()].copy_from_slice(packet);
+            set_relay(
+                &mut copy[..packet.len()],
+                header.hop_limit - 1,
+                self.config.address,
+            );
+            let delay = rebroadcast_delay(snr, self.config.slot_time, self.radio.random());
+            self.enqueue(&header, &copy[..packet.len()], now + delay as u64);
+            self.service(now);
+        }
+
+        if header.dst != self.config.address && !header.is_broadcast() {
+            return None;
+        }
+        let payload = &packet[HEADER_SIZE..];
+        out.get_mut(..payload.len())?.copy_from_slice(payload);
+        Some(Received {
+            origin: header.origin,
+            dst: header.dst,
+            id: header.id,
+            hops: header.hops(),
+            relay: header.relay,
+            len: payload.len(),
+            rssi,
+            snr,
+        })
+    }
+
+    fn enqueue(&mut self, header: &Header, packet: &[u8], due: u64) {
+        let mut bytes = Vec::new();
+        let _ = bytes.extend_from_slice(packet);
+        // a full queue drops the rebroadcast, other nodes relay it
+        let _ = self.queue.push(Queued {
+            origin: header.origin,
+            id: header.id,
+            due,
+            packet: bytes,
+        });
+    }
+
+    /// Send the first due packet, or wait for the next one
+    fn service(&mut self, now: u64) {
+        if self.transmitting {
+            return;
+        }
+        let Some(next) = self.queue.iter().map(|q| q.due).min() else {
+            self.radio.stop_timer();
+            return;
+        };
+        let Some(i) = self.queue.iter().position(|q| q.due == next) else {
+            return;
+        };
+
+        let wait =
+            (next.saturating_sub(now)).max(self.radio.time_until_tx(self.queue[i].packet.len()));
+        if wait > 0 {
+            self.radio.start_timer(wait.min(u32::MAX as u64) as u32);
+            return;
+        }
+        let queued = self.queue.remove(i);
+        self.transmitting = true;
+        self.radio.transmit(&queued.packet);
+    }
+
+    fn has_seen(&self, origin: u16, id: u32) -> bool {
+        self.seen.iter().flatten().any(|&seen| seen == (origin, id))
+    }
+
+    fn remember(&mut self, origin: u16, id: u32) {
+        self.seen[self.next_seen] = Some((origin, id));
+        self.next_seen = (self.next_seen + 1) % MESH_SEEN;
+    }
+
+    /// Update the neighbour table, the oldest entry makes room when it is full
+    fn heard(&mut self, address: u16, rssi: i16, snr: i8, now: u64) {
+        if let Some(n) = self.neighbours.iter_mut().find(|n| n.address == address) {
+            n.rssi = rssi;
+            n.snr = snr;
+            n.last_heard = now;
+            n.packets = n.packets.saturating_add(1);
+            return;
+        }
+
+        let neighbour = Neighbour {
+            address,
+            rssi,
+            snr,
+            last_heard: now,
+            packets: 1,
+        };
+        if let Err(neighbour) = self.neighbours.push(neighbour)
+            && let Some(oldest) = self.neighbours.iter_mut().min_by_key(|n| n.last_heard)
+        {
+            *oldest = neighbour;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLOT_TIME: u32 = 10;
+
+    /// Simulated radio of one node, transmissions take no time
+    #[derive(Default)]
+    struct SimRadio {
+        now: u64,
+        air: Option<std::vec::Vec<u8>>,
+        transmissions: usize,
+        timer: Option<u64>,
+        /// next value of `random`, incremented on every call
+        random: u32,
+        random_calls: usize,
+    }
+
+    impl MeshRadio for SimRadio {
+        fn transmit(&mut self, packet: &[u8]) {
+            assert!(self.air.is_none(), "transmit while on the air");
+            self.air = Some(packet.to_vec());
+            self.transmissions += 1;
+        }
+
+        fn time_until_tx(&self, _len: usize) -> u64 {
+            0
+        }
+
+        fn start_timer(&mut self, ms: u32) {
+            self.timer = Some(self.now + ms as u64);
+        }
+
+        fn stop_timer(&mut self) {
+            self.timer = None;
+        }
+
+        fn random(&mut self) -> u32 {
+            self.random_calls += 1;
+            self.random = self.random.wrapping_add(1);
+            self.random - 1
+        }
+    }
+
+    /// Nodes and the SNR of the links between them, no collisions
+    struct SimNet {
+        now: u64,
+        nodes: std::vec::Vec<MeshNode<SimRadio>>,
+        links: std::vec::Vec<(usize, usize, i8)>,
+        received: std::vec::Vec<std::vec::Vec<(Received, std::vec::Vec<u8>)>>,
+    }
+
+    impl SimNet {
+        /// Nodes 1, 2, .. with `random` starting at the given values
+        fn new(randoms: &[u32], hop_limit: u8) -> Self {
+            let nodes = randoms
+                .iter()
+                .enumerate()
+                .map(|(i, &random)| {
+                    let radio = SimRadio {
+                        random,
+                        ..Default::default()
+                    };
+                    let config = MeshConfig {
+                        address: i as u16 + 1,
+                        hop_limit,
+                        slot_time: SLOT_TIME,
+                        neighbour_timeout: 60_000,
+                        relay: true,
+                    };
+                    MeshNode::new(radio, config).unwrap()
+                })
+                .collect();
+            Self {
+                now: 0,
+                nodes,
+                links: std::vec::Vec::new(),
+                received: randoms.iter().map(|_| std::vec::Vec::new()).collect(),
+            }
+        }
+
+        fn link(mut self, a: usize, b: usize, snr: i8) -> Self {
+            self.links.push((a, b, snr));
+            self
+        }
+
+        /// Nodes `0 - 1 - 2 - ..`
+        fn chain(randoms: &[u32], hop_limit: u8) -> Self {
+            let mut net = Self::new(randoms, hop_limit);
+            for i in 1..randoms.len() {
+                net = net.link(i - 1, i, 0);
+            }
+            net
+        }
+
+        fn send(&mut self, from: usize, dst: u16, payload: &[u8]) -> u32 {
+            let now = self.now;
+            self.nodes[from].radio().now = now;
+            self.nodes[from].send(dst, payload, now).unwrap()
+        }
+
+        fn neighbours_of(&self, node: usize) -> impl Iterator<Item = (usize, i8)> + '_ {
+            self.links.iter().filter_map(move |&(a, b, snr)| {
+                (a == node)
+                    .then_some((b, snr))
+                    .or((b == node).then_some((a, snr)))
+            })
+        }
+
+        /// Deliver every transmission and fire the timers until the network is quiet
+        fn run(&mut self) {
+            for _ in 0..1_000 {
+                if let Some(from) = self.nodes.iter_mut().position(|n| n.radio().air.is_some()) {
+                    let packet = self.nodes[from].radio().air.take().unwrap();
+                    let targets: std::vec::Vec<_> = self.neighbours_of(from).collect();
+                    for (to, snr) in targets {
+                        let now = self.now;
+                        let node = &mut self.nodes[to];
+                        node.radio().now = now;
+                        let mut out = [0u8; MAX_PACKET_SIZE];
+                        if let Some(rx) = node.on_rx(&packet, -60, snr, now, &mut out) {
+                            self.received[to].push((rx, out[..rx.len].to_vec()));
+                        }
+                    }
+                    let now = self.now;
+                    self.nodes[from].on_tx_done(now);
+                    continue;
+                }
+
+                let Some((i, due)) = self
+                    .nodes
+                    .iter_mut()
+                    .enumerate()
+                    .filter_map(|(i, n)| Some((i, n.radio().timer?)))
+                    .min_by_key(|&(_, due)| due)
+                else {
+                    return;
+                };
+                self.now = due;
+                let node = &mut self.nodes[i];
+                node.radio().timer = None;
+                node.radio().now = due;
+                node.on_timer(due);
+            }
+            panic!("network never quiet");
+        }
+
+        fn transmissions(&mut self, node: usize) -> usize {
+            self.nodes[node].radio().transmissions
+        }
+    }
+
+    #[test]
+    fn contention() {
+        assert_eq!(contention_window(-30), CW_MIN);
+        assert_eq!(contention_window(SNR_MIN), CW_MIN);
+        assert_eq!(contention_window(SNR_MAX), CW_MAX);
+        assert_eq!(contention_window(40), CW_MAX);
+        assert!(contention_window(-5) > CW_MIN && contention_window(-5) < CW_MAX);
+
+        assert_eq!(rebroadcast_delay(SNR_MIN, 10, 5), 10);
+        // wraps into the window
+        assert_eq!(rebroadcast_delay(SNR_MIN, 10, 7), 30);
+        assert_eq!(rebroadcast_delay(SNR_MAX, 10, 200), 72 * 10);
+    }
+
+    #[test]
+    fn random_drawn_on_first_send() {
+        let mut net = SimNet::chain(&[100, 0], 3);
+        assert_eq!(net.nodes[0].radio().random_calls, 0);
+        assert_eq!(net.send(0, 2, b"a"), 100);
+        assert_eq!(net.send(0, 2, b"b"), 101);
+        assert_eq!(net.nodes[0].radio().random_calls, 1);
+
+        let radio = SimRadio::default();
+        let config = MeshConfig {
+            address: ADDR_BROADCAST,
+            hop_limit: 3,
+            slot_time: SLOT_TIME,
+            neighbour_timeout: 1,
+            relay: true,
+        };
+        assert!(matches!(
+            MeshNode::new(radio, config),
+            Err(MeshError::InvalidAddress)
+        ));
+        assert_eq!(
+            net.nodes[0].send(1, b"me", 0),
+            Err(MeshError::InvalidAddress)
+        );
+    }
+
+    #[test]
+    fn flooding_across_chain() {
+        let mut net = SimNet::chain(&[0, 10, 20, 30], 3);
+        let id = net.send(0, 4, b"to the end");
+        net.run();
+
+        let (rx, payload) = &net.received[3][0];
+        assert_eq!(net.received[3].len(), 1);
+        assert_eq!(payload, b"to the end");
+        assert_eq!((rx.origin, rx.dst, rx.id), (1, 4, id));
+        assert_eq!(rx.hops, 2);
+        assert_eq!(rx.relay, 3);
+        // relays don't deliver packets for other nodes
+        assert!(net.received[1].is_empty() && net.received[2].is_empty());
+
+        // every node but the destination sends it once
+        assert_eq!(net.transmissions(0), 1);
+        assert_eq!(net.transmissions(1), 1);
+        assert_eq!(net.transmissions(2), 1);
+        assert_eq!(net.transmissions(3), 0);
+    }
+
+    #[test]
+    fn broadcast_reaches_everyone() {
+        let mut net = SimNet::chain(&[0, 10, 20, 30], 3);
+        net.send(0, ADDR_BROADCAST, b"all");
+        net.run();
+        assert!(net.received[0].is_empty(), "own packet delivered");
+        for (hops, received) in net.received[1..].iter().enumerate() {
+            assert_eq!(received.len(), 1);
+            assert_eq!(received[0].0.hops, hops as u8);
+        }
+        // the last node still has hop limit left, its copy is heard by nobody new
+        assert_eq!(net.transmissions(3), 1);
+    }
+
+    #[test]
+    fn hop_limit() {
+        let mut net = SimNet::chain(&[0, 10, 20, 30, 40], 2);
+        net.send(0, ADDR_BROADCAST, b"near");
+        net.run();
+        assert_eq!(net.received[1].len(), 1);
+        assert_eq!(net.received[2].len(), 1);
+        // hop limit 0 when it reaches node 3: delivered, not rebroadcast
+        assert_eq!(net.received[3].len(), 1);
+        assert_eq!(net.received[3][0].0.hops, 2);
+        assert_eq!(net.transmissions(3), 0);
+        assert!(net.received[4].is_empty());
+
+        // the origin caps the hop limit
+        let mut net = SimNet::chain(&[0, 10], 15);
+        net.send(0, ADDR_BROADCAST, b"x");
+        let packet = net.nodes[0].radio().air.clone().unwrap();
+        let header = decode(&packet).unwrap();
+        assert_eq!((header.hop_start, header.hop_limit), (MAX_HOPS, MAX_HOPS));
+    }
+
+    #[test]
+    fn duplicates_suppressed() {
+        // diamond: 0 - 1, 0 - 2, 1 - 3, 2 - 3, 1 and 2 can't hear each other
+        let mut net = SimNet::new(&[0, 10, 20, 30], 3)
+            .link(0, 1, 0)
+            .link(0, 2, 0)
+            .link(1, 3, 0)
+            .link(2, 3, 0);
+        net.send(0, ADDR_BROADCAST, b"once");
+        net.run();
+        for received in &net.received[1..] {
+            assert_eq!(received.len(), 1);
+        }
+        assert_eq!(net.transmissions(1), 1);
+        assert_eq!(net.transmissions(2), 1);
+        // node 3 queued the first copy and drops it on hearing the second, both its neighbours
+        // already have the packet
+        assert_eq!(net.transmissions(3), 0);
+    }
+
+    #[test]
+    fn overheard_rebroadcast_cancels_ours() {
+        // 1 and 2 both hear 0 and each other, 1 heard 0 badly and relays first
+        let mut net = SimNet::new(&[0, 1, 50], 3)
+            .link(0, 1, SNR_MIN)
+            .link(0, 2, SNR_MAX)
+            .link(1, 2, 0);
+        net.send(0, ADDR_BROADCAST, b"flood");
+        net.run();
+        assert_eq!(net.received[1].len(), 1);
+        assert_eq!(net.received[2].len(), 1);
+        assert_eq!(net.transmissions(1), 1);
+        assert_eq!(net.transmissions(2), 0, "cancelled rebroadcast was sent");
+        assert_eq!(net.nodes[2].radio().timer, None);
+    }
+
+    #[test]
+    fn not_relaying() {
+        let mut net = SimNet::chain(&[0, 10, 20], 3);
+        net.nodes[1].config.relay = false;
+        net.send(0, ADDR_BROADCAST, b"x");
+        net.run();
+        assert_eq!(net.received[1].len(), 1);
+        assert!(net.received[2].is_empty());
+    }
+
+    #[test]
+    fn neighbour_table() {
+        let mut net = SimNet::new(&[0, 10, 20], 0).link(0, 2, 5).link(1, 2, -3);
+        net.send(0, 3, b"a");
+        net.run();
+        net.now = 30_000;
+        net.send(1, 3, b"b");
+        net.run();
+
+        let node = &net.nodes[2];
+        let first = node.neighbour(1, 30_000).unwrap();
+        assert_eq!((first.snr, first.packets, first.last_heard), (5, 1, 0));
+        assert_eq!(node.neighbours(30_000).count(), 2);
+        // node 1 is forgotten after the timeout
+        assert!(node.neighbour(1, 60_000).is_none());
+        assert!(node.neighbour(2, 60_000).is_some());
+    }
+}
@@ -0,0 +1,179 @@
+/// Largest packet the radio carries
+pub const MAX_PACKET_SIZE: usize = 255;
+/// Flags, destination, origin, packet id, hops and relay
+pub const HEADER_SIZE: usize = 12;
+/// Largest payload of a packet
+pub const MAX_PAYLOAD_SIZE: usize = MAX_PACKET_SIZE - HEADER_SIZE;
+
+/// Destination of a packet for every node
+pub const ADDR_BROADCAST: u16 = 0xFFFF;
+/// Highest hop limit, fits the 4-bit fields
+pub const MAX_HOPS: u8 = 7;
+
+/// Packet format version, top 2 bits of the flags
+pub const VERSION: u8 = 0;
+const VERSION_MASK: u8 = 0xC0;
+
+/// Mesh packet parsing/building error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketError {
+    /// packet is shorter than its header
+    TooShort,
+    /// output buffer too small or payload longer than allowed
+    TooLong,
+    /// unknown packet format version
+    UnsupportedVersion,
+    /// hop limit above the hops the packet started with
+    InvalidHops,
+}
+
+/// Packet header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub flags: u8,
+    /// final destination
+    pub dst: u16,
+    /// node that created the packet
+    pub origin: u16,
+    /// picked by the origin, with `origin` identifies the packet across the mesh
+    pub id: u32,
+    /// hop limit the origin set
+    pub hop_start: u8,
+    /// rebroadcasts left
+    pub hop_limit: u8,
+    /// node that transmitted this copy
+    pub relay: u16,
+}
+
+impl Header {
+    /// Whether the packet goes to every node
+    pub fn is_broadcast(&self) -> bool {
+        self.dst == ADDR_BROADCAST
+    }
+
+    /// Rebroadcasts the packet went through
+    pub fn hops(&self) -> u8 {
+        self.hop_start - self.hop_limit
+    }
+}
+
+/// Build a packet into `buf`, returns its length.
+///
+/// Layout, multi-byte values little endian: flags (1), destination (2), origin (2), id (4),
+/// hop start << 4 | hop limit (1), relay (2), payload.
+pub fn encode(header: &Header, payload: &[u8], buf: &mut [u8]) -> Result<usize, PacketError> {
+    let len = HEADER_SIZE + payload.len();
+    if len > buf.len().min(MAX_PACKET_SIZE) {
+        return Err(PacketError::TooLong);
+    }
+    if header.hop_limit > header.hop_start || header.hop_start > MAX_HOPS {
+        return Err(PacketError::InvalidHops);
+    }
+
+    buf[0] = (header.flags & !VERSION_MASK) | (VERSION << 6);
+    buf[1..3].copy_from_slice(&header.dst.to_le_bytes());
+    buf[3..5].copy_from_slice(&header.origin.to_le_bytes());
+    buf[5..9].copy_from_slice(&header.id.to_le_bytes());
+    buf[9] = (header.hop_start << 4) | header.hop_limit;
+    buf[10..12].copy_from_slice(&header.relay.to_le_bytes());
+    buf[HEADER_SIZE..len].copy_from_slice(payload);
+    Ok(len)
+}
+
+/// Parse the header of `packet`, the payload follows at [`HEADER_SIZE`]
+pub fn decode(packet: &[u8]) -> Result<Header, PacketError> {
+    if packet.len() < HEADER_SIZE {
+        return Err(PacketError::TooShort);
+    }
+    if packet.len() > MAX_PACKET_SIZE {
+        return Err(PacketError::TooLong);
+    }
+    if (packet[0] & VERSION_MASK) >> 6 != VERSION {
+        return Err(PacketError::UnsupportedVersion);
+    }
+
+    let header = Header {
+        flags: packet[0] & !VERSION_MASK,
+        dst: u16::from_le_bytes([packet[1], packet[2]]),
+        origin: u16::from_le_bytes([packet[3], packet[4]]),
+        id: u32::from_le_bytes([packet[5], packet[6], packet[7], packet[8]]),
+        hop_start: packet[9] >> 4,
+        hop_limit: packet[9] & 0x0F,
+        relay: u16::from_le_bytes([packet[10], packet[11]]),
+    };
+    if header.hop_limit > header.hop_start || header.hop_start > MAX_HOPS {
+        return Err(PacketError::InvalidHops);
+    }
+    Ok(header)
+}
+
+/// Rewrite the hop limit and relay of an encoded packet for its rebroadcast
+pub fn set_relay(packet: &mut [u8], hop_limit: u8, relay: u16) {
+    packet[9] = (packet[9] & 0xF0) | (hop_limit & 0x0F);
+    packet[10..12].copy_from_slice(&relay.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: Header = Header {
+        flags: 0x01,
+        dst: ADDR_BROADCAST,
+        origin: 0x0102,
+        id: 0x0A0B_0C0D,
+        hop_start: 3,
+        hop_limit: 3,
+        relay: 0x0102,
+    };
+
+    #[test]
+    fn round_trip() {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let len = encode(&HEADER, b"data", &mut buf).unwrap();
+        assert_eq!(
+            &buf[..len],
+            b"\x01\xFF\xFF\x02\x01\x0D\x0C\x0B\x0A\x33\x02\x01data"
+        );
+        assert_eq!(decode(&buf[..len]), Ok(HEADER));
+        assert!(HEADER.is_broadcast());
+        assert_eq!(HEADER.hops(), 0);
+
+        set_relay(&mut buf[..len], 1, 0x0304);
+        let relayed = decode(&buf[..len]).unwrap();
+        assert_eq!((relayed.hop_limit, relayed.relay), (1, 0x0304));
+        assert_eq!(relayed.hops(), 2);
+        assert_eq!(&buf[HEADER_SIZE..len], b"data");
+    }
+
+    #[test]
+    fn errors() {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        assert_eq!(
+            encode(&HEADER, &[0; MAX_PAYLOAD_SIZE + 1], &mut buf),
+            Err(PacketError::TooLong)
+        );
+        assert!(encode(&HEADER, &[0; MAX_PAYLOAD_SIZE], &mut buf).is_ok());
+        let hops = Header {
+            hop_limit: 4,
+            ..HEADER
+        };
+        assert_eq!(encode(&hops, &[], &mut buf), Err(PacketError::InvalidHops));
+        let hops = Header {
+            hop_start: MAX_HOPS + 1,
+            hop_limit: 0,
+            ..HEADER
+        };
+        assert_eq!(encode(&hops, &[], &mut buf), Err(PacketError::InvalidHops));
+
+        let len = encode(&HEADER, &[], &mut buf).unwrap();
+        assert_eq!(decode(&buf[..len - 1]), Err(PacketError::TooShort));
+        assert_eq!(decode(&[0; MAX_PACKET_SIZE + 1]), Err(PacketError::TooLong));
+        let mut bad = buf;
+        bad[0] |= 0x80;
+        assert_eq!(decode(&bad[..len]), Err(PacketError::UnsupportedVersion));
+        bad = buf;
+        bad[9] = 0x24;
+        assert_eq!(decode(&bad[..len]), Err(PacketError::InvalidHops));
+    }
+}
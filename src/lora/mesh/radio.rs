@@ -0,0 +1,202 @@
+use crate::lora::{
+    airtime::airtime,
+    mesh::{MeshConfig, MeshError, MeshNode, MeshRadio, Received, packet::MAX_PACKET_SIZE},
+    radio::{
+        RadioEvents, RadioModem, radio_init, radio_rx, radio_send, radio_set_channel,
+        radio_set_rx_config, radio_set_tx_config, radio_standby, radio_time_on_air,
+    },
+    timer::{
+        TimerEvent, timer_get_current_time, timer_init, timer_set_value, timer_start, timer_stop,
+    },
+};
+use crate::rng::rng_u32;
+
+/// Transmission timeout (ms)
+const TX_TIMEOUT: usize = 3_000;
+
+/// Radio settings of a mesh, shared by every node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshRadioConfig {
+    /// frequency (Hz)
+    pub frequency: u32,
+    /// spreading factor
+    pub sf: u8,
+    /// bandwidth index, 0 = 125 kHz, 1 = 250 kHz, 2 = 500 kHz
+    pub bw: u8,
+    /// coding rate, 1 = 4/5 .. 4 = 4/8
+    pub coding_rate: u8,
+    /// output power (dBm)
+    pub power: i8,
+    /// preamble length (symbols)
+    pub preamble: u16,
+    /// duty cycle band of the frequency in the [`airtime`] ledger, `None` outside the bands
+    pub band: Option<u8>,
+}
+
+/// [`MeshRadio`] on the LoRa radio driver, the LoRa timer and the RNG service
+pub struct LoRaPort {
+    band: Option<u8>,
+    /// time on air (ms) of the packet being sent
+    tx_time_on_air: u32,
+}
+
+impl MeshRadio for LoRaPort {
+    fn transmit(&mut self, packet: &[u8]) {
+        self.tx_time_on_air = radio_time_on_air(RadioModem::LoRa, packet.len() as u8) as u32;
+        radio_standby();
+        radio_send(packet);
+    }
+
+    fn time_until_tx(&self, len: usize) -> u64 {
+        let time_on_air = radio_time_on_air(RadioModem::LoRa, len as u8) as u32;
+        airtime()
+            .time_until_tx(timer_get_current_time(), self.band, time_on_air)
+            .unwrap_or(u64::MAX)
+    }
+
+    fn start_timer(&mut self, ms: u32) {
+        let timer = unsafe { &mut MESH_TIMER };
+        timer_stop(timer);
+        timer_set_value(timer, ms as usize);
+        timer_start(timer);
+    }
+
+    fn stop_timer(&mut self) {
+        timer_stop(unsafe { &mut MESH_TIMER });
+    }
+
+    fn random(&mut self) -> u32 {
+        rng_u32()
+    }
+}
+
+struct Mesh {
+    node: Option<MeshNode<LoRaPort>>,
+    on_rx: Option<fn(packet: &Received, payload: &[u8])>,
+}
+
+static mut MESH: Mesh = Mesh {
+    node: None,
+    on_rx: None,
+};
+
+static mut MESH_TIMER: TimerEvent = TimerEvent {
+    id: 0,
+    timestamp: 0,
+    reload_value: 0,
+    is_running: false,
+    callback: None,
+};
+
+static MESH_EVENTS: RadioEvents = RadioEvents {
+    tx_done: Some(on_tx_done),
+    tx_timeout: Some(on_tx_done),
+    rx_done: Some(on_rx_done),
+    rx_timeout: Some(listen),
+    rx_error: Some(listen),
+    rx_crc_error: None,
+    fhss_change_channel: None,
+    cad_done: None,
+};
+
+/// Join the mesh, `on_rx` gets the packets for this node and the broadcasts.
+///
+/// Takes over the radio events like the MAC does, the radio listens whenever it doesn't
+/// send.
+pub fn mesh_init(
+    radio: &MeshRadioConfig,
+    config: MeshConfig,
+    on_rx: fn(packet: &Received, payload: &[u8]),
+) -> Result<(), MeshError> {
+    let port = LoRaPort {
+        band: radio.band,
+        tx_time_on_air: 0,
+    };
+    let node = MeshNode::new(port, config)?;
+    let mesh = unsafe { &mut MESH };
+    mesh.node = Some(node);
+    mesh.on_rx = Some(on_rx);
+
+    radio_init(&MESH_EVENTS);
+    radio_set_channel(radio.frequency as usize);
+    radio_set_tx_config(
+        RadioModem::LoRa,
+        radio.power,
+        0,
+        radio.bw as usize,
+        radio.sf as usize,
+        radio.coding_rate,
+        radio.preamble,
+        false,
+        true,
+        false,
+        0,
+        false,
+        TX_TIMEOUT,
+    );
+    radio_set_rx_config(
+        RadioModem::LoRa,
+        radio.bw as usize,
+        radio.sf as usize,
+        radio.coding_rate,
+        0,
+        radio.preamble,
+        0,
+        false,
+        0,
+        true,
+        false,
+        0,
+        false,
+        true,
+    );
+
+    timer_init(unsafe { &mut MESH_TIMER }, on_timer);
+    listen();
+    Ok(())
+}
+
+/// Send `payload` to `dst` across the mesh, see [`MeshNode::send`]
+pub fn mesh_send(dst: u16, payload: &[u8]) -> Result<u32, MeshError> {
+    let node = mesh_node().ok_or(MeshError::Busy)?;
+    node.send(dst, payload, timer_get_current_time())
+}
+
+/// Node of [`mesh_init`]
+pub fn mesh_node() -> Option<&'static mut MeshNode<LoRaPort>> {
+    unsafe { MESH.node.as_mut() }
+}
+
+/// Back to continuous reception
+fn listen() {
+    radio_rx(0);
+}
+
+fn on_tx_done() {
+    let Some(node) = mesh_node() else {
+        return;
+    };
+    let now = timer_get_current_time();
+    let port = node.radio();
+    airtime().record(now, port.band, port.tx_time_on_air);
+
+    listen();
+    node.on_tx_done(now);
+}
+
+fn on_rx_done(payload: &[u8], rssi: i16, snr: i8) {
+    let Some(node) = mesh_node() else {
+        return;
+    };
+    let mut out = [0u8; MAX_PACKET_SIZE];
+    let received = node.on_rx(payload, rssi, snr, timer_get_current_time(), &mut out);
+    if let (Some(packet), Some(on_rx)) = (received, unsafe { MESH.on_rx }) {
+        on_rx(&packet, &out[..packet.len]);
+    }
+}
+
+fn on_timer() {
+    if let Some(node) = mesh_node() {
+        node.on_timer(timer_get_current_time());
+    }
+}
@@ -1,6 +1,24 @@
+/// Duty cycle and airtime budget ledger
+pub mod airtime;
 /// LoRa main drivers
 pub mod driver;
+/// Listen before talk
+pub mod lbt;
+/// Radio statistics and link quality
+pub mod link_quality;
+/// LoRaWAN frame parser/builder
+pub mod lorawan;
+/// LoRaWAN Class A/C MAC layer
+pub mod mac;
+/// Managed flooding mesh network layer
+pub mod mesh;
+/// Point-to-point LoRa link layer
+pub mod p2p;
 /// LoRa radio drivers
 pub mod radio;
+/// LoRaWAN regional parameters
+pub mod region;
 /// LoRa timer
 pub mod timer;
+/// CAD / RX duty cycle wake-on-radio receiver
+pub mod wor;
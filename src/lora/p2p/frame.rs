@@ -0,0 +1,364 @@
+use crate::crypto::{BLOCK_SIZE, Block, BlockCipher, aes::Aes128, cmac::cmac, ctr_apply};
+
+/// Largest frame the radio carries
+pub const MAX_FRAME_SIZE: usize = 255;
+/// Flags, destination, source and sequence number (low 16 bits)
+pub const HEADER_SIZE: usize = 7;
+/// High 16 bits of the sequence number, secured frames only
+pub const SEQ_EXT_SIZE: usize = 2;
+/// Truncated CMAC of secured frames
+pub const MIC_SIZE: usize = 4;
+/// Largest payload of a secured frame, plain frames take [`SEQ_EXT_SIZE`] + [`MIC_SIZE`] more
+pub const MAX_PAYLOAD_SIZE: usize = MAX_FRAME_SIZE - HEADER_SIZE - SEQ_EXT_SIZE - MIC_SIZE;
+
+/// Destination of a frame for every node, never acknowledged
+pub const ADDR_BROADCAST: u16 = 0xFFFF;
+
+/// Frame format version, top 2 bits of the flags
+pub const VERSION: u8 = 0;
+const VERSION_MASK: u8 = 0xC0;
+
+/// The sender wants an acknowledgement
+pub const FLAG_ACK_REQ: u8 = 0x01;
+/// Acknowledgement of the frame with the same sequence number
+pub const FLAG_ACK: u8 = 0x02;
+/// Payload encrypted (AES-CTR) and frame authenticated (AES-CMAC)
+pub const FLAG_SECURED: u8 = 0x04;
+
+/// P2P frame parsing/building error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// frame is shorter than its header requires
+    TooShort,
+    /// output buffer too small or payload longer than allowed
+    TooLong,
+    /// unknown frame format version
+    UnsupportedVersion,
+    /// secured frame without keys
+    NoKeys,
+    /// MIC check failed
+    InvalidMic,
+}
+
+/// Frame header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub flags: u8,
+    pub dst: u16,
+    pub src: u16,
+    /// only the low 16 bits travel in plain frames
+    pub seq: u32,
+}
+
+impl Header {
+    /// Whether the sender waits for an acknowledgement
+    pub fn ack_requested(&self) -> bool {
+        self.flags & FLAG_ACK_REQ != 0
+    }
+
+    /// Whether this frame acknowledges another one
+    pub fn is_ack(&self) -> bool {
+        self.flags & FLAG_ACK != 0
+    }
+
+    /// Whether the frame is encrypted and authenticated
+    pub fn is_secured(&self) -> bool {
+        self.flags & FLAG_SECURED != 0
+    }
+
+    /// Whether the frame goes to every node
+    pub fn is_broadcast(&self) -> bool {
+        self.dst == ADDR_BROADCAST
+    }
+
+    fn len(&self) -> usize {
+        HEADER_SIZE + if self.is_secured() { SEQ_EXT_SIZE } else { 0 }
+    }
+}
+
+/// Payload encryption and MIC keys shared by the nodes of a network
+#[derive(Clone)]
+pub struct P2pKeys {
+    enc: Aes128,
+    mac: Aes128,
+}
+
+impl P2pKeys {
+    pub fn new(enc_key: &Block, mac_key: &Block) -> Self {
+        Self {
+            enc: Aes128::new(enc_key),
+            mac: Aes128::new(mac_key),
+        }
+    }
+
+    /// Both keys from a single network key: `aes128_encrypt(key, 0x01 | 0x00..)` and
+    /// `aes128_encrypt(key, 0x02 | 0x00..)`
+    pub fn derive(network_key: &Block) -> Self {
+        let mut cipher = Aes128::new(network_key);
+        let mut enc_key = [0u8; BLOCK_SIZE];
+        let mut mac_key = [0u8; BLOCK_SIZE];
+        enc_key[0] = 0x01;
+        mac_key[0] = 0x02;
+        cipher.encrypt_block(&mut enc_key);
+        cipher.encrypt_block(&mut mac_key);
+        let keys = Self::new(&enc_key, &mac_key);
+        for byte in enc_key.iter_mut().chain(mac_key.iter_mut()) {
+            unsafe { core::ptr::write_volatile(byte, 0) };
+        }
+        keys
+    }
+
+    /// Counter block: 0x01 | dst | src | seq | 0x00.., unique per sender and sequence number
+    fn counter(header: &Header) -> Block {
+        let mut counter = [0u8; BLOCK_SIZE];
+        counter[0] = 0x01;
+        counter[1..3].copy_from_slice(&header.dst.to_le_bytes());
+        counter[3..5].copy_from_slice(&header.src.to_le_bytes());
+        counter[5..9].copy_from_slice(&header.seq.to_le_bytes());
+        counter
+    }
+
+    fn mic(&mut self, authenticated: &[u8]) -> [u8; MIC_SIZE] {
+        let full = cmac(&mut self.mac, &[authenticated]);
+        [full[0], full[1], full[2], full[3]]
+    }
+}
+
+/// Build a frame into `buf`, returns its length.
+///
+/// Secured frames ([`FLAG_SECURED`]) need `keys`.
+pub fn encode(
+    header: &Header,
+    payload: &[u8],
+    keys: Option<&mut P2pKeys>,
+    buf: &mut [u8],
+) -> Result<usize, FrameError> {
+    let header_len = header.len();
+    let len = header_len + payload.len() + if header.is_secured() { MIC_SIZE } else { 0 };
+    if len > buf.len().min(MAX_FRAME_SIZE) {
+        return Err(FrameError::TooLong);
+    }
+
+    buf[0] = (header.flags & !VERSION_MASK) | (VERSION << 6);
+    buf[1..3].copy_from_slice(&header.dst.to_le_bytes());
+    buf[3..5].copy_from_slice(&header.src.to_le_bytes());
+    buf[5..7].copy_from_slice(&(header.seq as u16).to_le_bytes());
+    buf[header_len..header_len + payload.len()].copy_from_slice(payload);
+
+    if header.is_secured() {
+        let keys = keys.ok_or(FrameError::NoKeys)?;
+        buf[HEADER_SIZE..header_len].copy_from_slice(&((header.seq >> 16) as u16).to_le_bytes());
+
+        let mut counter = P2pKeys::counter(header);
+        ctr_apply(
+            &mut keys.enc,
+            &mut counter,
+            &mut buf[header_len..header_len + payload.len()],
+        );
+        let mic = keys.mic(&buf[..len - MIC_SIZE]);
+        buf[len - MIC_SIZE..len].copy_from_slice(&mic);
+    }
+
+    Ok(len)
+}
+
+/// Parse the header of `frame` without checking the MIC
+pub fn parse_header(frame: &[u8]) -> Result<Header, FrameError> {
+    if frame.len() < HEADER_SIZE {
+        return Err(FrameError::TooShort);
+    }
+    if (frame[0] & VERSION_MASK) >> 6 != VERSION {
+        return Err(FrameError::UnsupportedVersion);
+    }
+
+    let mut header = Header {
+        flags: frame[0] & !VERSION_MASK,
+        dst: u16::from_le_bytes([frame[1], frame[2]]),
+        src: u16::from_le_bytes([frame[3], frame[4]]),
+        seq: u16::from_le_bytes([frame[5], frame[6]]) as u32,
+    };
+    if header.is_secured() {
+        if frame.len() < HEADER_SIZE + SEQ_EXT_SIZE + MIC_SIZE {
+            return Err(FrameError::TooShort);
+        }
+        header.seq |= (u16::from_le_bytes([frame[7], frame[8]]) as u32) << 16;
+    }
+    Ok(header)
+}
+
+/// Check and decrypt `frame` into `out`, returns the header and the payload length
+pub fn decode(
+    frame: &[u8],
+    keys: Option<&mut P2pKeys>,
+    out: &mut [u8],
+) -> Result<(Header, usize), FrameError> {
+    let header = parse_header(frame)?;
+    let header_len = header.len();
+
+    if !header.is_secured() {
+        let payload = &frame[header_len..];
+        let out = out.get_mut(..payload.len()).ok_or(FrameError::TooLong)?;
+        out.copy_from_slice(payload);
+        return Ok((header, payload.len()));
+    }
+
+    let keys = keys.ok_or(FrameError::NoKeys)?;
+    let (authenticated, mic) = frame.split_at(frame.len() - MIC_SIZE);
+    // compare without an early exit
+    let diff = keys
+        .mic(authenticated)
+        .iter()
+        .zip(mic)
+        .fold(0, |acc, (a, b)| acc | (a ^ b));
+    if diff != 0 {
+        return Err(FrameError::InvalidMic);
+    }
+
+    let payload = &authenticated[header_len..];
+    let out = out.get_mut(..payload.len()).ok_or(FrameError::TooLong)?;
+    out.copy_from_slice(payload);
+    let mut counter = P2pKeys::counter(&header);
+    ctr_apply(&mut keys.enc, &mut counter, out);
+    Ok((header, payload.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENC_KEY: Block = [0x11; 16];
+    const MAC_KEY: Block = [0x22; 16];
+
+    fn header(flags: u8, seq: u32) -> Header {
+        Header {
+            flags,
+            dst: 0x0201,
+            src: 0x0403,
+            seq,
+        }
+    }
+
+    #[test]
+    fn plain_round_trip() {
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        let len = encode(&header(FLAG_ACK_REQ, 0x0001_0605), b"abc", None, &mut buf).unwrap();
+        assert_eq!(&buf[..len], b"\x01\x01\x02\x03\x04\x05\x06abc");
+
+        let mut out = [0u8; 8];
+        let (parsed, n) = decode(&buf[..len], None, &mut out).unwrap();
+        // only the low 16 bits of the sequence number travel
+        assert_eq!(parsed, header(FLAG_ACK_REQ, 0x0605));
+        assert!(parsed.ack_requested() && !parsed.is_ack() && !parsed.is_secured());
+        assert_eq!(&out[..n], b"abc");
+    }
+
+    #[test]
+    fn secured_round_trip() {
+        let mut keys = P2pKeys::new(&ENC_KEY, &MAC_KEY);
+        let h = header(FLAG_SECURED, 0x1234_5678);
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        let payload = [0xA5u8; 40];
+        let len = encode(&h, &payload, Some(&mut keys), &mut buf).unwrap();
+        assert_eq!(len, HEADER_SIZE + SEQ_EXT_SIZE + payload.len() + MIC_SIZE);
+        assert_eq!(&buf[7..9], &[0x34, 0x12]);
+        assert_ne!(&buf[9..49], &payload[..]);
+
+        let mut out = [0u8; MAX_PAYLOAD_SIZE];
+        let (parsed, n) = decode(&buf[..len], Some(&mut keys), &mut out).unwrap();
+        assert_eq!(parsed, h);
+        assert_eq!(&out[..n], &payload[..]);
+
+        // another sequence number gives another key stream
+        let mut other = [0u8; MAX_FRAME_SIZE];
+        encode(
+            &header(FLAG_SECURED, 0x1234_5679),
+            &payload,
+            Some(&mut keys),
+            &mut other,
+        )
+        .unwrap();
+        assert_ne!(&other[9..49], &buf[9..49]);
+
+        // every byte is authenticated
+        for i in 0..len {
+            let mut tampered = buf;
+            tampered[i] ^= 0x80;
+            assert!(decode(&tampered[..len], Some(&mut keys), &mut out).is_err());
+        }
+        assert_eq!(decode(&buf[..len], None, &mut out), Err(FrameError::NoKeys));
+        let mut wrong = P2pKeys::new(&ENC_KEY, &ENC_KEY);
+        assert_eq!(
+            decode(&buf[..len], Some(&mut wrong), &mut out),
+            Err(FrameError::InvalidMic)
+        );
+    }
+
+    #[test]
+    fn derived_keys() {
+        let network_key = [0x2B; 16];
+        let mut derived = P2pKeys::derive(&network_key);
+
+        let mut cipher = Aes128::new(&network_key);
+        let (mut enc_key, mut mac_key) = ([0u8; 16], [0u8; 16]);
+        enc_key[0] = 0x01;
+        mac_key[0] = 0x02;
+        cipher.encrypt_block(&mut enc_key);
+        cipher.encrypt_block(&mut mac_key);
+        let mut explicit = P2pKeys::new(&enc_key, &mac_key);
+
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        let len = encode(&header(FLAG_SECURED, 7), b"x", Some(&mut derived), &mut buf).unwrap();
+        let mut out = [0u8; 1];
+        assert!(decode(&buf[..len], Some(&mut explicit), &mut out).is_ok());
+        assert_eq!(&out, b"x");
+    }
+
+    #[test]
+    fn errors() {
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        assert_eq!(
+            encode(&header(FLAG_SECURED, 0), b"x", None, &mut buf),
+            Err(FrameError::NoKeys)
+        );
+        let mut keys = P2pKeys::new(&ENC_KEY, &MAC_KEY);
+        let too_long = [0u8; MAX_PAYLOAD_SIZE + 1];
+        assert_eq!(
+            encode(
+                &header(FLAG_SECURED, 0),
+                &too_long,
+                Some(&mut keys),
+                &mut buf
+            ),
+            Err(FrameError::TooLong)
+        );
+        assert!(
+            encode(
+                &header(FLAG_SECURED, 0),
+                &too_long[1..],
+                Some(&mut keys),
+                &mut buf
+            )
+            .is_ok()
+        );
+        assert_eq!(
+            encode(&header(0, 0), b"abc", None, &mut buf[..9]),
+            Err(FrameError::TooLong)
+        );
+
+        assert_eq!(parse_header(&[0; 6]), Err(FrameError::TooShort));
+        assert_eq!(
+            parse_header(&[FLAG_SECURED, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Err(FrameError::TooShort)
+        );
+        assert_eq!(
+            parse_header(&[0x40, 0, 0, 0, 0, 0, 0]),
+            Err(FrameError::UnsupportedVersion)
+        );
+
+        let len = encode(&header(0, 0), b"abcd", None, &mut buf).unwrap();
+        assert_eq!(
+            decode(&buf[..len], None, &mut [0u8; 3]),
+            Err(FrameError::TooLong)
+        );
+    }
+}
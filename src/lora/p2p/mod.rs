@@ -0,0 +1,653 @@
+use heapless::Vec;
+
+use crate::lora::p2p::frame::{
+    ADDR_BROADCAST, FLAG_ACK, FLAG_ACK_REQ, FLAG_SECURED, FrameError, HEADER_SIZE, Header,
+    MAX_FRAME_SIZE, MIC_SIZE, P2pKeys, SEQ_EXT_SIZE, decode, encode,
+};
+
+/// P2P frame format and payload protection
+pub mod frame;
+/// P2P link on the LoRa radio driver
+pub mod radio;
+
+/// Peers remembered for duplicate and replay suppression.
+///
+/// The oldest entry makes room for a new peer. An evicted peer is unknown again, so its
+/// next secured frame is accepted whatever its sequence number: a recorded frame of it can be
+/// replayed once. Networks with more senders than this need a larger table, or an
+/// application level check (timestamps) on top.
+pub const P2P_PEERS: usize = 8;
+
+/// What the link needs from the radio, implemented on the real driver by [`radio`] and by a
+/// simulated channel on the host
+pub trait P2pRadio {
+    /// Transmit `frame`, [`P2pLink::on_tx_done`] follows
+    fn transmit(&mut self, frame: &[u8]);
+    /// Time (ms) before `len` bytes may be sent (duty cycle), 0 when free
+    fn time_until_tx(&self, len: usize) -> u64;
+    /// (Re)start the single link timer, [`P2pLink::on_timeout`] follows after `ms`
+    fn start_timer(&mut self, ms: u32);
+    /// Stop the link timer
+    fn stop_timer(&mut self);
+}
+
+/// Link settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct P2pConfig {
+    /// own address, [`ADDR_BROADCAST`] is not allowed
+    pub address: u16,
+    /// time to wait for an ACK after the end of a transmission (ms)
+    pub ack_timeout: u32,
+    /// transmissions after the first one when no ACK comes back
+    pub max_retries: u8,
+}
+
+/// P2P request error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum P2pError {
+    /// a frame is still being sent or acknowledged
+    Busy,
+    /// the duty cycle blocks the channel for this many ms
+    DutyCycle(u64),
+    /// own address or destination not usable
+    InvalidAddress,
+    /// frame could not be built
+    Frame(FrameError),
+}
+
+impl From<FrameError> for P2pError {
+    fn from(error: FrameError) -> Self {
+        P2pError::Frame(error)
+    }
+}
+
+/// Outcome reported by the link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum P2pEvent {
+    /// unacknowledged frame or broadcast sent
+    Sent { dst: u16, seq: u32 },
+    /// the destination acknowledged frame `seq`
+    Delivered { dst: u16, seq: u32 },
+    /// no ACK after every retry
+    Failed { dst: u16, seq: u32 },
+    /// new frame for us (or broadcast), its payload is in the `on_rx` buffer
+    Received {
+        src: u16,
+        dst: u16,
+        seq: u32,
+        len: usize,
+        rssi: i16,
+        snr: i8,
+    },
+}
+
+/// Frame on the air
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transmitting {
+    Data,
+    Ack,
+}
+
+/// Last sequence number seen from a peer
+#[derive(Debug, Clone, Copy)]
+struct Peer {
+    address: u16,
+    seq: u32,
+}
+
+/// Addressed P2P link: sequence numbers, ACKs with retries, duplicate suppression and
+/// optional payload protection.
+///
+/// Pure state machine, the radio events are passed in and [`P2pEvent`]s come out.
+pub struct P2pLink<R: P2pRadio> {
+    radio: R,
+    config: P2pConfig,
+    keys: Option<P2pKeys>,
+    seq: u32,
+    /// header and bytes of the frame being sent or waiting for its ACK
+    pending: Option<Header>,
+    tx: Vec<u8, MAX_FRAME_SIZE>,
+    retries_left: u8,
+    transmitting: Option<Transmitting>,
+    ack: [u8; HEADER_SIZE + SEQ_EXT_SIZE + MIC_SIZE],
+    peers: [Option<Peer>; P2P_PEERS],
+    next_peer: usize,
+}
+
+impl<R: P2pRadio> P2pLink<R> {
+    /// Link over `radio`, frames are secured when `keys` are given
+    pub fn new(radio: R, config: P2pConfig, keys: Option<P2pKeys>) -> Result<Self, P2pError> {
+        if config.address == ADDR_BROADCAST {
+            return Err(P2pError::InvalidAddress);
+        }
+        Ok(Self {
+            radio,
+            config,
+            keys,
+            seq: 0,
+            pending: None,
+            tx: Vec::new(),
+            retries_left: 0,
+            transmitting: None,
+            ack: [0; HEADER_SIZE + SEQ_EXT_SIZE + MIC_SIZE],
+            peers: [None; P2P_PEERS],
+            next_peer: 0,
+        })
+    }
+
+    pub fn radio(&mut self) -> &mut R {
+        &mut self.radio
+    }
+
+    /// Next sequence number
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    /// Restore the sequence number after a reset, peers drop secured frames with numbers
+    /// they have already seen
+    pub fn set_seq(&mut self, seq: u32) {
+        self.seq = seq;
+    }
+
+    /// A frame is being sent or waits for its ACK
+    pub fn is_busy(&self) -> bool {
+        self.pending.is_some() || self.transmitting.is_some()
+    }
+
+    /// Send `payload` to `dst`, returns its sequence number.
+    ///
+    /// `confirmed` frames are repeated until acknowledged, broadcasts never are.
+    pub fn send(&mut self, dst: u16, payload: &[u8], confirmed: bool) -> Result<u32, P2pError> {
+        if self.is_busy() {
+            return Err(P2pError::Busy);
+        }
+        if dst == self.config.address {
+            return Err(P2pError::InvalidAddress);
+        }
+
+        let mut flags = 0;
+        if confirmed && dst != ADDR_BROADCAST {
+            flags |= FLAG_ACK_REQ;
+        }
+        if self.keys.is_some() {
+            flags |= FLAG_SECURED;
+        }
+        let header = Header {
+            flags,
+            dst,
+            src: self.config.address,
+            seq: self.seq,
+        };
+
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        let len = encode(&header, payload, self.keys.as_mut(), &mut buf)?;
+        let wait = self.radio.time_until_tx(len);
+        if wait > 0 {
+            return Err(P2pError::DutyCycle(wait));
+        }
+
+        self.tx.clear();
+        let _ = self.tx.extend_from_slice(&buf[..len]);
+        self.seq = self.seq.wrapping_add(1);
+        self.pending = Some(header);
+        self.retries_left = self.config.max_retries;
+        self.transmitting = Some(Transmitting::Data);
+        self.radio.transmit(&self.tx);
+        Ok(header.seq)
+    }
+
+    /// The radio finished a transmission
+    pub fn on_tx_done(&mut self) -> Option<P2pEvent> {
+        if self.transmitting.take()? != Transmitting::Data {
+            return None;
+        }
+        let header = self.pending?;
+        if header.ack_requested() {
+            self.radio.start_timer(self.config.ack_timeout);
+            None
+        } else {
+            self.pending = None;
+            Some(P2pEvent::Sent {
+                dst: header.dst,
+                seq: header.seq,
+            })
+        }
+    }
+
+    /// The link timer expired: retry or give up
+    pub fn on_timeout(&mut self) -> Option<P2pEvent> {
+        let header = self.pending?;
+        if self.transmitting.is_some() {
+            // an ACK of ours is on the air, retry once it is out
+            self.radio.start_timer(self.config.ack_timeout);
+            return None;
+        }
+        if self.retries_left == 0 {
+            self.pending = None;
+            return Some(P2pEvent::Failed {
+                dst: header.dst,
+                seq: header.seq,
+            });
+        }
+
+        let wait = self.radio.time_until_tx(self.tx.len());
+        if wait > 0 {
+            self.radio.start_timer(wait.min(u32::MAX as u64) as u32);
+            return None;
+        }
+        self.retries_left -= 1;
+        self.transmitting = Some(Transmitting::Data);
+        self.radio.transmit(&self.tx);
+        None
+    }
+
+    /// A frame was received, its payload goes to `out`
+    pub fn on_rx(&mut self, frame: &[u8], rssi: i16, snr: i8, out: &mut [u8]) -> Option<P2pEvent> {
+        let (header, len) = decode(frame, self.keys.as_mut(), out).ok()?;
+        // plain frames are ignored once keys are set
+        if self.keys.is_some() && !header.is_secured() {
+            return None;
+        }
+        if header.dst != self.config.address && !header.is_broadcast() {
+            return None;
+        }
+
+        if header.is_ack() {
+            let pending = self.pending?;
+            if !pending.ack_requested() || header.src != pending.dst || header.seq != pending.seq {
+                return None;
+            }
+            self.radio.stop_timer();
+            self.pending = None;
+            return Some(P2pEvent::Delivered {
+                dst: pending.dst,
+                seq: pending.seq,
+            });
+        }
+
+        // replayed or stale frames are dropped
+        let fresh = self.check_peer(&header)?;
+        if header.ack_requested() && !header.is_broadcast() {
+            // duplicates are acknowledged again, the first ACK may have been lost
+            self.send_ack(&header);
+        }
+
+        fresh.then_some(P2pEvent::Received {
+            src: header.src,
+            dst: header.dst,
+            seq: header.seq,
+            len,
+            rssi,
+            snr,
+        })
+    }
+
+    /// `Some(true)` for a new frame, `Some(false)` for a repeat of the last one, `None` for
+    /// an older secured frame
+    fn check_peer(&mut self, header: &Header) -> Option<bool> {
+        let peer = self
+            .peers
+            .iter_mut()
+            .flatten()
+            .find(|peer| peer.address == header.src);
+
+        let Some(peer) = peer else {
+            self.peers[self.next_peer] = Some(Peer {
+                address: header.src,
+                seq: header.seq,
+            });
+            self.next_peer = (self.next_peer + 1) % P2P_PEERS;
+            return Some(true);
+        };
+
+        if header.seq == peer.seq {
+            return Some(false);
+        }
+        if header.is_secured() && header.seq < peer.seq {
+            return None;
+        }
+        peer.seq = header.seq;
+        Some(true)
+    }
+
+    fn send_ack(&mut self, header: &Header) {
+        if self.transmitting.is_some() {
+            // the sender will retry
+            return;
+        }
+        let ack = Header {
+            flags: FLAG_ACK | (header.flags & FLAG_SECURED),
+            dst: header.src,
+            src: self.config.address,
+            seq: header.seq,
+        };
+        let Ok(len) = encode(&ack, &[], self.keys.as_mut(), &mut self.ack) else {
+            return;
+        };
+        if self.radio.time_until_tx(len) > 0 {
+            return;
+        }
+        self.transmitting = Some(Transmitting::Ack);
+        self.radio.transmit(&self.ack[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simulated radio: transmitted frames stay on the air until a test delivers or drops them
+    #[derive(Default)]
+    struct SimRadio {
+        air: Option<std::vec::Vec<u8>>,
+        transmissions: usize,
+        /// duty cycle wait reported for every frame
+        busy_for: u64,
+        timer: Option<u32>,
+    }
+
+    impl P2pRadio for SimRadio {
+        fn transmit(&mut self, frame: &[u8]) {
+            assert!(self.air.is_none(), "transmit while on the air");
+            self.air = Some(frame.to_vec());
+            self.transmissions += 1;
+        }
+
+        fn time_until_tx(&self, _len: usize) -> u64 {
+            self.busy_for
+        }
+
+        fn start_timer(&mut self, ms: u32) {
+            self.timer = Some(ms);
+        }
+
+        fn stop_timer(&mut self) {
+            self.timer = None;
+        }
+    }
+
+    const A: u16 = 0x0001;
+    const B: u16 = 0x0002;
+    const ACK_TIMEOUT: u32 = 1_000;
+    const NETWORK_KEY: [u8; 16] = [
+        0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F,
+        0x3C,
+    ];
+
+    fn node(address: u16, secured: bool) -> P2pLink<SimRadio> {
+        let config = P2pConfig {
+            address,
+            ack_timeout: ACK_TIMEOUT,
+            max_retries: 2,
+        };
+        let keys = secured.then(|| P2pKeys::derive(&NETWORK_KEY));
+        P2pLink::new(SimRadio::default(), config, keys).unwrap()
+    }
+
+    /// End the transmission of `from`, returns its event and the frame sent
+    fn take_air(from: &mut P2pLink<SimRadio>) -> (Option<P2pEvent>, std::vec::Vec<u8>) {
+        let frame = from.radio().air.take().expect("nothing on the air");
+        (from.on_tx_done(), frame)
+    }
+
+    fn receive(to: &mut P2pLink<SimRadio>, frame: &[u8]) -> Option<(P2pEvent, std::vec::Vec<u8>)> {
+        let mut out = [0u8; MAX_FRAME_SIZE];
+        let event = to.on_rx(frame, -40, 9, &mut out)?;
+        let len = match event {
+            P2pEvent::Received { len, .. } => len,
+            _ => 0,
+        };
+        Some((event, out[..len].to_vec()))
+    }
+
+    /// Carry the frame on the air of `from` to `to`
+    fn deliver(
+        from: &mut P2pLink<SimRadio>,
+        to: &mut P2pLink<SimRadio>,
+    ) -> (Option<P2pEvent>, Option<(P2pEvent, std::vec::Vec<u8>)>) {
+        let (sent, frame) = take_air(from);
+        (sent, receive(to, &frame))
+    }
+
+    #[test]
+    fn unconfirmed() {
+        let (mut a, mut b) = (node(A, false), node(B, false));
+        assert_eq!(a.send(B, b"hello", false), Ok(0));
+        assert!(a.is_busy());
+        assert_eq!(a.send(B, b"again", false), Err(P2pError::Busy));
+
+        let (sent, received) = deliver(&mut a, &mut b);
+        assert_eq!(sent, Some(P2pEvent::Sent { dst: B, seq: 0 }));
+        assert!(!a.is_busy());
+        let (event, payload) = received.unwrap();
+        assert_eq!(
+            event,
+            P2pEvent::Received {
+                src: A,
+                dst: B,
+                seq: 0,
+                len: 5,
+                rssi: -40,
+                snr: 9
+            }
+        );
+        assert_eq!(payload, b"hello");
+        // no ACK for unconfirmed frames
+        assert!(b.radio().air.is_none());
+        assert_eq!(a.seq(), 1);
+    }
+
+    #[test]
+    fn addresses() {
+        let config = P2pConfig {
+            address: ADDR_BROADCAST,
+            ack_timeout: ACK_TIMEOUT,
+            max_retries: 0,
+        };
+        assert!(matches!(
+            P2pLink::new(SimRadio::default(), config, None),
+            Err(P2pError::InvalidAddress)
+        ));
+
+        let (mut a, mut b, mut c) = (node(A, false), node(B, false), node(0x0003, false));
+        assert_eq!(a.send(A, b"me", false), Err(P2pError::InvalidAddress));
+
+        // frames for another node are ignored
+        a.send(B, b"to b", true).unwrap();
+        let (_, frame) = take_air(&mut a);
+        assert!(receive(&mut c, &frame).is_none());
+        assert!(c.radio().air.is_none());
+
+        // broadcasts reach everyone and are never acknowledged
+        let mut a = node(A, false);
+        a.send(ADDR_BROADCAST, b"all", true).unwrap();
+        let (sent, frame) = take_air(&mut a);
+        assert_eq!(
+            sent,
+            Some(P2pEvent::Sent {
+                dst: ADDR_BROADCAST,
+                seq: 0
+            })
+        );
+        assert!(receive(&mut b, &frame).is_some());
+        assert!(receive(&mut c, &frame).is_some());
+        assert!(b.radio().air.is_none());
+    }
+
+    #[test]
+    fn acknowledged() {
+        let (mut a, mut b) = (node(A, false), node(B, false));
+        let seq = a.send(B, b"ping", true).unwrap();
+
+        let (sent, received) = deliver(&mut a, &mut b);
+        assert_eq!(sent, None);
+        assert_eq!(a.radio().timer, Some(ACK_TIMEOUT));
+        assert!(matches!(received, Some((P2pEvent::Received { .. }, _))));
+
+        let (ack_sent, ack) = deliver(&mut b, &mut a);
+        assert_eq!(ack_sent, None);
+        assert_eq!(ack.unwrap().0, P2pEvent::Delivered { dst: B, seq });
+        assert_eq!(a.radio().timer, None);
+        assert!(!a.is_busy());
+        assert_eq!(a.radio().transmissions, 1);
+    }
+
+    #[test]
+    fn retry_after_lost_frame() {
+        let (mut a, mut b) = (node(A, false), node(B, false));
+        let seq = a.send(B, b"ping", true).unwrap();
+        // lost
+        take_air(&mut a);
+        assert_eq!(a.on_timeout(), None);
+        assert_eq!(a.radio().transmissions, 2);
+
+        let (_, received) = deliver(&mut a, &mut b);
+        assert!(matches!(received, Some((P2pEvent::Received { .. }, _))));
+        let (_, ack) = deliver(&mut b, &mut a);
+        assert_eq!(ack.unwrap().0, P2pEvent::Delivered { dst: B, seq });
+    }
+
+    #[test]
+    fn lost_ack_is_resent_without_duplicate() {
+        let (mut a, mut b) = (node(A, false), node(B, false));
+        let seq = a.send(B, b"ping", true).unwrap();
+        let (_, received) = deliver(&mut a, &mut b);
+        assert!(received.is_some());
+        // ACK lost
+        take_air(&mut b);
+
+        a.on_timeout();
+        let (_, received) = deliver(&mut a, &mut b);
+        assert!(received.is_none(), "duplicate reported");
+        // acknowledged again
+        let (_, ack) = deliver(&mut b, &mut a);
+        assert_eq!(ack.unwrap().0, P2pEvent::Delivered { dst: B, seq });
+    }
+
+    #[test]
+    fn gives_up_after_retries() {
+        let mut a = node(A, false);
+        let seq = a.send(B, b"ping", true).unwrap();
+        for _ in 0..2 {
+            take_air(&mut a);
+            assert_eq!(a.on_timeout(), None);
+        }
+        take_air(&mut a);
+        assert_eq!(a.on_timeout(), Some(P2pEvent::Failed { dst: B, seq }));
+        assert_eq!(a.radio().transmissions, 3);
+        assert!(!a.is_busy());
+        // a late timeout does nothing
+        assert_eq!(a.on_timeout(), None);
+    }
+
+    #[test]
+    fn stale_ack_ignored() {
+        let (mut a, mut b) = (node(A, false), node(B, false));
+        a.send(B, b"one", true).unwrap();
+        deliver(&mut a, &mut b);
+        let (_, ack) = take_air(&mut b);
+        // ACK of another sequence number
+        let mut other = ack.clone();
+        other[5] ^= 1;
+        assert!(receive(&mut a, &other).is_none());
+        assert!(a.is_busy());
+        assert!(receive(&mut a, &ack).is_some());
+    }
+
+    #[test]
+    fn duty_cycle() {
+        let mut a = node(A, false);
+        a.radio().busy_for = 500;
+        assert_eq!(a.send(B, b"ping", true), Err(P2pError::DutyCycle(500)));
+        assert!(!a.is_busy());
+
+        a.radio().busy_for = 0;
+        a.send(B, b"ping", true).unwrap();
+        take_air(&mut a);
+        // the retry waits for the channel
+        a.radio().busy_for = 700;
+        assert_eq!(a.on_timeout(), None);
+        assert_eq!(a.radio().timer, Some(700));
+        assert!(a.radio().air.is_none());
+        a.radio().busy_for = 0;
+        a.on_timeout();
+        assert_eq!(a.radio().transmissions, 2);
+    }
+
+    #[test]
+    fn secured_round_trip() {
+        let (mut a, mut b) = (node(A, true), node(B, true));
+        a.set_seq(0x0001_0002);
+        a.send(B, b"secret payload", true).unwrap();
+        let (_, frame) = take_air(&mut a);
+        assert_eq!(frame.len(), HEADER_SIZE + SEQ_EXT_SIZE + 14 + MIC_SIZE);
+        assert!(!frame.windows(6).any(|w| w == b"secret"));
+
+        // tampered frames are dropped without an ACK
+        let mut tampered = frame.clone();
+        tampered[HEADER_SIZE + SEQ_EXT_SIZE] ^= 1;
+        assert!(receive(&mut b, &tampered).is_none());
+        assert!(b.radio().air.is_none());
+
+        let (event, payload) = receive(&mut b, &frame).unwrap();
+        assert!(matches!(
+            event,
+            P2pEvent::Received {
+                seq: 0x0001_0002,
+                ..
+            }
+        ));
+        assert_eq!(payload, b"secret payload");
+        let (_, ack) = deliver(&mut b, &mut a);
+        assert!(matches!(ack, Some((P2pEvent::Delivered { .. }, _))));
+
+        // another network key, or no key at all
+        let mut plain = node(B, false);
+        assert!(receive(&mut plain, &frame).is_none());
+        let mut other = P2pLink::new(
+            SimRadio::default(),
+            P2pConfig {
+                address: B,
+                ack_timeout: ACK_TIMEOUT,
+                max_retries: 0,
+            },
+            Some(P2pKeys::derive(&[0; 16])),
+        )
+        .unwrap();
+        assert!(receive(&mut other, &frame).is_none());
+
+        // plain frames are ignored once keys are set
+        let mut sender = node(A, false);
+        sender.send(B, b"plain", false).unwrap();
+        let (_, frame) = take_air(&mut sender);
+        assert!(receive(&mut b, &frame).is_none());
+    }
+
+    #[test]
+    fn replays() {
+        let (mut a, mut b) = (node(A, true), node(B, true));
+        let mut recorded = std::vec::Vec::new();
+        for _ in 0..2 {
+            a.send(B, b"open", false).unwrap();
+            let (_, frame) = take_air(&mut a);
+            assert!(receive(&mut b, &frame).is_some());
+            recorded.push(frame);
+        }
+        // older and repeated frames are dropped while A is remembered
+        assert!(receive(&mut b, &recorded[0]).is_none());
+        assert!(receive(&mut b, &recorded[1]).is_none());
+
+        // P2P_PEERS other senders evict A
+        for address in 0x100..0x100 + P2P_PEERS as u16 {
+            let mut peer = node(address, true);
+            peer.send(B, b"hi", false).unwrap();
+            let (_, frame) = take_air(&mut peer);
+            assert!(receive(&mut b, &frame).is_some());
+        }
+        // documented limit of the table: the first replay of an evicted peer gets through
+        assert!(receive(&mut b, &recorded[0]).is_some());
+        assert!(receive(&mut b, &recorded[0]).is_none());
+        assert!(receive(&mut b, &recorded[1]).is_some());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::lora::{
+    airtime::airtime,
+    p2p::{
+        P2pConfig, P2pError, P2pEvent, P2pLink, P2pRadio, frame::MAX_FRAME_SIZE, frame::P2pKeys,
+    },
+    radio::{
+        RadioEvents, RadioModem, radio_init, radio_rx, radio_send, radio_set_channel,
+        radio_set_rx_config, radio_set_tx_config, radio_standby, radio_time_on_air,
+    },
+    timer::{
+        TimerEvent, timer_get_current_time, timer_init, timer_set_value, timer_start, timer_stop,
+    },
+};
+use crate::rng::rng_range;
+
+/// Random time (ms) added to the ACK timeout so two senders drift apart
+const ACK_JITTER: i32 = 200;
+/// Transmission timeout (ms)
+const TX_TIMEOUT: usize = 3_000;
+
+/// Radio settings of a P2P link, shared by every node of the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct P2pRadioConfig {
+    /// frequency (Hz)
+    pub frequency: u32,
+    /// spreading factor
+    pub sf: u8,
+    /// bandwidth index, 0 = 125 kHz, 1 = 250 kHz, 2 = 500 kHz
+    pub bw: u8,
+    /// coding rate, 1 = 4/5 .. 4 = 4/8
+    pub coding_rate: u8,
+    /// output power (dBm)
+    pub power: i8,
+    /// preamble length (symbols), [`crate::lora::wor::WorConfig::preamble_symbols`] to reach
+    /// wake-on-radio receivers
+    pub preamble: u16,
+    /// duty cycle band of the frequency in the [`airtime`] ledger, `None` outside the bands
+    pub band: Option<u8>,
+}
+
+/// [`P2pRadio`] on the LoRa radio driver and the LoRa timer
+pub struct LoRaPort {
+    band: Option<u8>,
+    /// time on air (ms) of the frame being sent
+    tx_time_on_air: u32,
+}
+
+impl P2pRadio for LoRaPort {
+    fn transmit(&mut self, frame: &[u8]) {
+        self.tx_time_on_air = radio_time_on_air(RadioModem::LoRa, frame.len() as u8) as u32;
+        radio_standby();
+        radio_send(frame);
+    }
+
+    fn time_until_tx(&self, len: usize) -> u64 {
+        let time_on_air = radio_time_on_air(RadioModem::LoRa, len as u8) as u32;
+        airtime()
+            .time_until_tx(timer_get_current_time(), self.band, time_on_air)
+            .unwrap_or(u64::MAX)
+    }
+
+    fn start_timer(&mut self, ms: u32) {
+        let timer = unsafe { &mut P2P_TIMER };
+        timer_stop(timer);
+        timer_set_value(
+            timer,
+            ms.saturating_add(rng_range(0, ACK_JITTER) as u32) as usize,
+        );
+        timer_start(timer);
+    }
+
+    fn stop_timer(&mut self) {
+        timer_stop(unsafe { &mut P2P_TIMER });
+    }
+}
+
+struct P2p {
+    link: Option<P2pLink<LoRaPort>>,
+    on_event: Option<fn(event: &P2pEvent, payload: &[u8])>,
+}
+
+static mut P2P: P2p = P2p {
+    link: None,
+    on_event: None,
+};
+
+static mut P2P_TIMER: TimerEvent = TimerEvent {
+    id: 0,
+    timestamp: 0,
+    reload_value: 0,
+    is_running: false,
+    callback: None,
+};
+
+static P2P_EVENTS: RadioEvents = RadioEvents {
+    tx_done: Some(on_tx_done),
+    tx_timeout: Some(on_tx_done),
+    rx_done: Some(on_rx_done),
+    rx_timeout: Some(listen),
+    rx_error: Some(listen),
+    rx_crc_error: None,
+    fhss_change_channel: None,
+    cad_done: None,
+};
+
+/// Start a P2P link on the radio, `on_event` gets the link events, with the payload of
+/// received frames.
+///
+/// Takes over the radio events like the MAC does, the radio listens whenever it doesn't
+/// send.
+pub fn p2p_init(
+    radio: &P2pRadioConfig,
+    config: P2pConfig,
+    keys: Option<P2pKeys>,
+    on_event: fn(event: &P2pEvent, payload: &[u8]),
+) -> Result<(), P2pError> {
+    let port = LoRaPort {
+        band: radio.band,
+        tx_time_on_air: 0,
+    };
+    let link = P2pLink::new(port, config, keys)?;
+    let p2p = unsafe { &mut P2P };
+    p2p.link = Some(link);
+    p2p.on_event = Some(on_event);
+
+    radio_init(&P2P_EVENTS);
+    radio_set_channel(radio.frequency as usize);
+    radio_set_tx_config(
+        RadioModem::LoRa,
+        radio.power,
+        0,
+        radio.bw as usize,
+        radio.sf as usize,
+        radio.coding_rate,
+        radio.preamble,
+        false,
+        true,
+        false,
+        0,
+        false,
+        TX_TIMEOUT,
+    );
+    radio_set_rx_config(
+        RadioModem::LoRa,
+        radio.bw as usize,
+        radio.sf as usize,
+        radio.coding_rate,
+        0,
+        radio.preamble,
+        0,
+        false,
+        0,
+        true,
+        false,
+        0,
+        false,
+        true,
+    );
+
+    timer_init(unsafe { &mut P2P_TIMER }, on_timer);
+    listen();
+    Ok(())
+}
+
+/// Send `payload` to `dst`, see [`P2pLink::send`]
+pub fn p2p_send(dst: u16, payload: &[u8], confirmed: bool) -> Result<u32, P2pError> {
+    let link = unsafe { P2P.link.as_mut() }.ok_or(P2pError::Busy)?;
+    link.send(dst, payload, confirmed)
+}
+
+/// Link of [`p2p_init`]
+pub fn p2p_link() -> Option<&'static mut P2pLink<LoRaPort>> {
+    unsafe { P2P.link.as_mut() }
+}
+
+fn dispatch(event: Option<P2pEvent>, payload: &[u8]) {
+    if let (Some(event), Some(on_event)) = (event, unsafe { P2P.on_event }) {
+        on_event(&event, payload);
+    }
+}
+
+/// Back to continuous reception
+fn listen() {
+    radio_rx(0);
+}
+
+fn on_tx_done() {
+    let Some(link) = p2p_link() else {
+        return;
+    };
+    let port = link.radio();
+    airtime().record(timer_get_current_time(), port.band, port.tx_time_on_air);
+
+    let event = link.on_tx_done();
+    listen();
+    dispatch(event, &[]);
+}
+
+fn on_rx_done(payload: &[u8], rssi: i16, snr: i8) {
+    let Some(link) = p2p_link() else {
+        return;
+    };
+    let mut out = [0u8; MAX_FRAME_SIZE];
+    let event = link.on_rx(payload, rssi, snr, &mut out);
+    let len = match event {
+        Some(P2pEvent::Received { len, .. }) => len,
+        _ => 0,
+    };
+    dispatch(event, &out[..len]);
+}
+
+fn on_timer() {
+    let Some(link) = p2p_link() else {
+        return;
+    };
+    let event = link.on_timeout();
+    dispatch(event, &[]);
+}
@@ -5,6 +5,7 @@ use crate::{
             sx126x_get_board_tcxo_wakeup_time, sx126x_read_register, sx126x_set_rf_tx_power,
             sx126x_write_registers,
         },
+        link_quality::{LinkEvent, LinkRate, link_record},
         radio::sx126x::{
             GfskModulationParams, GfskPacketParams, LORA_MAC_PRIVATE_SYNCWORD,
             LORA_MAC_PUBLIC_SYNCWORD, LoRaModulationParams, LoRaPacketParams, LoRaPacketStatus,
@@ -15,22 +16,23 @@ use crate::{
             RadioModShapings, RadioOperatingModes, RadioPacketLengthModes, RadioPacketTypes,
             RadioPreambleDetection, RadioRampTimes, RadioRegulatorMode, RadioStandbyModes,
             SleepParams, Sx126x, sx126x_clear_irq_status, sx126x_get_irq_status,
-            sx126x_get_operating_mode, sx126x_get_packet_status, sx126x_get_payload,
-            sx126x_get_rssi_inst, sx126x_init, sx126x_send_payload, sx126x_set_buffer_base_address,
-            sx126x_set_cad, sx126x_set_cad_params, sx126x_set_dio_irq_params,
-            sx126x_set_lora_symb_num_timeout, sx126x_set_modulation_params,
-            sx126x_set_operating_mode, sx126x_set_packet_params, sx126x_set_packet_type,
-            sx126x_set_regulator_mode, sx126x_set_rf_frequency, sx126x_set_rx,
-            sx126x_set_rx_boosted, sx126x_set_rx_duty_cycle, sx126x_set_sleep, sx126x_set_standby,
-            sx126x_set_stop_rx_timer_on_preamble_detect, sx126x_set_sync_word, sx126x_set_tx,
-            sx126x_set_tx_continuous_wave, sx126x_set_tx_params, sx126x_set_whitening_seed,
+            sx126x_get_operating_mode, sx126x_get_packet_status, sx126x_get_packet_type,
+            sx126x_get_payload, sx126x_get_rssi_inst, sx126x_init, sx126x_send_payload,
+            sx126x_set_buffer_base_address, sx126x_set_cad, sx126x_set_cad_params,
+            sx126x_set_dio_irq_params, sx126x_set_lora_symb_num_timeout,
+            sx126x_set_modulation_params, sx126x_set_operating_mode, sx126x_set_packet_params,
+            sx126x_set_packet_type, sx126x_set_regulator_mode, sx126x_set_rf_frequency,
+            sx126x_set_rx, sx126x_set_rx_boosted, sx126x_set_rx_duty_cycle, sx126x_set_sleep,
+            sx126x_set_standby, sx126x_set_stop_rx_timer_on_preamble_detect, sx126x_set_sync_word,
+            sx126x_set_tx, sx126x_set_tx_continuous_wave, sx126x_set_tx_params,
+            sx126x_set_whitening_seed,
         },
         timer::{
             TimerEvent, timer_get_current_time, timer_get_elapsed_time, timer_init,
             timer_set_value, timer_start, timer_stop,
         },
     },
-    peripherals::delay::delay_ms,
+    peripherals::{delay::delay_ms, micros::micros},
 };
 
 pub mod sx126x;
@@ -60,6 +62,8 @@ pub struct RadioEvents {
     pub rx_done: Option<fn(payload: &[u8], rssi: i16, snr: i8)>,
     pub rx_timeout: Option<fn()>,
     pub rx_error: Option<fn()>,
+    /// Called with the corrupted payload when a frame fails its CRC check, before `rx_error`
+    pub rx_crc_error: Option<fn(payload: &[u8], rssi: i16, snr: i8)>,
     pub fhss_change_channel: Option<fn(current_channel: u8)>,
     pub cad_done: Option<fn(channel_activity_detected: bool)>,
 }
@@ -127,6 +131,7 @@ static mut C_RADIO_EVENTS_RUST: RadioEvents = RadioEvents {
     rx_done: None,
     rx_timeout: None,
     rx_error: None,
+    rx_crc_error: None,
     fhss_change_channel: None,
     cad_done: None,
 };
@@ -169,6 +174,7 @@ unsafe fn store_c_events(events: *mut RadioEvents_t) {
             rx_error: e
                 .RxError
                 .map(|f| core::mem::transmute::<extern "C" fn(), fn()>(f)),
+            rx_crc_error: None,
             fhss_change_channel: e
                 .FhssChangeChannel
                 .map(|f| core::mem::transmute::<extern "C" fn(u8), fn(u8)>(f)),
@@ -349,7 +355,7 @@ const FSK_BANDWIDTHS: &[FskBandwidth] = &[
 ];
 
 /// LoRa bandwidth index-to-enum mapping, matching the C `Bandwidths[]` array
-const BANDWIDTHS: &[RadioLoRaBandwidths] = &[
+pub const BANDWIDTHS: &[RadioLoRaBandwidths] = &[
     RadioLoRaBandwidths::Bw125,
     RadioLoRaBandwidths::Bw250,
     RadioLoRaBandwidths::Bw500,
@@ -368,9 +374,13 @@ static mut RX_TIMEOUT: usize = 0;
 static mut RX_CONTINUOUS: bool = false;
 
 static mut RADIO_PKT_STATUS: Option<PacketStatus> = None;
+/// Last frequency given to `radio_set_channel`, for the link statistics
+static mut RADIO_CHANNEL: usize = 0;
 
 static mut IRQ_FIRED: bool = false;
 static mut IRQ_REGS: u16 = 0;
+/// [`micros`] when DIO1 last fired
+static mut IRQ_MICROS: u32 = 0;
 
 static mut RADIO_PUBLIC_NETWORK_PREVIOUS: bool = false;
 static mut RADIO_PUBLIC_NETWORK_CURRENT: bool = false;
@@ -395,6 +405,9 @@ static mut RX_TIMEOUT_TIMER: TimerEvent = TimerEvent {
     callback: None,
 };
 
+/// Longest CAD (ms) before giving up on the `CadDone` interrupt
+const CAD_TIMEOUT: usize = 2000;
+
 static mut CAD_TIMEOUT_TIMER: TimerEvent = TimerEvent {
     id: 0,
     timestamp: 0,
@@ -403,27 +416,6 @@ static mut CAD_TIMEOUT_TIMER: TimerEvent = TimerEvent {
     callback: None,
 };
 
-static mut RNG_NEXT: usize = 1;
-
-pub fn srand1(seed: usize) {
-    unsafe { RNG_NEXT = seed };
-}
-
-pub fn rand1() -> i32 {
-    const RAND_LOCAL_MAX: u64 = 2_147_483_647;
-    unsafe {
-        RNG_NEXT = ((RNG_NEXT as u64)
-            .wrapping_mul(1_103_515_245)
-            .wrapping_add(12345)
-            % RAND_LOCAL_MAX) as usize;
-        RNG_NEXT as i32
-    }
-}
-
-pub fn randr(min: i32, max: i32) -> i32 {
-    rand1() % (max - min + 1) + min
-}
-
 fn sx126x_state() -> &'static mut Sx126x {
     unsafe { SX126X.as_mut().expect("radio not initialised") }
 }
@@ -543,6 +535,20 @@ pub fn radio_set_modem(modem: RadioModem) {
 /// Sets the channel frequency.
 pub fn radio_set_channel(freq: usize) {
     sx126x_set_rf_frequency(freq);
+    unsafe { RADIO_CHANNEL = freq };
+}
+
+/// Frequency of the last `radio_set_channel`.
+pub fn radio_get_channel() -> usize {
+    unsafe { RADIO_CHANNEL }
+}
+
+/// Modem selected by the last `radio_set_modem`.
+pub fn radio_get_modem() -> RadioModem {
+    match sx126x_get_packet_type() {
+        RadioPacketTypes::Gfsk => RadioModem::Fsk,
+        _ => RadioModem::LoRa,
+    }
 }
 
 /// Checks if the channel is free for the given time.
@@ -570,7 +576,9 @@ pub fn radio_is_channel_free(
     true
 }
 
-/// Generates a 32-bit random value based on RSSI readings.
+/// Generates a 32-bit random value from the RSSI noise LSBs.
+///
+/// Raw noise, feed it to [`crate::rng::rng_add_entropy`] rather than using it directly.
 pub fn radio_random() -> usize {
     radio_set_modem(RadioModem::LoRa);
     sx126x_set_rx(0);
@@ -580,7 +588,6 @@ pub fn radio_random() -> usize {
         delay_ms(1);
         rnd |= ((sx126x_get_rssi_inst() as usize) & 0x01) << i;
     }
-    rnd = rnd.wrapping_add(rand1() as usize);
     radio_sleep();
     rnd
 }
@@ -1064,7 +1071,19 @@ pub fn radio_rx_boosted(timeout: usize) {
 }
 
 /// Sets the Rx duty cycle management parameters.
+///
+/// Both times are in steps of 15.625 µs, the radio wakes the MCU through the usual RX events.
 pub fn radio_set_rx_duty_cycle(rx_time: usize, sleep_time: usize) {
+    let irq_flags = RadioIrqMasks::RxDone as u16
+        | RadioIrqMasks::CrcError as u16
+        | RadioIrqMasks::HeaderError as u16
+        | RadioIrqMasks::RxTxTimeout as u16;
+    sx126x_set_dio_irq_params(
+        irq_flags,
+        irq_flags,
+        RadioIrqMasks::None as u16,
+        RadioIrqMasks::None as u16,
+    );
     sx126x_set_rx_duty_cycle(rx_time, sleep_time);
 }
 
@@ -1107,10 +1126,38 @@ pub fn radio_start_cad(symbols: u8) {
 
     sx126x_set_cad();
 
-    timer_set_value(unsafe { &mut CAD_TIMEOUT_TIMER }, 2000);
+    timer_set_value(unsafe { &mut CAD_TIMEOUT_TIMER }, CAD_TIMEOUT);
     timer_start(unsafe { &mut CAD_TIMEOUT_TIMER });
 }
 
+/// Runs a Channel Activity Detection and waits for it, without the `cad_done` event.
+///
+/// Returns true when a LoRa preamble was detected.
+pub fn radio_cad_detect(symbols: u8) -> bool {
+    radio_start_cad(symbols);
+
+    let start = timer_get_current_time();
+    let mut detected = false;
+    while timer_get_elapsed_time(start) < CAD_TIMEOUT as u64 {
+        if unsafe { core::ptr::read_volatile(&raw const IRQ_FIRED) } {
+            _disable_irq();
+            let irq = unsafe { IRQ_REGS };
+            if irq & RadioIrqMasks::CadDone as u16 != 0 {
+                unsafe { IRQ_FIRED = false };
+            }
+            _enable_irq();
+            if irq & RadioIrqMasks::CadDone as u16 != 0 {
+                detected = irq & RadioIrqMasks::CadActivityDetected as u16 != 0;
+                break;
+            }
+        }
+    }
+
+    timer_stop(unsafe { &mut CAD_TIMEOUT_TIMER });
+    sx126x_set_operating_mode(RadioOperatingModes::StdbyRc);
+    detected
+}
+
 /// Transmit with a raw timeout (timeout << 6).
 pub fn radio_tx(timeout: usize) {
     sx126x_set_tx(timeout << 6);
@@ -1201,6 +1248,17 @@ pub fn radio_set_public_network(enable: bool) {
     }
 }
 
+/// Returns the status (RSSI, SNR, frequency error) of the last received packet.
+pub fn radio_get_packet_status() -> Option<PacketStatus> {
+    unsafe { RADIO_PKT_STATUS }
+}
+
+/// [`micros`] when the radio last interrupted, at the end of the last received packet in
+/// `rx_done`. Only meaningful once `micros_init` has started the clock.
+pub fn radio_get_irq_micros() -> u32 {
+    unsafe { IRQ_MICROS }
+}
+
 /// Gets the time required for the board + radio to get out of sleep (ms).
 pub fn radio_get_wakeup_time() -> usize {
     sx126x_get_board_tcxo_wakeup_time() + RADIO_WAKEUP_TIME
@@ -1218,11 +1276,19 @@ macro_rules! dispatch_event {
     };
 }
 
+/// Count `event` on the current channel and datarate
+fn record_link_event(event: LinkEvent) {
+    let rate = LinkRate::from_modulation(&sx126x_state().modulation_params);
+    link_record(unsafe { RADIO_CHANNEL } as u32, rate, event);
+}
+
 fn radio_on_tx_timeout_irq() {
+    record_link_event(LinkEvent::TxTimeout);
     dispatch_event!(tx_timeout);
 }
 
 fn radio_on_rx_timeout_irq() {
+    record_link_event(LinkEvent::RxTimeout);
     dispatch_event!(rx_timeout);
 }
 
@@ -1234,6 +1300,7 @@ fn radio_on_cad_timeout_irq() {
 /// Called from the DIO1 interrupt handler.
 pub fn radio_on_dio_irq() {
     unsafe {
+        IRQ_MICROS = micros();
         IRQ_FIRED = true;
         IRQ_REGS = sx126x_get_irq_status();
     }
@@ -1256,6 +1323,7 @@ pub fn radio_irq_process() {
     if irq & RadioIrqMasks::TxDone as u16 != 0 {
         timer_stop(unsafe { &mut TX_TIMEOUT_TIMER });
         sx126x_set_operating_mode(RadioOperatingModes::StdbyRc);
+        record_link_event(LinkEvent::TxDone);
         dispatch_event!(tx_done);
     }
 
@@ -1275,15 +1343,13 @@ pub fn radio_irq_process() {
         let pkt_status = sx126x_get_packet_status();
         unsafe { RADIO_PKT_STATUS = Some(pkt_status) };
 
-        if irq & RadioIrqMasks::CrcError as u16 == 0
-            && let Some(events) = unsafe { RADIO_EVENTS }
-            && let Some(cb) = events.rx_done
-        {
-            let (rssi, snr) = match &pkt_status {
-                PacketStatus::LoRa(ls) => (ls.rssi_pkt as i16 + ls.snr_pkt as i16, ls.snr_pkt),
-                PacketStatus::Gfsk(gs) => (gs.rssi_avg as i16, 0),
-            };
-            (cb)(&rx_buf[..size], rssi, snr);
+        let (rssi, snr) = pkt_status.rssi_snr();
+        if irq & RadioIrqMasks::CrcError as u16 == 0 {
+            record_link_event(LinkEvent::RxOk { rssi, snr });
+            dispatch_event!(rx_done, &rx_buf[..size], rssi, snr);
+        } else {
+            record_link_event(LinkEvent::CrcError { rssi, snr });
+            dispatch_event!(rx_crc_error, &rx_buf[..size], rssi, snr);
         }
     }
 
@@ -1311,11 +1377,13 @@ pub fn radio_irq_process() {
             RadioOperatingModes::Tx => {
                 timer_stop(unsafe { &mut TX_TIMEOUT_TIMER });
                 sx126x_set_operating_mode(RadioOperatingModes::StdbyRc);
+                record_link_event(LinkEvent::TxTimeout);
                 dispatch_event!(tx_timeout);
             }
             RadioOperatingModes::Rx => {
                 timer_stop(unsafe { &mut RX_TIMEOUT_TIMER });
                 sx126x_set_operating_mode(RadioOperatingModes::StdbyRc);
+                record_link_event(LinkEvent::RxTimeout);
                 dispatch_event!(rx_timeout);
             }
             _ => {}
@@ -1328,6 +1396,7 @@ pub fn radio_irq_process() {
         if !unsafe { RX_CONTINUOUS } {
             sx126x_set_operating_mode(RadioOperatingModes::StdbyRc);
         }
+        record_link_event(LinkEvent::HeaderError);
         dispatch_event!(rx_timeout);
     }
 }
@@ -545,6 +545,16 @@ pub enum PacketStatus {
     LoRa(LoRaPacketStatus),
 }
 
+impl PacketStatus {
+    /// RSSI and SNR reported for the packet, the LoRa RSSI corrected by the SNR
+    pub fn rssi_snr(&self) -> (i16, i8) {
+        match self {
+            PacketStatus::LoRa(ls) => (ls.rssi_pkt as i16 + ls.snr_pkt as i16, ls.snr_pkt),
+            PacketStatus::Gfsk(gs) => (gs.rssi_avg as i16, 0),
+        }
+    }
+}
+
 /// Represents the Rx internal counters values when GFSK or LoRa packet type is used
 #[derive(Debug, Clone, Copy)]
 pub struct RxCounter {
@@ -904,7 +914,7 @@ pub fn sx126x_get_random() -> usize {
 
     sx126x_set_standby(RadioStandbyModes::StdbyRc);
 
-    usize::from_be_bytes(random_bytes)
+    u32::from_be_bytes(random_bytes) as usize
 }
 
 pub fn sx126x_set_sleep(sleep_config: SleepParams) {
@@ -0,0 +1,100 @@
+use super::{Channel, ChannelPlanKind, DataRate, RegionParams};
+
+/// Channels defined by default
+pub const NUMB_DEFAULT_CHANNELS: usize = 2;
+
+/// Lowest uplink data rate
+pub const TX_MIN_DATARATE: u8 = 0;
+/// Highest uplink data rate
+pub const TX_MAX_DATARATE: u8 = 7;
+/// Default uplink data rate
+pub const DEFAULT_DATARATE: u8 = 2;
+/// Join-requests are sent at DR2, valid with and without dwell time
+pub const JOIN_DATARATE: u8 = 2;
+/// Largest RX1 data rate offset, 6 and 7 raise the data rate
+pub const MAX_RX1_DR_OFFSET: u8 = 7;
+
+/// Highest TX power index (lowest power)
+pub const MIN_TX_POWER: u8 = 7;
+/// Default max EIRP (dBm)
+pub const DEFAULT_MAX_EIRP: f32 = 16.0;
+/// Default antenna gain (dBi)
+pub const DEFAULT_ANTENNA_GAIN: f32 = 2.15;
+
+/// RX2 frequency of AS923-1 (Hz), shifted for the other groups
+pub const RX_WND_2_FREQ: u32 = 923_200_000;
+/// RX2 data rate
+pub const RX_WND_2_DR: u8 = 2;
+
+/// Carrier sense threshold before transmitting in Japan (dBm, ARIB STD-T108)
+pub const RSSI_FREE_TH_JAPAN: i16 = -80;
+/// Carrier sense duration in Japan (ms)
+pub const CARRIER_SENSE_TIME_JAPAN: usize = 5;
+
+/// DR0..DR7, dwell time off
+pub const DATA_RATES: [DataRate; 8] = [
+    DataRate::lora(12, 0, 59),
+    DataRate::lora(11, 0, 59),
+    DataRate::lora(10, 0, 59),
+    DataRate::lora(9, 0, 123),
+    DataRate::lora(8, 0, 250),
+    DataRate::lora(7, 0, 250),
+    DataRate::lora(7, 1, 250),
+    DataRate::fsk(50_000, 250),
+];
+
+/// Single 1% band
+pub const BANDS: [u16; 1] = [100];
+
+/// AS923 parameters
+pub const PARAMS: RegionParams = RegionParams {
+    data_rates: &DATA_RATES,
+    tx_min_dr: TX_MIN_DATARATE,
+    tx_max_dr: TX_MAX_DATARATE,
+    default_dr: DEFAULT_DATARATE,
+    max_rx1_dr_offset: MAX_RX1_DR_OFFSET,
+    max_eirp: DEFAULT_MAX_EIRP,
+    antenna_gain: DEFAULT_ANTENNA_GAIN,
+    max_tx_power_index: MIN_TX_POWER,
+    rx2_frequency: RX_WND_2_FREQ,
+    rx2_dr: RX_WND_2_DR,
+    bands: &BANDS,
+    plan: ChannelPlanKind::Dynamic,
+    default_channels: NUMB_DEFAULT_CHANNELS,
+    tx_param_setup: true,
+};
+
+/// Default channels of AS923-1, shifted for the other groups
+pub const DEFAULT_CHANNELS: [Channel; NUMB_DEFAULT_CHANNELS] = [
+    Channel {
+        frequency: 923_200_000,
+        rx1_frequency: 0,
+        min_dr: 0,
+        max_dr: 5,
+        band: 0,
+    },
+    Channel {
+        frequency: 923_400_000,
+        rx1_frequency: 0,
+        min_dr: 0,
+        max_dr: 5,
+        band: 0,
+    },
+];
+
+/// Band of a frequency, covers every group
+pub fn band_of(frequency: u32) -> Option<u8> {
+    (915_000_000..=928_000_000)
+        .contains(&frequency)
+        .then_some(0)
+}
+
+/// RX1 data rate, offsets 6 and 7 mean -1 and -2
+pub fn rx1_dr(dr: u8, offset: u8) -> u8 {
+    let offset = if offset > 5 {
+        5 - offset as i8
+    } else {
+        offset as i8
+    };
+    (dr as i8 - offset).clamp(0, 5) as u8
+}
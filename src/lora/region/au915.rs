@@ -0,0 +1,83 @@
+use super::{ChannelPlan, ChannelPlanKind, DataRate, RegionParams, us915};
+
+/// Lowest uplink data rate
+pub const TX_MIN_DATARATE: u8 = 0;
+/// Highest uplink data rate
+pub const TX_MAX_DATARATE: u8 = 6;
+/// Default uplink data rate
+pub const DEFAULT_DATARATE: u8 = 2;
+/// Largest RX1 data rate offset
+pub const MAX_RX1_DR_OFFSET: u8 = 5;
+
+/// Highest TX power index (lowest power)
+pub const MIN_TX_POWER: u8 = 14;
+/// Default max EIRP (dBm)
+pub const DEFAULT_MAX_EIRP: f32 = 30.0;
+/// Default antenna gain (dBi)
+pub const DEFAULT_ANTENNA_GAIN: f32 = 2.15;
+
+/// RX2 frequency (Hz)
+pub const RX_WND_2_FREQ: u32 = 923_300_000;
+/// RX2 data rate
+pub const RX_WND_2_DR: u8 = 8;
+
+/// DR0..DR13, dwell time off
+pub const DATA_RATES: [DataRate; 14] = [
+    DataRate::lora(12, 0, 59),
+    DataRate::lora(11, 0, 59),
+    DataRate::lora(10, 0, 59),
+    DataRate::lora(9, 0, 123),
+    DataRate::lora(8, 0, 250),
+    DataRate::lora(7, 0, 250),
+    DataRate::lora(8, 2, 250),
+    DataRate::RFU,
+    DataRate::lora(12, 2, 61),
+    DataRate::lora(11, 2, 137),
+    DataRate::lora(10, 2, 250),
+    DataRate::lora(9, 2, 250),
+    DataRate::lora(8, 2, 250),
+    DataRate::lora(7, 2, 250),
+];
+
+/// No duty cycle
+pub const BANDS: [u16; 1] = [1];
+
+/// AU915 parameters
+pub const PARAMS: RegionParams = RegionParams {
+    data_rates: &DATA_RATES,
+    tx_min_dr: TX_MIN_DATARATE,
+    tx_max_dr: TX_MAX_DATARATE,
+    default_dr: DEFAULT_DATARATE,
+    max_rx1_dr_offset: MAX_RX1_DR_OFFSET,
+    max_eirp: DEFAULT_MAX_EIRP,
+    antenna_gain: DEFAULT_ANTENNA_GAIN,
+    max_tx_power_index: MIN_TX_POWER,
+    rx2_frequency: RX_WND_2_FREQ,
+    rx2_dr: RX_WND_2_DR,
+    bands: &BANDS,
+    plan: ChannelPlanKind::Fixed,
+    default_channels: us915::NB_125KHZ_CHANNELS + us915::NB_500KHZ_CHANNELS,
+    tx_param_setup: true,
+};
+
+/// 64 channels at 915.2 MHz + 200 kHz (DR0..5), 8 at 915.9 MHz + 1.6 MHz (DR6)
+pub fn default_channels() -> ChannelPlan {
+    us915::fixed_plan(915_200_000, 915_900_000, 5, 6, us915::rx1_frequency)
+}
+
+/// Band of a frequency
+pub fn band_of(frequency: u32) -> Option<u8> {
+    (915_000_000..=928_000_000)
+        .contains(&frequency)
+        .then_some(0)
+}
+
+/// Join data rate, alternates between a 125 kHz (DR2) and a 500 kHz (DR6) channel
+pub fn join_dr(trial: u16) -> u8 {
+    if trial % 2 == 1 { 2 } else { 6 }
+}
+
+/// RX1 data rate: DR0..6 map to DR8..13, lowered by the offset down to DR8
+pub fn rx1_dr(dr: u8, offset: u8) -> u8 {
+    (8 + dr as i8 - offset as i8).clamp(8, 13) as u8
+}
@@ -0,0 +1,93 @@
+use super::{
+    CHANNEL_MASK_SIZE, Channel, ChannelPlan, ChannelPlanKind, DataRate, MAX_CHANNELS, RegionParams,
+};
+
+/// Lowest uplink data rate
+pub const TX_MIN_DATARATE: u8 = 0;
+/// Highest uplink data rate
+pub const TX_MAX_DATARATE: u8 = 5;
+/// Default uplink data rate
+pub const DEFAULT_DATARATE: u8 = 0;
+/// Largest RX1 data rate offset
+pub const MAX_RX1_DR_OFFSET: u8 = 5;
+
+/// Highest TX power index (lowest power)
+pub const MIN_TX_POWER: u8 = 7;
+/// Default max EIRP (dBm)
+pub const DEFAULT_MAX_EIRP: f32 = 19.15;
+/// Default antenna gain (dBi)
+pub const DEFAULT_ANTENNA_GAIN: f32 = 2.15;
+
+/// RX2 frequency (Hz)
+pub const RX_WND_2_FREQ: u32 = 505_300_000;
+/// RX2 data rate
+pub const RX_WND_2_DR: u8 = 0;
+
+/// DR0..DR5
+pub const DATA_RATES: [DataRate; 6] = [
+    DataRate::lora(12, 0, 59),
+    DataRate::lora(11, 0, 59),
+    DataRate::lora(10, 0, 59),
+    DataRate::lora(9, 0, 123),
+    DataRate::lora(8, 0, 250),
+    DataRate::lora(7, 0, 250),
+];
+
+/// No duty cycle
+pub const BANDS: [u16; 1] = [1];
+
+/// CN470 parameters
+pub const PARAMS: RegionParams = RegionParams {
+    data_rates: &DATA_RATES,
+    tx_min_dr: TX_MIN_DATARATE,
+    tx_max_dr: TX_MAX_DATARATE,
+    default_dr: DEFAULT_DATARATE,
+    max_rx1_dr_offset: MAX_RX1_DR_OFFSET,
+    max_eirp: DEFAULT_MAX_EIRP,
+    antenna_gain: DEFAULT_ANTENNA_GAIN,
+    max_tx_power_index: MIN_TX_POWER,
+    rx2_frequency: RX_WND_2_FREQ,
+    rx2_dr: RX_WND_2_DR,
+    bands: &BANDS,
+    plan: ChannelPlanKind::Fixed,
+    default_channels: MAX_CHANNELS,
+    tx_param_setup: false,
+};
+
+/// 96 uplink channels at 470.3 MHz + 200 kHz, RX1 on 500.3 MHz + 200 kHz * (channel % 48)
+pub fn default_channels() -> ChannelPlan {
+    let mut plan = ChannelPlan::empty();
+    for i in 0..MAX_CHANNELS {
+        plan.channels[i] = Some(Channel {
+            frequency: 470_300_000 + i as u32 * 200_000,
+            rx1_frequency: 500_300_000 + (i % 48) as u32 * 200_000,
+            min_dr: TX_MIN_DATARATE,
+            max_dr: TX_MAX_DATARATE,
+            band: 0,
+        });
+        plan.set_enabled(i, true);
+    }
+    plan
+}
+
+/// Band of a frequency
+pub fn band_of(frequency: u32) -> Option<u8> {
+    (470_000_000..=510_000_000)
+        .contains(&frequency)
+        .then_some(0)
+}
+
+/// LinkADRReq mask: ChMaskCntl 0..5 sets a block of 16 channels, 6 enables every channel
+pub(super) fn link_adr_mask(
+    plan: &ChannelPlan,
+    ch_mask: u16,
+    ch_mask_cntl: u8,
+) -> Option<[u16; CHANNEL_MASK_SIZE]> {
+    let mut mask = plan.mask;
+    match ch_mask_cntl {
+        0..=5 => mask[ch_mask_cntl as usize] = ch_mask,
+        6 => mask.fill(0xFFFF),
+        _ => return None,
+    }
+    Some(mask)
+}
@@ -0,0 +1,141 @@
+use super::{Channel, ChannelPlanKind, DataRate, RegionParams};
+
+/// Channels defined by default
+pub const NUMB_DEFAULT_CHANNELS: usize = 3;
+
+/// Lowest uplink data rate
+pub const TX_MIN_DATARATE: u8 = 0;
+/// Highest uplink data rate
+pub const TX_MAX_DATARATE: u8 = 7;
+/// Default uplink data rate
+pub const DEFAULT_DATARATE: u8 = 0;
+/// Largest RX1 data rate offset
+pub const MAX_RX1_DR_OFFSET: u8 = 5;
+
+/// Lowest TX power index (highest power)
+pub const MAX_TX_POWER: u8 = 0;
+/// Highest TX power index (lowest power)
+pub const MIN_TX_POWER: u8 = 7;
+/// Default max EIRP (dBm)
+pub const DEFAULT_MAX_EIRP: f32 = 16.0;
+/// Default antenna gain (dBi)
+pub const DEFAULT_ANTENNA_GAIN: f32 = 2.15;
+
+/// RX2 frequency (Hz)
+pub const RX_WND_2_FREQ: u32 = 869_525_000;
+/// RX2 data rate
+pub const RX_WND_2_DR: u8 = 0;
+
+/// DR0..DR7
+pub const DATA_RATES: [DataRate; 8] = [
+    DataRate::lora(12, 0, 59),
+    DataRate::lora(11, 0, 59),
+    DataRate::lora(10, 0, 59),
+    DataRate::lora(9, 0, 123),
+    DataRate::lora(8, 0, 250),
+    DataRate::lora(7, 0, 250),
+    DataRate::lora(7, 1, 250),
+    DataRate::fsk(50_000, 250),
+];
+
+/// Duty cycle of each band, as `1 / x`
+pub const BANDS: [u16; 5] = [
+    100,  //  1.0 %
+    100,  //  1.0 %
+    1000, //  0.1 %
+    10,   // 10.0 %
+    100,  //  1.0 %
+];
+
+/// EU868 parameters
+pub const PARAMS: RegionParams = RegionParams {
+    data_rates: &DATA_RATES,
+    tx_min_dr: TX_MIN_DATARATE,
+    tx_max_dr: TX_MAX_DATARATE,
+    default_dr: DEFAULT_DATARATE,
+    max_rx1_dr_offset: MAX_RX1_DR_OFFSET,
+    max_eirp: DEFAULT_MAX_EIRP,
+    antenna_gain: DEFAULT_ANTENNA_GAIN,
+    max_tx_power_index: MIN_TX_POWER,
+    rx2_frequency: RX_WND_2_FREQ,
+    rx2_dr: RX_WND_2_DR,
+    bands: &BANDS,
+    plan: ChannelPlanKind::Dynamic,
+    default_channels: NUMB_DEFAULT_CHANNELS,
+    tx_param_setup: false,
+};
+
+/// Default channels, every device has to support them
+pub const DEFAULT_CHANNELS: [Channel; NUMB_DEFAULT_CHANNELS] = [
+    Channel {
+        frequency: 868_100_000,
+        rx1_frequency: 0,
+        min_dr: 0,
+        max_dr: 5,
+        band: 1,
+    },
+    Channel {
+        frequency: 868_300_000,
+        rx1_frequency: 0,
+        min_dr: 0,
+        max_dr: 5,
+        band: 1,
+    },
+    Channel {
+        frequency: 868_500_000,
+        rx1_frequency: 0,
+        min_dr: 0,
+        max_dr: 5,
+        band: 1,
+    },
+];
+
+/// Band of an uplink frequency, mirrors `VerifyTxFreq` of RegionEU868.c
+pub fn band_of(frequency: u32) -> Option<u8> {
+    match frequency {
+        863_000_000..865_000_000 => Some(2),
+        865_000_000..=868_000_000 => Some(0),
+        868_000_001..=868_600_000 => Some(1),
+        868_700_000..=869_200_000 => Some(2),
+        869_400_000..=869_650_000 => Some(3),
+        869_700_000..=870_000_000 => Some(4),
+        _ => None,
+    }
+}
+
+/// Join data rate, DR5 most of the time with a fallback to slower data rates
+pub fn join_dr(trial: u16) -> u8 {
+    match trial {
+        t if t % 48 == 0 => 0,
+        t if t % 32 == 0 => 1,
+        t if t % 24 == 0 => 2,
+        t if t % 16 == 0 => 3,
+        t if t % 8 == 0 => 4,
+        _ => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bands() {
+        assert_eq!(band_of(863_000_000), Some(2));
+        assert_eq!(band_of(867_100_000), Some(0));
+        assert_eq!(band_of(868_100_000), Some(1));
+        assert_eq!(band_of(868_650_000), None);
+        assert_eq!(band_of(869_525_000), Some(3));
+        assert_eq!(band_of(870_000_001), None);
+    }
+
+    #[test]
+    fn join_data_rates() {
+        assert_eq!(join_dr(1), 5);
+        assert_eq!(join_dr(8), 4);
+        assert_eq!(join_dr(16), 3);
+        assert_eq!(join_dr(24), 2);
+        assert_eq!(join_dr(32), 1);
+        assert_eq!(join_dr(48), 0);
+    }
+}
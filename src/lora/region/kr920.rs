@@ -0,0 +1,93 @@
+use super::{Channel, ChannelPlanKind, DataRate, RegionParams};
+
+/// Channels defined by default
+pub const NUMB_DEFAULT_CHANNELS: usize = 3;
+
+/// Lowest uplink data rate
+pub const TX_MIN_DATARATE: u8 = 0;
+/// Highest uplink data rate
+pub const TX_MAX_DATARATE: u8 = 5;
+/// Default uplink data rate
+pub const DEFAULT_DATARATE: u8 = 0;
+/// Largest RX1 data rate offset
+pub const MAX_RX1_DR_OFFSET: u8 = 5;
+
+/// Highest TX power index (lowest power)
+pub const MIN_TX_POWER: u8 = 7;
+/// Default max EIRP (dBm)
+pub const DEFAULT_MAX_EIRP: f32 = 14.0;
+/// Default antenna gain (dBi)
+pub const DEFAULT_ANTENNA_GAIN: f32 = 2.15;
+
+/// RX2 frequency (Hz)
+pub const RX_WND_2_FREQ: u32 = 921_900_000;
+/// RX2 data rate
+pub const RX_WND_2_DR: u8 = 0;
+
+/// Carrier sense threshold before transmitting (dBm)
+pub const RSSI_FREE_TH: i16 = -65;
+/// Carrier sense duration (ms)
+pub const CARRIER_SENSE_TIME: usize = 6;
+
+/// DR0..DR5
+pub const DATA_RATES: [DataRate; 6] = [
+    DataRate::lora(12, 0, 59),
+    DataRate::lora(11, 0, 59),
+    DataRate::lora(10, 0, 59),
+    DataRate::lora(9, 0, 123),
+    DataRate::lora(8, 0, 250),
+    DataRate::lora(7, 0, 250),
+];
+
+/// No duty cycle, listen before talk instead
+pub const BANDS: [u16; 1] = [1];
+
+/// KR920 parameters
+pub const PARAMS: RegionParams = RegionParams {
+    data_rates: &DATA_RATES,
+    tx_min_dr: TX_MIN_DATARATE,
+    tx_max_dr: TX_MAX_DATARATE,
+    default_dr: DEFAULT_DATARATE,
+    max_rx1_dr_offset: MAX_RX1_DR_OFFSET,
+    max_eirp: DEFAULT_MAX_EIRP,
+    antenna_gain: DEFAULT_ANTENNA_GAIN,
+    max_tx_power_index: MIN_TX_POWER,
+    rx2_frequency: RX_WND_2_FREQ,
+    rx2_dr: RX_WND_2_DR,
+    bands: &BANDS,
+    plan: ChannelPlanKind::Dynamic,
+    default_channels: NUMB_DEFAULT_CHANNELS,
+    tx_param_setup: false,
+};
+
+/// Default channels, every device has to support them
+pub const DEFAULT_CHANNELS: [Channel; NUMB_DEFAULT_CHANNELS] = [
+    Channel {
+        frequency: 922_100_000,
+        rx1_frequency: 0,
+        min_dr: 0,
+        max_dr: 5,
+        band: 0,
+    },
+    Channel {
+        frequency: 922_300_000,
+        rx1_frequency: 0,
+        min_dr: 0,
+        max_dr: 5,
+        band: 0,
+    },
+    Channel {
+        frequency: 922_500_000,
+        rx1_frequency: 0,
+        min_dr: 0,
+        max_dr: 5,
+        band: 0,
+    },
+];
+
+/// Band of a frequency
+pub fn band_of(frequency: u32) -> Option<u8> {
+    (920_900_000..=923_300_000)
+        .contains(&frequency)
+        .then_some(0)
+}
@@ -0,0 +1,915 @@
+/// AS923 channel plans (groups 1 to 4)
+pub mod as923;
+/// AU915-928 channel plan
+pub mod au915;
+/// CN470-510 channel plan
+pub mod cn470;
+/// EU863-870 channel plan
+pub mod eu868;
+/// IN865-867 channel plan
+pub mod in865;
+/// KR920-923 channel plan
+pub mod kr920;
+/// US902-928 channel plan
+pub mod us915;
+
+/// Largest amount of channels a region can define (CN470)
+pub const MAX_CHANNELS: usize = 96;
+/// Amount of 16-bit words in a channel mask
+pub const CHANNEL_MASK_SIZE: usize = MAX_CHANNELS.div_ceil(16);
+/// Channels of a dynamic channel plan (EU868 like regions)
+pub const DYNAMIC_MAX_CHANNELS: usize = 16;
+
+/// Physical layer settings of a data rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modulation {
+    /// LoRa, bandwidth index 0 = 125 kHz, 1 = 250 kHz, 2 = 500 kHz
+    LoRa { sf: u8, bw: u8 },
+    /// FSK, bitrate in bit/s
+    Fsk { bitrate: u32 },
+}
+
+/// Data rate table entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataRate {
+    /// modulation used for this data rate
+    pub modulation: Modulation,
+    /// largest MACPayload (FHDR + FPort + FRMPayload) without repeater, 0 for RFU data rates
+    pub max_payload: u8,
+}
+
+impl DataRate {
+    /// Reserved data rate, filtered out by [`Region::data_rate`]
+    pub const RFU: Self = Self::lora(0, 0, 0);
+
+    /// LoRa data rate
+    pub const fn lora(sf: u8, bw: u8, max_payload: u8) -> Self {
+        Self {
+            modulation: Modulation::LoRa { sf, bw },
+            max_payload,
+        }
+    }
+
+    /// FSK data rate
+    pub const fn fsk(bitrate: u32, max_payload: u8) -> Self {
+        Self {
+            modulation: Modulation::Fsk { bitrate },
+            max_payload,
+        }
+    }
+
+    /// Duration of a symbol in ms
+    pub fn symbol_time(&self) -> f64 {
+        match self.modulation {
+            Modulation::LoRa { sf, bw } => {
+                let bw_hz = 125_000u32 << bw;
+                (1u32 << sf) as f64 / bw_hz as f64 * 1000.0
+            }
+            // 1 symbol equals 1 byte
+            Modulation::Fsk { bitrate } => 8.0 / bitrate as f64 * 1000.0,
+        }
+    }
+
+    /// Time on air (ms) of a `len` byte PHYPayload with the LoRaWAN uplink settings:
+    /// 8 symbol preamble, coding rate 4/5, explicit header and CRC (5 byte preamble, 3 byte
+    /// sync word, length byte and CRC for FSK)
+    pub fn time_on_air(&self, len: u8) -> u32 {
+        let ms = match self.modulation {
+            Modulation::LoRa { sf, .. } => {
+                let t_symbol = self.symbol_time();
+                let low_dr_optimize = (t_symbol >= 16.0) as i32;
+                let numerator = 8 * len as i32 - 4 * sf as i32 + 28 + 16;
+                let denominator = 4 * (sf as i32 - 2 * low_dr_optimize);
+                let payload_symbols = 8 + (numerator.max(0) + denominator - 1) / denominator * 5;
+                (8.0 + 4.25 + payload_symbols as f64) * t_symbol
+            }
+            Modulation::Fsk { .. } => (5 + 3 + 1 + len as u32 + 2) as f64 * self.symbol_time(),
+        };
+        libm::ceil(ms) as u32
+    }
+}
+
+/// Uplink channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Channel {
+    /// uplink frequency (Hz)
+    pub frequency: u32,
+    /// RX1 frequency (Hz), 0 to use the uplink frequency
+    pub rx1_frequency: u32,
+    /// lowest data rate allowed on the channel
+    pub min_dr: u8,
+    /// highest data rate allowed on the channel
+    pub max_dr: u8,
+    /// duty cycle band the channel belongs to
+    pub band: u8,
+}
+
+impl Channel {
+    /// Whether `dr` may be used on this channel
+    pub fn supports_dr(&self, dr: u8) -> bool {
+        (self.min_dr..=self.max_dr).contains(&dr)
+    }
+}
+
+/// Channels known to the device along with the enabled mask
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelPlan {
+    /// defined channels
+    pub channels: [Option<Channel>; MAX_CHANNELS],
+    /// enabled channels, bit `i % 16` of word `i / 16` for channel `i`
+    pub mask: [u16; CHANNEL_MASK_SIZE],
+}
+
+impl ChannelPlan {
+    /// Plan without any channel
+    pub const fn empty() -> Self {
+        Self {
+            channels: [None; MAX_CHANNELS],
+            mask: [0; CHANNEL_MASK_SIZE],
+        }
+    }
+
+    /// Whether channel `index` is defined and enabled
+    pub fn is_enabled(&self, index: usize) -> bool {
+        index < MAX_CHANNELS
+            && self.channels[index].is_some()
+            && self.mask[index / 16] & (1 << (index % 16)) != 0
+    }
+
+    /// Enable or disable channel `index`
+    pub fn set_enabled(&mut self, index: usize, enable: bool) {
+        if index >= MAX_CHANNELS {
+            return;
+        }
+        if enable {
+            self.mask[index / 16] |= 1 << (index % 16);
+        } else {
+            self.mask[index / 16] &= !(1 << (index % 16));
+        }
+    }
+
+    /// Amount of enabled channels
+    pub fn enabled_count(&self) -> usize {
+        (0..MAX_CHANNELS).filter(|&i| self.is_enabled(i)).count()
+    }
+
+    /// Whether every bit of `mask` points at a defined channel and at least one is set
+    fn is_valid_mask(&self, mask: &[u16; CHANNEL_MASK_SIZE]) -> bool {
+        let mut any = false;
+        for i in 0..MAX_CHANNELS {
+            if mask[i / 16] & (1 << (i % 16)) != 0 {
+                if self.channels[i].is_none() {
+                    return false;
+                }
+                any = true;
+            }
+        }
+        any
+    }
+}
+
+/// Status bits of a LinkADRAns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkAdrStatus {
+    /// TX power accepted
+    pub power_ack: bool,
+    /// data rate accepted
+    pub dr_ack: bool,
+    /// channel mask accepted
+    pub channel_mask_ack: bool,
+}
+
+impl LinkAdrStatus {
+    /// All parameters accepted, the request may be applied
+    pub fn is_ok(&self) -> bool {
+        self.power_ack && self.dr_ack && self.channel_mask_ack
+    }
+
+    /// Status byte of the LinkADRAns
+    pub fn to_byte(&self) -> u8 {
+        (self.power_ack as u8) << 2 | (self.dr_ack as u8) << 1 | self.channel_mask_ack as u8
+    }
+}
+
+/// Status bits of a NewChannelAns / DlChannelAns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelStatus {
+    /// data rate range (NewChannelReq) or uplink frequency existence (DlChannelReq) accepted
+    pub dr_ack: bool,
+    /// frequency accepted
+    pub frequency_ack: bool,
+}
+
+impl ChannelStatus {
+    /// Status byte of the answer
+    pub fn to_byte(&self) -> u8 {
+        (self.dr_ack as u8) << 1 | self.frequency_ack as u8
+    }
+}
+
+/// How the region handles channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelPlanKind {
+    /// up to 16 channels, the network adds channels with the CFList and NewChannelReq
+    Dynamic,
+    /// fixed channels, the network only changes the mask (CFList type 1 and LinkADRReq)
+    Fixed,
+}
+
+/// Constant parameters of a region, see the LoRaWAN Regional Parameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegionParams {
+    /// data rate table, indexed by DR
+    pub data_rates: &'static [DataRate],
+    /// lowest uplink data rate
+    pub tx_min_dr: u8,
+    /// highest uplink data rate
+    pub tx_max_dr: u8,
+    /// default uplink data rate
+    pub default_dr: u8,
+    /// largest RX1 data rate offset
+    pub max_rx1_dr_offset: u8,
+    /// default max EIRP (dBm)
+    pub max_eirp: f32,
+    /// default antenna gain (dBi)
+    pub antenna_gain: f32,
+    /// highest valid TX power index (lowest power)
+    pub max_tx_power_index: u8,
+    /// default RX2 frequency (Hz)
+    pub rx2_frequency: u32,
+    /// default RX2 data rate
+    pub rx2_dr: u8,
+    /// duty cycle of each band, as `1 / x`
+    pub bands: &'static [u16],
+    /// dynamic or fixed channel plan
+    pub plan: ChannelPlanKind,
+    /// channels defined by default, they can't be modified by the network (dynamic plans)
+    pub default_channels: usize,
+    /// the region implements TxParamSetupReq
+    pub tx_param_setup: bool,
+}
+
+/// AS923 frequency group, each one shifts the whole plan by a fixed offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum As923Group {
+    /// AS923-1, 923.2 MHz
+    Group1,
+    /// AS923-2, 921.4 MHz
+    Group2,
+    /// AS923-3, 916.6 MHz
+    Group3,
+    /// AS923-4, 917.3 MHz
+    Group4,
+}
+
+impl As923Group {
+    /// Offset from the AS923-1 frequencies (Hz)
+    pub fn frequency_offset(&self) -> i32 {
+        match self {
+            As923Group::Group1 => 0,
+            As923Group::Group2 => -1_800_000,
+            As923Group::Group3 => -6_600_000,
+            As923Group::Group4 => -5_900_000,
+        }
+    }
+}
+
+/// LoRaWAN regional parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// Europe 863-870 MHz
+    Eu868,
+    /// United States 902-928 MHz
+    Us915,
+    /// Australia 915-928 MHz
+    Au915,
+    /// Asia 923 MHz
+    As923(As923Group),
+    /// South Korea 920-923 MHz
+    Kr920,
+    /// India 865-867 MHz
+    In865,
+    /// China 470-510 MHz
+    Cn470,
+}
+
+impl Region {
+    /// Constant parameters of the region
+    pub fn params(&self) -> &'static RegionParams {
+        match self {
+            Region::Eu868 => &eu868::PARAMS,
+            Region::Us915 => &us915::PARAMS,
+            Region::Au915 => &au915::PARAMS,
+            Region::As923(_) => &as923::PARAMS,
+            Region::Kr920 => &kr920::PARAMS,
+            Region::In865 => &in865::PARAMS,
+            Region::Cn470 => &cn470::PARAMS,
+        }
+    }
+
+    /// Data rate table, indexed by DR
+    pub fn data_rates(&self) -> &'static [DataRate] {
+        self.params().data_rates
+    }
+
+    /// Physical settings of `dr`, `None` for unknown or RFU data rates
+    pub fn data_rate(&self, dr: u8) -> Option<&'static DataRate> {
+        self.data_rates()
+            .get(dr as usize)
+            .filter(|d| d.max_payload != 0)
+    }
+
+    /// Lowest uplink data rate
+    pub fn min_tx_dr(&self) -> u8 {
+        self.params().tx_min_dr
+    }
+
+    /// Highest uplink data rate
+    pub fn max_tx_dr(&self) -> u8 {
+        self.params().tx_max_dr
+    }
+
+    /// Data rate used until ADR or the application changes it
+    pub fn default_dr(&self) -> u8 {
+        self.params().default_dr
+    }
+
+    /// Largest FRMPayload allowed at `dr`, FHDR and FPort excluded
+    pub fn max_payload(&self, dr: u8, fopts_len: usize) -> usize {
+        self.data_rate(dr).map_or(0, |d| {
+            (d.max_payload as usize).saturating_sub(7 + 1 + fopts_len)
+        })
+    }
+
+    /// Default max EIRP (dBm)
+    pub fn max_eirp(&self) -> f32 {
+        self.params().max_eirp
+    }
+
+    /// Default antenna gain (dBi)
+    pub fn antenna_gain(&self) -> f32 {
+        self.params().antenna_gain
+    }
+
+    /// Highest valid TX power index (lowest power)
+    pub fn max_tx_power_index(&self) -> u8 {
+        self.params().max_tx_power_index
+    }
+
+    /// Conducted power for TX power index `index`, `None` if out of range
+    pub fn tx_power_dbm(&self, index: u8, max_eirp: f32, antenna_gain: f32) -> Option<i8> {
+        if index > self.max_tx_power_index() {
+            return None;
+        }
+        Some(libm::floorf(max_eirp - (index as f32 * 2.0) - antenna_gain) as i8)
+    }
+
+    /// Largest RX1 data rate offset
+    pub fn max_rx1_dr_offset(&self) -> u8 {
+        self.params().max_rx1_dr_offset
+    }
+
+    /// Downlink data rate in RX1 for an uplink at `dr`
+    pub fn rx1_dr(&self, dr: u8, offset: u8) -> u8 {
+        match self {
+            Region::Us915 => us915::rx1_dr(dr, offset),
+            Region::Au915 => au915::rx1_dr(dr, offset),
+            Region::As923(_) => as923::rx1_dr(dr, offset),
+            Region::In865 => in865::rx1_dr(dr, offset),
+            _ => dr.saturating_sub(offset),
+        }
+    }
+
+    /// Default RX2 frequency (Hz) and data rate
+    pub fn rx2_default(&self) -> (u32, u8) {
+        let params = self.params();
+        (self.shift(params.rx2_frequency), params.rx2_dr)
+    }
+
+    /// Duty cycle of each band, as `1 / x`
+    pub fn bands(&self) -> &'static [u16] {
+        self.params().bands
+    }
+
+    /// Band a frequency belongs to, `None` if the device may not use it
+    pub fn band_of(&self, frequency: u32) -> Option<u8> {
+        match self {
+            Region::Eu868 => eu868::band_of(frequency),
+            Region::Us915 => us915::band_of(frequency),
+            Region::Au915 => au915::band_of(frequency),
+            Region::As923(_) => as923::band_of(frequency),
+            Region::Kr920 => kr920::band_of(frequency),
+            Region::In865 => in865::band_of(frequency),
+            Region::Cn470 => cn470::band_of(frequency),
+        }
+    }
+
+    /// Default channel plan
+    pub fn default_channels(&self) -> ChannelPlan {
+        let mut plan = match self {
+            Region::Eu868 => dynamic_plan(&eu868::DEFAULT_CHANNELS),
+            Region::Us915 => us915::default_channels(),
+            Region::Au915 => au915::default_channels(),
+            Region::As923(_) => dynamic_plan(&as923::DEFAULT_CHANNELS),
+            Region::Kr920 => dynamic_plan(&kr920::DEFAULT_CHANNELS),
+            Region::In865 => dynamic_plan(&in865::DEFAULT_CHANNELS),
+            Region::Cn470 => cn470::default_channels(),
+        };
+        for channel in plan.channels.iter_mut().flatten() {
+            channel.frequency = self.shift(channel.frequency);
+        }
+        plan
+    }
+
+    /// Whether channel `index` may be used for join-requests
+    pub fn is_join_channel(&self, index: usize) -> bool {
+        match self.params().plan {
+            ChannelPlanKind::Dynamic => index < self.params().default_channels,
+            ChannelPlanKind::Fixed => index < MAX_CHANNELS,
+        }
+    }
+
+    /// Data rate for the `trial`-th join-request, cycles through the allowed data rates
+    pub fn join_dr(&self, trial: u16) -> u8 {
+        match self {
+            Region::Us915 => us915::join_dr(trial),
+            Region::Au915 => au915::join_dr(trial),
+            Region::As923(_) => as923::JOIN_DATARATE,
+            _ => eu868::join_dr(trial),
+        }
+    }
+
+    /// Apply the CFList of a join-accept
+    pub fn apply_cf_list(&self, plan: &mut ChannelPlan, cf_list: &[u8; 16]) {
+        match (self.params().plan, cf_list[15]) {
+            // type 0, up to 5 frequencies (24-bit, 100 Hz steps) after the default channels
+            (ChannelPlanKind::Dynamic, 0) => {
+                let first = self.params().default_channels;
+                for (i, f) in cf_list[..15].chunks_exact(3).enumerate() {
+                    let index = first + i;
+                    let frequency = u32::from_le_bytes([f[0], f[1], f[2], 0]) * 100;
+                    plan.channels[index] = self.band_of(frequency).map(|band| Channel {
+                        frequency,
+                        rx1_frequency: 0,
+                        min_dr: self.min_tx_dr(),
+                        max_dr: 5,
+                        band,
+                    });
+                    plan.set_enabled(index, plan.channels[index].is_some());
+                }
+            }
+            // type 1, channel mask words
+            (ChannelPlanKind::Fixed, 1) => {
+                let mut mask = [0u16; CHANNEL_MASK_SIZE];
+                for (word, m) in cf_list[..14].chunks_exact(2).zip(mask.iter_mut()) {
+                    *m = u16::from_le_bytes([word[0], word[1]]);
+                }
+                if plan.is_valid_mask(&mask) {
+                    plan.mask = mask;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Validate a LinkADRReq, on success `plan.mask` holds the new mask
+    pub fn link_adr_req(
+        &self,
+        plan: &mut ChannelPlan,
+        dr: u8,
+        tx_power: u8,
+        ch_mask: u16,
+        ch_mask_cntl: u8,
+    ) -> LinkAdrStatus {
+        let mask = match self {
+            Region::Us915 | Region::Au915 => us915::link_adr_mask(plan, ch_mask, ch_mask_cntl),
+            Region::Cn470 => cn470::link_adr_mask(plan, ch_mask, ch_mask_cntl),
+            _ => dynamic_link_adr_mask(plan, ch_mask, ch_mask_cntl),
+        };
+
+        let mut status = LinkAdrStatus {
+            power_ack: false,
+            dr_ack: false,
+            channel_mask_ack: mask.is_some_and(|m| plan.is_valid_mask(&m)),
+        };
+        if !status.channel_mask_ack {
+            return status;
+        }
+        if let Some(mask) = mask {
+            plan.mask = mask;
+        }
+
+        // 0xF keeps the current value (LoRaWAN 1.0.4)
+        status.dr_ack = dr == 0x0F
+            || (dr >= self.min_tx_dr()
+                && dr <= self.max_tx_dr()
+                && self.data_rate(dr).is_some()
+                && (0..MAX_CHANNELS).any(|i| {
+                    plan.is_enabled(i) && plan.channels[i].is_some_and(|c| c.supports_dr(dr))
+                }));
+        status.power_ack = tx_power == 0x0F || tx_power <= self.max_tx_power_index();
+        status
+    }
+
+    /// Validate and apply a NewChannelReq, a frequency of 0 removes the channel
+    pub fn new_channel_req(
+        &self,
+        plan: &mut ChannelPlan,
+        index: u8,
+        frequency: u32,
+        min_dr: u8,
+        max_dr: u8,
+    ) -> ChannelStatus {
+        let index = index as usize;
+        if self.params().plan == ChannelPlanKind::Fixed
+            || index >= DYNAMIC_MAX_CHANNELS
+            || self.is_join_channel(index)
+        {
+            return ChannelStatus {
+                dr_ack: false,
+                frequency_ack: false,
+            };
+        }
+
+        if frequency == 0 {
+            plan.channels[index] = None;
+            plan.set_enabled(index, false);
+            return ChannelStatus {
+                dr_ack: true,
+                frequency_ack: true,
+            };
+        }
+
+        let band = self.band_of(frequency);
+        let status = ChannelStatus {
+            dr_ack: min_dr <= max_dr && max_dr <= self.max_tx_dr(),
+            frequency_ack: band.is_some(),
+        };
+        if let (true, true, Some(band)) = (status.dr_ack, status.frequency_ack, band) {
+            plan.channels[index] = Some(Channel {
+                frequency,
+                rx1_frequency: 0,
+                min_dr,
+                max_dr,
+                band,
+            });
+            plan.set_enabled(index, true);
+        }
+        status
+    }
+
+    /// Validate and apply a DlChannelReq
+    pub fn dl_channel_req(
+        &self,
+        plan: &mut ChannelPlan,
+        index: u8,
+        frequency: u32,
+    ) -> ChannelStatus {
+        if self.params().plan == ChannelPlanKind::Fixed {
+            return ChannelStatus {
+                dr_ack: false,
+                frequency_ack: false,
+            };
+        }
+
+        let channel = plan
+            .channels
+            .get_mut(index as usize)
+            .and_then(|c| c.as_mut());
+        let status = ChannelStatus {
+            dr_ack: channel.is_some(),
+            frequency_ack: self.band_of(frequency).is_some(),
+        };
+        if let (Some(channel), true) = (channel, status.frequency_ack) {
+            channel.rx1_frequency = frequency;
+        }
+        status
+    }
+
+    /// Max EIRP (dBm) of a TxParamSetupReq, `None` if the region doesn't implement it
+    pub fn tx_param_setup_eirp(&self, eirp_dwell_time: u8) -> Option<f32> {
+        const MAX_EIRP: [f32; 16] = [
+            8.0, 10.0, 12.0, 13.0, 14.0, 16.0, 18.0, 20.0, 21.0, 24.0, 26.0, 27.0, 29.0, 30.0,
+            33.0, 36.0,
+        ];
+        self.params()
+            .tx_param_setup
+            .then(|| MAX_EIRP[(eirp_dwell_time & 0x0F) as usize])
+    }
+
+    /// Apply the frequency offset of the region variant
+    fn shift(&self, frequency: u32) -> u32 {
+        match self {
+            Region::As923(group) => frequency.wrapping_add_signed(group.frequency_offset()),
+            _ => frequency,
+        }
+    }
+}
+
+/// Plan with `defaults` defined and enabled
+fn dynamic_plan(defaults: &[Channel]) -> ChannelPlan {
+    let mut plan = ChannelPlan::empty();
+    for (i, channel) in defaults.iter().enumerate() {
+        plan.channels[i] = Some(*channel);
+        plan.set_enabled(i, true);
+    }
+    plan
+}
+
+/// LinkADRReq mask of a dynamic plan: ChMaskCntl 0 sets channels 0..15, 6 enables every channel
+fn dynamic_link_adr_mask(
+    plan: &ChannelPlan,
+    ch_mask: u16,
+    ch_mask_cntl: u8,
+) -> Option<[u16; CHANNEL_MASK_SIZE]> {
+    let mut mask = [0u16; CHANNEL_MASK_SIZE];
+    mask[0] = match ch_mask_cntl {
+        0 => ch_mask,
+        6 => (0..DYNAMIC_MAX_CHANNELS)
+            .filter(|&i| plan.channels[i].is_some())
+            .fold(0, |m, i| m | 1 << i),
+        _ => return None,
+    };
+    Some(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Region; 10] = [
+        Region::Eu868,
+        Region::Us915,
+        Region::Au915,
+        Region::As923(As923Group::Group1),
+        Region::As923(As923Group::Group2),
+        Region::As923(As923Group::Group3),
+        Region::As923(As923Group::Group4),
+        Region::Kr920,
+        Region::In865,
+        Region::Cn470,
+    ];
+
+    #[test]
+    fn time_on_air() {
+        // 13 byte PHYPayload, LoRaWAN calculators give 46.3 ms at SF7 and 1155.1 ms at SF12
+        assert_eq!(eu868::DATA_RATES[5].time_on_air(13), 47);
+        assert_eq!(eu868::DATA_RATES[0].time_on_air(13), 1156);
+        assert_eq!(eu868::DATA_RATES[5].symbol_time(), 1.024);
+        assert_eq!(eu868::DATA_RATES[6].symbol_time(), 0.512);
+        // FSK: 5 preamble + 3 sync + length + 13 + 2 CRC bytes at 50 kbit/s
+        assert_eq!(eu868::DATA_RATES[7].time_on_air(13), 4);
+        assert!(eu868::DATA_RATES[0].time_on_air(51) > eu868::DATA_RATES[0].time_on_air(50));
+    }
+
+    #[test]
+    fn defaults_are_consistent() {
+        for region in ALL {
+            let plan = region.default_channels();
+            assert!(plan.enabled_count() > 0, "{region:?}");
+            assert!(
+                region.data_rate(region.default_dr()).is_some(),
+                "{region:?}"
+            );
+            assert!(region.min_tx_dr() <= region.max_tx_dr(), "{region:?}");
+            let (rx2_frequency, rx2_dr) = region.rx2_default();
+            assert!(region.data_rate(rx2_dr).is_some(), "{region:?}");
+            assert!(region.band_of(rx2_frequency).is_some(), "{region:?}");
+            for channel in plan.channels.iter().flatten() {
+                assert_eq!(
+                    region.band_of(channel.frequency),
+                    Some(channel.band),
+                    "{region:?} {}",
+                    channel.frequency
+                );
+            }
+            for trial in 1..50 {
+                let dr = region.join_dr(trial);
+                assert!(
+                    (0..MAX_CHANNELS).any(|i| region.is_join_channel(i)
+                        && plan.is_enabled(i)
+                        && plan.channels[i].is_some_and(|c| c.supports_dr(dr))),
+                    "{region:?} join DR{dr}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn as923_groups_shift_the_plan() {
+        let group2 = Region::As923(As923Group::Group2);
+        assert_eq!(
+            group2.default_channels().channels[0].map(|c| c.frequency),
+            Some(921_400_000)
+        );
+        assert_eq!(group2.rx2_default(), (921_400_000, 2));
+        assert_eq!(
+            Region::As923(As923Group::Group3).rx2_default().0,
+            916_600_000
+        );
+    }
+
+    #[test]
+    fn rx1_data_rate() {
+        assert_eq!(Region::Eu868.rx1_dr(5, 2), 3);
+        assert_eq!(Region::Eu868.rx1_dr(1, 3), 0);
+        assert_eq!(Region::Us915.rx1_dr(0, 0), 10);
+        assert_eq!(Region::Us915.rx1_dr(4, 0), 13);
+        assert_eq!(Region::Us915.rx1_dr(0, 3), 8);
+        let as923 = Region::As923(As923Group::Group1);
+        assert_eq!(as923.rx1_dr(2, 6), 3);
+        assert_eq!(as923.rx1_dr(5, 7), 5);
+        assert_eq!(as923.rx1_dr(2, 5), 0);
+    }
+
+    #[test]
+    fn payload_sizes_and_power() {
+        assert_eq!(Region::Eu868.max_payload(0, 0), 51);
+        assert_eq!(Region::Eu868.max_payload(5, 0), 242);
+        assert_eq!(Region::Eu868.max_payload(0, 5), 46);
+        assert_eq!(Region::Us915.max_payload(0, 0), 11);
+        // RFU and unknown data rates
+        assert_eq!(Region::Us915.max_payload(5, 0), 0);
+        assert_eq!(Region::Eu868.max_payload(8, 0), 0);
+        assert!(Region::Us915.data_rate(6).is_none());
+
+        assert_eq!(Region::Eu868.tx_power_dbm(0, 16.0, 2.15), Some(13));
+        assert_eq!(Region::Eu868.tx_power_dbm(7, 16.0, 2.15), Some(-1));
+        assert_eq!(Region::Eu868.tx_power_dbm(8, 16.0, 2.15), None);
+    }
+
+    #[test]
+    fn cf_list_type_0() {
+        let region = Region::Eu868;
+        let mut plan = region.default_channels();
+        // 867.1, 867.3, 867.5, 867.7 MHz, 0
+        let cf_list = [
+            0x18, 0x4F, 0x84, 0xE8, 0x56, 0x84, 0xB8, 0x5E, 0x84, 0x88, 0x66, 0x84, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        region.apply_cf_list(&mut plan, &cf_list);
+        assert_eq!(plan.enabled_count(), 7);
+        assert_eq!(
+            plan.channels[3].map(|c| (c.frequency, c.band)),
+            Some((867_100_000, 0))
+        );
+        assert_eq!(plan.channels[6].map(|c| c.frequency), Some(867_700_000));
+        assert!(plan.channels[7].is_none());
+
+        // type 1 is ignored by dynamic plans
+        let mut plan = region.default_channels();
+        let mut mask_list = [0u8; 16];
+        mask_list[15] = 1;
+        region.apply_cf_list(&mut plan, &mask_list);
+        assert_eq!(plan, region.default_channels());
+    }
+
+    #[test]
+    fn cf_list_type_1() {
+        let region = Region::Us915;
+        let mut plan = region.default_channels();
+        // second sub-band (channels 8..15) and its 500 kHz channel 65
+        let mut cf_list = [0u8; 16];
+        cf_list[0] = 0x00;
+        cf_list[1] = 0xFF;
+        cf_list[8] = 0x02;
+        cf_list[15] = 1;
+        region.apply_cf_list(&mut plan, &cf_list);
+        assert_eq!(plan.enabled_count(), 9);
+        assert!(plan.is_enabled(8) && plan.is_enabled(15) && plan.is_enabled(65));
+        assert!(!plan.is_enabled(0) && !plan.is_enabled(16) && !plan.is_enabled(64));
+
+        // an empty mask is refused
+        let mut cf_list = [0u8; 16];
+        cf_list[15] = 1;
+        let before = plan;
+        region.apply_cf_list(&mut plan, &cf_list);
+        assert_eq!(plan, before);
+    }
+
+    #[test]
+    fn link_adr_req_dynamic() {
+        let region = Region::Eu868;
+        let mut plan = region.default_channels();
+        let status = region.link_adr_req(&mut plan, 5, 1, 0b101, 0);
+        assert!(status.is_ok());
+        assert_eq!(plan.mask[0], 0b101);
+
+        // channel 3 isn't defined
+        let mut plan = region.default_channels();
+        let status = region.link_adr_req(&mut plan, 5, 1, 0b1000, 0);
+        assert_eq!(status.to_byte(), 0);
+        assert_eq!(plan, region.default_channels());
+
+        // DR7 is FSK only on channels that support it, power index 8 doesn't exist
+        let mut plan = region.default_channels();
+        let status = region.link_adr_req(&mut plan, 7, 8, 0b111, 0);
+        assert_eq!(status.to_byte(), 0b001);
+
+        // ChMaskCntl 6 enables every defined channel, 0xF keeps DR and power
+        let mut plan = region.default_channels();
+        plan.mask[0] = 0b001;
+        let status = region.link_adr_req(&mut plan, 0x0F, 0x0F, 0, 6);
+        assert!(status.is_ok());
+        assert_eq!(plan.mask[0], 0b111);
+
+        let mut plan = region.default_channels();
+        assert!(!region.link_adr_req(&mut plan, 5, 1, 0, 3).channel_mask_ack);
+    }
+
+    #[test]
+    fn link_adr_req_fixed() {
+        let region = Region::Us915;
+        let mut plan = region.default_channels();
+        // ChMaskCntl 7 disables every 125 kHz channel, ChMask sets the 500 kHz ones
+        assert!(!region.link_adr_req(&mut plan, 4, 0, 0x0000, 7).is_ok());
+        assert!(region.link_adr_req(&mut plan, 4, 0, 0x0002, 7).is_ok());
+        assert_eq!(plan.enabled_count(), 1);
+        assert!(plan.is_enabled(65));
+
+        // ChMaskCntl 5 turns on sub-band 2 and its 500 kHz channel
+        let mut plan = region.default_channels();
+        assert!(region.link_adr_req(&mut plan, 0, 0, 0x0002, 5).is_ok());
+        assert_eq!(plan.mask[..5], [0xFF00, 0, 0, 0, 0x0002]);
+
+        // DR0 needs a 125 kHz channel
+        let mut plan = region.default_channels();
+        let status = region.link_adr_req(&mut plan, 0, 0, 0x0001, 7);
+        assert_eq!(status.to_byte(), 0b101);
+
+        let region = Region::Cn470;
+        let mut plan = region.default_channels();
+        assert!(region.link_adr_req(&mut plan, 0, 0, 0x00FF, 2).is_ok());
+        assert_eq!(plan.mask[2], 0x00FF);
+    }
+
+    #[test]
+    fn new_and_dl_channel_req() {
+        let region = Region::Eu868;
+        let mut plan = region.default_channels();
+
+        // default channels are read-only
+        let status = region.new_channel_req(&mut plan, 0, 867_100_000, 0, 5);
+        assert_eq!(status.to_byte(), 0);
+
+        let status = region.new_channel_req(&mut plan, 3, 867_100_000, 0, 5);
+        assert_eq!(status.to_byte(), 0b11);
+        assert!(plan.is_enabled(3));
+
+        // bad frequency or data rate range, nothing changes
+        let before = plan;
+        assert_eq!(
+            region
+                .new_channel_req(&mut plan, 4, 900_000_000, 0, 5)
+                .to_byte(),
+            0b10
+        );
+        assert_eq!(
+            region
+                .new_channel_req(&mut plan, 4, 867_300_000, 5, 2)
+                .to_byte(),
+            0b01
+        );
+        assert_eq!(plan, before);
+
+        // frequency 0 removes the channel
+        assert_eq!(
+            region.new_channel_req(&mut plan, 3, 0, 0, 0).to_byte(),
+            0b11
+        );
+        assert!(plan.channels[3].is_none());
+        assert!(!plan.is_enabled(3));
+
+        assert_eq!(
+            region.dl_channel_req(&mut plan, 1, 869_525_000).to_byte(),
+            0b11
+        );
+        assert_eq!(plan.channels[1].unwrap().rx1_frequency, 869_525_000);
+        assert_eq!(
+            region.dl_channel_req(&mut plan, 5, 869_525_000).to_byte(),
+            0b01
+        );
+
+        let mut plan = Region::Us915.default_channels();
+        assert_eq!(
+            Region::Us915
+                .new_channel_req(&mut plan, 3, 903_000_000, 0, 3)
+                .to_byte(),
+            0
+        );
+        assert_eq!(
+            Region::Us915
+                .dl_channel_req(&mut plan, 0, 923_300_000)
+                .to_byte(),
+            0
+        );
+    }
+
+    #[test]
+    fn tx_param_setup() {
+        assert_eq!(Region::Eu868.tx_param_setup_eirp(0x05), None);
+        let as923 = Region::As923(As923Group::Group1);
+        assert_eq!(as923.tx_param_setup_eirp(0x05), Some(16.0));
+        assert_eq!(as923.tx_param_setup_eirp(0x3F), Some(36.0));
+    }
+}
@@ -0,0 +1,163 @@
+use super::{CHANNEL_MASK_SIZE, Channel, ChannelPlan, ChannelPlanKind, DataRate, RegionParams};
+
+/// 125 kHz uplink channels
+pub const NB_125KHZ_CHANNELS: usize = 64;
+/// 500 kHz uplink channels
+pub const NB_500KHZ_CHANNELS: usize = 8;
+
+/// Lowest uplink data rate
+pub const TX_MIN_DATARATE: u8 = 0;
+/// Highest uplink data rate
+pub const TX_MAX_DATARATE: u8 = 4;
+/// Default uplink data rate
+pub const DEFAULT_DATARATE: u8 = 0;
+/// Largest RX1 data rate offset
+pub const MAX_RX1_DR_OFFSET: u8 = 3;
+
+/// Highest TX power index (lowest power)
+pub const MIN_TX_POWER: u8 = 14;
+/// Default max EIRP (dBm)
+pub const DEFAULT_MAX_EIRP: f32 = 30.0;
+/// Default antenna gain (dBi)
+pub const DEFAULT_ANTENNA_GAIN: f32 = 2.15;
+
+/// RX2 frequency (Hz)
+pub const RX_WND_2_FREQ: u32 = 923_300_000;
+/// RX2 data rate
+pub const RX_WND_2_DR: u8 = 8;
+
+/// DR0..DR13
+pub const DATA_RATES: [DataRate; 14] = [
+    DataRate::lora(10, 0, 19),
+    DataRate::lora(9, 0, 61),
+    DataRate::lora(8, 0, 133),
+    DataRate::lora(7, 0, 250),
+    DataRate::lora(8, 2, 250),
+    DataRate::RFU,
+    DataRate::RFU,
+    DataRate::RFU,
+    DataRate::lora(12, 2, 61),
+    DataRate::lora(11, 2, 137),
+    DataRate::lora(10, 2, 250),
+    DataRate::lora(9, 2, 250),
+    DataRate::lora(8, 2, 250),
+    DataRate::lora(7, 2, 250),
+];
+
+/// No duty cycle, dwell time is handled by the frame sizes
+pub const BANDS: [u16; 1] = [1];
+
+/// US915 parameters
+pub const PARAMS: RegionParams = RegionParams {
+    data_rates: &DATA_RATES,
+    tx_min_dr: TX_MIN_DATARATE,
+    tx_max_dr: TX_MAX_DATARATE,
+    default_dr: DEFAULT_DATARATE,
+    max_rx1_dr_offset: MAX_RX1_DR_OFFSET,
+    max_eirp: DEFAULT_MAX_EIRP,
+    antenna_gain: DEFAULT_ANTENNA_GAIN,
+    max_tx_power_index: MIN_TX_POWER,
+    rx2_frequency: RX_WND_2_FREQ,
+    rx2_dr: RX_WND_2_DR,
+    bands: &BANDS,
+    plan: ChannelPlanKind::Fixed,
+    default_channels: NB_125KHZ_CHANNELS + NB_500KHZ_CHANNELS,
+    tx_param_setup: false,
+};
+
+/// Downlink channel `index % 8` used in RX1
+pub fn rx1_frequency(index: usize) -> u32 {
+    923_300_000 + (index % 8) as u32 * 600_000
+}
+
+/// 64 channels at 902.3 MHz + 200 kHz (DR0..3), 8 at 903.0 MHz + 1.6 MHz (DR4)
+pub fn default_channels() -> ChannelPlan {
+    fixed_plan(902_300_000, 903_000_000, 3, 4, rx1_frequency)
+}
+
+/// 125 kHz and 500 kHz channels of a US915 like plan, all enabled
+pub(super) fn fixed_plan(
+    base_125khz: u32,
+    base_500khz: u32,
+    max_dr_125khz: u8,
+    dr_500khz: u8,
+    rx1_frequency: fn(usize) -> u32,
+) -> ChannelPlan {
+    let mut plan = ChannelPlan::empty();
+    for i in 0..NB_125KHZ_CHANNELS {
+        plan.channels[i] = Some(Channel {
+            frequency: base_125khz + i as u32 * 200_000,
+            rx1_frequency: rx1_frequency(i),
+            min_dr: 0,
+            max_dr: max_dr_125khz,
+            band: 0,
+        });
+        plan.set_enabled(i, true);
+    }
+    for i in 0..NB_500KHZ_CHANNELS {
+        let index = NB_125KHZ_CHANNELS + i;
+        plan.channels[index] = Some(Channel {
+            frequency: base_500khz + i as u32 * 1_600_000,
+            rx1_frequency: rx1_frequency(index),
+            min_dr: dr_500khz,
+            max_dr: dr_500khz,
+            band: 0,
+        });
+        plan.set_enabled(index, true);
+    }
+    plan
+}
+
+/// Band of a frequency
+pub fn band_of(frequency: u32) -> Option<u8> {
+    (902_000_000..=928_000_000)
+        .contains(&frequency)
+        .then_some(0)
+}
+
+/// Join data rate, alternates between a 125 kHz (DR0) and a 500 kHz (DR4) channel
+pub fn join_dr(trial: u16) -> u8 {
+    if trial % 2 == 1 { 0 } else { 4 }
+}
+
+/// RX1 data rate: DR0..4 map to DR10..13, lowered by the offset down to DR8
+pub fn rx1_dr(dr: u8, offset: u8) -> u8 {
+    (10 + dr as i8 - offset as i8).clamp(8, 13) as u8
+}
+
+/// LinkADRReq mask of a US915 like plan
+///
+/// ChMaskCntl 0..4 sets a block of 16 channels, 5 enables 125 kHz blocks of 8 channels
+/// (and their 500 kHz channel), 6/7 enable/disable every 125 kHz channel and set the
+/// 500 kHz channels from ChMask.
+pub(super) fn link_adr_mask(
+    plan: &ChannelPlan,
+    ch_mask: u16,
+    ch_mask_cntl: u8,
+) -> Option<[u16; CHANNEL_MASK_SIZE]> {
+    let mut mask = plan.mask;
+    match ch_mask_cntl {
+        0..=3 => mask[ch_mask_cntl as usize] = ch_mask,
+        4 => mask[4] = ch_mask & 0x00FF,
+        5 => {
+            let blocks = ch_mask & 0x00FF;
+            for block in 0..8 {
+                let on = blocks & (1 << block) != 0;
+                let bits = 0xFFu16 << ((block % 2) * 8);
+                if on {
+                    mask[block / 2] |= bits;
+                } else {
+                    mask[block / 2] &= !bits;
+                }
+            }
+            mask[4] = blocks;
+        }
+        6 | 7 => {
+            let all = if ch_mask_cntl == 6 { 0xFFFF } else { 0 };
+            mask[..4].fill(all);
+            mask[4] = ch_mask & 0x00FF;
+        }
+        _ => return None,
+    }
+    Some(mask)
+}
@@ -5,7 +5,7 @@ use crate::{
     peripherals::regs::RTC,
 };
 
-const MAX_TIMERS: usize = 16;
+pub const MAX_TIMERS: usize = 16;
 
 static NEXT_TIMER_ID: AtomicUsize = AtomicUsize::new(1);
 static HAS_LOOPED_THROUGH_MAIN: AtomicU8 = AtomicU8::new(0);
@@ -20,6 +20,16 @@ pub struct TimerEvent {
     pub callback: Option<fn()>,
 }
 
+/// Queued timer, as reported by [`timer_pending`]
+#[derive(Debug, Clone, Copy)]
+pub struct PendingTimer {
+    pub id: usize,
+    /// time left (ms)
+    pub remaining: usize,
+    pub reload_value: usize,
+    pub callback: Option<fn()>,
+}
+
 pub struct TimerSysTime {
     pub seconds: usize,
     pub subseconds: i16,
@@ -211,6 +221,22 @@ pub fn timer_stop(timer: &mut TimerEvent) {
     _enable_irq();
 }
 
+/// Snapshot of the queued timers, the next one to expire first
+pub fn timer_pending() -> heapless::Vec<PendingTimer, MAX_TIMERS> {
+    _disable_irq();
+    let elapsed = RTC.get_elapsed_time() as usize;
+    let pending = unsafe { TIMER_EVENTS.iter() }
+        .map(|event| PendingTimer {
+            id: event.id,
+            remaining: event.timestamp.saturating_sub(elapsed),
+            reload_value: event.reload_value,
+            callback: event.callback,
+        })
+        .collect();
+    _enable_irq();
+    pending
+}
+
 pub fn timer_reset(timer: &mut TimerEvent) {
     timer_stop(timer);
     timer_start(timer);
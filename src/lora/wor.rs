@@ -0,0 +1,207 @@
+use crate::lora::{
+    radio::{
+        BANDWIDTHS, RadioEvents, RadioModem, radio_init, radio_rx, radio_set_channel,
+        radio_set_rx_config, radio_set_rx_duty_cycle, radio_sleep, radio_start_cad,
+        radio_symb_time,
+    },
+    timer::{TimerEvent, timer_init, timer_set_value, timer_start, timer_stop},
+};
+
+/// Extra preamble symbols on top of the wake interval, covers clock drift and the
+/// receiver start-up
+pub const WOR_PREAMBLE_MARGIN: u16 = 4;
+/// Time (ms) allowed for the frame after the preamble once the receiver is on
+const WOR_FRAME_TIMEOUT: usize = 1_000;
+/// `radio_set_rx_duty_cycle` time step (ns)
+const RX_DUTY_CYCLE_STEP_NS: u64 = 15_625;
+
+/// How the receiver looks for preambles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorMode {
+    /// The radio alternates listening and sleep by itself (`SetRxDutyCycle`), the MCU only
+    /// wakes on a received frame
+    RxDutyCycle,
+    /// A timer wakes the MCU every interval to run a CAD, the receiver only turns on when
+    /// it detects a preamble. Costs an MCU wake-up per interval, but a CAD draws less than
+    /// listening.
+    Cad,
+}
+
+/// Wake-on-radio receiver settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorConfig {
+    /// frequency (Hz)
+    pub frequency: u32,
+    /// spreading factor
+    pub sf: u8,
+    /// bandwidth index, 0 = 125 kHz, 1 = 250 kHz, 2 = 500 kHz
+    pub bw: u8,
+    /// coding rate, 1 = 4/5 .. 4 = 4/8
+    pub coding_rate: u8,
+    /// time between two looks at the channel (ms)
+    pub interval: u32,
+    /// symbols listened to (or CAD symbols) at every look
+    pub listen_symbols: u8,
+    /// how to look for preambles
+    pub mode: WorMode,
+}
+
+impl WorConfig {
+    /// LoRa symbol time (ms)
+    pub fn symbol_time(&self) -> f64 {
+        let bw = BANDWIDTHS
+            .get(self.bw as usize)
+            .copied()
+            .unwrap_or(BANDWIDTHS[0]);
+        radio_symb_time(bw, self.sf)
+    }
+
+    /// Preamble the senders must use so at least one look falls inside it
+    pub fn preamble_symbols(&self) -> u16 {
+        wor_preamble_symbols(self.symbol_time(), self.interval, self.listen_symbols)
+    }
+}
+
+/// Preamble length (symbols) covering a whole `interval` (ms) plus two looks of
+/// `listen_symbols`, saturated to the 16-bit preamble register
+pub fn wor_preamble_symbols(symbol_time: f64, interval: u32, listen_symbols: u8) -> u16 {
+    let interval_symbols = libm::ceil(interval as f64 / symbol_time);
+    let symbols = interval_symbols + 2.0 * listen_symbols as f64 + WOR_PREAMBLE_MARGIN as f64;
+    symbols.min(u16::MAX as f64) as u16
+}
+
+/// Longest wake interval (ms) a `preamble_symbols` preamble allows, the inverse of
+/// [`wor_preamble_symbols`]
+pub fn wor_wake_interval(symbol_time: f64, preamble_symbols: u16, listen_symbols: u8) -> u32 {
+    let overhead = 2 * listen_symbols as u16 + WOR_PREAMBLE_MARGIN;
+    let symbols = preamble_symbols.saturating_sub(overhead);
+    libm::floor(symbols as f64 * symbol_time) as u32
+}
+
+/// Average share of the time spent listening, in per mille
+pub fn wor_listen_ratio(symbol_time: f64, interval: u32, listen_symbols: u8) -> u16 {
+    let listen = listen_symbols as f64 * symbol_time;
+    (listen / (listen + interval as f64) * 1000.0) as u16
+}
+
+/// Convert ms to `radio_set_rx_duty_cycle` steps
+fn duty_cycle_steps(ms: f64) -> usize {
+    (ms * 1_000_000.0 / RX_DUTY_CYCLE_STEP_NS as f64) as usize
+}
+
+struct Wor {
+    config: Option<WorConfig>,
+    on_rx: Option<fn(payload: &[u8], rssi: i16, snr: i8)>,
+    cad_timer: TimerEvent,
+}
+
+static mut WOR: Wor = Wor {
+    config: None,
+    on_rx: None,
+    cad_timer: TimerEvent {
+        id: 0,
+        timestamp: 0,
+        reload_value: 0,
+        is_running: false,
+        callback: None,
+    },
+};
+
+static WOR_EVENTS: RadioEvents = RadioEvents {
+    tx_done: None,
+    tx_timeout: None,
+    rx_done: Some(on_rx_done),
+    rx_timeout: Some(rearm),
+    rx_error: Some(rearm),
+    rx_crc_error: None,
+    fhss_change_channel: None,
+    cad_done: Some(on_cad_done),
+};
+
+/// Start the low-power receiver, `on_rx` gets every frame.
+///
+/// Takes over the radio events like the MAC does. Senders use
+/// [`WorConfig::preamble_symbols`] as their preamble length.
+pub fn wor_start(config: &WorConfig, on_rx: fn(payload: &[u8], rssi: i16, snr: i8)) {
+    let wor = unsafe { &mut WOR };
+    wor.config = Some(*config);
+    wor.on_rx = Some(on_rx);
+
+    radio_init(&WOR_EVENTS);
+    radio_set_channel(config.frequency as usize);
+    radio_set_rx_config(
+        RadioModem::LoRa,
+        config.bw as usize,
+        config.sf as usize,
+        config.coding_rate,
+        0,
+        config.preamble_symbols(),
+        0,
+        false,
+        0,
+        true,
+        false,
+        0,
+        false,
+        false,
+    );
+
+    timer_init(&mut wor.cad_timer, on_cad_timer);
+    rearm();
+}
+
+/// Stop looking for preambles and put the radio to sleep
+pub fn wor_stop() {
+    let wor = unsafe { &mut WOR };
+    wor.config = None;
+    timer_stop(&mut wor.cad_timer);
+    radio_sleep();
+}
+
+/// Go back to looking for preambles
+fn rearm() {
+    let wor = unsafe { &mut WOR };
+    let Some(config) = wor.config else {
+        return;
+    };
+
+    match config.mode {
+        WorMode::RxDutyCycle => {
+            let listen = config.listen_symbols as f64 * config.symbol_time();
+            radio_set_rx_duty_cycle(
+                duty_cycle_steps(listen),
+                duty_cycle_steps(config.interval as f64),
+            );
+        }
+        WorMode::Cad => {
+            radio_sleep();
+            timer_set_value(&mut wor.cad_timer, config.interval as usize);
+            timer_start(&mut wor.cad_timer);
+        }
+    }
+}
+
+fn on_cad_timer() {
+    if let Some(config) = unsafe { WOR.config } {
+        radio_start_cad(config.listen_symbols);
+    }
+}
+
+fn on_cad_done(channel_activity_detected: bool) {
+    let Some(config) = (unsafe { WOR.config }) else {
+        return;
+    };
+    if channel_activity_detected {
+        // the rest of the preamble plus the frame
+        radio_rx(config.interval as usize + WOR_FRAME_TIMEOUT);
+    } else {
+        rearm();
+    }
+}
+
+fn on_rx_done(payload: &[u8], rssi: i16, snr: i8) {
+    if let Some(on_rx) = unsafe { WOR.on_rx } {
+        on_rx(payload, rssi, snr);
+    }
+    rearm();
+}
@@ -1,30 +1,37 @@
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 #![allow(static_mut_refs)]
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
 
+#[cfg(not(test))]
 core::arch::global_asm!(include_str!("startup.S"));
 
-use crate::{
-    class_c::app_start,
-    peripherals::{
-        delay::delay_ms,
-        gpio::{GpioMode, GpioPin},
-        rcc::{
-            RCC_OSC_XO32K, RCC_PERIPHERAL_GPIOA, RCC_PERIPHERAL_GPIOB, RCC_PERIPHERAL_GPIOC,
-            RCC_PERIPHERAL_GPIOD, RCC_PERIPHERAL_LORA, RCC_PERIPHERAL_PWR, RCC_PERIPHERAL_RTC,
-            RCC_PERIPHERAL_SAC, RCC_PERIPHERAL_UART0,
-        },
-        regs::{GPIOA, GPIOB, PWR, RCC, RTC, UART0},
+use crate::peripherals::{
+    buffered_uart::uart0,
+    delay::delay_ms,
+    gpio::{GpioMode, GpioPin},
+    rcc::{
+        RCC_OSC_XO32K, RCC_PERIPHERAL_GPIOA, RCC_PERIPHERAL_GPIOB, RCC_PERIPHERAL_GPIOC,
+        RCC_PERIPHERAL_GPIOD, RCC_PERIPHERAL_LORA, RCC_PERIPHERAL_PWR, RCC_PERIPHERAL_RTC,
+        RCC_PERIPHERAL_SAC, RCC_PERIPHERAL_UART0,
     },
+    regs::{GPIOA, GPIOB, PWR, RCC, RTC, UART_IFLS_RX_1_2, UART0},
 };
 
+/// AT command interface
+pub mod at;
 /// Class C LoRaWAN module
+#[cfg(not(test))]
 pub mod class_c;
 /// Core Cortex M4 Utilities
 pub mod cortex;
+/// AES-128, CTR, CMAC, SHA-256 and CTR_DRBG in software
+pub mod crypto;
 /// C FFI Bindings for ASR6601 SDK
+#[cfg(not(test))]
 pub mod ffi;
+/// Single-channel LoRaWAN gateway
+pub mod gateway;
 /// Interrupts
 pub mod interrupts;
 /// LoRa module
@@ -35,6 +42,14 @@ pub mod lora_config;
 pub mod peripherals;
 /// Serial printing
 pub mod print;
+/// Random number service
+pub mod rng;
+/// Debug shell
+pub mod shell;
+/// Raw LoRa packet sniffer
+pub mod sniffer;
+/// Helpers shared by the applications
+pub mod util;
 
 // use crate::lora::radio::{self, RadioEvents, RadioModem};
 // use crate::lora::timer::{self, TimerEvent, TimerSysTime};
@@ -123,6 +138,7 @@ pub mod print;
 //         rx_done: None,
 //         rx_timeout: None,
 //         rx_error: None,
+//         rx_crc_error: None,
 //         fhss_change_channel: None,
 //         cad_done: None,
 //     };
@@ -195,12 +211,30 @@ pub mod print;
 // }
 
 /// entry point
+#[cfg(not(test))]
 #[unsafe(no_mangle)]
 pub extern "C" fn main() -> ! {
     board_init();
     // run_smoke_tests();
     // loop {}
-    app_start();
+    #[cfg(feature = "sniffer")]
+    sniffer::app_start();
+    #[cfg(all(feature = "gateway", not(feature = "sniffer")))]
+    gateway::app_start();
+    #[cfg(all(feature = "at", not(any(feature = "sniffer", feature = "gateway"))))]
+    at::server::app_start();
+    #[cfg(all(
+        feature = "shell",
+        not(any(feature = "sniffer", feature = "gateway", feature = "at"))
+    ))]
+    shell::commands::app_start();
+    #[cfg(not(any(
+        feature = "sniffer",
+        feature = "gateway",
+        feature = "at",
+        feature = "shell"
+    )))]
+    class_c::app_start();
 }
 
 /// initialize UART for logging
@@ -210,6 +244,7 @@ pub fn uart_log_init() {
 
     UART0.init(Default::default()).unwrap();
     UART0.cmd(true);
+    uart0().enable(UART_IFLS_RX_1_2);
 }
 
 /// init board, enable peripheral clocks, etc.
@@ -240,6 +275,7 @@ pub fn board_init() {
 }
 
 /// rust panic handler
+#[cfg(not(test))]
 #[panic_handler]
 pub fn panic_handler(info: &core::panic::PanicInfo) -> ! {
     if let Some(location) = info.location() {
@@ -252,5 +288,7 @@ pub fn panic_handler(info: &core::panic::PanicInfo) -> ! {
     } else {
         println!("Panicked at unknown location");
     }
+    // the message sits in the TX buffer, its interrupt may never come
+    uart0().flush_tx();
     loop {}
 }
@@ -0,0 +1,297 @@
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::{
+    cortex::{
+        IRQType,
+        func::{_disable_irq, _enable_irq},
+        nvic_disable_irq, nvic_enable_irq,
+    },
+    peripherals::{
+        buffered_uart::{
+            ERROR_BUFFER_OVERFLOW, ERROR_FRAMING, ERROR_OVERRUN, ERROR_PARITY, RingBuffer,
+            UartError, UartErrors, critical_section,
+        },
+        lpuart::{LpuartConfig, LpuartCr0, LpuartInterrupt, LpuartRxStatus},
+        pwr::PWR_LP_MODE_STOP3,
+        rcc::{RCC_LPUART_CLK_SOURCE_XO32K, RCC_OSC_XO32K, RCC_PERIPHERAL_LPUART},
+        regs::{LPUART, PWR, RCC},
+    },
+};
+
+/// Receive buffer size of the LPUART
+pub const LPUART_RX_BUFFER_SIZE: usize = 64;
+/// Transmit buffer size of the LPUART
+pub const LPUART_TX_BUFFER_SIZE: usize = 128;
+
+static LPUART_RX: RingBuffer<LPUART_RX_BUFFER_SIZE> = RingBuffer::new();
+static LPUART_TX: RingBuffer<LPUART_TX_BUFFER_SIZE> = RingBuffer::new();
+/// `ERROR_*` bits of `buffered_uart`
+static LPUART_ERRORS: AtomicU8 = AtomicU8::new(0);
+static LPUART_ENABLED: AtomicBool = AtomicBool::new(false);
+/// a byte was written since the last [`BufferedLpuart::flush_tx`]
+static LPUART_TX_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Receive status flags and the error they raise, cleared by the interrupt handler
+const LPUART_RX_ERRORS: [(LpuartRxStatus, u8); 3] = [
+    (LpuartRxStatus::Sr0ParityError, ERROR_PARITY),
+    (LpuartRxStatus::Sr0StopError, ERROR_FRAMING),
+    (LpuartRxStatus::Sr0RxOverflow, ERROR_OVERRUN),
+];
+
+/// Interrupt-driven LPUART, a serial channel that keeps working in STOP mode.
+///
+/// Clocked from the 32.768 kHz crystal, the LPUART receives while the MCU is in deep sleep
+/// and wakes it on the start bit or the received byte, as chosen by the wake-up fields of
+/// [`LpuartConfig`]. Bytes are collected by the interrupt handler, [`Self::sleep`] enters
+/// STOP3 until the next one. The pins are left to the caller (`set_iomux`).
+#[derive(Clone, Copy)]
+pub struct BufferedLpuart;
+
+impl BufferedLpuart {
+    /// Clock the LPUART from XO32K, configure it with `config` and switch to interrupt-driven
+    /// operation. Baud rates above 9600 don't work from a 32 kHz clock.
+    pub fn init(&self, config: LpuartConfig) {
+        RCC.enable_oscillator(RCC_OSC_XO32K, true);
+        RCC.set_lpuart_clk_src(RCC_LPUART_CLK_SOURCE_XO32K);
+        RCC.enable_peripheral_clk(RCC_PERIPHERAL_LPUART, true);
+
+        LPUART.init(config);
+        LPUART.config_tx(true);
+        LPUART.config_rx(true);
+        self.enable();
+    }
+
+    /// Enable the receive, error and wake-up interrupts
+    pub fn enable(&self) {
+        for (status, _) in LPUART_RX_ERRORS {
+            LPUART.clear_rx_status(status);
+        }
+        LPUART.clear_rx_status(LpuartRxStatus::Sr0StartValid);
+        LPUART.clear_rx_status(LpuartRxStatus::Sr0RxDone);
+
+        LPUART.config_interrupt(LpuartInterrupt::Cr1RxNotEmpty, true);
+        LPUART.config_interrupt(LpuartInterrupt::Cr1ParityError, true);
+        LPUART.config_interrupt(LpuartInterrupt::Cr1StopError, true);
+        LPUART.config_interrupt(LpuartInterrupt::Cr1RxOverflow, true);
+        // with start bit wake-up the MCU is running again before the byte is complete
+        LPUART.config_interrupt(
+            LpuartInterrupt::Cr1StartValid,
+            LPUART.cr0.read() & LpuartCr0::StartWakeup as usize != 0,
+        );
+
+        LPUART_ENABLED.store(true, Ordering::Release);
+        nvic_enable_irq(IRQType::Lpuart);
+    }
+
+    /// Back to polled operation, after sending what is buffered
+    pub fn disable(&self) {
+        self.flush_tx();
+        nvic_disable_irq(IRQType::Lpuart);
+        for interrupt in [
+            LpuartInterrupt::Cr1RxNotEmpty,
+            LpuartInterrupt::Cr1ParityError,
+            LpuartInterrupt::Cr1StopError,
+            LpuartInterrupt::Cr1RxOverflow,
+            LpuartInterrupt::Cr1StartValid,
+            LpuartInterrupt::Cr1TxEmpty,
+        ] {
+            LPUART.config_interrupt(interrupt, false);
+        }
+        LPUART_ENABLED.store(false, Ordering::Release);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        LPUART_ENABLED.load(Ordering::Acquire)
+    }
+
+    /// Bytes waiting in the receive buffer
+    pub fn rx_available(&self) -> usize {
+        LPUART_RX.len()
+    }
+
+    /// Next received byte, without waiting
+    pub fn try_read_byte(&self) -> Option<u8> {
+        LPUART_RX.pop()
+    }
+
+    /// Copy the received bytes into `buf` without waiting, returns how many
+    pub fn try_read(&self, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+        for slot in buf.iter_mut() {
+            let Some(byte) = LPUART_RX.pop() else {
+                break;
+            };
+            *slot = byte;
+            len += 1;
+        }
+        len
+    }
+
+    /// Queue as much of `data` as fits without waiting, returns how many bytes
+    pub fn try_write(&self, data: &[u8]) -> usize {
+        let len = critical_section(|| {
+            data.iter()
+                .take_while(|&&byte| LPUART_TX.push(byte))
+                .count()
+        });
+        self.start_tx();
+        len
+    }
+
+    /// Queue all of `data`, waiting for room when the buffer is full
+    pub fn queue_all(&self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let len = self.try_write(data);
+            data = &data[len..];
+        }
+    }
+
+    /// Wait until everything queued has been sent
+    pub fn flush_tx(&self) {
+        while !LPUART_TX.is_empty() {
+            self.start_tx();
+        }
+        if LPUART_TX_STARTED.swap(false, Ordering::AcqRel) {
+            while !LPUART.get_tx_done() {}
+        }
+    }
+
+    /// Drop the received bytes
+    pub fn clear_rx(&self) {
+        LPUART_RX.clear();
+    }
+
+    /// Receive errors since the last call
+    pub fn take_errors(&self) -> UartErrors {
+        UartErrors::from_bits(LPUART_ERRORS.swap(0, Ordering::AcqRel))
+    }
+
+    /// Enter STOP3 until the LPUART or another wake-up source (RTC, radio, GPIO) fires.
+    ///
+    /// Returns at once when received bytes are waiting, the queued ones are sent first.
+    pub fn sleep(&self) {
+        self.flush_tx();
+        _disable_irq();
+        if LPUART_RX.is_empty() {
+            // a pending interrupt ends the sleep even with the interrupts disabled
+            PWR.deepsleep_wfi(PWR_LP_MODE_STOP3);
+        }
+        _enable_irq();
+    }
+
+    /// Move the next queued byte into the data register, the TX empty interrupt sends the rest
+    fn start_tx(&self) {
+        critical_section(|| {
+            self.fill_tx();
+            LPUART.config_interrupt(LpuartInterrupt::Cr1TxEmpty, !LPUART_TX.is_empty());
+        });
+    }
+
+    fn fill_tx(&self) {
+        if !LPUART.get_tx_empty() {
+            return;
+        }
+        if let Some(byte) = LPUART_TX.pop() {
+            LPUART.clear_tx_done();
+            LPUART.data.write(byte as usize);
+            LPUART_TX_STARTED.store(true, Ordering::Release);
+        }
+    }
+
+    /// Interrupt handler body, called by `LPUART_IRQHandler`
+    pub fn on_irq(&self) {
+        let mut errors = 0;
+        for (status, error) in LPUART_RX_ERRORS {
+            if LPUART.get_rx_status(status) {
+                LPUART.clear_rx_status(status);
+                errors |= error;
+            }
+        }
+        // the wake-up events carry no data
+        for status in [LpuartRxStatus::Sr0StartValid, LpuartRxStatus::Sr0RxDone] {
+            if LPUART.get_rx_status(status) {
+                LPUART.clear_rx_status(status);
+            }
+        }
+
+        while LPUART.get_rx_not_empty() {
+            if !LPUART_RX.push(LPUART.data.read() as u8) {
+                errors |= ERROR_BUFFER_OVERFLOW;
+            }
+        }
+        LPUART_ERRORS.fetch_or(errors, Ordering::AcqRel);
+
+        self.fill_tx();
+        if LPUART_TX.is_empty() {
+            LPUART.config_interrupt(LpuartInterrupt::Cr1TxEmpty, false);
+        }
+    }
+}
+
+/// Interrupt handler of the LPUART, does nothing unless [`BufferedLpuart`] is enabled
+pub fn lpuart_irq_handler() {
+    if BufferedLpuart.is_enabled() {
+        BufferedLpuart.on_irq();
+    }
+}
+
+impl core::fmt::Write for BufferedLpuart {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.queue_all(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl embedded_io::ErrorType for BufferedLpuart {
+    type Error = UartError;
+}
+
+impl embedded_io::Read for BufferedLpuart {
+    /// Waits for at least one byte, a receive error seen before is reported first
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if let Some(error) = self.take_errors().first() {
+                return Err(error);
+            }
+            let len = self.try_read(buf);
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+impl embedded_io::ReadReady for BufferedLpuart {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.rx_available() > 0)
+    }
+}
+
+impl embedded_io::Write for BufferedLpuart {
+    /// Waits for room for at least one byte
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let len = self.try_write(buf);
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush_tx();
+        Ok(())
+    }
+}
+
+impl embedded_io::WriteReady for BufferedLpuart {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!LPUART_TX.is_full())
+    }
+}
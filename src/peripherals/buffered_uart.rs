@@ -0,0 +1,506 @@
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+};
+
+use crate::{
+    cortex::{
+        IRQType,
+        func::{_disable_irq, _get_primask, _set_primask},
+        nvic_disable_irq, nvic_enable_irq,
+    },
+    peripherals::{
+        regs::{
+            UART_DR_BREAK_ERROR, UART_DR_DATA, UART_DR_FRAME_ERROR, UART_DR_OVERRUN_ERROR,
+            UART_DR_PARITY_ERROR, UART_IFLS_TX_1_2, UART_INTERRUPT_BREAK_ERROR,
+            UART_INTERRUPT_FRAME_ERROR, UART_INTERRUPT_OVERRUN_ERROR, UART_INTERRUPT_PARITY_ERROR,
+            UART_INTERRUPT_RX_DONE, UART_INTERRUPT_RX_TIMEOUT, UART_INTERRUPT_TX_DONE, UART0,
+            UART0_BASE, UART1, UART1_BASE, UART2, UART2_BASE, UART3, UART3_BASE,
+        },
+        uart::{Uart, UartFlag},
+    },
+};
+
+/// Receive buffer size of each UART
+pub const UART_RX_BUFFER_SIZE: usize = 128;
+/// Transmit buffer size of each UART
+pub const UART_TX_BUFFER_SIZE: usize = 256;
+
+/// Error interrupts of the UART
+const UART_INTERRUPT_ERRORS: usize = UART_INTERRUPT_FRAME_ERROR
+    | UART_INTERRUPT_PARITY_ERROR
+    | UART_INTERRUPT_BREAK_ERROR
+    | UART_INTERRUPT_OVERRUN_ERROR;
+
+/// Byte ring buffer for one producer and one consumer, which may be an interrupt handler
+pub struct RingBuffer<const N: usize> {
+    buf: UnsafeCell<[u8; N]>,
+    /// bytes ever written, wrapping
+    head: AtomicUsize,
+    /// bytes ever read, wrapping
+    tail: AtomicUsize,
+}
+
+unsafe impl<const N: usize> Sync for RingBuffer<N> {}
+
+impl<const N: usize> RingBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: UnsafeCell::new([0; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.head
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.tail.load(Ordering::Acquire))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    /// Append `byte`, `false` when the buffer is full. Producer side only.
+    pub fn push(&self, byte: u8) -> bool {
+        let head = self.head.load(Ordering::Relaxed);
+        if head.wrapping_sub(self.tail.load(Ordering::Acquire)) == N {
+            return false;
+        }
+        unsafe { (*self.buf.get())[head % N] = byte };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Take the oldest byte. Consumer side only.
+    pub fn pop(&self) -> Option<u8> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if self.head.load(Ordering::Acquire) == tail {
+            return None;
+        }
+        let byte = unsafe { (*self.buf.get())[tail % N] };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Some(byte)
+    }
+
+    /// Drop everything. Consumer side only.
+    pub fn clear(&self) {
+        self.tail
+            .store(self.head.load(Ordering::Acquire), Ordering::Release);
+    }
+}
+
+impl<const N: usize> Default for RingBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Receive errors since they were last taken
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UartErrors {
+    /// stop bit missing
+    pub framing: bool,
+    pub parity: bool,
+    /// line held low for longer than a frame
+    pub line_break: bool,
+    /// the hardware FIFO was full
+    pub overrun: bool,
+    /// the receive buffer was full, bytes were dropped
+    pub buffer_overflow: bool,
+}
+
+pub(super) const ERROR_FRAMING: u8 = 1 << 0;
+pub(super) const ERROR_PARITY: u8 = 1 << 1;
+pub(super) const ERROR_BREAK: u8 = 1 << 2;
+pub(super) const ERROR_OVERRUN: u8 = 1 << 3;
+pub(super) const ERROR_BUFFER_OVERFLOW: u8 = 1 << 4;
+
+impl UartErrors {
+    pub(super) fn from_bits(bits: u8) -> Self {
+        Self {
+            framing: bits & ERROR_FRAMING != 0,
+            parity: bits & ERROR_PARITY != 0,
+            line_break: bits & ERROR_BREAK != 0,
+            overrun: bits & ERROR_OVERRUN != 0,
+            buffer_overflow: bits & ERROR_BUFFER_OVERFLOW != 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The error reported by the [`embedded_io`] traits, losing data first
+    pub fn first(&self) -> Option<UartError> {
+        if self.overrun {
+            Some(UartError::Overrun)
+        } else if self.buffer_overflow {
+            Some(UartError::BufferOverflow)
+        } else if self.framing {
+            Some(UartError::Framing)
+        } else if self.parity {
+            Some(UartError::Parity)
+        } else if self.line_break {
+            Some(UartError::Break)
+        } else {
+            None
+        }
+    }
+}
+
+/// Receive error of the [`embedded_io`] traits, the first one of [`UartErrors`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartError {
+    Framing,
+    Parity,
+    Break,
+    Overrun,
+    BufferOverflow,
+}
+
+impl embedded_io::Error for UartError {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            UartError::Framing | UartError::Parity | UartError::Break => {
+                embedded_io::ErrorKind::InvalidData
+            }
+            UartError::Overrun | UartError::BufferOverflow => embedded_io::ErrorKind::Other,
+        }
+    }
+}
+
+struct UartState {
+    rx: RingBuffer<UART_RX_BUFFER_SIZE>,
+    tx: RingBuffer<UART_TX_BUFFER_SIZE>,
+    /// `ERROR_*` bits
+    errors: AtomicU8,
+    /// the line went quiet after some bytes
+    idle: AtomicBool,
+    enabled: AtomicBool,
+}
+
+impl UartState {
+    const fn new() -> Self {
+        Self {
+            rx: RingBuffer::new(),
+            tx: RingBuffer::new(),
+            errors: AtomicU8::new(0),
+            idle: AtomicBool::new(false),
+            enabled: AtomicBool::new(false),
+        }
+    }
+}
+
+static UART_STATES: [UartState; 4] = [const { UartState::new() }; 4];
+
+/// Run `f` with the interrupts disabled, restoring the previous mask after
+pub(super) fn critical_section<R>(f: impl FnOnce() -> R) -> R {
+    let primask = _get_primask();
+    _disable_irq();
+    let result = f();
+    _set_primask(primask);
+    result
+}
+
+/// Interrupt-driven UART with receive and transmit ring buffers.
+///
+/// The UART must be initialised with [`Uart::init`] first. Receive errors are collected until
+/// [`Self::take_errors`], the idle flag is raised by the RX timeout: the line stayed quiet for
+/// 32 bit times with bytes left in the FIFO.
+#[derive(Clone, Copy)]
+pub struct BufferedUart {
+    uart: &'static Uart,
+    state: &'static UartState,
+    irq: IRQType,
+}
+
+impl BufferedUart {
+    /// Buffered driver of `uart`, one of `UART0`..`UART3`
+    pub fn new(uart: &'static Uart) -> Self {
+        let (index, irq) = match uart.ptr() as usize {
+            UART0_BASE => (0, IRQType::Uart0),
+            UART1_BASE => (1, IRQType::Uart1),
+            UART2_BASE => (2, IRQType::Uart2),
+            UART3_BASE => (3, IRQType::Uart3),
+            _ => unreachable!(),
+        };
+        Self {
+            uart,
+            state: &UART_STATES[index],
+            irq,
+        }
+    }
+
+    /// Switch to interrupt-driven operation with the FIFOs on. The RX interrupt fires at
+    /// `rx_threshold` (`UART_IFLS_RX_*`), the RX timeout collects the rest.
+    pub fn enable(&self, rx_threshold: usize) {
+        self.uart.config_fifo(true);
+        self.uart.set_rx_fifo_threshold(rx_threshold);
+        self.uart.set_tx_fifo_threshold(UART_IFLS_TX_1_2);
+        self.uart.clear_interrupt(
+            UART_INTERRUPT_RX_DONE
+                | UART_INTERRUPT_RX_TIMEOUT
+                | UART_INTERRUPT_TX_DONE
+                | UART_INTERRUPT_ERRORS,
+        );
+        self.uart.config_interrupt(
+            UART_INTERRUPT_RX_DONE | UART_INTERRUPT_RX_TIMEOUT | UART_INTERRUPT_ERRORS,
+            true,
+        );
+        self.state.enabled.store(true, Ordering::Release);
+        nvic_enable_irq(self.irq);
+    }
+
+    /// Back to polled operation, after sending what is buffered
+    pub fn disable(&self) {
+        self.flush_tx();
+        nvic_disable_irq(self.irq);
+        self.uart.config_interrupt(
+            UART_INTERRUPT_RX_DONE
+                | UART_INTERRUPT_RX_TIMEOUT
+                | UART_INTERRUPT_TX_DONE
+                | UART_INTERRUPT_ERRORS,
+            false,
+        );
+        self.state.enabled.store(false, Ordering::Release);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state.enabled.load(Ordering::Acquire)
+    }
+
+    /// Bytes waiting in the receive buffer
+    pub fn rx_available(&self) -> usize {
+        self.state.rx.len()
+    }
+
+    /// Bytes waiting to be sent
+    pub fn tx_pending(&self) -> usize {
+        self.state.tx.len()
+    }
+
+    /// Copy the received bytes into `buf` without waiting, returns how many
+    pub fn try_read(&self, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+        for slot in buf.iter_mut() {
+            let Some(byte) = self.state.rx.pop() else {
+                break;
+            };
+            *slot = byte;
+            len += 1;
+        }
+        len
+    }
+
+    /// Next received byte, without waiting
+    pub fn try_read_byte(&self) -> Option<u8> {
+        self.state.rx.pop()
+    }
+
+    /// Queue as much of `data` as fits without waiting, returns how many bytes
+    pub fn try_write(&self, data: &[u8]) -> usize {
+        let len = critical_section(|| {
+            data.iter()
+                .take_while(|&&byte| self.state.tx.push(byte))
+                .count()
+        });
+        self.start_tx();
+        len
+    }
+
+    /// Queue all of `data`, waiting for room when the buffer is full. Works with the interrupts
+    /// disabled too, the buffer is then emptied into the FIFO here.
+    pub fn queue_all(&self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let len = self.try_write(data);
+            data = &data[len..];
+        }
+    }
+
+    /// Everything queued has left the shift register
+    pub fn is_tx_idle(&self) -> bool {
+        self.state.tx.is_empty() && !self.uart.get_flag_status(UartFlag::Busy)
+    }
+
+    /// Wait until everything queued has left the shift register
+    pub fn flush_tx(&self) {
+        while !self.state.tx.is_empty() {
+            self.start_tx();
+        }
+        while self.uart.get_flag_status(UartFlag::Busy) {}
+    }
+
+    /// Drop the received bytes
+    pub fn clear_rx(&self) {
+        self.state.rx.clear();
+    }
+
+    /// Receive errors since the last call
+    pub fn take_errors(&self) -> UartErrors {
+        UartErrors::from_bits(self.state.errors.swap(0, Ordering::AcqRel))
+    }
+
+    /// `true` once after the line went idle following some bytes
+    pub fn take_idle(&self) -> bool {
+        self.state.idle.swap(false, Ordering::AcqRel)
+    }
+
+    /// Move queued bytes into the FIFO, the TX interrupt takes over while some are left
+    fn start_tx(&self) {
+        critical_section(|| {
+            self.fill_tx_fifo();
+            self.uart
+                .config_interrupt(UART_INTERRUPT_TX_DONE, !self.state.tx.is_empty());
+        });
+    }
+
+    fn fill_tx_fifo(&self) {
+        while !self.uart.get_flag_status(UartFlag::TxFifoFull) {
+            let Some(byte) = self.state.tx.pop() else {
+                break;
+            };
+            self.uart.dr.write(byte as usize);
+        }
+    }
+
+    fn drain_rx_fifo(&self) {
+        let mut errors = 0;
+        while !self.uart.get_flag_status(UartFlag::RxFifoEmpty) {
+            let word = self.uart.receive_raw();
+            if word & UART_DR_FRAME_ERROR != 0 {
+                errors |= ERROR_FRAMING;
+            }
+            if word & UART_DR_PARITY_ERROR != 0 {
+                errors |= ERROR_PARITY;
+            }
+            if word & UART_DR_OVERRUN_ERROR != 0 {
+                errors |= ERROR_OVERRUN;
+            }
+            if word & UART_DR_BREAK_ERROR != 0 {
+                // the byte of a break is not data
+                errors |= ERROR_BREAK;
+                continue;
+            }
+            if !self.state.rx.push((word & UART_DR_DATA) as u8) {
+                errors |= ERROR_BUFFER_OVERFLOW;
+            }
+        }
+        self.state.errors.fetch_or(errors, Ordering::AcqRel);
+    }
+
+    /// Interrupt handler body, called by `UARTx_IRQHandler`
+    pub fn on_irq(&self) {
+        let rx_timeout = self.uart.get_interrupt_status(UART_INTERRUPT_RX_TIMEOUT);
+        if rx_timeout || self.uart.get_interrupt_status(UART_INTERRUPT_RX_DONE) {
+            self.drain_rx_fifo();
+            self.uart
+                .clear_interrupt(UART_INTERRUPT_RX_DONE | UART_INTERRUPT_RX_TIMEOUT);
+        }
+        if rx_timeout {
+            self.state.idle.store(true, Ordering::Release);
+        }
+
+        if self.uart.get_interrupt_status(UART_INTERRUPT_ERRORS) {
+            if self.uart.get_interrupt_status(UART_INTERRUPT_OVERRUN_ERROR) {
+                self.state.errors.fetch_or(ERROR_OVERRUN, Ordering::AcqRel);
+            }
+            self.uart.clear_interrupt(UART_INTERRUPT_ERRORS);
+        }
+
+        if self.uart.get_interrupt_status(UART_INTERRUPT_TX_DONE) {
+            self.fill_tx_fifo();
+            if self.state.tx.is_empty() {
+                self.uart.config_interrupt(UART_INTERRUPT_TX_DONE, false);
+            }
+            self.uart.clear_interrupt(UART_INTERRUPT_TX_DONE);
+        }
+    }
+}
+
+/// Interrupt handler of `uart`, does nothing unless its buffered mode is enabled
+pub fn uart_irq_handler(uart: &'static Uart) {
+    let uart = BufferedUart::new(uart);
+    if uart.is_enabled() {
+        uart.on_irq();
+    }
+}
+
+/// Buffered driver of UART0, the console of [`crate::print`]
+pub fn uart0() -> BufferedUart {
+    BufferedUart::new(&UART0)
+}
+
+/// Buffered driver of UART1
+pub fn uart1() -> BufferedUart {
+    BufferedUart::new(&UART1)
+}
+
+/// Buffered driver of UART2
+pub fn uart2() -> BufferedUart {
+    BufferedUart::new(&UART2)
+}
+
+/// Buffered driver of UART3
+pub fn uart3() -> BufferedUart {
+    BufferedUart::new(&UART3)
+}
+
+impl embedded_io::ErrorType for BufferedUart {
+    type Error = UartError;
+}
+
+impl embedded_io::Read for BufferedUart {
+    /// Waits for at least one byte, a receive error seen before is reported first
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if let Some(error) = self.take_errors().first() {
+                return Err(error);
+            }
+            let len = self.try_read(buf);
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+impl embedded_io::ReadReady for BufferedUart {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.rx_available() > 0)
+    }
+}
+
+impl embedded_io::Write for BufferedUart {
+    /// Waits for room for at least one byte
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let len = self.try_write(buf);
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush_tx();
+        Ok(())
+    }
+}
+
+impl embedded_io::WriteReady for BufferedUart {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.state.tx.is_full())
+    }
+}
@@ -5,7 +5,7 @@ use crate::{
         rcc::{
             RCC_PERIPHERAL_GPIOA, RCC_PERIPHERAL_GPIOB, RCC_PERIPHERAL_GPIOC, RCC_PERIPHERAL_GPIOD,
         },
-        regs::{GPIOA_BASE, GPIOD_BASE, RCC},
+        regs::{GPIOA, GPIOA_BASE, GPIOB, GPIOC, GPIOD, GPIOD_BASE, RCC},
     },
     set_reg_bits, toggle_reg_bits,
 };
@@ -54,6 +54,49 @@ impl GpioPin {
     pub const BLUE_LED: Self = Self::Pin7;
     pub const COOL_WHITE_LED: Self = Self::Pin14;
     pub const WARM_WHITE_LED: Self = Self::Pin15;
+
+    /// The pins by number
+    pub const ALL: [GpioPin; 16] = [
+        Self::Pin0,
+        Self::Pin1,
+        Self::Pin2,
+        Self::Pin3,
+        Self::Pin4,
+        Self::Pin5,
+        Self::Pin6,
+        Self::Pin7,
+        Self::Pin8,
+        Self::Pin9,
+        Self::Pin10,
+        Self::Pin11,
+        Self::Pin12,
+        Self::Pin13,
+        Self::Pin14,
+        Self::Pin15,
+    ];
+}
+
+/// GPIO port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioPort {
+    A,
+    B,
+    C,
+    D,
+}
+
+impl GpioPort {
+    pub const ALL: [GpioPort; 4] = [GpioPort::A, GpioPort::B, GpioPort::C, GpioPort::D];
+
+    /// The registers of the port
+    pub fn gpio(self) -> &'static Gpio {
+        match self {
+            GpioPort::A => &GPIOA,
+            GpioPort::B => &GPIOB,
+            GpioPort::C => &GPIOC,
+            GpioPort::D => &GPIOD,
+        }
+    }
 }
 
 /// GPIO pin mode
@@ -0,0 +1,308 @@
+use heapless::Vec;
+
+use crate::{
+    cortex::{
+        IRQType,
+        func::{_disable_irq, _enable_irq},
+        nvic_enable_irq,
+    },
+    lora::timer::{TimerEvent, timer_init, timer_set_value, timer_start},
+    peripherals::gpio::{GpioPin, GpioPort, IntType},
+};
+
+/// Most pins with a registered interrupt handler
+pub const GPIO_IRQ_MAX_HANDLERS: usize = 8;
+/// Period of the debounce timer (ms), debounce times are rounded up to it
+pub const GPIO_DEBOUNCE_TICK_MS: usize = 5;
+
+/// What calls the handler of a pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioTrigger {
+    RisingEdge,
+    FallingEdge,
+    RisingFallingEdge,
+    /// the pin goes high, or is high when the handler is registered
+    HighLevel,
+    /// the pin goes low, or is low when the handler is registered
+    LowLevel,
+}
+
+impl GpioTrigger {
+    /// Hardware interrupt type, levels are caught by the edge into them
+    fn int_type(self) -> IntType {
+        match self {
+            GpioTrigger::RisingEdge | GpioTrigger::HighLevel => IntType::RisingEdge,
+            GpioTrigger::FallingEdge | GpioTrigger::LowLevel => IntType::FallingEdge,
+            GpioTrigger::RisingFallingEdge => IntType::RisingFallingEdge,
+        }
+    }
+
+    /// Whether a settled `level` following `last` calls the handler
+    fn fires(self, last: bool, level: bool) -> bool {
+        match self {
+            GpioTrigger::RisingEdge => !last && level,
+            GpioTrigger::FallingEdge => last && !level,
+            GpioTrigger::RisingFallingEdge => last != level,
+            GpioTrigger::HighLevel => level,
+            GpioTrigger::LowLevel => !level,
+        }
+    }
+
+    /// Level that wakes the MCU from deep sleep, the opposite of `level` for both edges
+    fn wakeup_level(self, level: bool) -> bool {
+        match self {
+            GpioTrigger::RisingEdge | GpioTrigger::HighLevel => true,
+            GpioTrigger::FallingEdge | GpioTrigger::LowLevel => false,
+            GpioTrigger::RisingFallingEdge => !level,
+        }
+    }
+}
+
+/// Pin interrupt handler, called from the GPIO interrupt or, once debounced, from the timer
+/// interrupt with the pin level
+pub type GpioCallback = fn(port: GpioPort, pin: GpioPin, level: bool);
+
+/// Registration of a pin interrupt, see [`gpio_irq_register`]
+#[derive(Clone, Copy)]
+pub struct GpioIrqConfig {
+    pub port: GpioPort,
+    /// must be configured as an input
+    pub pin: GpioPin,
+    pub trigger: GpioTrigger,
+    /// time the pin must stay put before the handler runs (ms), 0 calls it on the edge
+    pub debounce_ms: usize,
+    /// wake the MCU from deep sleep, STOP3 included
+    pub wakeup: bool,
+    pub callback: GpioCallback,
+}
+
+/// Error of [`gpio_irq_register`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioIrqError {
+    /// [`GPIO_IRQ_MAX_HANDLERS`] pins have a handler
+    Full,
+    /// the pin has a handler already
+    InUse,
+}
+
+struct GpioHandler {
+    config: GpioIrqConfig,
+    /// debounced level
+    level: bool,
+    /// time left until the pin is settled (ms), `None` when not debouncing
+    settling: Option<usize>,
+}
+
+impl GpioHandler {
+    fn is(&self, port: GpioPort, pin: GpioPin) -> bool {
+        self.config.port == port && self.config.pin as usize == pin as usize
+    }
+
+    /// Enable the interrupt and the wake-up, which follows the level for both edges
+    fn arm(&self) {
+        let GpioIrqConfig {
+            port,
+            pin,
+            trigger,
+            wakeup,
+            ..
+        } = self.config;
+        let gpio = port.gpio();
+        gpio.config_interrupt(pin, trigger.int_type());
+        if wakeup {
+            let wakeup_level = trigger.wakeup_level(self.level);
+            gpio.config_wakeup(pin, true, wakeup_level);
+            gpio.config_stop3_wakeup(pin, true, wakeup_level);
+        }
+    }
+}
+
+static mut GPIO_HANDLERS: Vec<GpioHandler, GPIO_IRQ_MAX_HANDLERS> = Vec::new();
+static mut DEBOUNCE_TIMER: TimerEvent = TimerEvent {
+    id: 0,
+    timestamp: 0,
+    reload_value: 0,
+    is_running: false,
+    callback: None,
+};
+
+/// Call `config.callback` on the interrupts of a pin.
+///
+/// A level trigger with the pin already at its level calls the handler before returning.
+pub fn gpio_irq_register(config: GpioIrqConfig) -> Result<(), GpioIrqError> {
+    let gpio = config.port.gpio();
+    let level = gpio.read(config.pin);
+
+    let timer = unsafe { &mut DEBOUNCE_TIMER };
+    if timer.id == 0 {
+        timer_init(timer, on_debounce_tick);
+        timer_set_value(timer, GPIO_DEBOUNCE_TICK_MS);
+    }
+
+    _disable_irq();
+    let handlers = unsafe { &mut GPIO_HANDLERS };
+    let result = if handlers.iter().any(|h| h.is(config.port, config.pin)) {
+        Err(GpioIrqError::InUse)
+    } else {
+        handlers
+            .push(GpioHandler {
+                config,
+                level,
+                settling: None,
+            })
+            .map_err(|_| GpioIrqError::Full)
+    };
+    if let Some(handler) = handlers.last()
+        && result.is_ok()
+    {
+        handler.arm();
+        nvic_enable_irq(IRQType::Gpio);
+    }
+    _enable_irq();
+    result?;
+
+    if matches!(
+        config.trigger,
+        GpioTrigger::HighLevel | GpioTrigger::LowLevel
+    ) && config.trigger.fires(level, level)
+    {
+        (config.callback)(config.port, config.pin, level);
+    }
+    Ok(())
+}
+
+/// Remove the handler of a pin and disable its interrupt and wake-up
+pub fn gpio_irq_unregister(port: GpioPort, pin: GpioPin) {
+    let gpio = port.gpio();
+    _disable_irq();
+    let handlers = unsafe { &mut GPIO_HANDLERS };
+    if let Some(index) = handlers.iter().position(|h| h.is(port, pin)) {
+        handlers.swap_remove(index);
+        gpio.config_interrupt(pin, IntType::None);
+        gpio.config_wakeup(pin, false, false);
+        gpio.config_stop3_wakeup(pin, false, false);
+    }
+    _enable_irq();
+}
+
+/// Debounced level of a registered pin
+pub fn gpio_irq_level(port: GpioPort, pin: GpioPin) -> Option<bool> {
+    unsafe { GPIO_HANDLERS.iter() }
+        .find(|h| h.is(port, pin))
+        .map(|h| h.level)
+}
+
+/// Interrupt handler of the GPIO ports, called by `GPIO_IRQHandler`
+pub fn gpio_irq_handler() {
+    let handlers = unsafe { &mut GPIO_HANDLERS };
+    let mut debouncing = false;
+
+    for port in GpioPort::ALL {
+        let gpio = port.gpio();
+        // flags of pins without a handler are cleared too
+        let flags = gpio.ifr.read();
+        if flags == 0 {
+            continue;
+        }
+        gpio.ifr.write(flags);
+
+        for handler in handlers.iter_mut().filter(|h| h.config.port == port) {
+            let pin = handler.config.pin;
+            if flags & (0x3 << (2 * pin as usize)) == 0 {
+                continue;
+            }
+
+            if handler.config.debounce_ms == 0 {
+                let level = gpio.read(pin);
+                let last = core::mem::replace(&mut handler.level, level);
+                let trigger = handler.config.trigger;
+                if trigger == GpioTrigger::RisingFallingEdge {
+                    handler.arm();
+                }
+                // same rule as the debounced path, a glitch gone by now doesn't count
+                if trigger.fires(last, level) {
+                    (handler.config.callback)(port, pin, level);
+                }
+            } else {
+                // ignore the bounces, the tick looks at the pin once it's settled
+                gpio.config_interrupt(pin, IntType::None);
+                handler.settling = Some(handler.config.debounce_ms);
+                debouncing = true;
+            }
+        }
+    }
+
+    if debouncing {
+        // no-op while the timer is queued
+        timer_start(unsafe { &mut DEBOUNCE_TIMER });
+    }
+}
+
+/// Debounce timer: call the handlers of the pins that settled and re-arm their interrupts
+fn on_debounce_tick() {
+    let handlers = unsafe { &mut GPIO_HANDLERS };
+    let mut debouncing = false;
+
+    for handler in handlers.iter_mut() {
+        let Some(left) = handler.settling else {
+            continue;
+        };
+        if left > GPIO_DEBOUNCE_TICK_MS {
+            handler.settling = Some(left - GPIO_DEBOUNCE_TICK_MS);
+            debouncing = true;
+            continue;
+        }
+
+        let GpioIrqConfig {
+            port,
+            pin,
+            trigger,
+            callback,
+            ..
+        } = handler.config;
+        let level = port.gpio().read(pin);
+        let last = core::mem::replace(&mut handler.level, level);
+        handler.settling = None;
+        handler.arm();
+        if trigger.fires(last, level) {
+            callback(port, pin, level);
+        }
+    }
+
+    if debouncing {
+        timer_start(unsafe { &mut DEBOUNCE_TIMER });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triggers() {
+        use GpioTrigger::*;
+        // (last, level) pairs: stays low, rises, falls, stays high
+        let cases = [(false, false), (false, true), (true, false), (true, true)];
+        let table = [
+            (RisingEdge, [false, true, false, false]),
+            (FallingEdge, [false, false, true, false]),
+            (RisingFallingEdge, [false, true, true, false]),
+            (HighLevel, [false, true, false, true]),
+            (LowLevel, [true, false, true, false]),
+        ];
+        for (trigger, expected) in table {
+            for ((last, level), fires) in cases.into_iter().zip(expected) {
+                assert_eq!(
+                    trigger.fires(last, level),
+                    fires,
+                    "{trigger:?} {last} {level}"
+                );
+            }
+        }
+
+        assert!(RisingEdge.wakeup_level(true));
+        assert!(!LowLevel.wakeup_level(true));
+        assert!(RisingFallingEdge.wakeup_level(false));
+        assert!(!RisingFallingEdge.wakeup_level(true));
+    }
+}
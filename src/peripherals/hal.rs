@@ -0,0 +1,295 @@
+use core::convert::Infallible;
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{ErrorType as PinErrorType, InputPin, OutputPin, StatefulOutputPin},
+    i2c::{self, Operation as I2cOperation, SevenBitAddress},
+    spi::{self, Operation as SpiOperation, SpiBus},
+};
+use embedded_hal_nb::{nb, serial};
+
+use crate::peripherals::{
+    buffered_uart::{BufferedUart, UartError},
+    delay::delay_us,
+    gpio::{Gpio, GpioPin, GpioPort},
+    i2c::{I2c, I2cError},
+    regs::{
+        UART_DR_BREAK_ERROR, UART_DR_DATA, UART_DR_FRAME_ERROR, UART_DR_OVERRUN_ERROR,
+        UART_DR_PARITY_ERROR,
+    },
+    spi::{SpiDevice, Ssp, SspError, SspWord},
+    uart::{Uart, UartFlag},
+};
+
+/// A GPIO pin, configured with [`Gpio::init`] first
+pub struct Pin {
+    gpio: &'static Gpio,
+    pin: GpioPin,
+}
+
+impl Pin {
+    pub fn new(port: GpioPort, pin: GpioPin) -> Self {
+        Self {
+            gpio: port.gpio(),
+            pin,
+        }
+    }
+}
+
+impl PinErrorType for Pin {
+    type Error = Infallible;
+}
+
+impl OutputPin for Pin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.gpio.write(self.pin, false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.gpio.write(self.pin, true);
+        Ok(())
+    }
+}
+
+impl StatefulOutputPin for Pin {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.gpio.odr.read() & (1 << self.pin as usize) != 0)
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.is_set_high()?)
+    }
+}
+
+impl InputPin for Pin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.gpio.read(self.pin))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.gpio.read(self.pin))
+    }
+}
+
+/// Busy-wait delay on SysTick, after [`crate::peripherals::delay::delay_init`]
+#[derive(Clone, Copy, Default)]
+pub struct Delay;
+
+impl DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.delay_us(ns.div_ceil(1000));
+    }
+
+    fn delay_us(&mut self, mut us: u32) {
+        // keeps the SysTick count of `delay_us` within 32 bits
+        while us > 0 {
+            let chunk = us.min(1_000_000);
+            delay_us(chunk as usize);
+            us -= chunk;
+        }
+    }
+}
+
+/// SPI bus on `SSP0`..`SSP2`, initialised as master and enabled with [`Ssp::cmd`] first.
+///
+/// Words are `u8` or `u16`, matching the data size of the [`crate::peripherals::spi::SspConfig`].
+pub struct Spi {
+    ssp: &'static Ssp,
+}
+
+impl Spi {
+    pub fn new(ssp: &'static Ssp) -> Self {
+        Self { ssp }
+    }
+}
+
+impl spi::Error for SspError {
+    fn kind(&self) -> spi::ErrorKind {
+        spi::ErrorKind::Other
+    }
+}
+
+impl spi::ErrorType for Spi {
+    type Error = SspError;
+}
+
+impl<W: SspWord + 'static> SpiBus<W> for Spi {
+    fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.ssp.transfer(words, &[])
+    }
+
+    fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        self.ssp.transfer(&mut [], words)
+    }
+
+    fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.ssp.transfer(read, write)
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.ssp.transfer_in_place(words)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.ssp.flush()
+    }
+}
+
+impl spi::ErrorType for SpiDevice {
+    type Error = SspError;
+}
+
+/// Words are `u8` or `u16`, matching the data size of the
+/// [`crate::peripherals::spi::SpiDeviceConfig`]
+impl<W: SspWord + 'static> spi::SpiDevice<W> for SpiDevice {
+    fn transaction(&mut self, operations: &mut [SpiOperation<'_, W>]) -> Result<(), Self::Error> {
+        SpiDevice::transaction(self, |ssp| {
+            for operation in operations {
+                match operation {
+                    SpiOperation::Read(words) => ssp.transfer(words, &[])?,
+                    SpiOperation::Write(words) => ssp.transfer(&mut [], words)?,
+                    SpiOperation::Transfer(read, write) => ssp.transfer(read, write)?,
+                    SpiOperation::TransferInPlace(words) => ssp.transfer_in_place(words)?,
+                    SpiOperation::DelayNs(ns) => {
+                        ssp.flush()?;
+                        Delay.delay_ns(*ns);
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+impl i2c::Error for I2cError {
+    fn kind(&self) -> i2c::ErrorKind {
+        match self {
+            I2cError::Nack => i2c::ErrorKind::NoAcknowledge(i2c::NoAcknowledgeSource::Unknown),
+            I2cError::ArbitrationLoss => i2c::ErrorKind::ArbitrationLoss,
+            I2cError::BusError => i2c::ErrorKind::Bus,
+            I2cError::UnitResetError | I2cError::Timeout => i2c::ErrorKind::Other,
+        }
+    }
+}
+
+/// I2C master on `I2C0`..`I2C2`, initialised and enabled with [`I2c::cmd`] first
+pub struct I2cBus {
+    i2c: &'static I2c,
+}
+
+impl I2cBus {
+    pub fn new(i2c: &'static I2c) -> Self {
+        Self { i2c }
+    }
+}
+
+impl i2c::ErrorType for I2cBus {
+    type Error = I2cError;
+}
+
+impl i2c::I2c<SevenBitAddress> for I2cBus {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [I2cOperation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.i2c.transaction(address, operations)
+    }
+}
+
+impl serial::Error for UartError {
+    fn kind(&self) -> serial::ErrorKind {
+        match self {
+            UartError::Framing => serial::ErrorKind::FrameFormat,
+            UartError::Parity => serial::ErrorKind::Parity,
+            UartError::Overrun | UartError::BufferOverflow => serial::ErrorKind::Overrun,
+            UartError::Break => serial::ErrorKind::Other,
+        }
+    }
+}
+
+/// Polled serial port on `UART0`..`UART3`, initialised with [`Uart::init`] first
+pub struct Serial {
+    uart: &'static Uart,
+}
+
+impl Serial {
+    pub fn new(uart: &'static Uart) -> Self {
+        Self { uart }
+    }
+}
+
+impl serial::ErrorType for Serial {
+    type Error = UartError;
+}
+
+impl serial::Read<u8> for Serial {
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        if self.uart.get_flag_status(UartFlag::RxFifoEmpty) {
+            return Err(nb::Error::WouldBlock);
+        }
+        let word = self.uart.receive_raw();
+        let error = if word & UART_DR_OVERRUN_ERROR != 0 {
+            Some(UartError::Overrun)
+        } else if word & UART_DR_BREAK_ERROR != 0 {
+            Some(UartError::Break)
+        } else if word & UART_DR_FRAME_ERROR != 0 {
+            Some(UartError::Framing)
+        } else if word & UART_DR_PARITY_ERROR != 0 {
+            Some(UartError::Parity)
+        } else {
+            None
+        };
+        match error {
+            Some(error) => Err(nb::Error::Other(error)),
+            None => Ok((word & UART_DR_DATA) as u8),
+        }
+    }
+}
+
+impl serial::Write<u8> for Serial {
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        if self.uart.get_flag_status(UartFlag::TxFifoFull) {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.uart.dr.write(word as usize);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        if self.uart.get_flag_status(UartFlag::Busy) {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(())
+    }
+}
+
+impl serial::ErrorType for BufferedUart {
+    type Error = UartError;
+}
+
+impl serial::Read<u8> for BufferedUart {
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        if let Some(error) = self.take_errors().first() {
+            return Err(nb::Error::Other(error));
+        }
+        self.try_read_byte().ok_or(nb::Error::WouldBlock)
+    }
+}
+
+impl serial::Write<u8> for BufferedUart {
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        if self.try_write(&[word]) == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        if !self.is_tx_idle() {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(())
+    }
+}
@@ -1,23 +1,42 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use embedded_hal::i2c::Operation;
+use heapless::Vec;
+
 use crate::{
     cortex::{VolatileRO, VolatileRW},
     define_reg,
     peripherals::{
+        delay::delay_us,
         rcc::{
             RCC_PCLK0, RCC_PCLK1, RCC_PERIPHERAL_I2C0, RCC_PERIPHERAL_I2C1, RCC_PERIPHERAL_I2C2,
         },
         regs::{
-            I2C_CR_ACKNAK_MASK, I2C_CR_FIFO_EN_MASK, I2C_CR_MASTER_ABORT_MASK,
-            I2C_CR_MASTER_STOP_DET_EN_MASK, I2C_CR_SCL_EN_MASK, I2C_CR_START_MASK,
-            I2C_CR_STOP_MASK, I2C_CR_TRANS_BEGIN_MASK, I2C_CR_TRANS_BYTE_MASK,
+            I2C_CR_ACKNAK_MASK, I2C_CR_BUS_RESET_REQUEST_MASK, I2C_CR_FIFO_EN_MASK,
+            I2C_CR_MASTER_ABORT_MASK, I2C_CR_MASTER_STOP_DET_EN_MASK, I2C_CR_SCL_EN_MASK,
+            I2C_CR_START_MASK, I2C_CR_STOP_MASK, I2C_CR_TRANS_BEGIN_MASK, I2C_CR_TRANS_BYTE_MASK,
             I2C_CR_TWSI_UNIT_EN_MASK, I2C_CR_UNIT_RESET_MASK, I2C_RFIFO_STATUS_SIZE_MASK,
-            I2C_SR_UNIT_BUSY_MASK, I2C_WFIFO_CONTROL_ACKNAK_MASK, I2C_WFIFO_CONTROL_START_MASK,
-            I2C_WFIFO_CONTROL_STOP_MASK, I2C_WFIFO_CONTROL_TB_MASK, I2C0, I2C0_BASE, I2C1_BASE,
-            I2C2_BASE, RCC,
+            I2C_SR_ACK_STATUS_MASK, I2C_SR_UNIT_BUSY_MASK, I2C_WFIFO_CONTROL_ACKNAK_MASK,
+            I2C_WFIFO_CONTROL_START_MASK, I2C_WFIFO_CONTROL_STOP_MASK, I2C_WFIFO_CONTROL_TB_MASK,
+            I2C0_BASE, I2C1_BASE, I2C2_BASE, RCC,
         },
     },
     toggle_reg_bits,
 };
 
+/// Default time a master transaction waits for each byte, START and STOP (µs), the SMBus
+/// clock low timeout
+pub const I2C_DEFAULT_TIMEOUT_US: usize = 25_000;
+/// SCL pulses sent by [`I2c::recover_bus`], enough for a slave to shift out a byte and its ACK
+pub const I2C_RECOVERY_CLOCKS: usize = 9;
+
+/// Per-unit transaction timeouts (µs), see [`I2c::set_timeout_us`]
+static I2C_TIMEOUTS_US: [AtomicUsize; 3] = [
+    AtomicUsize::new(I2C_DEFAULT_TIMEOUT_US),
+    AtomicUsize::new(I2C_DEFAULT_TIMEOUT_US),
+    AtomicUsize::new(I2C_DEFAULT_TIMEOUT_US),
+];
+
 /// I2C mode.
 #[repr(usize)]
 pub enum I2cMode {
@@ -84,6 +103,7 @@ pub enum I2cInterrupt {
 }
 
 /// I2C flags.
+#[derive(Clone, Copy)]
 #[repr(usize)]
 pub enum I2cFlag {
     /// Receive FIFO is empty.
@@ -154,9 +174,18 @@ fn is_fifo_mode(cr: usize) -> bool {
 }
 
 /// I2C error types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum I2cError {
     /// Unit reset timed out (unit busy).
     UnitResetError,
+    /// The slave didn't acknowledge the address or a data byte.
+    Nack,
+    /// Another master won the bus.
+    ArbitrationLoss,
+    /// A misplaced START or STOP was detected.
+    BusError,
+    /// The bus or the slave didn't respond in time, the bus was recovered.
+    Timeout,
 }
 
 define_reg! {
@@ -241,6 +270,10 @@ impl I2c {
 
         self.lcr.write(slv | flv << 9);
         self.wcr.write(flv / 3);
+
+        if let I2cSettings::Slave { slave_addr } = config.settings {
+            self.sar.write(slave_addr);
+        }
     }
 
     /// Enable or disable the I2C peripheral.
@@ -343,6 +376,21 @@ impl I2c {
         }
     }
 
+    /// Receive the last byte with a NAK and send the stop request after it.
+    pub fn master_receive_stop(&self) {
+        if is_fifo_mode(self.cr.read()) {
+            self.wfifo.write(
+                I2C_WFIFO_CONTROL_TB_MASK
+                    | I2C_WFIFO_CONTROL_ACKNAK_MASK
+                    | I2C_WFIFO_CONTROL_STOP_MASK,
+            );
+        } else {
+            toggle_reg_bits!(self.cr, I2C_CR_START_MASK, false);
+            toggle_reg_bits!(self.cr, I2C_CR_ACKNAK_MASK | I2C_CR_STOP_MASK, true);
+            toggle_reg_bits!(self.cr, I2C_CR_TRANS_BYTE_MASK, true);
+        }
+    }
+
     /// Clear the flag status of the specified I2C flag.
     pub fn clear_flag_status(&self, flag: I2cFlag) {
         self.sr.write(1 << flag as usize);
@@ -351,7 +399,7 @@ impl I2c {
     /// Get the flag status of the specified I2C flag.
     pub fn get_flag_status(&self, flag: I2cFlag) -> bool {
         if matches!(flag, I2cFlag::RFifoEmpty) {
-            I2C0.rfifo_status.read() & I2C_RFIFO_STATUS_SIZE_MASK == 0
+            self.rfifo_status.read() & I2C_RFIFO_STATUS_SIZE_MASK == 0
         } else {
             self.sr.read() & (1 << flag as usize) != 0
         }
@@ -361,4 +409,218 @@ impl I2c {
     pub fn get_interrupt_status(&self, interrupt: I2cInterrupt) -> bool {
         self.sr.read() & (1 << interrupt as usize) != 0
     }
+
+    /// Set how long a master transaction waits for each byte, START and STOP (µs).
+    ///
+    /// Defaults to [`I2C_DEFAULT_TIMEOUT_US`], slaves stretching the clock longer need more.
+    pub fn set_timeout_us(&self, timeout_us: usize) {
+        I2C_TIMEOUTS_US[self.index()].store(timeout_us, Ordering::Relaxed);
+    }
+
+    /// Write `bytes` to the slave at `address`.
+    pub fn write(&self, address: u8, bytes: &[u8]) -> Result<(), I2cError> {
+        self.transaction(address, &mut [Operation::Write(bytes)])
+    }
+
+    /// Read `buffer.len()` bytes from the slave at `address`.
+    pub fn read(&self, address: u8, buffer: &mut [u8]) -> Result<(), I2cError> {
+        self.transaction(address, &mut [Operation::Read(buffer)])
+    }
+
+    /// Write `bytes` then, after a repeated START, read `buffer.len()` bytes from the slave
+    /// at `address`. This is the usual register read.
+    pub fn write_read(&self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), I2cError> {
+        self.transaction(
+            address,
+            &mut [Operation::Write(bytes), Operation::Read(buffer)],
+        )
+    }
+
+    /// Run `operations` on the slave at `address` as a master, polling.
+    ///
+    /// A START and the address are sent before the first operation and whenever the
+    /// direction changes, consecutive operations of the same direction are merged, and a STOP
+    /// ends the transaction. The last byte of a read before a write or the STOP is NAKed.
+    ///
+    /// A NAK or a bus error sends the STOP and fails. A timeout sends the STOP and recovers
+    /// the bus with [`Self::recover_bus`] before failing with [`I2cError::Timeout`].
+    pub fn transaction(
+        &self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), I2cError> {
+        // a slave holding SDA low keeps the bus busy, clock it out first
+        if !self.wait_until(|| !self.get_flag_status(I2cFlag::BusBusy)) {
+            self.recover_bus()?;
+        }
+
+        let result = self.run(address, operations);
+        if result == Err(I2cError::Timeout) {
+            self.master_send_stop();
+            self.recover_bus()?;
+        }
+        result
+    }
+
+    /// Addresses of the slaves that acknowledge a write, from 0x08 to 0x77 (the reserved
+    /// addresses are skipped).
+    pub fn scan(&self) -> Vec<u8, 112> {
+        let mut found = Vec::new();
+        for address in 0x08..=0x77 {
+            if self.write(address, &[]).is_ok() {
+                // can't overflow, there are 112 addresses
+                found.push(address).ok();
+            }
+        }
+        found
+    }
+
+    /// Free a bus stuck by a slave holding SDA low, typically after a reset in the middle of a
+    /// read.
+    ///
+    /// The unit toggles SCL [`I2C_RECOVERY_CLOCKS`] times so the slave finishes its byte and
+    /// releases SDA, then is reset with [`Self::unit_reset`] and reconfigured as before. Fails
+    /// with [`I2cError::Timeout`] if the bus is still busy afterwards.
+    pub fn recover_bus(&self) -> Result<(), I2cError> {
+        let cr = self.cr.read()
+            & !(I2C_CR_START_MASK
+                | I2C_CR_STOP_MASK
+                | I2C_CR_ACKNAK_MASK
+                | I2C_CR_TRANS_BYTE_MASK
+                | I2C_CR_TRANS_BEGIN_MASK
+                | I2C_CR_MASTER_ABORT_MASK
+                | I2C_CR_BUS_RESET_REQUEST_MASK
+                | I2C_CR_UNIT_RESET_MASK);
+
+        self.rst_cycl.write(I2C_RECOVERY_CLOCKS);
+        toggle_reg_bits!(self.cr, I2C_CR_BUS_RESET_REQUEST_MASK, true);
+        // cleared by the unit once the clocks and a STOP are sent
+        let reset_done = self.wait_until(|| self.cr.read() & I2C_CR_BUS_RESET_REQUEST_MASK == 0);
+        toggle_reg_bits!(self.cr, I2C_CR_BUS_RESET_REQUEST_MASK, false);
+
+        self.unit_reset(1000)?;
+        self.cr.write(cr);
+
+        if !reset_done || !self.wait_until(|| !self.get_flag_status(I2cFlag::BusBusy)) {
+            return Err(I2cError::Timeout);
+        }
+        Ok(())
+    }
+
+    /// Index of the unit, `I2C0` to `I2C2`
+    pub(super) fn index(&self) -> usize {
+        match self.ptr() as usize {
+            I2C0_BASE => 0,
+            I2C1_BASE => 1,
+            I2C2_BASE => 2,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Poll `done` for up to the timeout of the unit, returns whether it became true
+    fn wait_until(&self, mut done: impl FnMut() -> bool) -> bool {
+        let timeout_us = I2C_TIMEOUTS_US[self.index()].load(Ordering::Relaxed);
+        let mut elapsed_us = 0;
+        while !done() {
+            if elapsed_us >= timeout_us {
+                return false;
+            }
+            delay_us(1);
+            elapsed_us += 1;
+        }
+        true
+    }
+
+    /// Wait for `flag`, failing on arbitration loss, a bus error or the timeout
+    fn wait_flag(&self, flag: I2cFlag) -> Result<(), I2cError> {
+        let mut error = None;
+        let set = self.wait_until(|| {
+            if self.get_flag_status(I2cFlag::ArbitrationLoss) {
+                self.clear_flag_status(I2cFlag::ArbitrationLoss);
+                error = Some(I2cError::ArbitrationLoss);
+            } else if self.get_flag_status(I2cFlag::BusErrorDet) {
+                self.clear_flag_status(I2cFlag::BusErrorDet);
+                self.master_send_stop();
+                error = Some(I2cError::BusError);
+            }
+            error.is_some() || self.get_flag_status(flag)
+        });
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+        if !set {
+            return Err(I2cError::Timeout);
+        }
+        self.clear_flag_status(flag);
+        Ok(())
+    }
+
+    /// Wait for the byte to be sent, failing and releasing the bus on a NAK
+    fn wait_sent(&self) -> Result<(), I2cError> {
+        self.wait_flag(I2cFlag::TransEmpty)?;
+        if self.sr.read() & I2C_SR_ACK_STATUS_MASK != 0 {
+            self.master_send_stop();
+            return Err(I2cError::Nack);
+        }
+        Ok(())
+    }
+
+    fn run(&self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), I2cError> {
+        let count = operations.len();
+        let mut reading = None;
+
+        for index in 0..count {
+            let last_operation = index + 1 == count;
+            // the last byte read before a write or the end is NAKed
+            let read_follows = matches!(operations.get(index + 1), Some(Operation::Read(_)));
+
+            match &mut operations[index] {
+                Operation::Write(bytes) => {
+                    if reading != Some(false) {
+                        self.master_send_start(address, I2cRW::Write as u8);
+                        self.wait_sent()?;
+                    }
+                    reading = Some(false);
+
+                    for (position, &byte) in bytes.iter().enumerate() {
+                        if last_operation && position + 1 == bytes.len() {
+                            self.master_send_stop_with_data(byte);
+                        } else {
+                            self.send_data(byte);
+                        }
+                        self.wait_sent()?;
+                    }
+                    if last_operation && bytes.is_empty() {
+                        self.master_send_stop();
+                    }
+                }
+                Operation::Read(buffer) => {
+                    if reading != Some(true) {
+                        self.master_send_start(address, I2cRW::Read as u8);
+                        self.wait_sent()?;
+                    }
+                    reading = Some(true);
+
+                    let len = buffer.len();
+                    for (position, slot) in buffer.iter_mut().enumerate() {
+                        let last_byte = position + 1 == len;
+                        if last_byte && last_operation {
+                            self.master_receive_stop();
+                        } else if last_byte && !read_follows {
+                            self.receive_mode(I2cAck::Nak);
+                        } else {
+                            self.receive_mode(I2cAck::Ack);
+                        }
+                        self.wait_flag(I2cFlag::RecvFull)?;
+                        *slot = self.receive_data();
+                    }
+                    if last_operation && len == 0 {
+                        self.master_send_stop();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,222 @@
+use crate::{
+    cortex::{IRQType, nvic_disable_irq, nvic_enable_irq},
+    peripherals::{
+        i2c::{I2c, I2cAck, I2cConfig, I2cInterrupt, I2cMode, I2cSettings},
+        regs::{
+            I2C_CR_GENERAL_CALL_DIS_MASK, I2C_SR_ACK_STATUS_MASK, I2C_SR_ARB_LOSS_DET_MASK,
+            I2C_SR_BUS_ERROR_MASK, I2C_SR_DBR_FULL_MASK, I2C_SR_IDBR_EMPTY_MASK,
+            I2C_SR_RW_MODE_MASK, I2C_SR_SLAVE_ADDR_DET_MASK, I2C_SR_SLAVE_STOP_DET_MASK,
+        },
+    },
+    toggle_reg_bits,
+};
+
+/// Status bits handled, and cleared, by the interrupt handler
+const I2C_TARGET_EVENTS: usize = I2C_SR_SLAVE_ADDR_DET_MASK
+    | I2C_SR_SLAVE_STOP_DET_MASK
+    | I2C_SR_DBR_FULL_MASK
+    | I2C_SR_IDBR_EMPTY_MASK
+    | I2C_SR_BUS_ERROR_MASK
+    | I2C_SR_ARB_LOSS_DET_MASK;
+
+/// Interrupts of the events above
+const I2C_TARGET_INTERRUPTS: [I2cInterrupt; 5] = [
+    I2cInterrupt::SlaveAddrDet,
+    I2cInterrupt::SlaveStopDet,
+    I2cInterrupt::RecvFull,
+    I2cInterrupt::TransEmpty,
+    I2cInterrupt::BusErrorDet,
+];
+
+/// Byte sent after the master NAKed, never clocked out
+const I2C_TARGET_FILL: u8 = 0xFF;
+
+/// Register map of an I2C target, see [`I2cTarget::enable`]
+#[derive(Clone, Copy)]
+pub struct I2cTargetConfig {
+    /// 7-bit address the unit answers to
+    pub address: u8,
+    /// value of a register, called for every byte the master reads
+    pub read: fn(register: u8) -> u8,
+    /// called for every byte the master writes
+    pub write: fn(register: u8, value: u8),
+    /// called on the STOP or repeated START ending a write of `len` registers from `register`
+    pub written: Option<fn(register: u8, len: usize)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TargetPhase {
+    Idle,
+    /// the next byte written is the register pointer
+    Register,
+    /// `len` registers written from `first`
+    Writing {
+        first: u8,
+        len: usize,
+    },
+    Reading,
+}
+
+struct TargetState {
+    config: I2cTargetConfig,
+    /// register pointer, advanced by every byte read or written
+    register: u8,
+    phase: TargetPhase,
+}
+
+impl TargetState {
+    /// End of a transaction, on a STOP, a repeated START or a bus error
+    fn end(&mut self) {
+        if let TargetPhase::Writing { first, len } = self.phase
+            && len > 0
+            && let Some(written) = self.config.written
+        {
+            written(first, len);
+        }
+        self.phase = TargetPhase::Idle;
+    }
+}
+
+static mut I2C_TARGETS: [Option<TargetState>; 3] = [None, None, None];
+
+/// Interrupt-driven I2C target (slave) with a register map, e.g. to use the chip as a
+/// coprocessor of a host MCU.
+///
+/// The first byte of a write sets the register pointer, the following ones are passed to
+/// [`I2cTargetConfig::write`]. A read returns [`I2cTargetConfig::read`] from the pointer on.
+/// The pointer advances by one per byte and wraps around, so a host reads a register with
+/// the usual write of its number, a repeated START and a read.
+///
+/// The unit stretches the clock from the address, and from every byte, until the interrupt
+/// handler has run the callback and released the bus: callbacks may take some time to
+/// compute a value, as long as the host allows that much clock stretching. They run in the
+/// interrupt handler. The clock and the pins are left to the caller.
+pub struct I2cTarget {
+    i2c: &'static I2c,
+    index: usize,
+    irq: IRQType,
+}
+
+impl I2cTarget {
+    /// Target driver of `i2c`, one of `I2C0`..`I2C2`
+    pub fn new(i2c: &'static I2c) -> Self {
+        let index = i2c.index();
+        let irq = [IRQType::I2c0, IRQType::I2c1, IRQType::I2c2][index];
+        Self { i2c, index, irq }
+    }
+
+    /// Configure the unit as a target at `config.address` and start answering the master.
+    ///
+    /// General calls are ignored.
+    pub fn enable(&self, config: I2cTargetConfig) {
+        self.disable();
+
+        self.i2c.init(I2cConfig {
+            mode: I2cMode::Slave,
+            fifo_mode_en: false,
+            settings: I2cSettings::Slave {
+                slave_addr: config.address as usize,
+            },
+        });
+        toggle_reg_bits!(self.i2c.cr, I2C_CR_GENERAL_CALL_DIS_MASK, true);
+        self.i2c.sr.write(I2C_TARGET_EVENTS);
+
+        unsafe {
+            I2C_TARGETS[self.index] = Some(TargetState {
+                config,
+                register: 0,
+                phase: TargetPhase::Idle,
+            })
+        };
+
+        for interrupt in I2C_TARGET_INTERRUPTS {
+            self.i2c.config_interrupt(interrupt, true);
+        }
+        self.i2c.cmd(true);
+        nvic_enable_irq(self.irq);
+    }
+
+    /// Stop answering the master and disable the unit
+    pub fn disable(&self) {
+        nvic_disable_irq(self.irq);
+        self.i2c.cmd(false);
+        for interrupt in I2C_TARGET_INTERRUPTS {
+            self.i2c.config_interrupt(interrupt, false);
+        }
+        unsafe { I2C_TARGETS[self.index] = None };
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        unsafe { I2C_TARGETS[self.index].is_some() }
+    }
+
+    /// Register pointer, where the next read or write of the master goes
+    pub fn register(&self) -> Option<u8> {
+        unsafe { I2C_TARGETS[self.index].as_ref() }.map(|state| state.register)
+    }
+
+    /// Interrupt handler body, called by `I2Cx_IRQHandler`
+    pub fn on_irq(&self) {
+        let Some(state) = (unsafe { I2C_TARGETS[self.index].as_mut() }) else {
+            return;
+        };
+        let sr = self.i2c.sr.read();
+        self.i2c.sr.write(sr & I2C_TARGET_EVENTS);
+
+        if sr & (I2C_SR_BUS_ERROR_MASK | I2C_SR_SLAVE_STOP_DET_MASK) != 0 {
+            state.end();
+        }
+
+        if sr & I2C_SR_SLAVE_ADDR_DET_MASK != 0 {
+            // a repeated START ends the write before it
+            state.end();
+            if sr & I2C_SR_RW_MODE_MASK != 0 {
+                state.phase = TargetPhase::Reading;
+                self.send(state);
+            } else {
+                state.phase = TargetPhase::Register;
+                self.i2c.receive_mode(I2cAck::Ack);
+            }
+        } else if sr & I2C_SR_DBR_FULL_MASK != 0 {
+            let byte = self.i2c.receive_data();
+            match state.phase {
+                TargetPhase::Register => {
+                    state.register = byte;
+                    state.phase = TargetPhase::Writing {
+                        first: byte,
+                        len: 0,
+                    };
+                }
+                TargetPhase::Writing { first, len } => {
+                    (state.config.write)(state.register, byte);
+                    state.register = state.register.wrapping_add(1);
+                    state.phase = TargetPhase::Writing {
+                        first,
+                        len: len + 1,
+                    };
+                }
+                TargetPhase::Idle | TargetPhase::Reading => {}
+            }
+            self.i2c.receive_mode(I2cAck::Ack);
+        } else if sr & I2C_SR_IDBR_EMPTY_MASK != 0 && state.phase == TargetPhase::Reading {
+            if sr & I2C_SR_ACK_STATUS_MASK == 0 {
+                self.send(state);
+            } else {
+                // the master wants no more, release the bus for its STOP
+                self.i2c.send_data(I2C_TARGET_FILL);
+            }
+        }
+    }
+
+    /// Send the register under the pointer and advance it
+    fn send(&self, state: &mut TargetState) {
+        let value = (state.config.read)(state.register);
+        state.register = state.register.wrapping_add(1);
+        self.i2c.send_data(value);
+    }
+}
+
+/// Interrupt handler of an I2C unit, does nothing unless it's an enabled [`I2cTarget`]
+pub fn i2c_target_irq_handler(i2c: &'static I2c) {
+    I2cTarget::new(i2c).on_irq();
+}
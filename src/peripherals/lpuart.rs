@@ -89,6 +89,7 @@ pub enum LpuartCr0 {
 }
 
 /// LPUART interrupt definitions
+#[derive(Clone, Copy)]
 #[repr(usize)]
 pub enum LpuartInterrupt {
     /// RX start valid interrupt
@@ -121,6 +122,7 @@ pub enum LpuartCr1 {
 }
 
 /// LPUART SR0 RX status flags
+#[derive(Clone, Copy)]
 #[repr(usize)]
 pub enum LpuartRxStatus {
     /// RX start valid
@@ -0,0 +1,60 @@
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use crate::{
+    cortex::{
+        IRQType,
+        func::{_disable_irq, _get_primask, _set_primask},
+        nvic_enable_irq,
+    },
+    peripherals::{
+        rcc::{RCC_PCLK0, RCC_PERIPHERAL_TIMER0},
+        regs::{RCC, TIMER0},
+        timer::{TimerClockDivision, TimerConfig, TimerCountMode, TimerInterrupt, TimerSr},
+    },
+};
+
+/// Overflows of the 16-bit counter, the high half of [`micros`]
+static OVERFLOWS: AtomicU16 = AtomicU16::new(0);
+
+/// Start TIMER0 as a free-running 1 MHz counter, extended to 32 bits by its update interrupt.
+///
+/// PCLK0 must be a whole number of MHz.
+pub fn micros_init() {
+    RCC.enable_peripheral_clk(RCC_PERIPHERAL_TIMER0, true);
+    TIMER0.init(TimerConfig {
+        prescaler: RCC.get_clk_freq(RCC_PCLK0) / 1_000_000 - 1,
+        counter_mode: TimerCountMode::Up,
+        period: 0xFFFF,
+        clock_division: TimerClockDivision::Div1,
+        autoreload_preload: false,
+        resv: 0,
+    });
+    OVERFLOWS.store(0, Ordering::Relaxed);
+    TIMER0.timer_clear_status(TimerSr::Uif);
+    TIMER0.timer_config_interrupt(TimerInterrupt::Uie, true);
+    nvic_enable_irq(IRQType::Timer0);
+    TIMER0.timer_cmd(true);
+}
+
+/// Time since [`micros_init`] (µs), wraps after about 71 minutes
+pub fn micros() -> u32 {
+    let primask = _get_primask();
+    _disable_irq();
+    let mut high = OVERFLOWS.load(Ordering::Relaxed);
+    let mut low = TIMER0.cnt.read() as u16;
+    if TIMER0.timer_get_status(TimerSr::Uif) {
+        // overflowed with the interrupt not served yet, maybe after the read
+        high = high.wrapping_add(1);
+        low = TIMER0.cnt.read() as u16;
+    }
+    _set_primask(primask);
+    (high as u32) << 16 | low as u32
+}
+
+/// TIMER0 update interrupt, count an overflow
+pub fn micros_irq_handler() {
+    if TIMER0.timer_get_status(TimerSr::Uif) {
+        TIMER0.timer_clear_status(TimerSr::Uif);
+        OVERFLOWS.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -1,11 +1,21 @@
+/// Interrupt-driven LPUART with wake-up from STOP mode
+pub mod buffered_lpuart;
+/// Interrupt-driven buffered UART
+pub mod buffered_uart;
 /// Delay functions
 pub mod delay;
 /// Flash memory
 pub mod flash;
 /// GPIO driver
 pub mod gpio;
+/// GPIO interrupt dispatch with debounce
+pub mod gpio_irq;
+/// embedded-hal 1.0 and embedded-hal-nb trait implementations
+pub mod hal;
 /// I2C Driver
 pub mod i2c;
+/// Interrupt-driven I2C target (slave) with a register map
+pub mod i2c_target;
 /// I2S Driver
 pub mod i2s;
 /// Independent Watchdog Timer
@@ -16,14 +26,22 @@ pub mod lcd;
 pub mod lptimer;
 /// Low Power UART
 pub mod lpuart;
+/// Microsecond clock on TIMER0
+pub mod micros;
+/// Owned peripherals: `Peripherals::take`, typed pins and bus handles
+pub mod owned;
 /// Power management
 pub mod pwr;
 /// RCC
 pub mod rcc;
 /// Tremo Registers
 pub mod regs;
+/// Security engine: AES, SHA-256 and TRNG
+pub mod sae;
 /// Serial Peripheral Interface
 pub mod spi;
+/// Interrupt-driven SPI slave
+pub mod spi_target;
 /// System
 pub mod system;
 /// Timer
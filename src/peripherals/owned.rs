@@ -0,0 +1,480 @@
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin, StatefulOutputPin};
+
+use crate::peripherals::{
+    gpio::{Gpio, GpioMode, GpioPin, GpioPort},
+    hal::{I2cBus, Serial, Spi},
+    i2c::{I2c, I2cConfig},
+    rcc::{
+        RCC_PERIPHERAL_I2C0, RCC_PERIPHERAL_I2C1, RCC_PERIPHERAL_I2C2, RCC_PERIPHERAL_SSP0,
+        RCC_PERIPHERAL_SSP1, RCC_PERIPHERAL_SSP2, RCC_PERIPHERAL_UART0, RCC_PERIPHERAL_UART1,
+        RCC_PERIPHERAL_UART2, RCC_PERIPHERAL_UART3,
+    },
+    regs::{I2C0, I2C1, I2C2, RCC, SSP0, SSP1, SSP2, UART0, UART1, UART2, UART3},
+    spi::{Ssp, SspConfig},
+    uart::{Uart, UartConfig, UartInitError},
+};
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Pin mode after reset, or after its peripheral was released
+pub struct Unconfigured;
+/// Input, `PULL` is [`Floating`], [`PullUp`] or [`PullDown`]
+pub struct Input<PULL>(PhantomData<PULL>);
+/// Output, `TYPE` is [`PushPull`] or [`OpenDrain`]
+pub struct Output<TYPE>(PhantomData<TYPE>);
+pub struct Analog;
+
+pub struct Floating;
+pub struct PullUp;
+pub struct PullDown;
+pub struct PushPull;
+pub struct OpenDrain;
+
+/// Pin `N` of port `P` (`'A'`..`'D'`) in `MODE`, one type per pin and mode
+pub struct Pin<const P: char, const N: u8, MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+impl<const P: char, const N: u8, MODE> Pin<P, N, MODE> {
+    pub const PORT: GpioPort = match P {
+        'A' => GpioPort::A,
+        'B' => GpioPort::B,
+        'C' => GpioPort::C,
+        'D' => GpioPort::D,
+        _ => panic!("GPIO port must be 'A'..'D'"),
+    };
+    pub const PIN: GpioPin = GpioPin::ALL[N as usize];
+
+    const fn new() -> Self {
+        Self { _mode: PhantomData }
+    }
+
+    fn gpio(&self) -> &'static Gpio {
+        Self::PORT.gpio()
+    }
+
+    /// Back to GPIO (iomux function 0) in `mode`
+    fn into_mode<M>(self, mode: GpioMode) -> Pin<P, N, M> {
+        self.gpio().set_iomux(Self::PIN, 0);
+        self.gpio().init(Self::PIN, mode);
+        Pin::new()
+    }
+
+    pub fn into_floating_input(self) -> Pin<P, N, Input<Floating>> {
+        self.into_mode(GpioMode::InputFloating)
+    }
+
+    pub fn into_pull_up_input(self) -> Pin<P, N, Input<PullUp>> {
+        self.into_mode(GpioMode::InputPullUp)
+    }
+
+    pub fn into_pull_down_input(self) -> Pin<P, N, Input<PullDown>> {
+        self.into_mode(GpioMode::InputPullDown)
+    }
+
+    /// Push-pull output, starting `high` or low
+    pub fn into_push_pull_output(self, high: bool) -> Pin<P, N, Output<PushPull>> {
+        self.into_mode(if high {
+            GpioMode::OutputPPHigh
+        } else {
+            GpioMode::OutputPPLow
+        })
+    }
+
+    /// Open-drain output, starting released (`high`) or low
+    pub fn into_open_drain_output(self, high: bool) -> Pin<P, N, Output<OpenDrain>> {
+        self.into_mode(if high {
+            GpioMode::OutputODHiz
+        } else {
+            GpioMode::OutputODLow
+        })
+    }
+
+    pub fn into_analog(self) -> Pin<P, N, Analog> {
+        self.into_mode(GpioMode::Analog)
+    }
+}
+
+impl<const P: char, const N: u8, MODE> ErrorType for Pin<P, N, MODE> {
+    type Error = core::convert::Infallible;
+}
+
+impl<const P: char, const N: u8, PULL> InputPin for Pin<P, N, Input<PULL>> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.gpio().read(Self::PIN))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.gpio().read(Self::PIN))
+    }
+}
+
+impl<const P: char, const N: u8, TYPE> OutputPin for Pin<P, N, Output<TYPE>> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.gpio().write(Self::PIN, false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.gpio().write(Self::PIN, true);
+        Ok(())
+    }
+}
+
+impl<const P: char, const N: u8, TYPE> StatefulOutputPin for Pin<P, N, Output<TYPE>> {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.gpio().odr.read() & (1 << N) != 0)
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.gpio().odr.read() & (1 << N) == 0)
+    }
+}
+
+macro_rules! ports {
+    ($($Port:ident $port:literal [$($pin:ident $n:literal),*];)*) => {
+        $(
+            #[doc = concat!("Pins of GPIO port ", $port)]
+            pub struct $Port {
+                $(pub $pin: Pin<$port, $n, Unconfigured>,)*
+            }
+
+            impl $Port {
+                const fn new() -> Self {
+                    Self {
+                        $($pin: Pin::new(),)*
+                    }
+                }
+            }
+        )*
+    };
+}
+
+ports! {
+    PortA 'A' [pa0 0, pa1 1, pa2 2, pa3 3, pa4 4, pa5 5, pa6 6, pa7 7,
+        pa8 8, pa9 9, pa10 10, pa11 11, pa12 12, pa13 13, pa14 14, pa15 15];
+    PortB 'B' [pb0 0, pb1 1, pb2 2, pb3 3, pb4 4, pb5 5, pb6 6, pb7 7,
+        pb8 8, pb9 9, pb10 10, pb11 11, pb12 12, pb13 13, pb14 14, pb15 15];
+    PortC 'C' [pc0 0, pc1 1, pc2 2, pc3 3, pc4 4, pc5 5, pc6 6, pc7 7,
+        pc8 8, pc9 9, pc10 10, pc11 11, pc12 12, pc13 13, pc14 14, pc15 15];
+    PortD 'D' [pd0 0, pd1 1, pd2 2, pd3 3, pd4 4, pd5 5, pd6 6, pd7 7,
+        pd8 8, pd9 9, pd10 10, pd11 11, pd12 12, pd13 13, pd14 14, pd15 15];
+}
+
+/// A UART, owned
+pub trait UartInstance {
+    fn regs() -> &'static Uart;
+    const RCC_PERIPHERAL: usize;
+}
+
+/// An I2C controller, owned
+pub trait I2cInstance {
+    fn regs() -> &'static I2c;
+    const RCC_PERIPHERAL: usize;
+}
+
+/// An SSP (SPI) controller, owned
+pub trait SspInstance {
+    fn regs() -> &'static Ssp;
+    const RCC_PERIPHERAL: usize;
+}
+
+/// Pins that carry a signal of peripheral `T`, with the `set_iomux` function that routes it
+pub trait UartTx<T> {
+    const FUNCTION: u8;
+}
+pub trait UartRx<T> {
+    const FUNCTION: u8;
+}
+pub trait I2cScl<T> {
+    const FUNCTION: u8;
+}
+pub trait I2cSda<T> {
+    const FUNCTION: u8;
+}
+pub trait SspSck<T> {
+    const FUNCTION: u8;
+}
+pub trait SspMosi<T> {
+    const FUNCTION: u8;
+}
+pub trait SspMiso<T> {
+    const FUNCTION: u8;
+}
+
+macro_rules! instances {
+    ($Trait:ident, $Regs:ty: $($Name:ident => $regs:ident, $rcc:ident;)*) => {
+        $(
+            #[doc = concat!("Ownership of `", stringify!($regs), "`")]
+            pub struct $Name {
+                _private: (),
+            }
+
+            impl $Trait for $Name {
+                fn regs() -> &'static $Regs {
+                    &$regs
+                }
+                const RCC_PERIPHERAL: usize = $rcc;
+            }
+        )*
+    };
+}
+
+instances! { UartInstance, Uart:
+    Uart0 => UART0, RCC_PERIPHERAL_UART0;
+    Uart1 => UART1, RCC_PERIPHERAL_UART1;
+    Uart2 => UART2, RCC_PERIPHERAL_UART2;
+    Uart3 => UART3, RCC_PERIPHERAL_UART3;
+}
+
+instances! { I2cInstance, I2c:
+    I2c0 => I2C0, RCC_PERIPHERAL_I2C0;
+    I2c1 => I2C1, RCC_PERIPHERAL_I2C1;
+    I2c2 => I2C2, RCC_PERIPHERAL_I2C2;
+}
+
+instances! { SspInstance, Ssp:
+    Ssp0 => SSP0, RCC_PERIPHERAL_SSP0;
+    Ssp1 => SSP1, RCC_PERIPHERAL_SSP1;
+    Ssp2 => SSP2, RCC_PERIPHERAL_SSP2;
+}
+
+/// Pin multiplexing table: `Signal<Instance>: port pin => set_iomux function`
+macro_rules! iomux {
+    ($($Signal:ident<$Instance:ident>: $port:literal $n:literal => $function:literal;)*) => {
+        $(
+            impl<MODE> $Signal<$Instance> for Pin<$port, $n, MODE> {
+                const FUNCTION: u8 = $function;
+            }
+        )*
+    };
+}
+
+// Routes of the ASR6601 pin multiplexing table, as wired on the RA-08 and the vendor
+// evaluation board: UART0 on PB0/PB1, I2C0 on PA14/PA15 and SSP0 on PA8/PA10/PA11 (its NSS,
+// PA9, stays a GPIO chip select). A route missing here fails to compile.
+iomux! {
+    UartTx<Uart0>: 'B' 1 => 1;
+    UartRx<Uart0>: 'B' 0 => 1;
+    I2cScl<I2c0>: 'A' 14 => 3;
+    I2cSda<I2c0>: 'A' 15 => 3;
+    SspSck<Ssp0>: 'A' 8 => 2;
+    SspMosi<Ssp0>: 'A' 10 => 2;
+    SspMiso<Ssp0>: 'A' 11 => 2;
+}
+
+/// Route pin `P`/`N` to function `F`
+fn route<const P: char, const N: u8, MODE>(pin: &Pin<P, N, MODE>, function: u8) {
+    pin.gpio().set_iomux(Pin::<P, N, MODE>::PIN, function);
+}
+
+/// A UART with its pins, initialised and enabled
+pub struct UartHandle<U, TX, RX> {
+    uart: U,
+    tx: TX,
+    rx: RX,
+}
+
+impl<U: UartInstance, const TP: char, const TN: u8, TM, const RP: char, const RN: u8, RM>
+    UartHandle<U, Pin<TP, TN, TM>, Pin<RP, RN, RM>>
+where
+    Pin<TP, TN, TM>: UartTx<U>,
+    Pin<RP, RN, RM>: UartRx<U>,
+{
+    pub fn new(
+        uart: U,
+        tx: Pin<TP, TN, TM>,
+        rx: Pin<RP, RN, RM>,
+        config: UartConfig,
+    ) -> Result<Self, UartInitError> {
+        RCC.enable_peripheral_clk(U::RCC_PERIPHERAL, true);
+        route(&tx, <Pin<TP, TN, TM> as UartTx<U>>::FUNCTION);
+        route(&rx, <Pin<RP, RN, RM> as UartRx<U>>::FUNCTION);
+        U::regs().init(config)?;
+        U::regs().cmd(true);
+        Ok(Self { uart, tx, rx })
+    }
+
+    /// Polled serial port with the embedded-hal-nb traits
+    pub fn serial(&self) -> Serial {
+        Serial::new(U::regs())
+    }
+
+    pub fn regs(&self) -> &'static Uart {
+        U::regs()
+    }
+
+    /// Disable the UART and hand back its parts, the pins are GPIOs again
+    pub fn release(self) -> (U, Pin<TP, TN, TM>, Pin<RP, RN, RM>) {
+        U::regs().cmd(false);
+        route(&self.tx, 0);
+        route(&self.rx, 0);
+        (self.uart, self.tx, self.rx)
+    }
+}
+
+/// An I2C controller with its pins, initialised and enabled
+pub struct I2cHandle<I, SCL, SDA> {
+    i2c: I,
+    scl: SCL,
+    sda: SDA,
+}
+
+impl<I: I2cInstance, const CP: char, const CN: u8, CM, const DP: char, const DN: u8, DM>
+    I2cHandle<I, Pin<CP, CN, CM>, Pin<DP, DN, DM>>
+where
+    Pin<CP, CN, CM>: I2cScl<I>,
+    Pin<DP, DN, DM>: I2cSda<I>,
+{
+    pub fn new(i2c: I, scl: Pin<CP, CN, CM>, sda: Pin<DP, DN, DM>, config: I2cConfig) -> Self {
+        RCC.enable_peripheral_clk(I::RCC_PERIPHERAL, true);
+        route(&scl, <Pin<CP, CN, CM> as I2cScl<I>>::FUNCTION);
+        route(&sda, <Pin<DP, DN, DM> as I2cSda<I>>::FUNCTION);
+        I::regs().init(config);
+        I::regs().cmd(true);
+        Self { i2c, scl, sda }
+    }
+
+    /// Master with the embedded-hal traits
+    pub fn bus(&self) -> I2cBus {
+        I2cBus::new(I::regs())
+    }
+
+    pub fn regs(&self) -> &'static I2c {
+        I::regs()
+    }
+
+    /// Disable the controller and hand back its parts, the pins are GPIOs again
+    pub fn release(self) -> (I, Pin<CP, CN, CM>, Pin<DP, DN, DM>) {
+        I::regs().cmd(false);
+        route(&self.scl, 0);
+        route(&self.sda, 0);
+        (self.i2c, self.scl, self.sda)
+    }
+}
+
+/// An SSP controller with its clock and data pins, initialised and enabled. The chip selects
+/// are GPIO outputs, see [`crate::peripherals::spi::SpiDevice`].
+pub struct SpiHandle<S, SCK, MOSI, MISO> {
+    ssp: S,
+    sck: SCK,
+    mosi: MOSI,
+    miso: MISO,
+}
+
+impl<
+    S: SspInstance,
+    const KP: char,
+    const KN: u8,
+    KM,
+    const OP: char,
+    const ON: u8,
+    OM,
+    const IP: char,
+    const IN: u8,
+    IM,
+> SpiHandle<S, Pin<KP, KN, KM>, Pin<OP, ON, OM>, Pin<IP, IN, IM>>
+where
+    Pin<KP, KN, KM>: SspSck<S>,
+    Pin<OP, ON, OM>: SspMosi<S>,
+    Pin<IP, IN, IM>: SspMiso<S>,
+{
+    pub fn new(
+        ssp: S,
+        sck: Pin<KP, KN, KM>,
+        mosi: Pin<OP, ON, OM>,
+        miso: Pin<IP, IN, IM>,
+        config: SspConfig,
+    ) -> Self {
+        RCC.enable_peripheral_clk(S::RCC_PERIPHERAL, true);
+        route(&sck, <Pin<KP, KN, KM> as SspSck<S>>::FUNCTION);
+        route(&mosi, <Pin<OP, ON, OM> as SspMosi<S>>::FUNCTION);
+        route(&miso, <Pin<IP, IN, IM> as SspMiso<S>>::FUNCTION);
+        S::regs().init(config);
+        S::regs().cmd(true);
+        Self {
+            ssp,
+            sck,
+            mosi,
+            miso,
+        }
+    }
+
+    /// Bus with the embedded-hal traits
+    pub fn bus(&self) -> Spi {
+        Spi::new(S::regs())
+    }
+
+    pub fn regs(&self) -> &'static Ssp {
+        S::regs()
+    }
+
+    /// Disable the controller and hand back its parts, the pins are GPIOs again
+    #[allow(clippy::type_complexity)]
+    pub fn release(self) -> (S, Pin<KP, KN, KM>, Pin<OP, ON, OM>, Pin<IP, IN, IM>) {
+        S::regs().cmd(false);
+        route(&self.sck, 0);
+        route(&self.mosi, 0);
+        route(&self.miso, 0);
+        (self.ssp, self.sck, self.mosi, self.miso)
+    }
+}
+
+/// Every pin and bus peripheral, owned once.
+///
+/// The statics of `regs.rs` stay available to the drivers of this crate, apps built on
+/// `Peripherals` get a pin or bus only by moving it out of here.
+pub struct Peripherals {
+    pub gpioa: PortA,
+    pub gpiob: PortB,
+    pub gpioc: PortC,
+    pub gpiod: PortD,
+    pub uart0: Uart0,
+    pub uart1: Uart1,
+    pub uart2: Uart2,
+    pub uart3: Uart3,
+    pub i2c0: I2c0,
+    pub i2c1: I2c1,
+    pub i2c2: I2c2,
+    pub ssp0: Ssp0,
+    pub ssp1: Ssp1,
+    pub ssp2: Ssp2,
+}
+
+impl Peripherals {
+    /// The peripherals, `None` after the first call
+    pub fn take() -> Option<Self> {
+        if TAKEN.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some(unsafe { Self::steal() })
+    }
+
+    /// The peripherals, whether taken or not
+    ///
+    /// # Safety
+    /// The caller must not use a pin or bus owned elsewhere.
+    pub unsafe fn steal() -> Self {
+        Self {
+            gpioa: PortA::new(),
+            gpiob: PortB::new(),
+            gpioc: PortC::new(),
+            gpiod: PortD::new(),
+            uart0: Uart0 { _private: () },
+            uart1: Uart1 { _private: () },
+            uart2: Uart2 { _private: () },
+            uart3: Uart3 { _private: () },
+            i2c0: I2c0 { _private: () },
+            i2c1: I2c1 { _private: () },
+            i2c2: I2c2 { _private: () },
+            ssp0: Ssp0 { _private: () },
+            ssp1: Ssp1 { _private: () },
+            ssp2: Ssp2 { _private: () },
+        }
+    }
+}
@@ -812,6 +812,11 @@ impl Rcc {
         self.rst_cr.read() & RCC_RST_CR_RESET_REQ_EN_MASK
     }
 
+    /// Get the reset cause flags, `RCC_RST_SR_*`
+    pub fn get_reset_status(&self) -> usize {
+        self.rst_sr.read()
+    }
+
     /// Set the divider of the I2S MCLK
     pub fn set_i2s_mclk_div(&self, div: usize) {
         set_reg_bits!(self.cr3, RCC_CR3_I2S_MCLK_DIV_MASK, div << 8);
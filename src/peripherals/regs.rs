@@ -491,6 +491,22 @@ pub const UART_IFLS_RX_1_2: usize = 0x00000010;
 pub const UART_IFLS_RX_3_4: usize = 0x00000018;
 pub const UART_IFLS_RX_7_8: usize = 0x00000020;
 
+/****************************UART interrupt bit definition*********************/
+pub const UART_INTERRUPT_RX_DONE: usize = 0x00000010;
+pub const UART_INTERRUPT_TX_DONE: usize = 0x00000020;
+pub const UART_INTERRUPT_RX_TIMEOUT: usize = 0x00000040;
+pub const UART_INTERRUPT_FRAME_ERROR: usize = 0x00000080;
+pub const UART_INTERRUPT_PARITY_ERROR: usize = 0x00000100;
+pub const UART_INTERRUPT_BREAK_ERROR: usize = 0x00000200;
+pub const UART_INTERRUPT_OVERRUN_ERROR: usize = 0x00000400;
+
+/****************************UART DR bit definition****************************/
+pub const UART_DR_DATA: usize = 0x000000FF;
+pub const UART_DR_FRAME_ERROR: usize = 0x00000100;
+pub const UART_DR_PARITY_ERROR: usize = 0x00000200;
+pub const UART_DR_BREAK_ERROR: usize = 0x00000400;
+pub const UART_DR_OVERRUN_ERROR: usize = 0x00000800;
+
 /****************************UART DMACR bit definition*************************/
 pub const UART_DMACR_ONERR_EN_MASK: usize = 0x00000004;
 
@@ -833,6 +849,80 @@ pub static SEC: Sec = Sec::new(SEC_BASE);
 
 pub const SEC_SR_FLASH_ACCESS_ERROR_MASK: usize = 0x00001000;
 
+define_reg! {
+    Sae
+    __Sae {
+        cr: VolatileRW<usize>,
+        hcr: VolatileRW<usize>,
+        sr: VolatileRW<usize>,
+        gpr0: VolatileRW<usize>,
+        gpr1: VolatileRW<usize>,
+        gpr2: VolatileRW<usize>,
+        gpr3: VolatileRW<usize>,
+        gpr4: VolatileRW<usize>,
+        gpr5: VolatileRW<usize>,
+        maskcr: VolatileRW<usize>,
+        maskdat0: VolatileRW<usize>,
+        maskdat1: VolatileRW<usize>,
+        maskdat2: VolatileRW<usize>,
+        bufcr: VolatileRW<usize>,
+    }
+}
+
+/// Algorithm RAM shared by the block ciphers and the hashes
+pub const SAE_ARAM_BASE: usize = 0x40031000;
+pub const SAE_BASE: usize = 0x40032000;
+pub static SAE: Sae = Sae::new(SAE_BASE);
+
+pub const SAE_CR_ALG_SHA: usize = 0x00000001;
+pub const SAE_CR_ALG_BLOCK: usize = 0x00000002;
+pub const SAE_CR_WORD_ACCESS: usize = 0x00000010;
+pub const SAE_CR_ENDIAN_TRANS: usize = 0x00000020;
+
+pub const SAE_HCR_RAM_CLR: usize = 0x00000001;
+pub const SAE_HCR_CLK_EN: usize = 0x00000008;
+
+pub const SAE_GPR0_MODE_MASK: usize = 0x00000003;
+pub const SAE_GPR0_AES_DEC: usize = 0x00000001;
+pub const SAE_GPR0_AES_KEY: usize = 0x00000002;
+pub const SAE_GPR0_HASH_SHA256: usize = 0x00000002;
+pub const SAE_GPR0_RUN: usize = 0x00000080;
+
+pub const SAE_GPR3_AES_ECB: usize = 0x00000010;
+
+pub const SAE_AES_KEY_OFFSET: usize = 0x640;
+pub const SAE_AES_IN_OFFSET: usize = 0x000;
+pub const SAE_AES_OUT_OFFSET: usize = 0x020;
+pub const SAE_SHA_VE_OFFSET: usize = 0x000;
+pub const SAE_SHA_WD_OFFSET: usize = 0x010;
+pub const SAE_SHA_VA_OFFSET: usize = 0x300;
+pub const SAE_SHA_K_OFFSET: usize = 0x310;
+
+define_reg! {
+    Rng
+    __Rng {
+        det: VolatileRW<usize>,
+        sr: VolatileRW<usize>,
+        clk: VolatileRW<usize>,
+        cr: VolatileRW<usize>,
+        reseed: VolatileRW<usize>,
+        data: VolatileRO<usize>,
+        pknum: VolatileRW<usize>,
+        pkres0: VolatileRW<usize>,
+        pkres1: VolatileRW<usize>,
+        pkres2: VolatileRW<usize>,
+    }
+}
+
+pub const RNG_BASE: usize = 0x40033000;
+pub static RNG: Rng = Rng::new(RNG_BASE);
+
+pub const RNG_SR_DATA_READY: usize = 0x00000001;
+pub const RNG_SR_CLK_ERROR: usize = 0x00000002;
+pub const RNG_CLK_EN: usize = 0x00000080;
+pub const RNG_CR_EN: usize = 0x00000080;
+pub const RNG_CR_TRUE_MODE: usize = 0x00000040;
+
 define_reg! {
     Qspi
     __Qspi {
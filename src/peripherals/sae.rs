@@ -0,0 +1,229 @@
+use crate::{
+    crypto::{
+        Block, BlockCipher, KeyInit,
+        sha256::{self, K, Sha256, Sha256Compress, State},
+    },
+    peripherals::{
+        rcc::RCC_PERIPHERAL_SAC,
+        regs::{
+            RCC, RNG, RNG_CLK_EN, RNG_CR_EN, RNG_CR_TRUE_MODE, RNG_SR_DATA_READY, SAE,
+            SAE_AES_IN_OFFSET, SAE_AES_KEY_OFFSET, SAE_AES_OUT_OFFSET, SAE_ARAM_BASE,
+            SAE_CR_ALG_BLOCK, SAE_CR_ALG_SHA, SAE_CR_ENDIAN_TRANS, SAE_CR_WORD_ACCESS,
+            SAE_GPR0_AES_DEC, SAE_GPR0_AES_KEY, SAE_GPR0_HASH_SHA256, SAE_GPR0_MODE_MASK,
+            SAE_GPR0_RUN, SAE_GPR3_AES_ECB, SAE_HCR_CLK_EN, SAE_HCR_RAM_CLR, SAE_SHA_K_OFFSET,
+            SAE_SHA_VA_OFFSET, SAE_SHA_VE_OFFSET, SAE_SHA_WD_OFFSET,
+        },
+    },
+    set_reg_bits, toggle_reg_bits,
+};
+
+/// TRNG clock divider
+const TRNG_CLK_DIV: usize = 0;
+
+/// What the engine RAM currently holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Loaded {
+    /// cleared or closed
+    Nothing,
+    /// expanded key of the [`HwAes128`] with this id
+    Aes(u32),
+    /// SHA-256 round constants
+    Sha256,
+}
+
+/// The engine is shared by every user, each one reloads it when somebody else ran in between
+static mut SAE_LOADED: Loaded = Loaded::Nothing;
+static mut SAE_NEXT_ID: u32 = 0;
+static mut TRNG_READY: bool = false;
+
+fn aram_write(offset: usize, data: &[u8]) {
+    for (i, word) in data.chunks_exact(4).enumerate() {
+        let word = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+        unsafe { core::ptr::write_volatile((SAE_ARAM_BASE + offset + i * 4) as *mut u32, word) };
+    }
+}
+
+fn aram_read(offset: usize, data: &mut [u8]) {
+    for (i, word) in data.chunks_exact_mut(4).enumerate() {
+        let value =
+            unsafe { core::ptr::read_volatile((SAE_ARAM_BASE + offset + i * 4) as *const u32) };
+        word.copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Clock the engine, clear its RAM and select an algorithm
+fn sae_open(alg: usize) {
+    RCC.enable_peripheral_clk(RCC_PERIPHERAL_SAC, true);
+    SAE.hcr
+        .write(SAE.hcr.read() | SAE_HCR_RAM_CLR | SAE_HCR_CLK_EN);
+    // byte order is swapped by the engine, the RAM takes the data as it is in memory
+    SAE.cr.write(SAE_CR_WORD_ACCESS | SAE_CR_ENDIAN_TRANS | alg);
+    while SAE.hcr.read() & SAE_HCR_RAM_CLR != 0 {}
+}
+
+/// Start the selected operation and wait for it
+fn sae_run() {
+    toggle_reg_bits!(SAE.gpr0, SAE_GPR0_RUN, true);
+    while SAE.gpr0.read() & SAE_GPR0_RUN != 0 {}
+}
+
+/// Clear the engine RAM (keys included) and stop its clock
+pub fn sae_close() {
+    toggle_reg_bits!(SAE.hcr, SAE_HCR_RAM_CLR, true);
+    while SAE.hcr.read() & SAE_HCR_RAM_CLR != 0 {}
+    toggle_reg_bits!(SAE.hcr, SAE_HCR_CLK_EN, false);
+    unsafe { SAE_LOADED = Loaded::Nothing };
+}
+
+/// AES-128 on the security engine.
+///
+/// The key stays in the engine between blocks and is loaded again only after another
+/// [`HwAes128`] or a SHA-256 used the engine.
+pub struct HwAes128 {
+    key: [u8; 16],
+    id: u32,
+}
+
+impl HwAes128 {
+    /// Load a 128-bit key into the engine
+    pub fn new(key: &[u8; 16]) -> Self {
+        let id = unsafe {
+            SAE_NEXT_ID = SAE_NEXT_ID.wrapping_add(1);
+            SAE_NEXT_ID
+        };
+        let mut cipher = Self { key: *key, id };
+        cipher.load();
+        cipher
+    }
+
+    fn load(&mut self) {
+        if unsafe { SAE_LOADED } == Loaded::Aes(self.id) {
+            return;
+        }
+
+        sae_open(SAE_CR_ALG_BLOCK);
+        SAE.gpr3.write(SAE_GPR3_AES_ECB);
+        // 128-bit key
+        SAE.gpr0.write(0);
+        aram_write(SAE_AES_KEY_OFFSET, &self.key);
+
+        // key expansion
+        set_reg_bits!(SAE.gpr0, SAE_GPR0_MODE_MASK, SAE_GPR0_AES_KEY);
+        sae_run();
+        set_reg_bits!(SAE.gpr0, SAE_GPR0_MODE_MASK, 0);
+
+        unsafe { SAE_LOADED = Loaded::Aes(self.id) };
+    }
+
+    fn crypt(&mut self, block: &mut Block, mode: usize) {
+        self.load();
+        set_reg_bits!(SAE.gpr0, SAE_GPR0_MODE_MASK, mode);
+        aram_write(SAE_AES_IN_OFFSET, block);
+        sae_run();
+        aram_read(SAE_AES_OUT_OFFSET, block);
+    }
+}
+
+impl BlockCipher for HwAes128 {
+    fn encrypt_block(&mut self, block: &mut Block) {
+        self.crypt(block, 0);
+    }
+
+    fn decrypt_block(&mut self, block: &mut Block) {
+        self.crypt(block, SAE_GPR0_AES_DEC);
+    }
+}
+
+impl KeyInit for HwAes128 {
+    fn with_key(key: &[u8; 16]) -> Self {
+        Self::new(key)
+    }
+}
+
+impl Drop for HwAes128 {
+    fn drop(&mut self) {
+        for byte in self.key.iter_mut() {
+            unsafe { core::ptr::write_volatile(byte, 0) };
+        }
+        if unsafe { SAE_LOADED } == Loaded::Aes(self.id) {
+            sae_close();
+        }
+    }
+}
+
+/// SHA-256 compression function on the security engine
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HwSha256Compress;
+
+impl Sha256Compress for HwSha256Compress {
+    fn compress(&mut self, state: &mut State, block: &[u8; sha256::BLOCK_SIZE]) {
+        if unsafe { SAE_LOADED } != Loaded::Sha256 {
+            sae_open(SAE_CR_ALG_SHA);
+            SAE.gpr0.write(SAE_GPR0_HASH_SHA256);
+            for (i, k) in K.iter().enumerate() {
+                aram_write(SAE_SHA_K_OFFSET + i * 4, &k.to_be_bytes());
+            }
+            unsafe { SAE_LOADED = Loaded::Sha256 };
+        }
+
+        // the chaining value goes in and out every block, so hashes can be interleaved
+        aram_write(SAE_SHA_VA_OFFSET, &state[..16]);
+        aram_write(SAE_SHA_VE_OFFSET, &state[16..]);
+        aram_write(SAE_SHA_WD_OFFSET, block);
+        sae_run();
+        aram_read(SAE_SHA_VA_OFFSET, &mut state[..16]);
+        aram_read(SAE_SHA_VE_OFFSET, &mut state[16..]);
+    }
+}
+
+/// Streaming SHA-256 on the security engine
+pub type HwSha256 = Sha256<HwSha256Compress>;
+
+/// SHA-256 of `data` on the security engine
+pub fn hw_sha256(data: &[u8]) -> [u8; sha256::DIGEST_SIZE] {
+    let mut hasher = HwSha256::with_core(HwSha256Compress);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Start the true random number generator
+pub fn trng_init() {
+    RCC.enable_peripheral_clk(RCC_PERIPHERAL_SAC, true);
+    RNG.clk.write(TRNG_CLK_DIV | RNG_CLK_EN);
+    RNG.cr.write(RNG_CR_TRUE_MODE);
+    toggle_reg_bits!(RNG.cr, RNG_CR_EN, true);
+    unsafe { TRNG_READY = true };
+}
+
+/// Stop the true random number generator
+pub fn trng_deinit() {
+    toggle_reg_bits!(RNG.cr, RNG_CR_EN, false);
+    toggle_reg_bits!(RNG.clk, RNG_CLK_EN, false);
+    unsafe { TRNG_READY = false };
+}
+
+/// Fill `buf` with random bytes, starting the generator if needed
+pub fn trng_fill(buf: &mut [u8]) {
+    if unsafe { !TRNG_READY } {
+        trng_init();
+    }
+    for byte in buf.iter_mut() {
+        while RNG.sr.read() & RNG_SR_DATA_READY == 0 {}
+        *byte = RNG.data.read() as u8;
+    }
+}
+
+/// Random 32-bit value from the TRNG
+pub fn trng_u32() -> u32 {
+    let mut bytes = [0u8; 4];
+    trng_fill(&mut bytes);
+    u32::from_le_bytes(bytes)
+}
+
+/// Run the software known answer vectors on the engine
+pub fn sae_self_test() -> bool {
+    let ok = crate::crypto::known_answer_test(HwAes128::new)
+        && crate::crypto::sha256_known_answer_test(|| HwSha256Compress);
+    sae_close();
+    ok
+}
@@ -1,7 +1,10 @@
+use core::cell::Cell;
+
 use crate::{
     cortex::{VolatileRO, VolatileRW},
     define_reg,
     peripherals::{
+        gpio::{GpioMode, GpioPin, GpioPort},
         rcc::{
             RCC_PCLK0, RCC_PCLK1, RCC_PERIPHERAL_SSP0, RCC_PERIPHERAL_SSP1, RCC_PERIPHERAL_SSP2,
         },
@@ -63,6 +66,12 @@ pub const SSP_INTERRUPT_RX_OVERRUN_AND_TIMEOUT: usize = 0x3;
 /// All interrupt
 pub const SSP_INTERRUPT_ALL: usize = 0xf;
 
+/// Depth of the TX and RX FIFOs (frames)
+pub const SSP_FIFO_DEPTH: usize = 8;
+/// Polls of the status flags without a frame moving before a transfer gives up, far more than
+/// a 16-bit frame takes at the slowest clock rate
+pub const SSP_TIMEOUT_POLLS: usize = 100_000;
+
 /// TX DMA enable
 pub const SSP_DMA_TX_EN: usize = 1 << 1;
 /// RX DMA enable
@@ -94,6 +103,41 @@ impl Default for SspConfig {
     }
 }
 
+/// SSP error types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SspError {
+    /// 16-bit frames are taken from pairs of bytes, the buffer has an odd length.
+    OddLength,
+    /// No frame moved for [`SSP_TIMEOUT_POLLS`] polls, the controller is stalled or unclocked.
+    Timeout,
+}
+
+/// Word of a transfer: `u8` for 4 to 8-bit frames, `u16` for up to 16-bit frames
+pub trait SspWord: Copy {
+    fn from_frame(frame: usize) -> Self;
+    fn frame(self) -> usize;
+}
+
+impl SspWord for u8 {
+    fn from_frame(frame: usize) -> Self {
+        frame as u8
+    }
+
+    fn frame(self) -> usize {
+        self as usize
+    }
+}
+
+impl SspWord for u16 {
+    fn from_frame(frame: usize) -> Self {
+        frame as u16
+    }
+
+    fn frame(self) -> usize {
+        self as usize
+    }
+}
+
 define_reg! {
     Ssp
     __Ssp {
@@ -145,27 +189,15 @@ impl Ssp {
         self.config_interrupt(SSP_INTERRUPT_ALL, false);
         self.clear_interrupt(SSP_INTERRUPT_ALL);
 
-        RCC.get_clk_freq(RCC_PCLK0);
-        let clk_freq = if self.ptr() as usize == SSP0_BASE {
-            RCC.get_clk_freq(RCC_PCLK0)
-        } else {
-            RCC.get_clk_freq(RCC_PCLK1)
-        };
-
         toggle_reg_bits!(self.cr0, 0x3 << 4, false);
         toggle_reg_bits!(self.cr0, config.format, true);
 
-        let scr = (clk_freq / 2 / config.sclk) - 1; // check if > 0 instead of != 0 
-        toggle_reg_bits!(self.cpsr, 0xff, false);
-        toggle_reg_bits!(self.cpsr, 0x2, true);
-        toggle_reg_bits!(self.cr0, 0xff << 8, false);
-        toggle_reg_bits!(self.cr0, scr << 8, true);
-
-        toggle_reg_bits!(self.cr0, 0x3 << 6, false);
-        toggle_reg_bits!(self.cr0, config.clk_pol | config.clk_phase, true);
-
-        toggle_reg_bits!(self.cr0, 0xf, false);
-        toggle_reg_bits!(self.cr0, config.data_size, true);
+        self.set_format(
+            config.sclk,
+            config.clk_pol,
+            config.clk_phase,
+            config.data_size,
+        );
 
         toggle_reg_bits!(self.cr1, 0x1 << 2, false);
         if config.role != SSP_ROLE_MASTER {
@@ -200,44 +232,476 @@ impl Ssp {
         toggle_reg_bits!(self.cr1, 0x1 << 1, enable);
     }
 
-    pub fn send_data(&self, tx_data: &[u8]) {
-        let data_size = self.cr0.read() & 0xf;
-        let mut offset = 0;
+    /// Change the clock rate, the mode and the frame size, on a disabled or idle controller.
+    /// A rate out of reach, zero included, gives the closest one the divider can make.
+    ///
+    /// The registers are left alone when they hold these settings already.
+    pub fn set_format(&self, sclk: usize, clk_pol: usize, clk_phase: usize, data_size: usize) {
+        let clk_freq = if self.ptr() as usize == SSP0_BASE {
+            RCC.get_clk_freq(RCC_PCLK0)
+        } else {
+            RCC.get_clk_freq(RCC_PCLK1)
+        };
+
+        // the prescaler is 2, SCLK = clk_freq / 2 / (scr + 1)
+        let scr = (clk_freq / 2 / sclk.max(1)).clamp(1, 0x100) - 1;
+        let cr0 = (self.cr0.read() & !0xffcf) | scr << 8 | clk_pol | clk_phase | data_size;
+        if self.cpsr.read() & 0xff == 0x2 && self.cr0.read() == cr0 {
+            return;
+        }
+
+        toggle_reg_bits!(self.cpsr, 0xff, false);
+        toggle_reg_bits!(self.cpsr, 0x2, true);
+        self.cr0.write(cr0);
+    }
+
+    /// Send `write` while receiving into `read`, as a master. The shorter one is padded with
+    /// zeros sent, or with frames dropped.
+    pub fn transfer<W: SspWord>(&self, read: &mut [W], write: &[W]) -> Result<(), SspError> {
+        transfer(self, read, write)
+    }
+
+    /// Send `words`, replacing them with the words received
+    pub fn transfer_in_place<W: SspWord>(&self, words: &mut [W]) -> Result<(), SspError> {
+        transfer_in_place(self, words)
+    }
 
-        if data_size > SSP_DATA_SIZE_8BIT {
-            while offset + 1 < tx_data.len() {
-                while !self.get_interrupt_status(SSP_FLAG_RX_FIFO_NOT_EMPTY) {}
-                let val = u16::from_le_bytes([tx_data[offset], tx_data[offset + 1]]);
-                self.dr.write(val as usize);
-                offset += 2;
+    /// Wait until the last frame has been shifted out
+    pub fn flush(&self) -> Result<(), SspError> {
+        for _ in 0..SSP_TIMEOUT_POLLS {
+            if !self.get_flag_status(SSP_FLAG_BUSY) {
+                return Ok(());
             }
-        } else {
-            while offset < tx_data.len() {
-                while !self.get_interrupt_status(SSP_FLAG_RX_FIFO_NOT_EMPTY) {}
-                self.dr.write(tx_data[offset] as usize);
-                offset += 1;
+        }
+        Err(SspError::Timeout)
+    }
+
+    /// Send bytes, discarding what is received. 16-bit frames are taken little endian, from
+    /// pairs of bytes, an odd length fails with [`SspError::OddLength`] before sending.
+    pub fn send_data(&self, tx_data: &[u8]) -> Result<(), SspError> {
+        send_data(self, tx_data)
+    }
+
+    /// Receive bytes, sending zeros. 16-bit frames are stored little endian, in pairs of
+    /// bytes, an odd length fails with [`SspError::OddLength`] before receiving.
+    pub fn receive_data(&self, rx_data: &mut [u8]) -> Result<(), SspError> {
+        receive_data(self, rx_data)
+    }
+}
+
+/// The FIFOs of an SSP controller, what a transfer drives
+trait SspFifo {
+    fn tx_not_full(&self) -> bool;
+    fn rx_not_empty(&self) -> bool;
+    fn push(&self, frame: usize);
+    fn pop(&self) -> usize;
+    /// The frame size, `SSP_DATA_SIZE_*`
+    fn data_size(&self) -> usize;
+}
+
+impl SspFifo for Ssp {
+    fn tx_not_full(&self) -> bool {
+        self.get_flag_status(SSP_FLAG_TX_FIFO_NOT_FULL)
+    }
+
+    fn rx_not_empty(&self) -> bool {
+        self.get_flag_status(SSP_FLAG_RX_FIFO_NOT_EMPTY)
+    }
+
+    fn push(&self, frame: usize) {
+        self.dr.write(frame);
+    }
+
+    fn pop(&self) -> usize {
+        self.dr.read()
+    }
+
+    fn data_size(&self) -> usize {
+        self.cr0.read() & 0xf
+    }
+}
+
+fn transfer<W: SspWord>(fifo: &impl SspFifo, read: &mut [W], write: &[W]) -> Result<(), SspError> {
+    exchange(
+        fifo,
+        read.len().max(write.len()),
+        |index| write.get(index).map_or(0, |word| word.frame()),
+        |index, frame| {
+            if let Some(slot) = read.get_mut(index) {
+                *slot = W::from_frame(frame);
             }
+        },
+    )
+}
+
+fn transfer_in_place<W: SspWord>(fifo: &impl SspFifo, words: &mut [W]) -> Result<(), SspError> {
+    let words = Cell::from_mut(words).as_slice_of_cells();
+    exchange(
+        fifo,
+        words.len(),
+        |index| words[index].get().frame(),
+        |index, frame| words[index].set(W::from_frame(frame)),
+    )
+}
+
+fn send_data(fifo: &impl SspFifo, tx_data: &[u8]) -> Result<(), SspError> {
+    if fifo.data_size() <= SSP_DATA_SIZE_8BIT {
+        return transfer::<u8>(fifo, &mut [], tx_data);
+    }
+    if !tx_data.len().is_multiple_of(2) {
+        return Err(SspError::OddLength);
+    }
+    exchange(
+        fifo,
+        tx_data.len() / 2,
+        |index| u16::from_le_bytes([tx_data[2 * index], tx_data[2 * index + 1]]) as usize,
+        |_, _| {},
+    )
+}
+
+fn receive_data(fifo: &impl SspFifo, rx_data: &mut [u8]) -> Result<(), SspError> {
+    if fifo.data_size() <= SSP_DATA_SIZE_8BIT {
+        return transfer::<u8>(fifo, rx_data, &[]);
+    }
+    if !rx_data.len().is_multiple_of(2) {
+        return Err(SspError::OddLength);
+    }
+    exchange(
+        fifo,
+        rx_data.len() / 2,
+        |_| 0,
+        |index, frame| {
+            rx_data[2 * index..2 * index + 2].copy_from_slice(&(frame as u16).to_le_bytes())
+        },
+    )
+}
+
+/// Clock `frames` frames through the FIFOs, `next` gives the frame to send at an index and
+/// `store` takes the one received. The TX FIFO is kept ahead of the RX one by at most its
+/// depth, so the RX FIFO can't overrun.
+///
+/// Fails with [`SspError::Timeout`] once no frame has moved for [`SSP_TIMEOUT_POLLS`] polls.
+fn exchange(
+    fifo: &impl SspFifo,
+    frames: usize,
+    mut next: impl FnMut(usize) -> usize,
+    mut store: impl FnMut(usize, usize),
+) -> Result<(), SspError> {
+    // frames left over by a write that didn't read them back, at most a FIFO and the one
+    // being shifted
+    for _ in 0..=SSP_FIFO_DEPTH {
+        if !fifo.rx_not_empty() {
+            break;
         }
+        fifo.pop();
     }
 
-    pub fn receive_data(&self, rx_data: &mut [u8]) {
-        let data_size = self.cr0.read() & 0xf;
-        let mut offset = 0;
+    let mut sent = 0;
+    let mut received = 0;
+    let mut idle_polls = 0;
+    while received < frames {
+        if idle_polls == SSP_TIMEOUT_POLLS {
+            return Err(SspError::Timeout);
+        }
+        idle_polls += 1;
 
-        if data_size > SSP_DATA_SIZE_8BIT {
-            while offset + 1 < rx_data.len() {
-                while !self.get_interrupt_status(SSP_FLAG_RX_FIFO_NOT_EMPTY) {}
-                let val = (self.dr.read() as u16).to_le_bytes();
-                rx_data[offset] = val[0];
-                rx_data[offset + 1] = val[1];
-                offset += 2;
+        if sent < frames && sent - received < SSP_FIFO_DEPTH && fifo.tx_not_full() {
+            fifo.push(next(sent));
+            sent += 1;
+            idle_polls = 0;
+        }
+        if fifo.rx_not_empty() {
+            store(received, fifo.pop());
+            received += 1;
+            idle_polls = 0;
+        }
+    }
+    Ok(())
+}
+
+/// Per-device settings of a [`SpiDevice`], applied before each of its transfers
+#[derive(Clone, Copy)]
+pub struct SpiDeviceConfig {
+    pub sclk: usize,
+    pub clk_pol: usize,
+    pub clk_phase: usize,
+    /// `SSP_DATA_SIZE_*`, 4 and 8-bit frames use `u8` words, 16-bit ones `u16`
+    pub data_size: usize,
+}
+
+impl Default for SpiDeviceConfig {
+    fn default() -> Self {
+        Self {
+            sclk: 1_000_000,
+            clk_pol: SPI_CLK_POLARITY_HIGH,
+            clk_phase: SPI_CLK_PHASE_2EDGE,
+            data_size: SSP_DATA_SIZE_8BIT,
+        }
+    }
+}
+
+/// A device on an SSP master bus, with a GPIO chip select (active low) and its own clock
+/// rate, mode and frame size.
+///
+/// Several devices can share a bus, initialised as master and enabled with [`Ssp::cmd`]
+/// first. Each transfer selects the device for its duration.
+pub struct SpiDevice {
+    ssp: &'static Ssp,
+    cs_port: GpioPort,
+    cs_pin: GpioPin,
+    config: SpiDeviceConfig,
+}
+
+impl SpiDevice {
+    /// The chip select is configured as an output, high (deselected)
+    pub fn new(
+        ssp: &'static Ssp,
+        cs_port: GpioPort,
+        cs_pin: GpioPin,
+        config: SpiDeviceConfig,
+    ) -> Self {
+        cs_port.gpio().init(cs_pin, GpioMode::OutputPPHigh);
+        Self {
+            ssp,
+            cs_port,
+            cs_pin,
+            config,
+        }
+    }
+
+    /// Run `f` on the bus with the device selected, its settings applied. The chip select
+    /// goes back high once the last frame is out, or `f` failed.
+    pub fn transaction<R>(
+        &self,
+        f: impl FnOnce(&'static Ssp) -> Result<R, SspError>,
+    ) -> Result<R, SspError> {
+        let SpiDeviceConfig {
+            sclk,
+            clk_pol,
+            clk_phase,
+            data_size,
+        } = self.config;
+        self.ssp.set_format(sclk, clk_pol, clk_phase, data_size);
+
+        let cs = self.cs_port.gpio();
+        cs.write(self.cs_pin, false);
+        let result = f(self.ssp);
+        let flushed = self.ssp.flush();
+        cs.write(self.cs_pin, true);
+        let result = result?;
+        flushed.map(|()| result)
+    }
+
+    /// See [`Ssp::transfer`]
+    pub fn transfer<W: SspWord>(&self, read: &mut [W], write: &[W]) -> Result<(), SspError> {
+        self.transaction(|ssp| ssp.transfer(read, write))
+    }
+
+    /// See [`Ssp::transfer_in_place`]
+    pub fn transfer_in_place<W: SspWord>(&self, words: &mut [W]) -> Result<(), SspError> {
+        self.transaction(|ssp| ssp.transfer_in_place(words))
+    }
+
+    pub fn write<W: SspWord>(&self, words: &[W]) -> Result<(), SspError> {
+        self.transaction(|ssp| ssp.transfer(&mut [], words))
+    }
+
+    pub fn read<W: SspWord>(&self, words: &mut [W]) -> Result<(), SspError> {
+        self.transaction(|ssp| ssp.transfer(words, &[]))
+    }
+
+    /// Write `write` then read into `read`, in one selection: the usual command and response
+    pub fn write_read<W: SspWord>(&self, write: &[W], read: &mut [W]) -> Result<(), SspError> {
+        self.transaction(|ssp| {
+            ssp.transfer(&mut [], write)?;
+            ssp.transfer(read, &[])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, collections::VecDeque, vec::Vec};
+
+    /// A controller looped back on itself, MISO tied to MOSI. A frame time is `frame_polls`
+    /// polls of the RX FIFO: the frame in the shift register lands in the RX FIFO and the next
+    /// one leaves the TX FIFO. Every `stall_every` frames pushed, the CPU is away (an
+    /// interrupt) while the bus runs on.
+    struct SimSsp {
+        frame_polls: usize,
+        polls: Cell<usize>,
+        stall_every: usize,
+        pushed: Cell<usize>,
+        data_size: usize,
+        tx: RefCell<VecDeque<usize>>,
+        shifter: Cell<Option<usize>>,
+        rx: RefCell<VecDeque<usize>>,
+        sent: RefCell<Vec<usize>>,
+    }
+
+    impl SimSsp {
+        fn new(data_size: usize) -> Self {
+            Self {
+                frame_polls: 1,
+                polls: Cell::new(0),
+                stall_every: usize::MAX,
+                pushed: Cell::new(0),
+                data_size,
+                tx: RefCell::new(VecDeque::new()),
+                shifter: Cell::new(None),
+                rx: RefCell::new(VecDeque::new()),
+                sent: RefCell::new(Vec::new()),
             }
-        } else {
-            while offset < rx_data.len() {
-                while !self.get_interrupt_status(SSP_FLAG_RX_FIFO_NOT_EMPTY) {}
-                rx_data[offset] = self.dr.read() as u8;
-                offset += 1;
+        }
+
+        fn shift(&self) {
+            if let Some(frame) = self.shifter.take() {
+                assert!(self.rx.borrow().len() < SSP_FIFO_DEPTH, "RX FIFO overrun");
+                self.rx.borrow_mut().push_back(frame);
+            }
+            let mask = (1 << (self.data_size + 1)) - 1;
+            if let Some(frame) = self.tx.borrow_mut().pop_front() {
+                self.sent.borrow_mut().push(frame & mask);
+                self.shifter.set(Some(frame & mask));
+            }
+        }
+    }
+
+    impl SspFifo for SimSsp {
+        fn tx_not_full(&self) -> bool {
+            self.tx.borrow().len() < SSP_FIFO_DEPTH
+        }
+
+        fn rx_not_empty(&self) -> bool {
+            self.polls.set(self.polls.get() + 1);
+            if self.polls.get().is_multiple_of(self.frame_polls) {
+                self.shift();
             }
+            !self.rx.borrow().is_empty()
+        }
+
+        fn push(&self, frame: usize) {
+            assert!(self.tx.borrow().len() < SSP_FIFO_DEPTH, "TX FIFO overflow");
+            self.tx.borrow_mut().push_back(frame);
+            self.pushed.set(self.pushed.get() + 1);
+            if self.pushed.get().is_multiple_of(self.stall_every) {
+                for _ in 0..2 * SSP_FIFO_DEPTH {
+                    self.shift();
+                }
+            }
+        }
+
+        fn pop(&self) -> usize {
+            self.rx.borrow_mut().pop_front().unwrap()
+        }
+
+        fn data_size(&self) -> usize {
+            self.data_size
         }
     }
+
+    #[test]
+    fn exchange_paced() {
+        let ssp = SimSsp {
+            frame_polls: 3,
+            stall_every: 20,
+            ..SimSsp::new(SSP_DATA_SIZE_8BIT)
+        };
+        // a burst well past both FIFOs, with the CPU called away now and then
+        let write: Vec<u8> = (0..100).collect();
+        let mut read = [0u8; 100];
+        let ahead = Cell::new(0);
+        exchange(
+            &ssp,
+            write.len(),
+            |index| {
+                ahead.set(ahead.get() + 1);
+                assert!(ahead.get() <= SSP_FIFO_DEPTH);
+                write[index] as usize
+            },
+            |index, frame| {
+                ahead.set(ahead.get() - 1);
+                read[index] = frame as u8;
+            },
+        )
+        .unwrap();
+        assert_eq!(read[..], write[..]);
+    }
+
+    #[test]
+    fn stale_frames_dropped() {
+        let ssp = SimSsp::new(SSP_DATA_SIZE_8BIT);
+        ssp.rx.borrow_mut().extend([0xEE, 0xEE]);
+        let mut words = [1u8, 2, 3];
+        transfer_in_place(&ssp, &mut words).unwrap();
+        assert_eq!(words, [1, 2, 3]);
+    }
+
+    #[test]
+    fn padding() {
+        let ssp = SimSsp::new(SSP_DATA_SIZE_8BIT);
+        let mut read = [0xFFu8; 4];
+        transfer(&ssp, &mut read, &[7u8, 8]).unwrap();
+        assert_eq!(read, [7, 8, 0, 0]);
+
+        let mut read = [0u8; 1];
+        transfer(&ssp, &mut read, &[9u8, 10, 11]).unwrap();
+        assert_eq!(read, [9]);
+        assert_eq!(*ssp.sent.borrow(), [7, 8, 0, 0, 9, 10, 11]);
+    }
+
+    #[test]
+    fn four_bit_frames() {
+        let ssp = SimSsp::new(SSP_DATA_SIZE_4BIT);
+        // one frame per byte, the high nibble isn't shifted out
+        send_data(&ssp, &[0x12, 0xAB, 0x0F]).unwrap();
+        assert_eq!(*ssp.sent.borrow(), [0x2, 0xB, 0xF]);
+
+        let mut words = [0x34u8, 0xC5];
+        transfer_in_place(&ssp, &mut words).unwrap();
+        assert_eq!(words, [0x4, 0x5]);
+
+        let mut rx = [0xFFu8; 2];
+        receive_data(&ssp, &mut rx).unwrap();
+        assert_eq!(rx, [0, 0]);
+    }
+
+    #[test]
+    fn sixteen_bit_frames() {
+        let ssp = SimSsp::new(SSP_DATA_SIZE_16BIT);
+        send_data(&ssp, &[0x34, 0x12, 0x78, 0x56]).unwrap();
+        assert_eq!(*ssp.sent.borrow(), [0x1234, 0x5678]);
+
+        let mut rx = [0xFFu8; 4];
+        receive_data(&ssp, &mut rx).unwrap();
+        assert_eq!(rx, [0; 4]);
+    }
+
+    #[test]
+    fn sixteen_bit_odd_send() {
+        let ssp = SimSsp::new(SSP_DATA_SIZE_16BIT);
+        assert_eq!(send_data(&ssp, &[1, 2, 3]), Err(SspError::OddLength));
+        assert!(ssp.sent.borrow().is_empty());
+    }
+
+    #[test]
+    fn sixteen_bit_odd_receive() {
+        let ssp = SimSsp::new(SSP_DATA_SIZE_16BIT);
+        let mut rx = [0xFFu8; 3];
+        assert_eq!(receive_data(&ssp, &mut rx), Err(SspError::OddLength));
+        assert_eq!(rx, [0xFF; 3]);
+    }
+
+    #[test]
+    fn stalled_bus_times_out() {
+        // an unclocked controller: frames go into the TX FIFO and never come out
+        let ssp = SimSsp {
+            frame_polls: usize::MAX,
+            ..SimSsp::new(SSP_DATA_SIZE_8BIT)
+        };
+        let mut read = [0u8; 20];
+        assert_eq!(transfer(&ssp, &mut read, &[]), Err(SspError::Timeout));
+        assert_eq!(ssp.tx.borrow().len(), SSP_FIFO_DEPTH);
+    }
 }
@@ -0,0 +1,198 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+    cortex::{IRQType, nvic_disable_irq, nvic_enable_irq},
+    peripherals::{
+        buffered_uart::{RingBuffer, critical_section},
+        regs::{SSP0_BASE, SSP1_BASE, SSP2_BASE},
+        spi::{
+            SSP_FLAG_RX_FIFO_NOT_EMPTY, SSP_FLAG_TX_FIFO_NOT_FULL, SSP_INTERRUPT_ALL,
+            SSP_INTERRUPT_RX_FIFO_OVERRUN, SSP_INTERRUPT_RX_FIFO_TRIGGER,
+            SSP_INTERRUPT_RX_OVERRUN_AND_TIMEOUT, SSP_INTERRUPT_RX_TIMEOUT,
+            SSP_INTERRUPT_TX_FIFO_TRIGGER, SSP_ROLE_SLAVE, Ssp, SspConfig,
+        },
+    },
+};
+
+/// Receive buffer size of each SPI target
+pub const SPI_TARGET_RX_BUFFER_SIZE: usize = 128;
+/// Transmit buffer size of each SPI target
+pub const SPI_TARGET_TX_BUFFER_SIZE: usize = 128;
+
+struct SpiTargetState {
+    rx: RingBuffer<SPI_TARGET_RX_BUFFER_SIZE>,
+    tx: RingBuffer<SPI_TARGET_TX_BUFFER_SIZE>,
+    /// a frame was lost since the last [`SpiTarget::take_overrun`]
+    overrun: AtomicBool,
+    enabled: AtomicBool,
+}
+
+impl SpiTargetState {
+    const fn new() -> Self {
+        Self {
+            rx: RingBuffer::new(),
+            tx: RingBuffer::new(),
+            overrun: AtomicBool::new(false),
+            enabled: AtomicBool::new(false),
+        }
+    }
+}
+
+static SPI_TARGET_STATES: [SpiTargetState; 3] = [const { SpiTargetState::new() }; 3];
+
+/// Interrupt-driven SPI slave, e.g. for a link to a host MCU that clocks the bus.
+///
+/// The bytes the host sends are collected into a buffer by the interrupt handler, the ones
+/// queued with [`Self::try_write`] are shifted out as the host clocks. Nothing queued, the
+/// host reads whatever is left in the transmit FIFO, so the protocol should let it poll for
+/// an answer (a status byte, a ready line on a GPIO). Frames are 4 to 8 bits, the clock and
+/// the pins, chip select included, are left to the caller.
+pub struct SpiTarget {
+    ssp: &'static Ssp,
+    state: &'static SpiTargetState,
+    irq: IRQType,
+}
+
+impl SpiTarget {
+    /// Target driver of `ssp`, one of `SSP0`..`SSP2`
+    pub fn new(ssp: &'static Ssp) -> Self {
+        let (index, irq) = match ssp.ptr() as usize {
+            SSP0_BASE => (0, IRQType::Ssp0),
+            SSP1_BASE => (1, IRQType::Ssp1),
+            SSP2_BASE => (2, IRQType::Ssp2),
+            _ => unreachable!(),
+        };
+        Self {
+            ssp,
+            state: &SPI_TARGET_STATES[index],
+            irq,
+        }
+    }
+
+    /// Initialise the controller as a slave with the mode and frame size of `config`, its
+    /// role and clock rate are ignored, and start receiving
+    pub fn enable(&self, config: SspConfig) {
+        self.disable();
+
+        self.ssp.init(SspConfig {
+            role: SSP_ROLE_SLAVE,
+            ..config
+        });
+        self.state.rx.clear();
+        self.state.overrun.store(false, Ordering::Relaxed);
+
+        self.ssp.config_interrupt(
+            SSP_INTERRUPT_RX_FIFO_TRIGGER | SSP_INTERRUPT_RX_OVERRUN_AND_TIMEOUT,
+            true,
+        );
+        self.state.enabled.store(true, Ordering::Release);
+        self.ssp.cmd(true);
+        nvic_enable_irq(self.irq);
+    }
+
+    /// Stop answering the host, what is still queued is dropped
+    pub fn disable(&self) {
+        nvic_disable_irq(self.irq);
+        self.ssp.cmd(false);
+        self.ssp.config_interrupt(SSP_INTERRUPT_ALL, false);
+        self.ssp.clear_interrupt(SSP_INTERRUPT_ALL);
+        self.state.enabled.store(false, Ordering::Release);
+        self.state.tx.clear();
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state.enabled.load(Ordering::Acquire)
+    }
+
+    /// Bytes waiting in the receive buffer
+    pub fn rx_available(&self) -> usize {
+        self.state.rx.len()
+    }
+
+    /// Bytes queued and not yet in the transmit FIFO
+    pub fn tx_pending(&self) -> usize {
+        self.state.tx.len()
+    }
+
+    /// Copy the received bytes into `buf` without waiting, returns how many
+    pub fn try_read(&self, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+        for slot in buf.iter_mut() {
+            let Some(byte) = self.state.rx.pop() else {
+                break;
+            };
+            *slot = byte;
+            len += 1;
+        }
+        len
+    }
+
+    /// Next received byte, without waiting
+    pub fn try_read_byte(&self) -> Option<u8> {
+        self.state.rx.pop()
+    }
+
+    /// Queue as much of `data` as fits for the host to read, returns how many bytes
+    pub fn try_write(&self, data: &[u8]) -> usize {
+        critical_section(|| {
+            let len = data
+                .iter()
+                .take_while(|&&byte| self.state.tx.push(byte))
+                .count();
+            self.fill_tx();
+            self.ssp
+                .config_interrupt(SSP_INTERRUPT_TX_FIFO_TRIGGER, !self.state.tx.is_empty());
+            len
+        })
+    }
+
+    /// Drop the received bytes
+    pub fn clear_rx(&self) {
+        self.state.rx.clear();
+    }
+
+    /// Whether a received frame was lost, in the FIFO or the buffer, since the last call
+    pub fn take_overrun(&self) -> bool {
+        self.state.overrun.swap(false, Ordering::AcqRel)
+    }
+
+    /// Move queued bytes into the transmit FIFO while it has room
+    fn fill_tx(&self) {
+        while self.ssp.get_flag_status(SSP_FLAG_TX_FIFO_NOT_FULL) {
+            let Some(byte) = self.state.tx.pop() else {
+                break;
+            };
+            self.ssp.dr.write(byte as usize);
+        }
+    }
+
+    /// Interrupt handler body, called by `SSPx_IRQHandler`
+    pub fn on_irq(&self) {
+        if self.ssp.get_interrupt_status(SSP_INTERRUPT_RX_FIFO_OVERRUN) {
+            self.state.overrun.store(true, Ordering::Release);
+        }
+        // only the overrun and the timeout are latched, the FIFO levels clear themselves
+        self.ssp
+            .clear_interrupt(SSP_INTERRUPT_RX_FIFO_OVERRUN | SSP_INTERRUPT_RX_TIMEOUT);
+
+        while self.ssp.get_flag_status(SSP_FLAG_RX_FIFO_NOT_EMPTY) {
+            if !self.state.rx.push(self.ssp.dr.read() as u8) {
+                self.state.overrun.store(true, Ordering::Release);
+            }
+        }
+
+        self.fill_tx();
+        if self.state.tx.is_empty() {
+            self.ssp
+                .config_interrupt(SSP_INTERRUPT_TX_FIFO_TRIGGER, false);
+        }
+    }
+}
+
+/// Interrupt handler of an SSP controller, does nothing unless it's an enabled [`SpiTarget`]
+pub fn spi_target_irq_handler(ssp: &'static Ssp) {
+    let target = SpiTarget::new(ssp);
+    if target.is_enabled() {
+        target.on_irq();
+    }
+}
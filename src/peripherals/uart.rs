@@ -140,6 +140,16 @@ impl Uart {
         (self.dr.read() & 0xFF) as u8
     }
 
+    /// Read the data register as is, the byte with its `UART_DR_*` error flags
+    pub fn receive_raw(&self) -> usize {
+        self.dr.read()
+    }
+
+    /// Enable or disable the TX and RX FIFOs, disabling them flushes them
+    pub fn config_fifo(&self, new_state: bool) {
+        toggle_reg_bits!(self.lcr_h, UART_LCR_H_FEN, new_state);
+    }
+
     /// Config the interrupt of the specified UART flag
     pub fn config_interrupt(&self, uart_interrupt: usize, new_state: bool) {
         toggle_reg_bits!(self.imsc, uart_interrupt, new_state);
@@ -1,13 +1,19 @@
 use core::fmt::Write;
 
-use crate::peripherals::regs::UART0;
+use crate::peripherals::{buffered_uart::uart0, regs::UART0};
 
+/// UART0 console, queued on the TX buffer once buffered mode is enabled
 pub struct SerialWriter;
 
 impl Write for SerialWriter {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        for byte in s.bytes() {
-            UART0.send_data(byte);
+        let uart = uart0();
+        if uart.is_enabled() {
+            uart.queue_all(s.as_bytes());
+        } else {
+            for byte in s.bytes() {
+                UART0.send_data(byte);
+            }
         }
         Ok(())
     }
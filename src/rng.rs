@@ -0,0 +1,142 @@
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    crypto::{
+        drbg::{CtrDrbg, SEED_SIZE},
+        sha256::Sha256,
+    },
+    peripherals::sae::trng_fill,
+};
+
+/// TRNG bytes condensed into every seed, twice the seed size
+const TRNG_SEED_BYTES: usize = 2 * SEED_SIZE;
+
+/// Entropy added since the last seed, hashed in at the next reseed
+struct Pool {
+    hasher: Sha256,
+    fresh: bool,
+}
+
+static mut DRBG: Option<CtrDrbg> = None;
+static mut POOL: Pool = Pool {
+    hasher: Sha256::new(),
+    fresh: false,
+};
+
+/// Hash the pending pool and a fresh TRNG read into a seed
+fn collect_seed() -> [u8; SEED_SIZE] {
+    let pool = unsafe { &mut POOL };
+    let mut trng = [0u8; TRNG_SEED_BYTES];
+    trng_fill(&mut trng);
+
+    let mut hasher = core::mem::take(&mut pool.hasher);
+    pool.fresh = false;
+    hasher.update(&trng);
+    for byte in trng.iter_mut() {
+        unsafe { core::ptr::write_volatile(byte, 0) };
+    }
+    hasher.finalize()
+}
+
+fn drbg() -> &'static mut CtrDrbg {
+    let drbg = unsafe { &mut DRBG };
+    match drbg {
+        Some(drbg) => {
+            if drbg.needs_reseed() || unsafe { POOL.fresh } {
+                drbg.reseed(&collect_seed());
+            }
+            drbg
+        }
+        None => drbg.insert(CtrDrbg::new(&collect_seed())),
+    }
+}
+
+/// Seed the generator from the TRNG.
+///
+/// Optional, the first request seeds it too. Radio noise added later with
+/// [`rng_add_entropy`] is mixed in at the next request.
+pub fn rng_init() {
+    drbg();
+}
+
+/// Queue extra entropy (RSSI noise, timing, ...) for the next request
+pub fn rng_add_entropy(data: &[u8]) {
+    let pool = unsafe { &mut POOL };
+    pool.hasher.update(data);
+    pool.fresh = true;
+}
+
+/// Force a reseed from the TRNG and the pending entropy
+pub fn rng_reseed() {
+    let seed = collect_seed();
+    match unsafe { &mut DRBG } {
+        Some(drbg) => drbg.reseed(&seed),
+        None => unsafe { DRBG = Some(CtrDrbg::new(&seed)) },
+    }
+}
+
+/// Fill `buf` with random bytes
+pub fn rng_fill(buf: &mut [u8]) {
+    drbg().generate(buf);
+}
+
+/// Random 32-bit value
+pub fn rng_u32() -> u32 {
+    drbg().next_u32()
+}
+
+/// Uniform value in `min..=max`, without the modulo bias of the old `randr`
+pub fn rng_range(min: i32, max: i32) -> i32 {
+    if max <= min {
+        return min;
+    }
+    let span = (max as i64 - min as i64 + 1) as u64;
+    // reject the top partial range
+    let zone = u64::MAX - (u64::MAX - span + 1) % span;
+    loop {
+        let value = drbg().next_u64();
+        if value <= zone {
+            return (min as i64 + (value % span) as i64) as i32;
+        }
+    }
+}
+
+/// Handle on the system generator for code written against `rand_core`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRng;
+
+impl RngCore for SystemRng {
+    fn next_u32(&mut self) -> u32 {
+        rng_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        drbg().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        rng_fill(dst);
+    }
+}
+
+impl CryptoRng for SystemRng {}
+
+// ── extern "C" wrappers matching utilities.h ───────────────────────────────
+
+/// The LoRaMac seeds with `Radio.Random()`, the value is mixed in as extra entropy
+#[unsafe(no_mangle)]
+pub extern "C" fn srand1(seed: u32) {
+    rng_add_entropy(&seed.to_le_bytes());
+}
+
+/// Random value in `0..=i32::MAX`
+#[unsafe(no_mangle)]
+pub extern "C" fn rand1() -> i32 {
+    (rng_u32() >> 1) as i32
+}
+
+/// Random value in `min..=max`
+#[unsafe(no_mangle)]
+pub extern "C" fn randr(min: i32, max: i32) -> i32 {
+    rng_range(min, max)
+}
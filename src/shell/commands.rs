@@ -0,0 +1,599 @@
+use core::sync::atomic::Ordering;
+
+use crate::{
+    lora::{
+        radio::{
+            RadioEvents, RadioModem, radio_init, radio_irq_process, radio_read, radio_rx,
+            radio_send, radio_set_channel, radio_set_rx_config, radio_set_tx_config,
+            radio_set_tx_continuous_wave, radio_standby, radio_write,
+        },
+        timer::{timer_get_current_time, timer_pending},
+    },
+    peripherals::{
+        buffered_uart::uart0,
+        rcc::{RCC_HCLK, RCC_PCLK0, RCC_PCLK1, RCC_SYS_CLK},
+        regs::{
+            I2C_CR_TWSI_UNIT_EN_MASK, I2C0, I2C1, I2C2, RCC, RCC_RST_SR_BOR_RESET_SR,
+            RCC_RST_SR_CPU_RESET_SR, RCC_RST_SR_EFC_RESET_SR, RCC_RST_SR_IWDG_RESET_SR,
+            RCC_RST_SR_SEC_RESET_SR, RCC_RST_SR_STANDBY_RESET_SR, RCC_RST_SR_WDG_RESET_SR, RTC,
+        },
+        system::system_reset,
+    },
+    print,
+    print::SerialWriter,
+    shell::{Args, LineEditor, REGISTER_BLOCKS, ShellCommand, ShellError, find_command},
+    util::{parse_hex, write_hex},
+};
+
+/// Prompt printed before every line
+const PROMPT: &str = "> ";
+/// Most words printed by `peek` and bytes by `rreg`
+const MAX_DUMP: u32 = 64;
+/// Radio transmission timeout (ms)
+const TX_TIMEOUT: usize = 3_000;
+
+/// Reset cause flags of `RCC.rst_sr` and their names
+const RESET_CAUSES: [(usize, &str); 7] = [
+    (RCC_RST_SR_BOR_RESET_SR, "brown-out"),
+    (RCC_RST_SR_IWDG_RESET_SR, "independent watchdog"),
+    (RCC_RST_SR_WDG_RESET_SR, "watchdog"),
+    (RCC_RST_SR_EFC_RESET_SR, "flash controller"),
+    (RCC_RST_SR_CPU_RESET_SR, "cpu (software)"),
+    (RCC_RST_SR_SEC_RESET_SR, "security"),
+    (RCC_RST_SR_STANDBY_RESET_SR, "standby wake-up"),
+];
+
+struct Shell {
+    editor: LineEditor,
+    /// commands of the app, searched first
+    commands: &'static [ShellCommand],
+    /// the radio events belong to the shell
+    radio_ready: bool,
+}
+
+static mut SHELL: Shell = Shell {
+    editor: LineEditor::new(),
+    commands: &[],
+    radio_ready: false,
+};
+
+/// radio events used by the radio commands
+static SHELL_EVENTS: RadioEvents = RadioEvents {
+    tx_done: Some(on_tx_done),
+    tx_timeout: Some(on_tx_timeout),
+    rx_done: Some(on_rx_done),
+    rx_timeout: Some(on_rx_timeout),
+    rx_error: Some(on_rx_error),
+    rx_crc_error: Some(on_rx_crc_error),
+    fhss_change_channel: None,
+    cad_done: None,
+};
+
+/// Built-in commands
+pub static BUILTIN_COMMANDS: &[ShellCommand] = &[
+    ShellCommand {
+        name: "help",
+        usage: "",
+        help: "list the commands",
+        run: help,
+    },
+    ShellCommand {
+        name: "blocks",
+        usage: "",
+        help: "list the register blocks usable as addresses",
+        run: blocks,
+    },
+    ShellCommand {
+        name: "peek",
+        usage: "<addr> [words]",
+        help: "read 32-bit registers, addr may be a block (rcc, uart0+0x18)",
+        run: peek,
+    },
+    ShellCommand {
+        name: "poke",
+        usage: "<addr> <value>",
+        help: "write a 32-bit register",
+        run: poke,
+    },
+    ShellCommand {
+        name: "rreg",
+        usage: "<addr> [bytes]",
+        help: "read SX126x registers",
+        run: rreg,
+    },
+    ShellCommand {
+        name: "wreg",
+        usage: "<addr> <value>",
+        help: "write an SX126x register",
+        run: wreg,
+    },
+    ShellCommand {
+        name: "tx",
+        usage: "<freq> <sf> <dbm> <hex>",
+        help: "send a LoRa packet at 125 kHz",
+        run: tx,
+    },
+    ShellCommand {
+        name: "rx",
+        usage: "<freq> <sf> [ms]",
+        help: "receive at 125 kHz, 0 ms or none for continuous",
+        run: rx,
+    },
+    ShellCommand {
+        name: "cw",
+        usage: "<freq> <dbm> [s]",
+        help: "transmit a continuous wave",
+        run: cw,
+    },
+    ShellCommand {
+        name: "idle",
+        usage: "",
+        help: "put the radio in standby",
+        run: idle,
+    },
+    ShellCommand {
+        name: "timers",
+        usage: "",
+        help: "list the pending LoRa timers",
+        run: timers,
+    },
+    ShellCommand {
+        name: "i2cscan",
+        usage: "<unit>",
+        help: "list the slaves on an I2C bus set up by the app",
+        run: i2c_scan,
+    },
+    ShellCommand {
+        name: "rtc",
+        usage: "",
+        help: "show the RTC calendar and the timer clock",
+        run: rtc,
+    },
+    ShellCommand {
+        name: "clocks",
+        usage: "",
+        help: "show the system and bus clocks",
+        run: clocks,
+    },
+    ShellCommand {
+        name: "reset",
+        usage: "",
+        help: "show the cause of the last reset",
+        run: reset_cause,
+    },
+    ShellCommand {
+        name: "reboot",
+        usage: "",
+        help: "restart the chip",
+        run: reboot,
+    },
+];
+
+fn shell_state() -> &'static mut Shell {
+    unsafe { &mut SHELL }
+}
+
+fn help(args: &mut Args) -> Result<(), ShellError> {
+    args.finish()?;
+    let tables = [shell_state().commands, BUILTIN_COMMANDS];
+    for command in tables.iter().flat_map(|table| table.iter()) {
+        print!(
+            "{:<8}{:<26}{}\r\n",
+            command.name, command.usage, command.help
+        );
+    }
+    Ok(())
+}
+
+fn blocks(args: &mut Args) -> Result<(), ShellError> {
+    args.finish()?;
+    for (name, base) in REGISTER_BLOCKS {
+        print!("{:<10}0x{:08X}\r\n", name, base);
+    }
+    Ok(())
+}
+
+fn peek(args: &mut Args) -> Result<(), ShellError> {
+    let addr = args.next_address()?;
+    let words = args.next_number_or(1)?;
+    args.finish()?;
+    if !addr.is_multiple_of(4) {
+        return Err(ShellError::Argument(1));
+    }
+    if !(1..=MAX_DUMP).contains(&words) {
+        return Err(ShellError::Argument(2));
+    }
+
+    for i in 0..words as usize {
+        let word_addr = addr + i * 4;
+        if i % 4 == 0 {
+            print!("0x{:08X}:", word_addr);
+        }
+        let value = unsafe { core::ptr::read_volatile(word_addr as *const u32) };
+        print!(" 0x{:08X}", value);
+        if i % 4 == 3 || i + 1 == words as usize {
+            print!("\r\n");
+        }
+    }
+    Ok(())
+}
+
+fn poke(args: &mut Args) -> Result<(), ShellError> {
+    let addr = args.next_address()?;
+    let value = args.next_number()?;
+    args.finish()?;
+    if !addr.is_multiple_of(4) {
+        return Err(ShellError::Argument(1));
+    }
+
+    unsafe { core::ptr::write_volatile(addr as *mut u32, value) };
+    let read_back = unsafe { core::ptr::read_volatile(addr as *const u32) };
+    print!("0x{:08X}: 0x{:08X}\r\n", addr, read_back);
+    Ok(())
+}
+
+/// Give the radio events to the shell, the first radio command takes them from the app
+fn ensure_radio() {
+    let shell = shell_state();
+    if !shell.radio_ready {
+        radio_init(&SHELL_EVENTS);
+        shell.radio_ready = true;
+    }
+}
+
+fn rreg(args: &mut Args) -> Result<(), ShellError> {
+    let addr = args.next_number()?;
+    let count = args.next_number_or(1)?;
+    args.finish()?;
+    if addr > u16::MAX as u32 {
+        return Err(ShellError::Argument(1));
+    }
+    if !(1..=MAX_DUMP).contains(&count) {
+        return Err(ShellError::Argument(2));
+    }
+
+    ensure_radio();
+    let mut buf = [0u8; MAX_DUMP as usize];
+    let buf = &mut buf[..count as usize];
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = radio_read((addr as u16).wrapping_add(i as u16));
+    }
+    for (i, chunk) in buf.chunks(16).enumerate() {
+        print!("0x{:04X}:", addr as usize + i * 16);
+        for byte in chunk {
+            print!(" {:02X}", byte);
+        }
+        print!("\r\n");
+    }
+    Ok(())
+}
+
+fn wreg(args: &mut Args) -> Result<(), ShellError> {
+    let addr = args.next_number()?;
+    let value = args.next_number()?;
+    args.finish()?;
+    if addr > u16::MAX as u32 {
+        return Err(ShellError::Argument(1));
+    }
+    if value > u8::MAX as u32 {
+        return Err(ShellError::Argument(2));
+    }
+
+    ensure_radio();
+    radio_write(addr as u16, value as u8);
+    Ok(())
+}
+
+/// Frequency (Hz) argument of the radio commands
+fn next_frequency(args: &mut Args) -> Result<usize, ShellError> {
+    Ok(args.next_number()? as usize)
+}
+
+/// Output power (dBm) argument of the radio commands
+fn next_power(args: &mut Args) -> Result<i8, ShellError> {
+    let index = args.index();
+    args.next_signed()?
+        .try_into()
+        .map_err(|_| ShellError::Argument(index))
+}
+
+/// Spreading factor argument of the radio commands
+fn next_sf(args: &mut Args) -> Result<usize, ShellError> {
+    let index = args.index();
+    let sf = args.next_number()?;
+    if !(5..=12).contains(&sf) {
+        return Err(ShellError::Argument(index));
+    }
+    Ok(sf as usize)
+}
+
+fn tx(args: &mut Args) -> Result<(), ShellError> {
+    let freq = next_frequency(args)?;
+    let sf = next_sf(args)?;
+    let power = next_power(args)?;
+    let hex = args.next_str()?;
+    args.finish()?;
+    let mut payload = [0u8; 255];
+    let len = parse_hex(hex, &mut payload).ok_or(ShellError::Argument(4))?;
+
+    ensure_radio();
+    radio_standby();
+    radio_set_channel(freq);
+    radio_set_tx_config(
+        RadioModem::LoRa,
+        power,
+        0,
+        0,
+        sf,
+        1,
+        8,
+        false,
+        true,
+        false,
+        0,
+        false,
+        TX_TIMEOUT,
+    );
+    radio_send(&payload[..len]);
+    Ok(())
+}
+
+fn rx(args: &mut Args) -> Result<(), ShellError> {
+    let freq = next_frequency(args)?;
+    let sf = next_sf(args)?;
+    let timeout = args.next_number_or(0)?;
+    args.finish()?;
+
+    ensure_radio();
+    radio_standby();
+    radio_set_channel(freq);
+    radio_set_rx_config(
+        RadioModem::LoRa,
+        0,
+        sf,
+        1,
+        0,
+        8,
+        0,
+        false,
+        0,
+        true,
+        false,
+        0,
+        false,
+        timeout == 0,
+    );
+    radio_rx(timeout as usize);
+    Ok(())
+}
+
+fn cw(args: &mut Args) -> Result<(), ShellError> {
+    let freq = next_frequency(args)?;
+    let power = next_power(args)?;
+    let seconds = args.next_number_or(5)?;
+    args.finish()?;
+    let seconds = u16::try_from(seconds).map_err(|_| ShellError::Argument(3))?;
+
+    ensure_radio();
+    radio_standby();
+    radio_set_tx_continuous_wave(freq, power, seconds);
+    Ok(())
+}
+
+fn idle(args: &mut Args) -> Result<(), ShellError> {
+    args.finish()?;
+    ensure_radio();
+    radio_standby();
+    Ok(())
+}
+
+fn timers(args: &mut Args) -> Result<(), ShellError> {
+    args.finish()?;
+    let pending = timer_pending();
+    if pending.is_empty() {
+        print!("no pending timers\r\n");
+    }
+    for timer in pending {
+        print!(
+            "#{:<4}in {:>8} ms  period {:>8} ms  ",
+            timer.id, timer.remaining, timer.reload_value
+        );
+        match timer.callback {
+            Some(callback) => {
+                print!("{:p}\r\n", callback);
+            }
+            None => {
+                print!("-\r\n");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn i2c_scan(args: &mut Args) -> Result<(), ShellError> {
+    let unit = args.next_number()?;
+    args.finish()?;
+    let i2c = match unit {
+        0 => &I2C0,
+        1 => &I2C1,
+        2 => &I2C2,
+        _ => return Err(ShellError::Argument(1)),
+    };
+    if i2c.cr.read() & I2C_CR_TWSI_UNIT_EN_MASK == 0 {
+        return Err(ShellError::Failed("unit not enabled"));
+    }
+    // a stuck bus would time out on every address
+    i2c.recover_bus()
+        .map_err(|_| ShellError::Failed("bus stuck"))?;
+
+    let found = i2c.scan();
+    for address in &found {
+        print!("0x{:02X} ", address);
+    }
+    print!("{} found\r\n", found.len());
+    Ok(())
+}
+
+fn rtc(args: &mut Args) -> Result<(), ShellError> {
+    args.finish()?;
+    let calendar = RTC.get_calendar();
+    print!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06} (weekday {})\r\n",
+        calendar.year.load(Ordering::Relaxed),
+        calendar.month.load(Ordering::Relaxed),
+        calendar.day.load(Ordering::Relaxed),
+        calendar.hour.load(Ordering::Relaxed),
+        calendar.minute.load(Ordering::Relaxed),
+        calendar.second.load(Ordering::Relaxed),
+        calendar.subsecond.load(Ordering::Relaxed),
+        calendar.week.load(Ordering::Relaxed),
+    );
+    print!("timer clock {} ms\r\n", timer_get_current_time());
+    Ok(())
+}
+
+fn clocks(args: &mut Args) -> Result<(), ShellError> {
+    args.finish()?;
+    for (name, clk) in [
+        ("sysclk", RCC_SYS_CLK),
+        ("hclk", RCC_HCLK),
+        ("pclk0", RCC_PCLK0),
+        ("pclk1", RCC_PCLK1),
+    ] {
+        print!("{:<8}{} Hz\r\n", name, RCC.get_clk_freq(clk));
+    }
+    Ok(())
+}
+
+fn reset_cause(args: &mut Args) -> Result<(), ShellError> {
+    args.finish()?;
+    let status = RCC.get_reset_status();
+    print!("0x{:02X}", status);
+    let mut causes = RESET_CAUSES.iter().filter(|(flag, _)| status & flag != 0);
+    match causes.next() {
+        Some((_, name)) => {
+            print!(" {}", name);
+        }
+        None => {
+            print!(" unknown");
+        }
+    }
+    for (_, name) in causes {
+        print!(", {}", name);
+    }
+    print!("\r\n");
+    Ok(())
+}
+
+fn reboot(args: &mut Args) -> Result<(), ShellError> {
+    args.finish()?;
+    system_reset();
+    Ok(())
+}
+
+/// Print the prompt and the line being typed again after an event
+fn redraw() {
+    print!("\r\n{}{}", PROMPT, shell_state().editor.line());
+}
+
+/// Print a radio event without losing the line being typed
+fn notify(args: core::fmt::Arguments) {
+    print!("\r\n{}", args);
+    redraw();
+}
+
+fn on_tx_done() {
+    notify(format_args!("tx done"));
+}
+
+fn on_tx_timeout() {
+    notify(format_args!("tx timeout"));
+}
+
+fn on_rx_done(payload: &[u8], rssi: i16, snr: i8) {
+    print!(
+        "\r\nrx {} bytes, rssi {} dBm, snr {} dB: ",
+        payload.len(),
+        rssi,
+        snr
+    );
+    let _ = write_hex(&mut SerialWriter, payload);
+    redraw();
+}
+
+fn on_rx_timeout() {
+    notify(format_args!("rx timeout"));
+}
+
+fn on_rx_error() {
+    notify(format_args!("rx error"));
+}
+
+fn on_rx_crc_error(payload: &[u8], rssi: i16, snr: i8) {
+    notify(format_args!(
+        "rx crc error, {} bytes, rssi {} dBm, snr {} dB",
+        payload.len(),
+        rssi,
+        snr
+    ));
+}
+
+/// Run one command line
+fn execute(line: &str) {
+    let Some(mut args) = Args::new(line) else {
+        print!("too many arguments\r\n");
+        return;
+    };
+    let Some(name) = args.command() else {
+        return;
+    };
+
+    let tables = [shell_state().commands, BUILTIN_COMMANDS];
+    let Some(command) = find_command(&tables, name) else {
+        print!("unknown command `{}`, try `help`\r\n", name);
+        return;
+    };
+    match (command.run)(&mut args) {
+        Ok(()) => {}
+        Err(ShellError::Unknown) => {
+            print!("unknown command `{}`\r\n", name);
+        }
+        Err(ShellError::Usage) => {
+            print!("usage: {} {}\r\n", command.name, command.usage);
+        }
+        Err(ShellError::Argument(index)) => {
+            print!("bad argument {}\r\n", index);
+        }
+        Err(ShellError::Failed(reason)) => {
+            print!("failed: {}\r\n", reason);
+        }
+    }
+}
+
+/// Start the shell on UART0, `commands` are offered next to the built-in ones
+pub fn shell_init(commands: &'static [ShellCommand]) {
+    shell_state().commands = commands;
+    print!("\r\ndebug shell, `help` lists the commands\r\n{}", PROMPT);
+}
+
+/// Handle the bytes received on UART0 without blocking, call it from the main loop
+pub fn shell_poll() {
+    let shell = shell_state();
+    while let Some(byte) = uart0().try_read_byte() {
+        if shell.editor.feed(byte, &mut SerialWriter) {
+            execute(shell.editor.line());
+            print!("{}", PROMPT);
+        }
+    }
+}
+
+/// debug shell start, never returns
+///
+/// Runs the shell with the built-in commands only, the radio commands own the radio events.
+pub fn app_start() -> ! {
+    shell_init(&[]);
+    loop {
+        radio_irq_process();
+        shell_poll();
+    }
+}
@@ -0,0 +1,312 @@
+use core::fmt::Write;
+
+use heapless::Vec;
+
+use crate::peripherals::regs::{
+    ADC_BASE, AFEC_BASE, BSTIMER0_SFR_BASE, BSTIMER1_SFR_BASE, CRC_BASE, DAC_BASE, EFC_BASE,
+    GPIOA_BASE, GPIOB_BASE, GPIOC_BASE, GPIOD_BASE, I2C0_BASE, I2C1_BASE, I2C2_BASE, I2S_BASE,
+    IWDG_BASE, LCD_BASE, LORAC_BASE, LPTIMER0_SFR_BASE, LPTIMER1_SFR_BASE, LPUART_BASE, PWR_BASE,
+    QSPI_BASE, RCC_BASE, RNG_BASE, RTC_REG_BASE, SAE_BASE, SEC_BASE, SSP0_BASE, SSP1_BASE,
+    SSP2_BASE, SYSCFG_BASE, TIMER0_SFR_BASE, TIMER1_SFR_BASE, TIMER2_SFR_BASE, TIMER3_SFR_BASE,
+    UART0_BASE, UART1_BASE, UART2_BASE, UART3_BASE, WDG_BASE,
+};
+
+/// Built-in commands and the shell on UART0
+pub mod commands;
+
+/// Longest command line
+pub const SHELL_LINE_MAX_SIZE: usize = 128;
+/// Most words on a command line, the command included
+pub const SHELL_MAX_ARGS: usize = 16;
+
+/// Register blocks of `regs.rs`, usable by name in addresses (`rcc`, `uart0+0x18`)
+pub const REGISTER_BLOCKS: &[(&str, usize)] = &[
+    ("rcc", RCC_BASE),
+    ("syscfg", SYSCFG_BASE),
+    ("pwr", PWR_BASE),
+    ("i2s", I2S_BASE),
+    ("uart0", UART0_BASE),
+    ("uart1", UART1_BASE),
+    ("lpuart", LPUART_BASE),
+    ("ssp0", SSP0_BASE),
+    ("i2c0", I2C0_BASE),
+    ("afec", AFEC_BASE),
+    ("lorac", LORAC_BASE),
+    ("timer0", TIMER0_SFR_BASE),
+    ("timer2", TIMER2_SFR_BASE),
+    ("bstimer0", BSTIMER0_SFR_BASE),
+    ("lptimer0", LPTIMER0_SFR_BASE),
+    ("lptimer1", LPTIMER1_SFR_BASE),
+    ("rtc", RTC_REG_BASE),
+    ("sec", SEC_BASE),
+    ("uart2", UART2_BASE),
+    ("uart3", UART3_BASE),
+    ("ssp1", SSP1_BASE),
+    ("ssp2", SSP2_BASE),
+    ("i2c1", I2C1_BASE),
+    ("i2c2", I2C2_BASE),
+    ("adc", ADC_BASE),
+    ("lcd", LCD_BASE),
+    ("dac", DAC_BASE),
+    ("timer1", TIMER1_SFR_BASE),
+    ("timer3", TIMER3_SFR_BASE),
+    ("bstimer1", BSTIMER1_SFR_BASE),
+    ("iwdg", IWDG_BASE),
+    ("wdg", WDG_BASE),
+    ("gpioa", GPIOA_BASE),
+    ("gpiob", GPIOB_BASE),
+    ("gpioc", GPIOC_BASE),
+    ("gpiod", GPIOD_BASE),
+    ("efc", EFC_BASE),
+    ("qspi", QSPI_BASE),
+    ("crc", CRC_BASE),
+    ("sae", SAE_BASE),
+    ("rng", RNG_BASE),
+];
+
+/// Command error, printed by the shell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellError {
+    /// no such command
+    Unknown,
+    /// missing or extra arguments, the usage line is printed
+    Usage,
+    /// argument `n` (from 1) is malformed or out of range
+    Argument(usize),
+    /// the command couldn't do it
+    Failed(&'static str),
+}
+
+/// Entry of a command table.
+///
+/// Apps pass their own table to [`commands::shell_init`], it is searched before the
+/// built-in one.
+pub struct ShellCommand {
+    pub name: &'static str,
+    /// arguments, e.g. `<addr> [words]`
+    pub usage: &'static str,
+    pub help: &'static str,
+    pub run: fn(args: &mut Args) -> Result<(), ShellError>,
+}
+
+/// Arguments of a command, numbers are decimal or `0x` hex
+pub struct Args<'a> {
+    words: Vec<&'a str, SHELL_MAX_ARGS>,
+    /// next word, 1 is the first argument
+    next: usize,
+}
+
+impl<'a> Args<'a> {
+    /// Split `line` on whitespace, `None` when it has too many words
+    pub fn new(line: &'a str) -> Option<Self> {
+        let mut words = Vec::new();
+        for word in line.split_ascii_whitespace() {
+            words.push(word).ok()?;
+        }
+        Some(Self { words, next: 1 })
+    }
+
+    /// The command name, `None` for an empty line
+    pub fn command(&self) -> Option<&'a str> {
+        self.words.first().copied()
+    }
+
+    /// Position of the next argument, for [`ShellError::Argument`]
+    pub fn index(&self) -> usize {
+        self.next
+    }
+
+    /// Arguments not taken yet
+    pub fn remaining(&self) -> usize {
+        self.words.len().saturating_sub(self.next)
+    }
+
+    /// Next argument as a string
+    pub fn next_str(&mut self) -> Result<&'a str, ShellError> {
+        let word = self.words.get(self.next).ok_or(ShellError::Usage)?;
+        self.next += 1;
+        Ok(word)
+    }
+
+    /// Next argument as a number
+    pub fn next_number(&mut self) -> Result<u32, ShellError> {
+        let index = self.index();
+        parse_number(self.next_str()?).ok_or(ShellError::Argument(index))
+    }
+
+    /// Next argument as a signed number
+    pub fn next_signed(&mut self) -> Result<i32, ShellError> {
+        let index = self.index();
+        let word = self.next_str()?;
+        let value = match word.strip_prefix('-') {
+            Some(magnitude) => parse_number(magnitude).map(|n| (n as i32).wrapping_neg()),
+            None => parse_number(word).map(|n| n as i32),
+        };
+        value.ok_or(ShellError::Argument(index))
+    }
+
+    /// Next argument as a number, `default` when there is none
+    pub fn next_number_or(&mut self, default: u32) -> Result<u32, ShellError> {
+        if self.remaining() == 0 {
+            return Ok(default);
+        }
+        self.next_number()
+    }
+
+    /// Next argument as an address, see [`parse_address`]
+    pub fn next_address(&mut self) -> Result<usize, ShellError> {
+        let index = self.index();
+        parse_address(self.next_str()?).ok_or(ShellError::Argument(index))
+    }
+
+    /// Fail with [`ShellError::Usage`] when arguments are left over
+    pub fn finish(&self) -> Result<(), ShellError> {
+        if self.remaining() > 0 {
+            return Err(ShellError::Usage);
+        }
+        Ok(())
+    }
+}
+
+/// Decimal or `0x` hex number
+pub fn parse_number(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Number, register block name or block name plus offset (`uart0+0x18`)
+pub fn parse_address(text: &str) -> Option<usize> {
+    let (name, offset) = match text.split_once('+') {
+        Some((name, offset)) => (name, parse_number(offset)?),
+        None => (text, 0),
+    };
+    let base = match REGISTER_BLOCKS
+        .iter()
+        .find(|(block, _)| block.eq_ignore_ascii_case(name))
+    {
+        Some(&(_, base)) => base,
+        None if offset == 0 => parse_number(name)? as usize,
+        None => return None,
+    };
+    Some(base + offset as usize)
+}
+
+/// Command `name` in `tables`, the first table first
+pub fn find_command<'a>(tables: &[&'a [ShellCommand]], name: &str) -> Option<&'a ShellCommand> {
+    tables
+        .iter()
+        .flat_map(|table| table.iter())
+        .find(|c| c.name == name)
+}
+
+/// Line editor: echo, backspace, `^U` to clear the line, `^C` to drop it and the up arrow to
+/// recall the last line
+pub struct LineEditor {
+    line: Vec<u8, SHELL_LINE_MAX_SIZE>,
+    history: Vec<u8, SHELL_LINE_MAX_SIZE>,
+    /// `line` is complete, it is cleared by the next byte
+    done: bool,
+    /// bytes of an escape sequence seen so far
+    escape: u8,
+    /// the last line ended with `\r`, a following `\n` is skipped
+    last_cr: bool,
+}
+
+impl LineEditor {
+    pub const fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            history: Vec::new(),
+            done: false,
+            escape: 0,
+            last_cr: false,
+        }
+    }
+
+    /// The line being edited, or the complete one after [`Self::feed`] returned `true`
+    pub fn line(&self) -> &str {
+        core::str::from_utf8(&self.line).unwrap_or("")
+    }
+
+    /// Handle one input byte, the echo goes to `echo`. Returns `true` when a line is complete.
+    pub fn feed(&mut self, byte: u8, echo: &mut impl Write) -> bool {
+        if self.done {
+            self.done = false;
+            if !self.line.is_empty() {
+                self.history = self.line.clone();
+            }
+            self.line.clear();
+        }
+        let last_cr = core::mem::replace(&mut self.last_cr, byte == b'\r');
+
+        // ESC [ A is the up arrow, other sequences are dropped
+        match (self.escape, byte) {
+            (0, 0x1B) => {
+                self.escape = 1;
+                return false;
+            }
+            (1, b'[') => {
+                self.escape = 2;
+                return false;
+            }
+            (2, b'A') => {
+                self.escape = 0;
+                self.erase(self.line.len(), echo);
+                self.line = self.history.clone();
+                let _ = echo.write_str(self.line());
+                return false;
+            }
+            (1..=2, _) => {
+                self.escape = 0;
+                return false;
+            }
+            _ => {}
+        }
+
+        match byte {
+            b'\n' if last_cr => false,
+            b'\r' | b'\n' => {
+                let _ = echo.write_str("\r\n");
+                self.done = true;
+                true
+            }
+            0x08 | 0x7F => {
+                self.erase(1, echo);
+                false
+            }
+            0x15 => {
+                self.erase(self.line.len(), echo);
+                false
+            }
+            0x03 => {
+                let _ = echo.write_str("^C\r\n");
+                self.line.clear();
+                self.done = true;
+                true
+            }
+            0x20..=0x7E => {
+                if self.line.push(byte).is_ok() {
+                    let _ = echo.write_char(byte as char);
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Remove the last `count` characters from the line and the terminal
+    fn erase(&mut self, count: usize, echo: &mut impl Write) {
+        for _ in 0..count.min(self.line.len()) {
+            self.line.pop();
+            let _ = echo.write_str("\x08 \x08");
+        }
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{
+    lora::{
+        radio::{
+            RadioEvents, RadioModem, radio_get_packet_status, radio_init, radio_irq_process,
+            radio_rx, radio_set_channel, radio_set_public_network, radio_set_rx_config,
+            sx126x::PacketStatus,
+        },
+        timer::timer_get_current_time,
+    },
+    peripherals::{
+        buffered_uart::uart0,
+        gpio::{GpioMode, GpioPin},
+        regs::GPIOA,
+    },
+    util::crc16_ccitt,
+};
+
+/// Frequency to listen on (Hz)
+pub const SNIFFER_FREQUENCY: usize = 868_100_000;
+/// LoRa bandwidth index (0 = 125 kHz, 1 = 250 kHz, 2 = 500 kHz)
+pub const SNIFFER_BANDWIDTH: u8 = 0;
+/// LoRa spreading factor (5..12)
+pub const SNIFFER_SPREADING_FACTOR: u8 = 7;
+/// LoRa coding rate (1 = 4/5 .. 4 = 4/8)
+pub const SNIFFER_CODING_RATE: u8 = 1;
+/// LoRa preamble length in symbols
+pub const SNIFFER_PREAMBLE_LENGTH: u16 = 8;
+/// use the LoRaWAN public sync word (0x34) instead of the private one (0x12)
+pub const SNIFFER_PUBLIC_NETWORK: bool = true;
+/// listen with inverted IQ (gateway downlinks) instead of normal IQ (uplinks)
+pub const SNIFFER_IQ_INVERTED: bool = false;
+
+/// First two bytes of every frame, used by the host to resynchronise
+pub const SNIFFER_FRAME_MAGIC: [u8; 2] = [0xA5, 0x5A];
+/// Frame format version
+pub const SNIFFER_FRAME_VERSION: u8 = 1;
+/// Frame header size, from the version byte up to the payload
+pub const SNIFFER_HEADER_SIZE: usize = 25;
+/// Largest encoded frame: magic + header + 255 byte payload + CRC
+pub const SNIFFER_FRAME_MAX_SIZE: usize = 2 + SNIFFER_HEADER_SIZE + 255 + 2;
+
+/// CRC status flag: the frame passed its CRC check
+pub const SNIFFER_FLAG_CRC_OK: u8 = 1 << 0;
+/// CRC status flag: the frame failed its CRC check
+pub const SNIFFER_FLAG_CRC_BAD: u8 = 1 << 1;
+/// Frame was received with inverted IQ
+pub const SNIFFER_FLAG_IQ_INVERTED: u8 = 1 << 2;
+
+/// A received frame along with its reception metadata
+pub struct SnifferFrame<'a> {
+    /// time of reception (ms since boot, see `timer_get_current_time`)
+    pub timestamp: u64,
+    /// frequency the frame was received on (Hz)
+    pub frequency: u32,
+    /// bandwidth index (0 = 125 kHz, 1 = 250 kHz, 2 = 500 kHz)
+    pub bandwidth: u8,
+    /// spreading factor
+    pub spreading_factor: u8,
+    /// coding rate (1 = 4/5 .. 4 = 4/8)
+    pub coding_rate: u8,
+    /// packet RSSI (dBm)
+    pub rssi: i16,
+    /// packet SNR (dB)
+    pub snr: i8,
+    /// frequency error reported by the radio (Hz)
+    pub freq_error: i32,
+    /// `SNIFFER_FLAG_*` bits
+    pub flags: u8,
+    /// raw PHY payload
+    pub payload: &'a [u8],
+}
+
+impl SnifferFrame<'_> {
+    /// Encode the frame into `out`, returns the amount of bytes written
+    ///
+    /// Layout (little endian):
+    /// `magic[2] version len timestamp[8] frequency[4] bw sf cr rssi[2] snr freq_error[4] flags payload[len] crc[2]`
+    ///
+    /// The CRC covers everything between the magic and the CRC itself.
+    pub fn encode(&self, out: &mut [u8; SNIFFER_FRAME_MAX_SIZE]) -> usize {
+        let len = self.payload.len().min(255);
+
+        out[0..2].copy_from_slice(&SNIFFER_FRAME_MAGIC);
+        out[2] = SNIFFER_FRAME_VERSION;
+        out[3] = len as u8;
+        out[4..12].copy_from_slice(&self.timestamp.to_le_bytes());
+        out[12..16].copy_from_slice(&self.frequency.to_le_bytes());
+        out[16] = self.bandwidth;
+        out[17] = self.spreading_factor;
+        out[18] = self.coding_rate;
+        out[19..21].copy_from_slice(&self.rssi.to_le_bytes());
+        out[21] = self.snr as u8;
+        out[22..26].copy_from_slice(&self.freq_error.to_le_bytes());
+        out[26] = self.flags;
+
+        let end = 2 + SNIFFER_HEADER_SIZE + len;
+        out[2 + SNIFFER_HEADER_SIZE..end].copy_from_slice(&self.payload[..len]);
+
+        let crc = crc16_ccitt(&out[2..end]);
+        out[end..end + 2].copy_from_slice(&crc.to_le_bytes());
+        end + 2
+    }
+}
+
+/// Stream a frame over UART0, with the RSSI and SNR reported by the radio
+fn send_frame(payload: &[u8], rssi: i16, snr: i8, crc_ok: bool) {
+    let freq_error = match radio_get_packet_status() {
+        Some(PacketStatus::LoRa(ls)) => ls.freq_error as i32,
+        Some(PacketStatus::Gfsk(gs)) => gs.freq_error as i32,
+        None => 0,
+    };
+
+    let mut flags = if crc_ok {
+        SNIFFER_FLAG_CRC_OK
+    } else {
+        SNIFFER_FLAG_CRC_BAD
+    };
+    if SNIFFER_IQ_INVERTED {
+        flags |= SNIFFER_FLAG_IQ_INVERTED;
+    }
+
+    let frame = SnifferFrame {
+        timestamp: timer_get_current_time(),
+        frequency: SNIFFER_FREQUENCY as u32,
+        bandwidth: SNIFFER_BANDWIDTH,
+        spreading_factor: SNIFFER_SPREADING_FACTOR,
+        coding_rate: SNIFFER_CODING_RATE,
+        rssi,
+        snr,
+        freq_error,
+        flags,
+        payload,
+    };
+
+    let mut buf = [0u8; SNIFFER_FRAME_MAX_SIZE];
+    let len = frame.encode(&mut buf);
+    uart0().queue_all(&buf[..len]);
+
+    GPIOA.toggle(GpioPin::GREEN_LED);
+}
+
+fn on_rx_done(payload: &[u8], rssi: i16, snr: i8) {
+    send_frame(payload, rssi, snr, true);
+}
+
+fn on_rx_crc_error(payload: &[u8], rssi: i16, snr: i8) {
+    send_frame(payload, rssi, snr, false);
+}
+
+/// radio events used by the sniffer
+static SNIFFER_EVENTS: RadioEvents = RadioEvents {
+    tx_done: None,
+    tx_timeout: None,
+    rx_done: Some(on_rx_done),
+    rx_timeout: None,
+    rx_error: None,
+    rx_crc_error: Some(on_rx_crc_error),
+    fhss_change_channel: None,
+    cad_done: None,
+};
+
+/// sniffer start, never returns
+///
+/// Keeps the radio in continuous RX and streams every frame over UART0,
+/// use `sniffer_pcap.py` on the host to turn the stream into a pcap file.
+pub fn app_start() -> ! {
+    GPIOA.init(GpioPin::GREEN_LED, GpioMode::OutputPPLow);
+
+    radio_init(&SNIFFER_EVENTS);
+    radio_set_public_network(SNIFFER_PUBLIC_NETWORK);
+    radio_set_channel(SNIFFER_FREQUENCY);
+    radio_set_rx_config(
+        RadioModem::LoRa,
+        SNIFFER_BANDWIDTH as usize,
+        SNIFFER_SPREADING_FACTOR as usize,
+        SNIFFER_CODING_RATE,
+        0,
+        SNIFFER_PREAMBLE_LENGTH,
+        0,
+        false,
+        0,
+        true,
+        false,
+        0,
+        SNIFFER_IQ_INVERTED,
+        true,
+    );
+    radio_rx(0);
+
+    loop {
+        radio_irq_process();
+    }
+}
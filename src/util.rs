@@ -0,0 +1,64 @@
+use core::fmt::{self, Write};
+
+/// Decode `hex` into `out`, returns the length written
+pub fn parse_hex(hex: &str, out: &mut [u8]) -> Option<usize> {
+    let hex = hex.as_bytes();
+    if !hex.len().is_multiple_of(2) || hex.len() / 2 > out.len() {
+        return None;
+    }
+    for (byte, pair) in out.iter_mut().zip(hex.chunks(2)) {
+        let digit = |c: u8| (c as char).to_digit(16);
+        *byte = (digit(pair[0])? << 4 | digit(pair[1])?) as u8;
+    }
+    Some(hex.len() / 2)
+}
+
+/// Upper case hex of `data`
+pub fn write_hex(w: &mut impl Write, data: &[u8]) -> fmt::Result {
+    data.iter().try_for_each(|b| write!(w, "{:02X}", b))
+}
+
+/// CRC-16/CCITT-FALSE, used to protect frames on the wire and the settings in flash
+pub fn crc16_ccitt(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        let mut out = [0u8; 4];
+        assert_eq!(parse_hex("00aBfF", &mut out), Some(3));
+        assert_eq!(out[..3], [0x00, 0xAB, 0xFF]);
+        assert_eq!(parse_hex("", &mut out), Some(0));
+        assert_eq!(parse_hex("0", &mut out), None);
+        assert_eq!(parse_hex("0g", &mut out), None);
+        assert_eq!(parse_hex("+1", &mut out), None);
+        assert_eq!(parse_hex("0011223344", &mut out), None);
+
+        let mut text = heapless::String::<8>::new();
+        write_hex(&mut text, &[0x00, 0xAB, 0x0F]).unwrap();
+        assert_eq!(text.as_str(), "00AB0F");
+    }
+
+    #[test]
+    fn crc16() {
+        // check value of the CRC catalogue
+        assert_eq!(crc16_ccitt(b"123456789"), 0x29B1);
+        assert_eq!(crc16_ccitt(b""), 0xFFFF);
+        assert_eq!(crc16_ccitt(&[0x00]), 0xE1F0);
+    }
+}